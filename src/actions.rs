@@ -0,0 +1,243 @@
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Typed model of the Github Actions context (`${{ toJSON(github) }}`)
+/// the importer runs with. Only the fields used by the pipeline are
+/// modelled.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContextPayload {
+    pub event_name: String,
+    #[serde(default)]
+    pub sha: Option<String>,
+    /// Repository the workflow runs in (owner/name)
+    #[serde(default)]
+    pub repository: Option<String>,
+    #[serde(default)]
+    pub repository_owner: Option<String>,
+    #[serde(default)]
+    pub token: Option<String>,
+    pub event: EventPayload,
+}
+
+/// Payload of the event that triggered the run, as delivered to
+/// workflows and webhooks
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventPayload {
+    #[serde(default)]
+    pub action: Option<String>,
+    /// Ref updated by a `push` event
+    #[serde(default, rename = "ref")]
+    pub ref_name: Option<String>,
+    /// Commit the ref pointed to before a `push` event
+    #[serde(default)]
+    pub before: Option<String>,
+    /// Commit the ref points to after a `push` event
+    #[serde(default)]
+    pub after: Option<String>,
+    #[serde(default)]
+    pub issue: Option<Issue>,
+    #[serde(default)]
+    pub comment: Option<Comment>,
+    /// Label added or removed by a `labeled` or `unlabeled` event
+    #[serde(default)]
+    pub label: Option<Label>,
+    /// User who triggered the event
+    #[serde(default)]
+    pub sender: Option<Owner>,
+    #[serde(default)]
+    pub pull_request: Option<PullRequest>,
+    #[serde(default)]
+    pub repository: Option<Repository>,
+    /// Free-form payload of `repository_dispatch` events, see
+    /// `DispatchPayload`
+    #[serde(default)]
+    pub client_payload: Option<serde_json::Value>,
+}
+
+/// Client payload accepted in `repository_dispatch` events, which lets
+/// other repositories and lab automation trigger targeted processing.
+/// For instance: `{"action": "remerge", "model": "WDC_SN720"}`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "kebab-case")]
+pub enum DispatchPayload {
+    /// Regenerates the merge, pdf and hwdb input of a model, or of
+    /// every model if none is given
+    Remerge {
+        #[serde(default)]
+        model: Option<String>,
+        /// resctl-bench version (major.minor), all versions if not set
+        #[serde(default)]
+        version: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Issue {
+    pub number: u64,
+    pub state: String,
+    pub locked: bool,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub body: Option<String>,
+    /// User who opened the issue
+    #[serde(default)]
+    pub user: Option<Owner>,
+    #[serde(default)]
+    pub labels: Vec<Label>,
+    /// Relation of the issue author with the repository
+    #[serde(default)]
+    pub author_association: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Label {
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Comment {
+    #[serde(default)]
+    pub body: Option<String>,
+    /// Relation of the author with the repository (OWNER, MEMBER,
+    /// COLLABORATOR, CONTRIBUTOR, NONE...)
+    #[serde(default)]
+    pub author_association: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PullRequest {
+    pub number: u64,
+    pub base: GitRef,
+    pub head: GitRef,
+    /// Whether the pull request was merged, for `closed` events
+    #[serde(default)]
+    pub merged: bool,
+    #[serde(default)]
+    pub merge_commit_sha: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GitRef {
+    #[serde(rename = "ref")]
+    pub ref_name: String,
+    pub sha: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Repository {
+    /// Numeric id, which the storage URLs of the attachments refer to
+    #[serde(default)]
+    pub id: Option<u64>,
+    pub name: String,
+    pub full_name: String,
+    pub owner: Owner,
+    #[serde(default)]
+    pub default_branch: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Owner {
+    pub login: String,
+}
+
+/// Collects the paths of the fields in `input` that are missing from
+/// `parsed`, i.e. the fields the typed model doesn't know about.
+fn unknown_fields(input: &Value, parsed: &Value, path: &str, unknown: &mut Vec<String>) {
+    if let (Value::Object(input), Value::Object(parsed)) = (input, parsed) {
+        for (key, value) in input {
+            let field = if path.is_empty() {
+                key.clone()
+            } else {
+                format!("{}.{}", path, key)
+            };
+            match parsed.get(key) {
+                Some(parsed_value) => unknown_fields(value, parsed_value, &field, unknown),
+                None => unknown.push(field),
+            }
+        }
+    }
+}
+
+impl ContextPayload {
+    /// Parses a Github workflow context, naming the field that failed
+    /// to parse and the event type on errors. In `strict` mode, fields
+    /// the model doesn't know about are reported as an error too, which
+    /// helps spotting schema drift.
+    pub fn parse(contents: &str, strict: bool) -> Result<Self> {
+        let value: Value = serde_json::from_str(contents).context("Github context is not valid json")?;
+        let event_name = value["event_name"].as_str().unwrap_or("unknown").to_string();
+        let context: ContextPayload = serde_path_to_error::deserialize(&value).map_err(|e| {
+            anyhow!(
+                "Failed to parse Github context for {} event at `{}`: {}",
+                event_name,
+                e.path(),
+                e.inner()
+            )
+        })?;
+        if strict {
+            let mut unknown = vec![];
+            unknown_fields(&value, &serde_json::to_value(&context)?, "", &mut unknown);
+            if !unknown.is_empty() {
+                bail!(
+                    "Github context for {} event has fields unknown to the model:\n  {}",
+                    event_name,
+                    unknown.join("\n  ")
+                );
+            }
+        }
+        Ok(context)
+    }
+
+    /// Builds a context from a webhook delivery, where the event name
+    /// comes from the `X-GitHub-Event` header and the body is the event
+    /// payload.
+    pub fn from_webhook(event_name: &str, event: EventPayload, token: Option<String>) -> Self {
+        ContextPayload {
+            event_name: event_name.to_string(),
+            sha: None,
+            repository: event.repository.as_ref().map(|r| r.full_name.clone()),
+            repository_owner: event.repository.as_ref().map(|r| r.owner.login.clone()),
+            token,
+            event,
+        }
+    }
+
+    /// Returns true for the issue and issue comment events the importer
+    /// processes submissions from
+    pub fn is_submission_event(&self) -> bool {
+        matches!(self.event_name.as_str(), "issues" | "issue_comment")
+            && matches!(
+                self.event.action.as_deref(),
+                Some("opened") | Some("created") | Some("edited")
+            )
+            && self.event.issue.is_some()
+    }
+
+    /// Returns the (from, to) commits of a `push` or `pull_request`
+    /// event, None for other events
+    pub fn commit_range(&self) -> Option<(String, String)> {
+        match self.event_name.as_str() {
+            "push" => Some((self.event.before.clone()?, self.event.after.clone()?)),
+            "pull_request" => {
+                let pr = self.event.pull_request.as_ref()?;
+                Some((pr.base.sha.clone(), pr.head.sha.clone()))
+            }
+            _ => None,
+        }
+    }
+
+    /// Parses the client payload of a `repository_dispatch` event
+    pub fn dispatch_payload(&self) -> Result<DispatchPayload> {
+        if self.event_name != "repository_dispatch" {
+            bail!("Not a repository_dispatch event: {}", self.event_name);
+        }
+        let payload = self
+            .event
+            .client_payload
+            .clone()
+            .context("repository_dispatch event without a client_payload")?;
+        serde_json::from_value(payload).context("Failed to parse repository_dispatch client_payload")
+    }
+}
@@ -0,0 +1,170 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::hwdb::hwdb_section_for;
+use crate::manifest::{Manifest, MANIFEST_FILE};
+use crate::output::OutputFormat;
+
+/// Directory the outputs of the merge runs are archived in
+pub static HWDB_ARCHIVE_DIR: &str = "hwdb-archive";
+
+static INDEX_FILE: &str = "index.json";
+
+/// An archived set of hwdb outputs from a single merge run
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchiveEntry {
+    /// Date of the run (YYYY-MM-DD)
+    pub date: String,
+    /// Commit of the database repository the outputs were built from
+    pub commit: Option<String>,
+    /// Directory holding the archived files, relative to the archive root
+    pub dir: PathBuf,
+    /// Archived files, relative to `dir`
+    pub artifacts: Vec<PathBuf>,
+}
+
+/// Keeps previous hwdb outputs under `<root>/<date>-<sha>/`, along with
+/// an index file mapping commits to their artifacts.
+pub struct HwdbArchive {
+    root: PathBuf,
+}
+
+impl HwdbArchive {
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        HwdbArchive { root: root.into() }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn index_path(&self) -> PathBuf {
+        self.root.join(INDEX_FILE)
+    }
+
+    /// Loads the archive index, oldest entries first. A missing index
+    /// is treated as an empty archive.
+    pub fn load_index(&self) -> Result<Vec<ArchiveEntry>> {
+        let path = self.index_path();
+        if !path.exists() {
+            return Ok(vec![]);
+        }
+        let contents = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read archive index {:?}", path))?;
+        serde_json::from_str(&contents)
+            .with_context(|| format!("Failed to parse archive index {:?}", path))
+    }
+
+    /// Copies `files` into a new archive directory named after the
+    /// current date and `commit`, and records it in the index.
+    pub fn store(&self, commit: Option<&str>, files: &[PathBuf]) -> Result<ArchiveEntry> {
+        let date = chrono::Utc::now().format("%Y-%m-%d").to_string();
+        let sha = match commit {
+            Some(c) => c.chars().take(12).collect(),
+            None => "local".to_string(),
+        };
+        let dir = PathBuf::from(format!("{}-{}", date, sha));
+        fs::create_dir_all(self.root.join(&dir))?;
+
+        let mut artifacts = vec![];
+        for file in files {
+            let filename = PathBuf::from(file.file_name().context("Malformed artifact path")?);
+            fs::copy(file, self.root.join(&dir).join(&filename))
+                .with_context(|| format!("Failed to archive {:?}", file))?;
+            artifacts.push(filename);
+        }
+
+        let entry = ArchiveEntry {
+            date,
+            commit: commit.map(str::to_string),
+            dir,
+            artifacts,
+        };
+        let mut index = self.load_index()?;
+        // Re-running for the same commit on the same day replaces the
+        // previous entry instead of duplicating it.
+        index.retain(|e| e.dir != entry.dir);
+        index.push(entry);
+        fs::write(self.index_path(), serde_json::to_string_pretty(&index)?)?;
+
+        Ok(index.pop().unwrap())
+    }
+
+    /// Returns the path of `name` in the most recent archived run that
+    /// has it, if any
+    pub fn latest_file(&self, name: &str) -> Result<Option<PathBuf>> {
+        Ok(self
+            .load_index()?
+            .iter()
+            .rev()
+            .map(|e| self.root.join(&e.dir).join(name))
+            .find(|p| p.exists()))
+    }
+
+    /// Returns the manifest of the last archived run, if it can be read
+    pub fn latest_manifest(&self) -> Option<Manifest> {
+        let path = self.latest_file(MANIFEST_FILE).ok()??;
+        serde_json::from_str(&fs::read_to_string(path).ok()?).ok()
+    }
+
+    /// Returns, for every archived run, the hwdb entry that was
+    /// generated for `model_name` (if any).
+    pub fn model_history(
+        &self,
+        model_name: &str,
+        hwdb_name: &str,
+    ) -> Result<Vec<(ArchiveEntry, Option<String>)>> {
+        let mut history = vec![];
+        for entry in self.load_index()? {
+            let path = self.root.join(&entry.dir).join(hwdb_name);
+            let section = match fs::read_to_string(&path) {
+                Ok(contents) => hwdb_section_for(&contents, model_name),
+                Err(_) => None,
+            };
+            history.push((entry, section));
+        }
+        Ok(history)
+    }
+}
+
+/// A run of the archive that changed the hwdb entry of a model
+#[derive(Debug, Serialize)]
+pub struct EntryChange {
+    pub date: String,
+    pub commit: Option<String>,
+    /// The hwdb entry of the model, if the run had one
+    pub entry: Option<String>,
+}
+
+/// Prints how the hwdb entry for `model_name` evolved over the archived
+/// runs, only showing the entries that changed.
+pub fn print_model_history(
+    archive: &HwdbArchive,
+    model_name: &str,
+    hwdb_name: &str,
+    format: OutputFormat,
+) -> Result<()> {
+    let mut changes: Vec<EntryChange> = vec![];
+    for (archived, section) in archive.model_history(model_name, hwdb_name)? {
+        if changes.last().is_some_and(|c| c.entry == section) {
+            continue;
+        }
+        changes.push(EntryChange { date: archived.date, commit: archived.commit, entry: section });
+    }
+    format.print(&changes, || {
+        for change in &changes {
+            let commit = change.commit.as_deref().unwrap_or("no commit");
+            println!("== {} ({}) ==", change.date, commit);
+            match &change.entry {
+                Some(s) => println!("{}\n", s),
+                None => println!("(no entry for {})\n", model_name),
+            }
+        }
+        if changes.is_empty() {
+            println!("No archived hwdb files found in {:?}", archive.root);
+        }
+        Ok(())
+    })
+}
@@ -0,0 +1,202 @@
+use json::{object, JsonValue};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use crate::common::load_json;
+use crate::config::set_once;
+use crate::error::{io_error, Error, Result};
+
+/// Runs resctl-bench in place of the binaries of the workspace
+pub trait BenchRunner: Send + Sync {
+    /// Runs resctl-bench `version` (X.Y) with `args` and returns its
+    /// output, like `run_resctl`
+    fn run(&self, version: &str, args: &[String]) -> Result<String>;
+}
+
+/// Parameters of a solution, as written in the hwdb files
+type Params = Vec<(&'static str, String)>;
+
+/// Full versions the mock reports, by major.minor
+static MOCK_VERSIONS: &[(&str, &str)] = &[("2.1", "2.1.3"), ("2.2", "2.2.5")];
+
+/// In-process stand-in for resctl-bench, for the self-test and local
+/// development without the binaries. It implements the runs of the
+/// pipeline (`--version`, `merge` and `format`) with simplified,
+/// deterministic outputs: the merge concatenates the iocost-tune series
+/// of the results and the solutions only depend on their data points.
+#[derive(Debug, Default)]
+pub struct MockBench;
+
+impl MockBench {
+    fn fail(version: &str, args: &[String], reason: impl Into<String>) -> Error {
+        Error::ResctlFailed {
+            version: version.to_string(),
+            args: args.join(" "),
+            stderr: reason.into(),
+        }
+    }
+
+    /// Returns the iocost-tune run of `result`
+    fn iocost_tune(result: &JsonValue) -> Option<&JsonValue> {
+        result.members().find(|v| v["spec"]["kind"] == "iocost-tune")
+    }
+
+    /// Returns the data points of the MOF series of `result`, outliers
+    /// included
+    fn data_points(result: &JsonValue) -> usize {
+        Self::iocost_tune(result).map_or(0, |r| {
+            let series = &r["result"]["data"]["MOF"];
+            series["data"].len() + series["outliers"].len()
+        })
+    }
+
+    /// Model and QoS parameters of the naive solution of a result with
+    /// `data_points`
+    fn solution(data_points: usize) -> (Params, Params) {
+        let n = data_points as u64;
+        let model = vec![
+            ("rbps", (1_000_000_000 + n * 10_000_000).to_string()),
+            ("rseqiops", (200_000 + n * 1_000).to_string()),
+            ("rrandiops", (180_000 + n * 1_000).to_string()),
+            ("wbps", (800_000_000 + n * 10_000_000).to_string()),
+            ("wseqiops", (150_000 + n * 1_000).to_string()),
+            ("wrandiops", (120_000 + n * 1_000).to_string()),
+        ];
+        let qos = vec![
+            ("rpct", "95.00".to_string()),
+            ("rlat", (1_000 + n * 10).to_string()),
+            ("wpct", "95.00".to_string()),
+            ("wlat", (2_000 + n * 10).to_string()),
+            ("min", format!("{:.2}", 50.0 + n as f64)),
+            ("max", format!("{:.2}", 50.0 + n as f64)),
+        ];
+        (model, qos)
+    }
+
+    fn params(params: &[(&str, String)]) -> String {
+        params.iter().map(|(k, v)| format!("{}={}", k, v)).collect::<Vec<_>>().join(" ")
+    }
+
+    /// Merges the `inputs` into `output`: the first result with the
+    /// iocost-tune series of all of them, and the solutions
+    fn merge(output: &Path, inputs: &[String]) -> Result<()> {
+        let mut merged = load_json(&inputs[0])?;
+        for input in &inputs[1..] {
+            let result = load_json(input)?;
+            let (Some(from), Some(into)) = (Self::iocost_tune(&result), merged
+                .members_mut()
+                .find(|v| v["spec"]["kind"] == "iocost-tune"))
+            else {
+                continue;
+            };
+            for (name, series) in from["result"]["data"].entries() {
+                for key in ["data", "outliers"] {
+                    for point in series[key].members() {
+                        into["result"]["data"][name][key].push(point.clone()).ok();
+                    }
+                }
+            }
+        }
+        let (model, qos) = Self::solution(Self::data_points(&merged));
+        let as_object = |params: &[(&str, String)]| {
+            let mut object = JsonValue::new_object();
+            for (key, value) in params {
+                object[*key] = value.parse::<f64>().unwrap_or_default().into();
+            }
+            object
+        };
+        if let Some(iocost_tune) = merged.members_mut().find(|v| v["spec"]["kind"] == "iocost-tune") {
+            iocost_tune["result"]["solutions"] = object! {
+                "naive": {
+                    "model": as_object(&model),
+                    "qos": as_object(&qos),
+                    "isol": 0.9,
+                    "lat_imp": 0.1,
+                    "work_csv": 0.7,
+                    "missing": 0.0,
+                }
+            };
+        }
+        let mut encoder = libflate::gzip::Encoder::new(vec![]).map_err(io_error(output))?;
+        encoder.write_all(merged.dump().as_bytes()).map_err(io_error(output))?;
+        fs::write(output, encoder.finish().into_result().map_err(io_error(output))?).map_err(io_error(output))
+    }
+
+    /// Formats the `result` file with `spec`
+    fn format(result_path: &str, spec: &str) -> Result<Option<String>> {
+        if let Some(pdf) = spec.strip_prefix("iocost-tune:pdf=") {
+            fs::write(pdf, "%PDF-1.4\n% resctl-bench mock\n").map_err(io_error(pdf))?;
+            return Ok(Some(String::new()));
+        }
+        let result = load_json(result_path)?;
+        let report = &result[0]["sysinfo"]["sysreqs_report"];
+        let model_name = report["scr_dev_model"].to_string();
+        let data_points = Self::data_points(&result);
+        let (model, qos) = Self::solution(data_points);
+        let hwdb = |fwrev: &str| {
+            format!(
+                concat!(
+                    "block:*:name:{}:fwrev:{}:\n",
+                    " IOCOST_SOLUTIONS=naive\n IOCOST_MODEL_NAIVE={}\n IOCOST_QOS_NAIVE={}\n"
+                ),
+                model_name,
+                fwrev,
+                Self::params(&model),
+                Self::params(&qos)
+            )
+        };
+        Ok(match spec {
+            "iocost-tune:hwdb" => Some(hwdb("*")),
+            "iocost-tune:hwdb-fwrev" => Some(hwdb(&report["scr_dev_fwrev"].to_string())),
+            "iocost-tune:high-level" => Some(format!(
+                concat!(
+                    "[naive]\n  info: isol=90.00% lat_imp=10.00% work_csv=70.00% missing=0.00%\n",
+                    "  model: {}\n  qos: {}\n"
+                ),
+                Self::params(&model),
+                Self::params(&qos)
+            )),
+            "iocost-tune" => Some(format!("iocost-tune of {}: {} data points\n", model_name, data_points)),
+            _ => None,
+        })
+    }
+}
+
+impl BenchRunner for MockBench {
+    fn run(&self, version: &str, args: &[String]) -> Result<String> {
+        let Some((_, full_version)) = MOCK_VERSIONS.iter().find(|(v, _)| *v == version) else {
+            return Err(MockBench::fail(version, args, format!("no mock of resctl-bench {}", version)));
+        };
+        let args_str: Vec<&str> = args.iter().map(String::as_str).collect();
+        match args_str.as_slice() {
+            ["--version"] => Ok(format!("resctl-bench {} mock\n", full_version)),
+            ["--result", output, "merge", inputs @ ..] if !inputs.is_empty() => {
+                let inputs: Vec<String> = inputs.iter().map(|i| i.to_string()).collect();
+                MockBench::merge(Path::new(output), &inputs).map(|_| String::new())
+            }
+            ["--result", result, "format", spec] => MockBench::format(result, spec)?
+                .ok_or_else(|| MockBench::fail(version, args, format!("unsupported format {}", spec))),
+            ["--result", result, "summary"] | ["--result", result, "format"] => {
+                let result = load_json(result)?;
+                Ok(format!("{} data points\n", MockBench::data_points(&result)))
+            }
+            _ => Err(MockBench::fail(version, args, "unsupported arguments")),
+        }
+    }
+}
+
+static BENCH_RUNNER: OnceLock<Box<dyn BenchRunner>> = OnceLock::new();
+
+/// Sets the runner of resctl-bench, replacing the binaries of the
+/// workspace.
+pub fn set_bench_runner(runner: Box<dyn BenchRunner>) -> anyhow::Result<()> {
+    set_once(&BENCH_RUNNER, runner, "runner of resctl-bench")
+}
+
+/// Returns the runner of resctl-bench set in place of the binaries, if
+/// any
+pub fn bench_runner() -> Option<&'static dyn BenchRunner> {
+    BENCH_RUNNER.get().map(|runner| runner.as_ref())
+}
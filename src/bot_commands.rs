@@ -0,0 +1,161 @@
+use anyhow::{bail, Context, Result};
+use glob::glob;
+use std::path::{Path, PathBuf};
+
+use crate::actions::ContextPayload;
+use crate::commits::commit_settings;
+use crate::index::ResultMetadata;
+
+/// Author associations allowed to run the maintainer commands
+static MAINTAINER_ASSOCIATIONS: &[&str] = &["OWNER", "MEMBER", "COLLABORATOR"];
+
+/// Commands maintainers can run by commenting on an issue, e.g.:
+///
+/// ```text
+/// /exclude 0123456789abcdef0123456789abcdef reason=broken cooling
+/// /retract 0123456789abcdef0123456789abcdef reason=bogus data
+/// /accept
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BotCommand {
+    /// Leaves a result out of the merges
+    Exclude { md5: String, reason: Option<String> },
+    /// Includes an excluded result in the merges again
+    Include { md5: String },
+    /// Removes a result from the database, see `retract`
+    Retract { md5: String, reason: String },
+    /// Moves the results of the issue pending review into the database,
+    /// see `review`
+    Accept,
+}
+
+impl BotCommand {
+    /// Parses the command in the first line of a comment `body`.
+    /// Returns `None` for comments which don't start with one.
+    pub fn parse(body: &str) -> Option<Result<Self>> {
+        let line = body.trim_start().lines().next()?.trim();
+        let (name, args) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        if name == "/accept" {
+            return Some(match args.trim() {
+                "" => Ok(BotCommand::Accept),
+                args => Err(anyhow::anyhow!("/accept takes no arguments, got: {}", args)),
+            });
+        }
+        let (md5, options) = args.trim().split_once(char::is_whitespace).unwrap_or((args.trim(), ""));
+        let options = options.trim();
+        let reason = options
+            .strip_prefix("reason=")
+            .map(str::trim)
+            .filter(|r| !r.is_empty())
+            .map(str::to_string);
+        if !options.is_empty() && !options.starts_with("reason=") {
+            return Some(Err(anyhow::anyhow!("Unknown {} option: {}", name, options)));
+        }
+        let md5 = md5.to_lowercase();
+        let command = match name {
+            "/exclude" => BotCommand::Exclude { md5, reason },
+            "/include" => BotCommand::Include { md5 },
+            "/retract" => match reason {
+                Some(reason) => BotCommand::Retract { md5, reason },
+                None => return Some(Err(anyhow::anyhow!("/retract needs a reason=..."))),
+            },
+            _ => return None,
+        };
+        Some(validate_md5(command.md5().unwrap_or_default()).map(|_| command))
+    }
+
+    /// Returns the command in the comment of an `issue_comment` event
+    /// `context`, if any
+    pub fn from_context(context: &ContextPayload) -> Option<Result<Self>> {
+        if context.event_name != "issue_comment" || context.event.action.as_deref() != Some("created") {
+            return None;
+        }
+        Self::parse(context.event.comment.as_ref()?.body.as_deref()?)
+    }
+
+    /// md5 checksum of the result the command applies to, none for the
+    /// commands applying to the results of the issue
+    pub fn md5(&self) -> Option<&str> {
+        match self {
+            BotCommand::Exclude { md5, .. }
+            | BotCommand::Include { md5 }
+            | BotCommand::Retract { md5, .. } => Some(md5),
+            BotCommand::Accept => None,
+        }
+    }
+
+    /// Describes the command, for the permission denied message
+    pub fn action(&self) -> &'static str {
+        match self {
+            BotCommand::Exclude { .. } => "exclude results from the merges",
+            BotCommand::Include { .. } => "include results in the merges",
+            BotCommand::Retract { .. } => "retract results",
+            BotCommand::Accept => "accept results pending review",
+        }
+    }
+
+    /// Branch the changes of the command posted in `issue` are committed
+    /// to, for review
+    pub fn branch(&self, issue: u64) -> String {
+        match self {
+            BotCommand::Exclude { md5, .. } => format!("iocost-bot/exclude-{}", md5),
+            BotCommand::Include { md5 } => format!("iocost-bot/include-{}", md5),
+            BotCommand::Retract { md5, .. } => format!("iocost-bot/retract-{}", md5),
+            BotCommand::Accept => commit_settings().branch(issue),
+        }
+    }
+
+    /// Runs a metadata command (`/exclude` or `/include`) on the database
+    /// in `database_dir`. Returns the result file and the changed
+    /// metadata file.
+    pub fn set_metadata(&self, database_dir: &Path) -> Result<(PathBuf, PathBuf)> {
+        let Some(md5) = self.md5() else {
+            bail!("{:?} doesn't change the metadata", self);
+        };
+        let result = find_result(database_dir, md5)?;
+        let mut metadata = ResultMetadata::load_for(&result).with_context(|| {
+            format!(
+                "{} has no metadata, run `iocost-ci migrate` to add it",
+                result.display()
+            )
+        })?;
+        match self {
+            BotCommand::Exclude { reason, .. } => {
+                metadata.exclude = true;
+                metadata.exclude_reason = reason.clone();
+            }
+            BotCommand::Include { .. } => {
+                metadata.exclude = false;
+                metadata.exclude_reason = None;
+            }
+            BotCommand::Retract { .. } | BotCommand::Accept => bail!("{:?} doesn't change the metadata", self),
+        }
+        metadata.save_for(&result)?;
+        let metadata_path = ResultMetadata::path_for(&result);
+        Ok((result, metadata_path))
+    }
+}
+
+/// Whether a comment author `association` allows running the maintainer
+/// commands
+pub fn is_maintainer(association: Option<&str>) -> bool {
+    association.is_some_and(|a| MAINTAINER_ASSOCIATIONS.contains(&a))
+}
+
+fn validate_md5(md5: &str) -> Result<()> {
+    if md5.len() != 32 || !md5.chars().all(|c| c.is_ascii_hexdigit()) {
+        bail!("Invalid md5 checksum {:?}", md5);
+    }
+    Ok(())
+}
+
+/// Returns the result file with the `md5` checksum in the database in
+/// `database_dir`
+pub fn find_result(database_dir: &Path, md5: &str) -> Result<PathBuf> {
+    validate_md5(md5)?;
+    let pattern = database_dir.join("*").join("*").join(format!("result-{}.json.gz", md5.to_lowercase()));
+    match glob(&pattern.to_string_lossy())?.flatten().next() {
+        Some(path) => Ok(path),
+        None => bail!("No result with md5 {} in {}", md5, database_dir.display()),
+    }
+}
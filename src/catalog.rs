@@ -0,0 +1,76 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{LazyLock, OnceLock};
+
+use crate::config::set_once;
+use crate::templates::TEMPLATES_DIR;
+
+/// Locale of the built-in messages
+pub static DEFAULT_LOCALE: &str = "en";
+/// Environment variable selecting the locale, if not set in the config
+pub static LOCALE_ENVVAR: &str = "IOCOST_LOCALE";
+
+static LOCALE: OnceLock<String> = OnceLock::new();
+
+/// Sets the locale of the bot messages and reports.
+pub fn set_locale(locale: &str) -> Result<()> {
+    set_once(&LOCALE, locale.to_string(), "locale")
+}
+
+/// Returns the locale of the bot messages and reports: the one set with
+/// `set_locale`, or the one in $IOCOST_LOCALE, or the default one.
+pub fn locale() -> &'static str {
+    LOCALE.get_or_init(|| {
+        std::env::var(LOCALE_ENVVAR).unwrap_or_else(|_| DEFAULT_LOCALE.to_string())
+    })
+}
+
+/// Messages keyed by name, for a locale
+pub struct Catalog {
+    messages: HashMap<String, String>,
+}
+
+impl Catalog {
+    /// Loads the built-in catalog, overridden by the one for `locale` in
+    /// `dir` (`<dir>/<locale>/messages.toml`), if set
+    pub fn load(dir: Option<&Path>, locale: &str) -> Result<Self> {
+        let mut messages: HashMap<String, String> =
+            toml::from_str(include_str!("../templates/messages.toml"))?;
+        if let Some(path) = dir
+            .map(|d| d.join(locale).join("messages.toml"))
+            .filter(|p| p.exists())
+        {
+            let translated: HashMap<String, String> =
+                toml::from_str(&std::fs::read_to_string(&path)?)?;
+            messages.extend(translated);
+        }
+        Ok(Catalog { messages })
+    }
+
+    /// Returns the message for `key` with the `{name}` placeholders
+    /// replaced by `args`, or the key itself if there is no such message
+    pub fn format(&self, key: &str, args: &[(&str, String)]) -> String {
+        let mut message = self
+            .messages
+            .get(key)
+            .cloned()
+            .unwrap_or_else(|| key.to_string());
+        for (name, value) in args {
+            message = message.replace(&format!("{{{}}}", name), value);
+        }
+        message
+    }
+}
+
+pub static CATALOG: LazyLock<Catalog> = LazyLock::new(|| {
+    Catalog::load(Some(Path::new(TEMPLATES_DIR)), locale()).unwrap_or_else(|e| {
+        eprintln!("Failed to load the {} message catalog, using the built-in one: {:#}", locale(), e);
+        Catalog::load(None, DEFAULT_LOCALE).expect("Invalid built-in message catalog")
+    })
+});
+
+/// Returns the translated message for `key`, see `Catalog::format`
+pub fn tr(key: &str, args: &[(&str, String)]) -> String {
+    CATALOG.format(key, args)
+}
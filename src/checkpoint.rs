@@ -0,0 +1,105 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Progress of a submitted URL in an import run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "stage", rename_all = "kebab-case")]
+pub enum UrlState {
+    /// The result was downloaded to `path`, and verified against the
+    /// checksum advertised by its host if any
+    Downloaded {
+        path: String,
+        #[serde(default)]
+        verified_checksum: Option<String>,
+    },
+    /// The result was validated, added to the database and its `files`
+    /// staged
+    Staged {
+        version: String,
+        model_name: String,
+        files: Vec<PathBuf>,
+    },
+    /// The result failed validation and was recorded in the failed
+    /// submissions ledger
+    Failed { error: String, ledger: PathBuf },
+    /// The result was already in the database, imported from another
+    /// submission
+    Duplicate { issue: Option<u64>, url: Option<String> },
+    /// The result of the issue was already merged into the database as
+    /// `file`, e.g. by an earlier import of the issue
+    Merged { file: PathBuf },
+    /// The result was validated and held back until the end of its
+    /// embargo
+    Embargoed { until: String },
+}
+
+/// Records which URLs of an issue have already been processed, so that
+/// re-running an interrupted import resumes where it left off. It is
+/// saved after every change and removed once the run completes.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ImportCheckpoint {
+    pub issue: u64,
+    pub urls: BTreeMap<String, UrlState>,
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+impl ImportCheckpoint {
+    /// Returns the state file path for `issue`
+    pub fn path_for(issue: u64) -> PathBuf {
+        PathBuf::from(format!("import-state-{}.json", issue))
+    }
+
+    /// Loads the checkpoint of `issue` if there is one, or returns an
+    /// empty one
+    pub fn load(issue: u64) -> Result<Self> {
+        let path = Self::path_for(issue);
+        let mut checkpoint = if path.exists() {
+            let checkpoint: Self = serde_json::from_str(&fs::read_to_string(&path)?)?;
+            println!(
+                "Resuming import of issue {}: {} URLs already processed",
+                issue,
+                checkpoint.urls.len()
+            );
+            checkpoint
+        } else {
+            ImportCheckpoint { issue, ..Default::default() }
+        };
+        checkpoint.path = path;
+        Ok(checkpoint)
+    }
+
+    pub fn get(&self, url: &str) -> Option<&UrlState> {
+        self.urls.get(url)
+    }
+
+    /// Returns the path of the downloaded file for `url` and the
+    /// checksum it was verified against, if it was downloaded and is
+    /// still there
+    pub fn downloaded(&self, url: &str) -> Option<(String, Option<String>)> {
+        match self.get(url) {
+            Some(UrlState::Downloaded { path, verified_checksum }) if Path::new(path).exists() => {
+                Some((path.clone(), verified_checksum.clone()))
+            }
+            _ => None,
+        }
+    }
+
+    /// Updates the state of `url` and saves the checkpoint
+    pub fn set(&mut self, url: &str, state: UrlState) -> Result<()> {
+        self.urls.insert(url.to_string(), state);
+        fs::write(&self.path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Removes the state file once the run is complete
+    pub fn finish(self) -> Result<()> {
+        if self.path.exists() {
+            fs::remove_file(&self.path)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,99 @@
+use anyhow::{bail, Result};
+use std::collections::BTreeSet;
+use std::sync::OnceLock;
+
+use crate::config::{set_once, CommitsConfig};
+use crate::signoff::SignOff;
+
+/// How the branches and commits importing the submissions are named, as
+/// templates with `{placeholder}` variables:
+///
+/// - `branch`: `{issue}`, which it must have once, as the issue of a
+///   branch is read back from its name
+/// - `title` and `trailer`: `{issue}`, `{models}` and `{versions}`, the
+///   comma-separated models and resctl-bench versions of the results
+/// - `trailer`: `{name}` and `{email}` of the sign-off too
+#[derive(Debug, Clone)]
+pub struct CommitSettings {
+    pub branch: String,
+    pub title: String,
+    pub trailer: String,
+}
+
+impl Default for CommitSettings {
+    fn default() -> Self {
+        CommitSettings {
+            branch: "iocost-bot/{issue}".to_string(),
+            title: "Automated update from issue {issue}".to_string(),
+            trailer: "Signed-off-by: {name} <{email}>".to_string(),
+        }
+    }
+}
+
+impl CommitSettings {
+    /// Creates the settings of the [commits] section of the config file
+    pub fn from_config(config: &CommitsConfig) -> Result<Self> {
+        let default = CommitSettings::default();
+        let settings = CommitSettings {
+            branch: config.branch.clone().unwrap_or(default.branch),
+            title: config.title.clone().unwrap_or(default.title),
+            trailer: config.trailer.clone().unwrap_or(default.trailer),
+        };
+        if settings.branch.matches("{issue}").count() != 1 {
+            bail!("The branch template {:?} must have one {{issue}} placeholder", settings.branch);
+        }
+        if settings.branch.replace("{issue}", "").contains(['{', '}']) {
+            bail!("The branch template {:?} can only have the {{issue}} placeholder", settings.branch);
+        }
+        Ok(settings)
+    }
+
+    /// Returns the branch the results of `issue` are committed to
+    pub fn branch(&self, issue: u64) -> String {
+        self.branch.replace("{issue}", &issue.to_string())
+    }
+
+    /// Returns the issue whose results are committed to `branch_name`,
+    /// if it is the branch of a submission
+    pub fn issue_of(&self, branch_name: &str) -> Option<u64> {
+        let (prefix, suffix) = self.branch.split_once("{issue}")?;
+        branch_name.strip_prefix(prefix)?.strip_suffix(suffix)?.parse().ok()
+    }
+
+    /// Returns the title of the commit importing the results of `issue`
+    /// for the (model, version) `models`
+    pub fn title(&self, issue: u64, models: &BTreeSet<(String, String)>) -> String {
+        expand(&self.title, issue, models)
+    }
+
+    /// Returns the trailer recording the `signoff` in the commit
+    /// importing the results of `issue` for the (model, version) `models`
+    pub fn trailer(&self, issue: u64, models: &BTreeSet<(String, String)>, signoff: &SignOff) -> String {
+        expand(&self.trailer, issue, models)
+            .replace("{name}", &signoff.name)
+            .replace("{email}", &signoff.email)
+    }
+}
+
+/// Replaces the `{issue}`, `{models}` and `{versions}` placeholders of
+/// `template`
+fn expand(template: &str, issue: u64, models: &BTreeSet<(String, String)>) -> String {
+    let join = |values: BTreeSet<&str>| values.into_iter().collect::<Vec<_>>().join(", ");
+    template
+        .replace("{issue}", &issue.to_string())
+        .replace("{models}", &join(models.iter().map(|(m, _)| m.as_str()).collect()))
+        .replace("{versions}", &join(models.iter().map(|(_, v)| v.as_str()).collect()))
+}
+
+static COMMIT_SETTINGS: OnceLock<CommitSettings> = OnceLock::new();
+
+/// Sets how the branches and commits are named.
+pub fn set_commit_settings(settings: CommitSettings) -> Result<()> {
+    set_once(&COMMIT_SETTINGS, settings, "commit settings")
+}
+
+/// Returns how the branches and commits are named, the default settings
+/// if none were set
+pub fn commit_settings() -> &'static CommitSettings {
+    COMMIT_SETTINGS.get_or_init(CommitSettings::default)
+}
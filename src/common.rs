@@ -1,15 +1,42 @@
-use anyhow::{anyhow, bail, Result, Context};
 use glob::glob;
 use json::JsonValue;
 use semver::{Version, VersionReq};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::fs;
 use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
 use regex::Regex;
-
-const MINIMUM_DATA_POINTS: usize = 4;
-const MINIMUM_DIFFERENT_RESULTS: u64 = 1;
+use serde::{Deserialize, Serialize};
+
+use crate::bench_runner::bench_runner;
+use crate::database::Database;
+use crate::error::{io_error, Error, Result};
+use crate::formatter::FORMATTER;
+use crate::fw_policy::fw_merge_policies;
+use crate::high_level::HighLevelSummary;
+use crate::hwdb::{HwdbCandidate, SignedResults, SupersededFirmware};
+use crate::index::ResultMetadata;
+use crate::lfs;
+use crate::merge_window::merge_window;
+use crate::metrics::METRICS;
+use crate::progress::{debug, log};
+use crate::resources::{output_accounted, PipelineStage, RESOURCES};
+use crate::retry::with_retries;
+
+/// resctl-bench versions (major.minor) the pipeline knows about
+pub static BENCH_VERSIONS: &[&str] = &["2.1", "2.2"];
+
+/// Data points a merge, or one of its iocost-tune series, needs for its
+/// solutions to be trusted
+pub const MINIMUM_DATA_POINTS: usize = 4;
+
+/// iocost-tune series the data points of the merges are counted in
+const DATA_POINTS_SERIES: &str = "MOF";
+
+/// Full versions of the resctl-bench binaries by major.minor, which
+/// `--version` is only run once for
+static FULL_VERSIONS: LazyLock<Mutex<HashMap<String, Version>>> = LazyLock::new(Mutex::default);
 
 #[derive(Debug, Eq, Hash, PartialEq)]
 pub enum MajorMinor {
@@ -29,24 +56,35 @@ impl BenchVersion {
     /// Creates a BenchVersion from a `version` string (X.Y), runs
     /// `resctl-bench --version` on the appropriate binary version and
     /// parses the output to get the full version string.
-    pub fn new(version: &str) -> Self {
+    pub fn new(version: &str) -> Result<Self> {
         let major_minor = match version {
             "2.1" => MajorMinor::V2_1,
             "2.2" => MajorMinor::V2_2,
-            _ => unimplemented!(),
+            _ => return Err(Error::UnsupportedVersion { version: version.to_string() }),
         };
-        let version_str = run_resctl(version, &["--version"])
-            .expect("Could not run resctl-bench to get version");
+        if let Some(semver) = FULL_VERSIONS.lock().unwrap().get(version) {
+            return Ok(BenchVersion { major_minor, semver: semver.clone() });
+        }
+        let version_str = run_resctl(version, &["--version"])?;
         let re = Regex::new(r"resctl-bench (?<version>\d+\.\d+\.\d+)[^\s]?")
             .unwrap();
-        let caps = re.captures(&version_str)
-            .expect(&format!("Error parsing resctl-bench --version: {}", version_str));
-        let semver = Version::parse(caps.name("version").unwrap().as_str())
-            .expect("Failed to parse version with semver");
-        BenchVersion {
+        let parse_error = || Error::ResctlVersion {
+            version: version.to_string(),
+            output: version_str.clone(),
+        };
+        let caps = re.captures(&version_str).ok_or_else(parse_error)?;
+        let semver = Version::parse(&caps["version"]).map_err(|_| parse_error())?;
+        FULL_VERSIONS.lock().unwrap().insert(version.to_string(), semver.clone());
+        Ok(BenchVersion {
             major_minor,
             semver,
-        }
+        })
+    }
+
+    /// Whether this version of resctl-bench can generate hwdb files
+    pub fn has_hwdb(&self) -> bool {
+        // The hwdb subcommand was introduced in resctl-bench v2.2.4.
+        !VersionReq::parse("<2.2.4").unwrap().matches(&self.semver)
     }
 }
 
@@ -58,6 +96,34 @@ pub struct BenchMerge {
     pub path: PathBuf,
     pub data_points: usize,
     pub fwmerge: Option<BenchFWMerge>,
+    /// Results left out of the merges by maintainers
+    pub excluded: Vec<ExcludedResult>,
+    /// Results of the merge signed by their vendor, if any
+    pub signed: Option<SignedResults>,
+}
+
+/// Data points of an iocost-tune series of a result
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Serialize)]
+pub struct SeriesPoints {
+    pub data: usize,
+    pub outliers: usize,
+}
+
+impl SeriesPoints {
+    /// Whether the series has too few data points, outliers aside, for
+    /// the solutions relying on it
+    pub fn is_starved(&self) -> bool {
+        self.data < MINIMUM_DATA_POINTS
+    }
+}
+
+/// A result excluded from the merges through its metadata
+#[derive(Debug, Clone, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub struct ExcludedResult {
+    pub path: PathBuf,
+    pub model_name: String,
+    pub version: String,
+    pub reason: Option<String>,
 }
 
 #[derive(Debug, Eq, Hash, PartialEq)]
@@ -65,204 +131,449 @@ pub struct BenchFWMerge {
     pub fwrev: String,
     pub path: PathBuf,
     pub data_points: usize,
+    /// Older firmware revisions whose results are superseded by those
+    /// of `fwrev` for the hwdb
+    pub superseded: Vec<String>,
+}
+
+/// A firmware revision of a model in a merge plan
+#[derive(Debug, Serialize)]
+pub struct FwrevPlan {
+    pub fwrev: String,
+    pub results: usize,
+    /// Estimated data points of the merge of its results
+    pub data_points: usize,
+}
+
+/// What merging a model would do, estimated from its result files
+/// without running resctl-bench
+#[derive(Debug, Serialize)]
+pub struct MergePlan {
+    pub model_name: String,
+    pub version: String,
+    /// Firmware revisions of the results, oldest first
+    pub fwrevs: Vec<FwrevPlan>,
+    /// Estimated data points of the generic merge
+    pub data_points: usize,
+    /// Policy of the firmware-specific merge of the model
+    pub policy: String,
+    /// Firmware revision which would get a merge and a hwdb section of
+    /// its own, if any
+    pub specific: Option<String>,
 }
 
-#[allow(dead_code)]
 impl BenchMerge {
-    pub fn merge(version: String, model_name: String) -> Result<Self> {
-        let directory = database_directory(&version, &model_name);
-        let output_path = merged_file(&version, &model_name, None);
+    /// Plans the merge of the results of `model_name` for a resctl-bench
+    /// `version` in `database`, applying the merge window, the maintainer
+    /// flags and the firmware-specific merge policy like `merge` does.
+    /// The data points of a merge are estimated as the sum of those of
+    /// its inputs.
+    pub fn plan(database: &Database, version: &str, model_name: &str) -> Result<MergePlan> {
+        let directory = database.model_dir(version, model_name)?;
+        let files = merge_window().filter(model_name, Self::result_paths_for(&directory)?)?;
+        let (files, _) = Self::weighted_inputs(version, model_name, files);
+        // Weighted results are repeated in the inputs, they are read once
+        let mut data_points: HashMap<PathBuf, usize> = HashMap::new();
+        let mut fwrevs = vec![];
+        for (fwrev, files) in Self::group_by_fwrev(files)? {
+            let mut fwrev_data_points = 0;
+            for file in &files {
+                if !data_points.contains_key(file) {
+                    data_points.insert(file.clone(), Self::get_data_points(file)?);
+                }
+                fwrev_data_points += data_points[file];
+            }
+            let mut unique = files;
+            unique.dedup();
+            fwrevs.push(FwrevPlan {
+                fwrev: fwrev.trim_matches('"').to_string(),
+                results: unique.len(),
+                data_points: fwrev_data_points,
+            });
+        }
+        let common_data_points = fwrevs.iter().map(|f| f.data_points).sum();
+        let policy = fw_merge_policies().policy_for(model_name);
+        let specific = fwrevs
+            .last()
+            .filter(|latest| policy.selects(latest.data_points, common_data_points))
+            .map(|latest| latest.fwrev.clone());
+        Ok(MergePlan {
+            model_name: model_name.to_string(),
+            version: version.to_string(),
+            fwrevs,
+            data_points: common_data_points,
+            policy: policy.to_string(),
+            specific,
+        })
+    }
 
-        Self::do_merge(&version, &directory, &output_path)?;
+    /// Merges the results of `model_name` for a resctl-bench `version`
+    /// in `database`. The database layout must exist.
+    pub fn merge(database: &Database, version: String, model_name: String) -> Result<Self> {
+        let _timer = METRICS.merge_duration.start_timer();
+        let _stage = RESOURCES.start(PipelineStage::Merge);
+        Self::merge_model(database, &version, &model_name).map_err(|e| Error::Merge {
+            model: model_name,
+            version,
+            error: Box::new(e),
+        })
+    }
+
+    fn merge_model(database: &Database, version: &str, model_name: &str) -> Result<Self> {
+        let directory = database.model_dir(version, model_name)?;
+        let output_path = database.merged_path(version, model_name, None)?;
+        let files = merge_window().filter(model_name, Self::result_paths_for(&directory)?)?;
+        let (files, excluded) = Self::weighted_inputs(version, model_name, files);
+        if files.is_empty() {
+            return Err(Error::NoResults {
+                model: model_name.to_string(),
+                version: version.to_string(),
+                dir: directory.clone(),
+            });
+        }
+
+        Self::merge_files(version, &files, &output_path)?;
 
         let data_points = Self::get_data_points(&output_path)?;
+        let signed = Self::signed_results(version, model_name, &files);
 
-        let fwmerge = Self::try_fwmerge(data_points, &version, &model_name, &directory)?;
+        let fwmerge = Self::try_fwmerge(database, data_points, version, model_name, &directory, files)?;
 
         Ok(BenchMerge {
-            version: BenchVersion::new(&version),
-            version_str: version,
-            model_name,
+            version: BenchVersion::new(version)?,
+            version_str: version.to_string(),
+            model_name: model_name.to_string(),
             path: output_path,
             data_points,
             fwmerge,
+            excluded,
+            signed,
+        })
+    }
+
+    /// Returns the results among the merge inputs `files` signed by their
+    /// vendor, None if none is
+    fn signed_results(version: &str, model_name: &str, files: &[PathBuf]) -> Option<SignedResults> {
+        // The inputs are repeated by their weight
+        let files: BTreeSet<&PathBuf> = files.iter().collect();
+        let mut signed_by: BTreeMap<String, usize> = BTreeMap::new();
+        for metadata in files.iter().filter_map(|f| ResultMetadata::load_for(f)) {
+            if let Some(vendor) = metadata.signed_by {
+                *signed_by.entry(vendor).or_default() += 1;
+            }
+        }
+        (!signed_by.is_empty()).then(|| SignedResults {
+            model_name: model_name.to_string(),
+            version: version.to_string(),
+            results: files.len(),
+            signed_by,
         })
     }
 
+    /// Applies the maintainer flags in the metadata of the result
+    /// `files` to the merge inputs: excluded results (and those with a
+    /// weight of 0) are left out and the others repeated `weight` times.
+    /// Returns the inputs and the excluded results.
+    fn weighted_inputs(
+        version: &str,
+        model_name: &str,
+        files: Vec<PathBuf>,
+    ) -> (Vec<PathBuf>, Vec<ExcludedResult>) {
+        let mut inputs = vec![];
+        let mut excluded = vec![];
+        for file in files {
+            let metadata = ResultMetadata::load_for(&file);
+            let weight = metadata.as_ref().and_then(|m| m.weight).unwrap_or(1);
+            match metadata {
+                Some(m) if m.exclude || weight == 0 => {
+                    log(format!(
+                        "Model {}: left {} out of the merge: {}",
+                        model_name,
+                        file.display(),
+                        m.exclude_reason.as_deref().unwrap_or("no reason given")
+                    ));
+                    excluded.push(ExcludedResult {
+                        path: file,
+                        model_name: model_name.to_string(),
+                        version: version.to_string(),
+                        reason: m.exclude_reason,
+                    });
+                }
+                _ => inputs.extend(std::iter::repeat_n(file, weight as usize)),
+            }
+        }
+        (inputs, excluded)
+    }
+
     fn try_fwmerge(
+        database: &Database,
         common_data_points: usize,
         version: &str,
         model_name: &str,
         directory: &Path,
+        files: Vec<PathBuf>,
     ) -> Result<Option<BenchFWMerge>> {
-        let results = Self::result_paths_for(directory)?;
-
-        let mut fwrev_map: HashMap<String, Vec<PathBuf>> = HashMap::new();
-
-        // This uses alphabetical sorting to determine the latest firmware revision.
-        // Based on how fwupd compares versions for NVME devices it should be good
-        // enough, as it uses the PLAIN format for version numbers of NVME devices,
-        // and does a simple g_strcmp0() for those.
-        let max_fwrev = results
-            .iter()
-            .map(|r| {
-                let json = &load_json(&r.to_string_lossy()).expect("Failed to load result")[0];
-                let fwrev = json["sysinfo"]["sysreqs_report"]["scr_dev_fwrev"].to_string();
-
-                fwrev_map.entry(fwrev.clone()).or_default().push(r.clone());
-
-                fwrev
-            })
-            .max_by(|a, b| a.cmp(b))
-            .unwrap();
-
-        let output_path = merged_file(version, model_name,
-            Some(max_fwrev.as_str()));
-        let mut arguments = vec![
-            "--result".to_string(),
-            output_path.to_string_lossy().to_string(),
-            "merge".to_string(),
-        ];
-        arguments.extend(
-            fwrev_map
-                .get(&max_fwrev)
-                .unwrap()
-                .iter()
-                .map(|p| p.to_string_lossy().to_string()),
-        );
-
-        let mut output = format!(
-            "Merging FW-specific results with: {}\n",
-            arguments.join(" ")
-        );
-        output.push_str(&run_resctl(version, arguments.as_slice())?);
-        println!("{}", output);
+        let mut fwrev_map = Self::group_by_fwrev(files)?;
+        let max_fwrev = fwrev_map
+            .keys()
+            .next_back()
+            .cloned()
+            .ok_or_else(|| Error::NoResults {
+                model: model_name.to_string(),
+                version: version.to_string(),
+                dir: directory.to_path_buf(),
+            })?;
+
+        let output_path = database.merged_path(version, model_name, Some(max_fwrev.as_str()))?;
+        let latest = fwrev_map.remove(&max_fwrev).unwrap_or_default();
+        log(format!("Merging the FW-specific results of {}", max_fwrev));
+        Self::merge_files(version, &latest, &output_path)?;
 
         let data_points = Self::get_data_points(&output_path)?;
-        // If there are almost the same number of results for the
-        // generic merge as there are for the specific fwrev, just use
-        // the generic one.
-        if (data_points as i64 - common_data_points as i64).unsigned_abs()
-            >= MINIMUM_DIFFERENT_RESULTS
-            && data_points >= MINIMUM_DATA_POINTS
-        {
-            println!(
+        // If the generic merge has about the same results as the specific
+        // fwrev, as the policy of the model sees it, just use the generic
+        // one.
+        let policy = fw_merge_policies().policy_for(model_name);
+        if policy.selects(data_points, common_data_points) {
+            log(format!(
                 "Model {} fwrev {} has enough data points: {}, generating specific solution.",
                 model_name, max_fwrev, data_points
-            );
+            ));
+            let mut superseded: Vec<String> = fwrev_map.keys().cloned().collect();
+            superseded.sort();
+            Self::set_superseded_by(&latest, None)?;
+            for paths in fwrev_map.values() {
+                Self::set_superseded_by(paths, Some(&max_fwrev))?;
+            }
             return Ok(Some(BenchFWMerge {
                 fwrev: max_fwrev,
                 path: output_path,
                 data_points,
+                superseded,
             }));
         }
+        for paths in fwrev_map.values().chain([&latest]) {
+            Self::set_superseded_by(paths, None)?;
+        }
 
         if data_points < MINIMUM_DATA_POINTS {
-            println!(
+            log(format!(
                 "Model {} fwrev {} has too few data points: {}, no specific solution generated.",
                 model_name, max_fwrev, data_points
-            );
+            ));
         } else {
-            println!("Model {} fwrev {} has almost the same input as the generic one, no specific solution generated.", model_name, max_fwrev);
+            log(format!(
+                "Model {} fwrev {} has almost the same input as the generic one under the {} policy, \
+                 no specific solution generated.",
+                model_name, max_fwrev, policy
+            ));
         }
 
-        std::fs::remove_file(output_path)?;
+        std::fs::remove_file(&output_path).map_err(io_error(output_path))?;
         Ok(None)
     }
 
+    /// Returns the firmware revisions superseded by the fwrev-specific
+    /// merge, if any
+    pub fn superseded_firmware(&self) -> Option<SupersededFirmware> {
+        self.fwmerge
+            .as_ref()
+            .filter(|fw| !fw.superseded.is_empty())
+            .map(|fw| SupersededFirmware {
+                model_name: self.model_name.clone(),
+                version: self.version_str.clone(),
+                fwrev: fw.fwrev.clone(),
+                superseded: fw.superseded.clone(),
+            })
+    }
+
+    /// Returns the hwdb candidate of the merge, whose hwdb input is
+    /// generated by `create_hwdb_in`
+    pub fn hwdb_candidate(&self) -> HwdbCandidate {
+        HwdbCandidate {
+            model_name: self.model_name.clone(),
+            version: self.version_str.clone(),
+            data_points: self.data_points,
+            hwdb_input: PathBuf::from(self.build_descriptive_filename("hwdb", None)),
+            superseded: self.superseded_firmware(),
+            signed: self.signed.clone(),
+        }
+    }
+
+    /// Marks the results in `paths` as superseded by those of `fwrev` in
+    /// their metadata, or clears the mark if `fwrev` is None
+    fn set_superseded_by(paths: &[PathBuf], fwrev: Option<&str>) -> Result<()> {
+        for path in paths {
+            let Some(mut metadata) = ResultMetadata::load_for(path) else {
+                continue;
+            };
+            if metadata.superseded_by.as_deref() == fwrev {
+                continue;
+            }
+            metadata.superseded_by = fwrev.map(str::to_string);
+            metadata
+                .save_for(path)
+                .map_err(io_error(ResultMetadata::path_for(path)))?;
+        }
+        Ok(())
+    }
+
+    /// Returns the result files in `directory` keyed by firmware
+    /// revision, oldest first.
+    pub fn results_by_fwrev(directory: &Path) -> Result<BTreeMap<String, Vec<PathBuf>>> {
+        Self::group_by_fwrev(Self::result_paths_for(directory)?)
+    }
+
+    /// Returns the result `files` keyed by firmware revision, oldest
+    /// first.
+    fn group_by_fwrev(files: Vec<PathBuf>) -> Result<BTreeMap<String, Vec<PathBuf>>> {
+        // This uses alphabetical sorting to determine the latest firmware revision.
+        // Based on how fwupd compares versions for NVME devices it should be good
+        // enough, as it uses the PLAIN format for version numbers of NVME devices,
+        // and does a simple g_strcmp0() for those.
+        let mut fwrev_map: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+        for r in files {
+            let json = &load_json(&r.to_string_lossy())?[0];
+            let fwrev = json["sysinfo"]["sysreqs_report"]["scr_dev_fwrev"].to_string();
+            fwrev_map.entry(fwrev).or_default().push(r);
+        }
+        Ok(fwrev_map)
+    }
+
     pub fn do_merge(version: &str, directory: &Path, output_path: &Path) -> Result<()> {
-        let results = Self::result_paths_for(directory)?
-            .into_iter()
-            .map(|p| p.to_string_lossy().to_string());
+        Self::merge_files(version, &Self::result_paths_for(directory)?, output_path)
+    }
 
-        let mut arguments = vec![
-            "--result".to_string(),
-            output_path.to_string_lossy().to_string(),
-            "merge".to_string(),
-        ];
-        arguments.extend(results);
+    /// Merges the result `files` into `output_path`. Each attempt
+    /// merges into a fresh path next to it, which replaces `output_path`
+    /// once the merge succeeded.
+    pub fn merge_files(version: &str, files: &[PathBuf], output_path: &Path) -> Result<()> {
+        let mut inputs = vec![];
+        for path in files {
+            inputs.push(lfs::resolve(path)?.to_string_lossy().to_string());
+        }
+        let dir = match output_path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        with_retries(&format!("Merging into {}", output_path.display()), || {
+            let attempt_dir = tempfile::tempdir_in(dir).map_err(io_error(dir))?;
+            let merged = attempt_dir.path().join(output_path.file_name().unwrap_or_default());
+            let mut arguments = vec![
+                "--result".to_string(),
+                merged.to_string_lossy().to_string(),
+                "merge".to_string(),
+            ];
+            arguments.extend(inputs.iter().cloned());
+
+            let mut output = format!("Merging results with: {}\n", arguments.join(" "));
+            output.push_str(&run_resctl(version, arguments.as_slice())?);
+            log(output);
+            fs::rename(&merged, output_path).map_err(io_error(output_path))
+        })
+    }
 
-        let mut output = format!("Merging results with: {}\n", arguments.join(" "));
-        output.push_str(&run_resctl(version, arguments.as_slice())?);
-        println!("{}", output);
+    /// Returns the data points of the MOF series of the iocost-tune
+    /// result in `path`, outliers included. None of the resctl-bench
+    /// versions formats them in a machine-readable way, so the result is
+    /// read, the series being laid out the same in 2.1 and 2.2.
+    pub fn get_data_points(path: &Path) -> Result<usize> {
+        let result = Self::iocost_tune_result(path)?;
+        let series = &result["result"]["data"][DATA_POINTS_SERIES];
+        // A layout change would otherwise count no data points and leave
+        // the model out of the hwdb without notice
+        if !series["data"].is_array() {
+            return Err(Error::MissingDataSeries {
+                file: path.to_path_buf(),
+                series: DATA_POINTS_SERIES.to_string(),
+            });
+        }
+        Ok(series["data"].len() + series["outliers"].len())
+    }
 
-        Ok(())
+    /// Returns the data points of each iocost-tune series of the result
+    /// in `path`, e.g. MOF or isol-01, by series: a model with enough
+    /// data points overall can still have too few for some QoS targets.
+    pub fn get_series_points(path: &Path) -> Result<BTreeMap<String, SeriesPoints>> {
+        let result = Self::iocost_tune_result(path)?;
+        Ok(result["result"]["data"]
+            .entries()
+            .map(|(name, series)| {
+                let points = SeriesPoints {
+                    data: series["data"].len(),
+                    outliers: series["outliers"].len(),
+                };
+                (name.to_string(), points)
+            })
+            .collect())
+    }
+
+    /// Returns the QoS solutions of the iocost-tune result in `path`,
+    /// none if resctl-bench found none
+    pub fn get_solutions(path: &Path) -> Result<HighLevelSummary> {
+        let result = Self::iocost_tune_result(path)?;
+        Ok(HighLevelSummary::from_solutions(&result["result"]["solutions"]))
     }
 
-    fn get_data_points(path: &Path) -> Result<usize> {
-        // TODO: we probably want to move this processing to resctl-bench format output.
-        let result = load_json(&path.to_string_lossy())?;
-        let result = result
+    fn iocost_tune_result(path: &Path) -> Result<JsonValue> {
+        load_json(&path.to_string_lossy())?
             .members()
             .find(|v| v["spec"]["kind"] == "iocost-tune")
-            .expect("Could not find iocost-tune spec in merge file");
-
-        Ok(result["result"]["data"]["MOF"]["data"].members().count()
-            + result["result"]["data"]["MOF"]["outliers"]
-                .members()
-                .count())
+            .cloned()
+            .ok_or_else(|| Error::MissingIocostTune { file: path.to_path_buf() })
     }
 
     fn result_paths_for(directory: &Path) -> Result<Vec<PathBuf>> {
         Ok(
-            glob(&format!("{}/*.json.gz", directory.to_string_lossy()))
-                .unwrap()
+            glob(&directory.join("*.json.gz").to_string_lossy())?
                 .flatten()
                 .collect(),
         )
     }
 
-    pub fn save_pdf_in(&self, target_dir: &Path) -> Result<()> {
+    /// Generates the pdf for the merged result in `target_dir` and
+    /// returns its path.
+    pub fn save_pdf_in(&self, target_dir: &Path) -> Result<PathBuf> {
+        let _stage = RESOURCES.start(PipelineStage::Pdf);
         let filename = self.build_descriptive_filename("pdf", None);
         save_pdf_to(&self.version_str, &self.path, target_dir, filename)
     }
 
-    pub fn create_hwdb_in(&self, target_dir: &Path) -> Result<()> {
-        fs::create_dir_all(target_dir).expect("Could not create the target hwdb directory");
+    /// Generates the hwdb file for the merged result in `target_dir`
+    /// and returns its path, or `None` if the resctl-bench version
+    /// can't generate hwdb files.
+    pub fn create_hwdb_in(&self, target_dir: &Path) -> Result<Option<PathBuf>> {
+        let _stage = RESOURCES.start(PipelineStage::Hwdb);
+        fs::create_dir_all(target_dir).map_err(io_error(target_dir))?;
 
-        // The hwdb subcommand was introduced in resctl-bench v2.2.4.
-        if VersionReq::parse("<2.2.4")
-            .unwrap()
-            .matches(&self.version.semver)
-        {
-            println!(
+        if !self.version.has_hwdb() {
+            log(format!(
                 "Skipping hwdb generation as this version of resctl-bench does not have hwdb support: {}",
                 self.version.semver
-            );
-            return Ok(());
+            ));
+            return Ok(None);
         }
 
         let filename = self.build_descriptive_filename("hwdb", None);
+        let hwdb_path = target_dir.join(filename);
 
-        let mut file = fs::File::create(target_dir.join(filename))?;
-
-        let output = run_resctl(
-            &self.version_str,
-            &[
-                "--result",
-                &self.path.to_string_lossy(),
-                "format",
-                "iocost-tune:hwdb",
-            ],
-        )?;
-
-        write!(file, "{}", output)?;
-
+        // The input of a previous run mustn't be taken for this one's
+        // when the generation fails
+        let format = |path: &Path, spec: &str| {
+            FORMATTER.format(&self.version_str, path, spec).inspect_err(|_| {
+                fs::remove_file(&hwdb_path).ok();
+            })
+        };
+        let mut output = format(&self.path, "iocost-tune:hwdb")?;
         if let Some(fwmerge) = &self.fwmerge {
-            let output = run_resctl(
-                &self.version_str,
-                &[
-                    "--result",
-                    &fwmerge.path.to_string_lossy(),
-                    "format",
-                    "iocost-tune:hwdb-fwrev",
-                ],
-            )?;
-
-            write!(file, "\n{}", output)?;
+            output.push('\n');
+            output.push_str(&format(&fwmerge.path, "iocost-tune:hwdb-fwrev")?);
         }
 
-        Ok(())
+        let mut file = fs::File::create(&hwdb_path).map_err(io_error(&hwdb_path))?;
+        write!(file, "{}", output).map_err(io_error(&hwdb_path))?;
+
+        Ok(Some(hwdb_path))
     }
 
     pub fn build_descriptive_filename<'a, D: Into<Option<&'a str>>>(
@@ -295,8 +606,8 @@ pub fn save_pdf_to(
     result: &Path,
     target_dir: &Path,
     filename: impl Into<Option<String>>,
-) -> Result<()> {
-    fs::create_dir_all(target_dir)?;
+) -> Result<PathBuf> {
+    fs::create_dir_all(target_dir).map_err(io_error(target_dir))?;
 
     // Build target path while replacing the json.gz extension with .pdf.
     let pdf_path = match filename.into() {
@@ -304,7 +615,10 @@ pub fn save_pdf_to(
         None => {
             let result_filename = result
                 .file_name()
-                .expect("Malformed result path")
+                .ok_or_else(|| Error::InvalidResult {
+                    file: result.to_path_buf(),
+                    reason: "malformed result path".to_string(),
+                })?
                 .to_string_lossy()
                 .to_string();
             target_dir
@@ -314,70 +628,92 @@ pub fn save_pdf_to(
         }
     };
 
-    println!("PDF Path: {:#?}\n", pdf_path);
-    run_resctl(
-        version,
-        &[
-            "--result",
-            &result.to_string_lossy(),
-            "format",
-            &format!("iocost-tune:pdf={}", pdf_path.to_string_lossy()),
-        ],
-    )
-    .map(|_| ())
+    log(format!("PDF Path: {:#?}\n", pdf_path));
+    FORMATTER
+        .format(version, result, &format!("iocost-tune:pdf={}", pdf_path.to_string_lossy()))
+        .map(|_| pdf_path)
+}
+
+/// Returns the summary resctl-bench prints for the `result` file, or
+/// its full formatted output with `full`
+pub fn format_result(version: &str, result: &Path, full: bool) -> Result<String> {
+    let result = result.to_string_lossy();
+    let subcommand = if full { "format" } else { "summary" };
+    run_resctl(version, &["--result", &result, subcommand])
 }
 
 /// Reads a gzipped json file, parses it and returns its contents
-#[allow(dead_code)]
 pub fn load_json(filename: &str) -> Result<JsonValue> {
-    let f = std::fs::File::open(&filename)?;
+    let invalid = |reason: String| Error::InvalidResult {
+        file: PathBuf::from(filename),
+        reason,
+    };
+    let path = lfs::resolve(Path::new(filename))?;
+    let f = std::fs::File::open(&path).map_err(io_error(filename))?;
 
     let mut buf = vec![];
-    libflate::gzip::Decoder::new(f)?.read_to_end(&mut buf)?;
+    libflate::gzip::Decoder::new(f)
+        .and_then(|mut d| d.read_to_end(&mut buf))
+        .map_err(|e| invalid(e.to_string()))?;
 
-    Ok(json::parse(&String::from_utf8(buf)?)?)
+    let contents = String::from_utf8(buf).map_err(|e| invalid(e.to_string()))?;
+    json::parse(&contents).map_err(|e| invalid(e.to_string()))
+}
+
+/// Whether resctl-bench runs on this platform. Elsewhere, only the
+/// commands that don't validate, merge or render results are available.
+pub const RESCTL_SUPPORTED: bool = cfg!(target_os = "linux");
+
+/// Returns the path of the resctl-bench binary of `version`
+pub fn resctl_bench_path(version: &str) -> PathBuf {
+    Path::new(".").join(format!("resctl-demo-v{}", version)).join("resctl-bench")
 }
 
 /// Runs a specific `version` of resctl-bench with a list of arguments
-/// (`args`) and returns its output.
+/// (`args`) and returns its output. The runner set with
+/// `set_bench_runner`, if any, runs in place of the binary.
 pub fn run_resctl<S: AsRef<std::ffi::OsStr>>(version: &str, args: &[S]) -> Result<String> {
-    let bench_path = format!("./resctl-demo-v{}/resctl-bench", version);
+    if let Some(runner) = bench_runner() {
+        let args: Vec<String> = args.iter().map(|a| a.as_ref().to_string_lossy().to_string()).collect();
+        debug(format!("Running resctl-bench {} {}", version, args.join(" ")));
+        return runner.run(version, &args);
+    }
+    if !RESCTL_SUPPORTED {
+        return Err(Error::ResctlUnsupported { version: version.to_string() });
+    }
+    let bench_path = resctl_bench_path(version);
     // args as string for error message
     let args_string = args
         .iter()
-        .map(|s| s.as_ref().to_str().unwrap())
+        .map(|s| s.as_ref().to_string_lossy())
         .collect::<Vec<_>>()
         .join(" ");
-    let output = std::process::Command::new(&bench_path)
-        .args(args)
-        .output()
-        .with_context(
-            || format!("Error running {} {}: ", bench_path, args_string))?;
+    // Label the subprocess time with the resctl-bench subcommand, which
+    // comes after the "--result <file>" pair if present.
+    let subcommand = match args.first().map(|a| a.as_ref().to_string_lossy()) {
+        Some(a) if a == "--result" => args.get(2).map(|a| a.as_ref().to_string_lossy()),
+        a => a,
+    }
+    .unwrap_or_default()
+    .to_string();
+    debug(format!("Running {} {}", bench_path.display(), args_string));
+    let timer = METRICS
+        .resctl_duration
+        .with_label_values(&[subcommand.as_str()])
+        .start_timer();
+    let output = output_accounted(std::process::Command::new(&bench_path).args(args))
+        .map_err(|error| Error::ResctlSpawn {
+            version: version.to_string(),
+            args: args_string.clone(),
+            error,
+        })?;
+    timer.observe_duration();
     if !output.stderr.is_empty() {
-        bail!(String::from_utf8(output.stderr)?);
+        return Err(Error::ResctlFailed {
+            version: version.to_string(),
+            args: args_string,
+            stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+        });
     }
-    String::from_utf8(output.stdout).map_err(|e| anyhow!(e))
-}
-
-/// Returns the database directory path for a specific restcl-bench
-/// version and HD model name
-pub fn database_directory(version: &str, model_name: &str) -> PathBuf {
-    PathBuf::from(format!("database/{}/{}", version, model_name))
-}
-
-/// Returns a file path for a merged result file for a specific
-/// resctl-bench `version` and HD `model_name`, with an optional
-/// `detail` string.
-/// It ensures that a "merged-results" output directory exists, creating
-/// it if necessary.
-pub fn merged_file(version: &str, model_name: &str, detail: Option<&str>)-> PathBuf {
-    fs::create_dir_all("merged-results").expect("Failed to create merged results dir");
-    let detail = match detail {
-        Some(d) => d,
-        None => "",
-    };
-    PathBuf::from("merged-results").join(&format!(
-        "{}-{}-{}-merged-results.json.gz",
-        version, model_name, detail
-    ))
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
 }
@@ -0,0 +1,346 @@
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+/// Top-level struct to parse the config toml file
+#[derive(Debug, Deserialize)]
+pub struct TomlData {
+    pub config: Config,
+    pub notifications: Option<NotificationsConfig>,
+    pub digest: Option<DigestConfig>,
+    pub metrics: Option<MetricsConfig>,
+    pub merge: Option<MergeConfig>,
+    pub fwmerge: Option<FwMergeConfig>,
+    pub limits: Option<LimitsConfig>,
+    pub http: Option<HttpConfig>,
+    pub urls: Option<UrlsConfig>,
+    pub lfs: Option<LfsConfig>,
+    pub storage: Option<StorageConfig>,
+    pub scrub: Option<ScrubConfig>,
+    pub consent: Option<ConsentConfig>,
+    pub signoff: Option<SignOffConfig>,
+    pub signatures: Option<SignaturesConfig>,
+    pub embargo: Option<EmbargoConfig>,
+    pub issues: Option<IssuesConfig>,
+    pub review: Option<ReviewConfig>,
+    pub commits: Option<CommitsConfig>,
+    pub retry: Option<RetryConfig>,
+    pub setup: Option<SetupConfig>,
+}
+
+/// Struct to parse the [config] section of the config toml file
+#[derive(Debug, Deserialize)]
+pub struct Config {
+    pub database_dir: Option<String>,
+    /// Locale of the bot messages and reports (default: en)
+    pub locale: Option<String>,
+    /// resctl-bench versions whose results are kept but no longer merged
+    /// into the hwdb (default: 2.1)
+    pub legacy_versions: Option<Vec<String>>,
+}
+
+/// Struct to parse the [notifications] section of the config toml file
+#[derive(Debug, Deserialize)]
+pub struct NotificationsConfig {
+    /// Slack incoming webhook URL
+    pub slack_webhook: Option<String>,
+    pub matrix: Option<MatrixConfig>,
+}
+
+/// Matrix room to post notifications to
+#[derive(Debug, Clone, Deserialize)]
+pub struct MatrixConfig {
+    /// Homeserver base URL, e.g. https://matrix.org
+    pub homeserver: String,
+    pub room_id: String,
+    pub access_token: String,
+}
+
+/// Struct to parse the [digest] section of the config toml file
+#[derive(Debug, Deserialize)]
+pub struct DigestConfig {
+    pub smtp: Option<SmtpConfig>,
+}
+
+/// SMTP server and addresses the activity digest is sent with
+#[derive(Debug, Deserialize)]
+pub struct SmtpConfig {
+    pub server: String,
+    pub port: Option<u16>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub from: String,
+    pub to: Vec<String>,
+}
+
+/// Struct to parse the [metrics] section of the config toml file
+#[derive(Debug, Deserialize)]
+pub struct MetricsConfig {
+    /// Prometheus pushgateway base URL
+    pub pushgateway: String,
+    /// Job name to push the metrics under
+    pub job: Option<String>,
+}
+
+/// Struct to parse the [merge] section of the config toml file
+#[derive(Debug, Deserialize)]
+pub struct MergeConfig {
+    /// Window of the models without a specific one
+    #[serde(flatten)]
+    pub window: MergeWindowConfig,
+    /// Windows of specific models, keyed by model name
+    #[serde(default)]
+    pub models: HashMap<String, MergeWindowConfig>,
+}
+
+/// Results included in the merges: either the last `max_age_months` or
+/// `since` a date (YYYY-MM-DD)
+#[derive(Debug, Deserialize)]
+pub struct MergeWindowConfig {
+    pub max_age_months: Option<u32>,
+    pub since: Option<String>,
+}
+
+/// Struct to parse the [fwmerge] section of the config toml file
+#[derive(Debug, Deserialize)]
+pub struct FwMergeConfig {
+    /// Policy of the models without a specific one
+    #[serde(flatten)]
+    pub policy: FwMergePolicyConfig,
+    /// Policies of specific models, keyed by model name
+    #[serde(default)]
+    pub models: HashMap<String, FwMergePolicyConfig>,
+}
+
+/// When the latest firmware revision gets a merge of its own: when its
+/// data points differ from the generic merge by at least `min_delta`
+/// (default: 1) or `min_ratio` of them, or whenever it has `min_points`
+#[derive(Debug, Deserialize)]
+pub struct FwMergePolicyConfig {
+    pub min_delta: Option<u64>,
+    pub min_ratio: Option<f64>,
+    pub min_points: Option<usize>,
+}
+
+/// Struct to parse the [limits] section of the config toml file
+#[derive(Debug, Deserialize)]
+pub struct LimitsConfig {
+    /// Maximum number of result URLs in a submission
+    pub max_urls: Option<usize>,
+    /// Maximum total size of the result files of a submission, in bytes
+    pub max_total_bytes: Option<u64>,
+    /// Maximum number of submissions of a user who is not a maintainer
+    /// processed per day, unlimited if not set
+    pub max_submissions_per_day: Option<usize>,
+}
+
+/// Struct to parse the [http] section of the config toml file
+#[derive(Debug, Deserialize)]
+pub struct HttpConfig {
+    /// Proxy URL of the downloads, overriding the HTTPS_PROXY
+    /// environment variable
+    pub proxy: Option<String>,
+    /// PEM file with extra root certificates to trust
+    pub ca_certificates: Option<PathBuf>,
+    /// Private hosts results can be submitted from
+    #[serde(default)]
+    pub hosts: Vec<HostConfig>,
+}
+
+/// Struct to parse the [urls] section of the config toml file, which
+/// sets the links of the submissions taken for result files
+#[derive(Debug, Deserialize)]
+pub struct UrlsConfig {
+    /// Extensions the result file links end with, query string aside
+    /// (default: [".json.gz"])
+    pub extensions: Option<Vec<String>>,
+}
+
+/// Credentials to fetch the results under a URL prefix, which is
+/// allowlisted too
+#[derive(Debug, Clone, Deserialize)]
+pub struct HostConfig {
+    /// https URL prefix the settings apply to
+    pub prefix: String,
+    /// Value of the Authorization header of the requests
+    pub authorization: Option<String>,
+    /// URL the results are actually fetched from, where {url} is
+    /// replaced with the submitted one. For instance, to append a
+    /// pre-signed query string: "{url}?X-Amz-Credential=..."
+    pub url_template: Option<String>,
+}
+
+/// Struct to parse the [lfs] section of the config toml file, which
+/// stores the new result files with Git LFS
+#[derive(Debug, Deserialize)]
+pub struct LfsConfig {
+    /// LFS server of the database repository, if not the Github one
+    pub url: Option<String>,
+}
+
+/// Struct to parse the [storage] section of the config toml file, which
+/// selects where the results and their metadata are kept
+#[derive(Debug, Deserialize)]
+pub struct StorageConfig {
+    /// "git" (default) to commit them to the repository, "s3" to keep
+    /// them in a bucket
+    pub backend: Option<String>,
+    pub bucket: Option<String>,
+    /// Region of the bucket (default: us-east-1)
+    pub region: Option<String>,
+    /// URL of an S3 compatible service, if not AWS
+    pub endpoint: Option<String>,
+    /// Prefix of the keys of the database in the bucket
+    pub prefix: Option<String>,
+}
+
+/// Struct to parse the [scrub] section of the config toml file, which
+/// lists the identifying fields of the results to scrub before storing
+/// them
+#[derive(Debug, Deserialize)]
+pub struct ScrubConfig {
+    /// Fields removed from the results, wherever they appear
+    pub remove: Option<Vec<String>>,
+    /// Fields whose value is replaced with a hash
+    pub hash: Option<Vec<String>>,
+    /// Whether to hash the user names of /home/<user> paths (default:
+    /// true)
+    pub home_paths: Option<bool>,
+}
+
+/// Struct to parse the [consent] section of the config toml file, which
+/// requires the submitters to agree to the data license
+#[derive(Debug, Deserialize)]
+pub struct ConsentConfig {
+    /// Name of the license of the database
+    pub license: String,
+    /// Where the license can be read
+    pub license_url: Option<String>,
+    /// Phrase agreeing to the license outside of the issue form
+    /// checkbox (default: "I agree")
+    pub phrase: Option<String>,
+}
+
+/// Struct to parse the [signoff] section of the config toml file
+#[derive(Debug, Deserialize)]
+pub struct SignOffConfig {
+    /// Whether the submissions need a `Signed-off-by` line of the issue
+    /// author
+    #[serde(default)]
+    pub required: bool,
+}
+
+/// Struct to parse the [signatures] section of the config toml file,
+/// which verifies the signatures submitted with the results
+#[derive(Debug, Deserialize)]
+pub struct SignaturesConfig {
+    /// gpg binary (default: "gpg")
+    pub gpg: Option<String>,
+    #[serde(default)]
+    pub keys: Vec<SignatureKeyConfig>,
+}
+
+/// OpenPGP public key of a vendor, in the [signatures] section
+#[derive(Debug, Deserialize)]
+pub struct SignatureKeyConfig {
+    pub vendor: String,
+    /// Public key file, binary or ASCII-armored
+    pub public_key: PathBuf,
+}
+
+/// Struct to parse the [embargo] section of the config toml file, which
+/// enables the embargoed submissions
+#[derive(Debug, Deserialize)]
+pub struct EmbargoConfig {
+    /// Private directory the embargoed results are held in
+    pub dir: Option<PathBuf>,
+    /// Object storage the embargoed results are held in, instead of the
+    /// directory
+    pub storage: Option<StorageConfig>,
+}
+
+/// Struct to parse the [issues] section of the config toml file
+#[derive(Debug, Deserialize)]
+pub struct IssuesConfig {
+    /// Whether to label the submission issues with the models of their
+    /// results (default: true)
+    pub model_labels: Option<bool>,
+    /// Whether to append the models and versions to the issue titles
+    /// (default: false)
+    pub retitle: Option<bool>,
+    /// File name of the issue form of the submissions, linked from the
+    /// issues without result links
+    pub form: Option<String>,
+    /// URL of the PDF report of a model, with `{model}` and `{version}`
+    /// placeholders, linked when the results of an issue are merged and
+    /// from the READMEs of the models
+    pub pdf_url: Option<String>,
+    /// URL of the hwdb entry of a model, with `{model}` and `{version}`
+    /// placeholders, linked when the results of an issue are merged
+    pub hwdb_url: Option<String>,
+}
+
+/// Struct to parse the [review] section of the config toml file
+#[derive(Debug, Deserialize)]
+pub struct ReviewConfig {
+    /// Whether the results of first-time contributors wait in the
+    /// pending area until a maintainer accepts them
+    #[serde(default)]
+    pub first_time_contributors: bool,
+}
+
+/// Struct to parse the [commits] section of the config toml file, see
+/// `CommitSettings` for the placeholders of the templates
+#[derive(Debug, Deserialize)]
+pub struct CommitsConfig {
+    /// Branch the results of an issue are committed to (default:
+    /// "iocost-bot/{issue}")
+    pub branch: Option<String>,
+    /// Title of the commits (default: "Automated update from issue
+    /// {issue}")
+    pub title: Option<String>,
+    /// Trailer recording the sign-off of the submitter (default:
+    /// "Signed-off-by: {name} <{email}>")
+    pub trailer: Option<String>,
+}
+
+/// Struct to parse the [retry] section of the config toml file
+#[derive(Debug, Deserialize)]
+pub struct RetryConfig {
+    /// Runs of a resctl-bench merge or format before it is declared
+    /// failed (default: 2)
+    pub attempts: Option<u32>,
+    /// Seconds to wait before the next attempt (default: 5)
+    pub delay_secs: Option<u64>,
+}
+
+/// Struct to parse the [setup] section of the config toml file, which
+/// tells `iocost-ci setup` where to build resctl-bench from
+#[derive(Debug, Deserialize)]
+pub struct SetupConfig {
+    /// git repository of resctl-demo (default: the upstream one)
+    pub repository: Option<String>,
+    /// Branch, tag or commit to build each version (X.Y) from
+    #[serde(default)]
+    pub refs: HashMap<String, String>,
+}
+
+impl TomlData {
+    /// Loads and parses the toml config file in `path`
+    pub fn load(path: &str) -> Result<Self> {
+        let contents =
+            fs::read_to_string(path).with_context(|| format!("Can't open config file: {}", path))?;
+        toml::from_str(&contents).with_context(|| format!("Error parsing toml file {}", path))
+    }
+}
+
+/// Sets the `name` setting of the process, read from the config file or
+/// the command line. Settings can only be set once, before they are
+/// first used: what already ran with another value can't be redone, so
+/// setting them again is an error.
+pub fn set_once<T>(setting: &OnceLock<T>, value: T, name: &str) -> Result<()> {
+    setting.set(value).map_err(|_| anyhow!("The {} can only be set once, before being used", name))
+}
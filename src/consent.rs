@@ -0,0 +1,60 @@
+use anyhow::Result;
+use std::sync::OnceLock;
+
+use crate::config::{set_once, ConsentConfig};
+
+/// Default phrase submitters can write to agree to the data license
+const DEFAULT_PHRASE: &str = "I agree";
+
+/// Agreement to the data license of the public database the submitters
+/// have to give before their results are imported, since the database
+/// is redistributed by downstream projects
+#[derive(Debug, Clone)]
+pub struct ConsentSettings {
+    /// Name of the license, such as CDLA-Permissive-2.0
+    pub license: String,
+    pub license_url: Option<String>,
+    /// Phrase agreeing to the license, when not using the checkbox of
+    /// the issue form
+    pub phrase: String,
+}
+
+impl ConsentSettings {
+    /// Creates the settings of the [consent] section of the config file
+    pub fn from_config(config: &ConsentConfig) -> Self {
+        ConsentSettings {
+            license: config.license.clone(),
+            license_url: config.license_url.clone(),
+            phrase: config.phrase.clone().unwrap_or(DEFAULT_PHRASE.to_string()),
+        }
+    }
+
+    /// Whether `body` agrees to the license: with a checked checkbox of
+    /// the issue form mentioning it or the phrase, or with the phrase
+    /// outside of an unchecked checkbox
+    pub fn is_given_in(&self, body: &str) -> bool {
+        let license = self.license.to_lowercase();
+        let phrase = self.phrase.to_lowercase();
+        body.lines().any(|line| {
+            let line = line.trim().trim_start_matches(['-', '*']).trim_start().to_lowercase();
+            if line.starts_with("[ ]") {
+                return false;
+            }
+            let checked = line.starts_with("[x]");
+            line.contains(&phrase) || (checked && line.contains(&license))
+        })
+    }
+}
+
+static CONSENT_SETTINGS: OnceLock<ConsentSettings> = OnceLock::new();
+
+/// Requires the submitters to agree to the data license.
+pub fn set_consent_settings(settings: ConsentSettings) -> Result<()> {
+    set_once(&CONSENT_SETTINGS, settings, "consent settings")
+}
+
+/// Returns the license the submitters have to agree to, None if they
+/// don't need to
+pub fn consent_settings() -> Option<&'static ConsentSettings> {
+    CONSENT_SETTINGS.get()
+}
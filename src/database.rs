@@ -0,0 +1,140 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::{io_error, Error, Result};
+use crate::layout::{layout_version, write_layout_version, CURRENT_LAYOUT_VERSION};
+
+/// Default directory of the merged results
+pub static MERGED_RESULTS_DIR: &str = "merged-results";
+
+/// Longest model name accepted as a directory name
+const MAX_NAME_LEN: usize = 128;
+
+/// Checks that `name`, taken from a result file, is safe to use as a
+/// path component: only ASCII alphanumerics and `_.+-`, not starting
+/// with a dot.
+pub fn validate_name(kind: &'static str, name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name.len() <= MAX_NAME_LEN
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_.+-".contains(c));
+    if !valid {
+        return Err(Error::InvalidName { kind, name: name.to_string() });
+    }
+    Ok(())
+}
+
+/// Checks that `version` is a resctl-bench major.minor version
+pub fn validate_version(version: &str) -> Result<()> {
+    let valid = version
+        .split_once('.')
+        .map(|(major, minor)| {
+            [major, minor]
+                .iter()
+                .all(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+        })
+        .unwrap_or(false);
+    if !valid {
+        return Err(Error::InvalidName { kind: "version", name: version.to_string() });
+    }
+    Ok(())
+}
+
+/// Layout of the results database and of the merged results generated
+/// from it:
+///
+/// ```text
+/// <root>/<version>/<model>/result-<md5>.json.gz
+/// <merged_dir>/<version>-<model>-<detail>-merged-results.json.gz
+/// ```
+///
+/// Paths are only built from validated names, and directories are only
+/// created by `ensure_layout`.
+#[derive(Debug, Clone)]
+pub struct Database {
+    root: PathBuf,
+    merged_dir: PathBuf,
+}
+
+impl Database {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Database {
+            root: root.into(),
+            merged_dir: PathBuf::from(MERGED_RESULTS_DIR),
+        }
+    }
+
+    /// Sets the directory of the merged results
+    pub fn with_merged_dir(mut self, merged_dir: impl Into<PathBuf>) -> Self {
+        self.merged_dir = merged_dir.into();
+        self
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Returns the directory of the results of `model_name` for a
+    /// resctl-bench `version`
+    pub fn model_dir(&self, version: &str, model_name: &str) -> Result<PathBuf> {
+        validate_version(version)?;
+        validate_name("model", model_name)?;
+        Ok(self.root.join(version).join(model_name))
+    }
+
+    /// Returns the path of the merged result of `model_name` for a
+    /// resctl-bench `version`, with an optional `detail` string (the
+    /// firmware revision for firmware-specific merges)
+    pub fn merged_path(&self, version: &str, model_name: &str, detail: Option<&str>) -> Result<PathBuf> {
+        validate_version(version)?;
+        validate_name("model", model_name)?;
+        if let Some(detail) = detail {
+            validate_name("detail", detail)?;
+        }
+        Ok(self.merged_dir.join(format!(
+            "{}-{}-{}-merged-results.json.gz",
+            version,
+            model_name,
+            detail.unwrap_or_default()
+        )))
+    }
+
+    /// Creates the database and merged results directories if needed,
+    /// declaring the current layout version in new databases, and
+    /// checks that the layout of existing ones is supported
+    pub fn ensure_layout(&self) -> Result<()> {
+        let layout_error = |e: anyhow::Error| Error::Layout {
+            root: self.root.clone(),
+            reason: format!("{:#}", e),
+        };
+        let fresh = !self.root.exists();
+        for dir in [&self.root, &self.merged_dir] {
+            fs::create_dir_all(dir).map_err(io_error(dir))?;
+        }
+        if fresh {
+            write_layout_version(&self.root, CURRENT_LAYOUT_VERSION).map_err(layout_error)?;
+            return Ok(());
+        }
+        let version = layout_version(&self.root).map_err(layout_error)?;
+        if version > CURRENT_LAYOUT_VERSION {
+            return Err(Error::Layout {
+                root: self.root.clone(),
+                reason: format!(
+                    "layout v{} is newer than the supported v{}",
+                    version, CURRENT_LAYOUT_VERSION
+                ),
+            });
+        }
+        if version < CURRENT_LAYOUT_VERSION {
+            eprintln!(
+                "Database {} uses layout v{}, run `iocost-ci migrate` to upgrade it to v{}",
+                self.root.display(),
+                version,
+                CURRENT_LAYOUT_VERSION
+            );
+        }
+        Ok(())
+    }
+}
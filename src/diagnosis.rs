@@ -0,0 +1,103 @@
+use crate::error::Error;
+
+/// Number of last lines of the resctl-bench stderr quoted in the comments
+const EXCERPT_LINES: usize = 20;
+/// Lines of the stderr excerpt are cut to this number of characters
+const MAX_EXCERPT_LINE_LEN: usize = 200;
+
+/// Common failures of the validation of a result, recognized to give the
+/// submitter a targeted hint rather than only the raw error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureKind {
+    /// The result was produced by a resctl-bench version the pipeline
+    /// can't process
+    VersionMismatch,
+    /// The result file is truncated or corrupted, e.g. by an interrupted
+    /// upload
+    Truncated,
+    /// The result doesn't include an iocost-tune run
+    NotIocostTune,
+}
+
+/// Lower case substrings of the error messages and of the resctl-bench
+/// stderr recognizing each kind of failure
+const PATTERNS: &[(FailureKind, &[&str])] = &[
+    (
+        FailureKind::Truncated,
+        &[
+            "unexpected end",
+            "unexpected eof",
+            "eof while parsing",
+            "corrupt deflate",
+            "invalid gzip header",
+            "truncated",
+        ],
+    ),
+    (
+        FailureKind::VersionMismatch,
+        &[
+            "unsupported resctl-bench version",
+            "version mismatch",
+            "incompatible version",
+            "bench_version",
+        ],
+    ),
+    (FailureKind::NotIocostTune, &["no iocost-tune", "iocost-tune spec", "unknown bench"]),
+];
+
+impl FailureKind {
+    /// Returns the key of the hint of the failure in the message catalog
+    pub fn hint(&self) -> &'static str {
+        match self {
+            FailureKind::VersionMismatch => "hint-version-mismatch",
+            FailureKind::Truncated => "hint-truncated",
+            FailureKind::NotIocostTune => "hint-not-iocost-tune",
+        }
+    }
+}
+
+/// What went wrong validating a result, as reported to the submitter
+#[derive(Debug)]
+pub struct Diagnosis {
+    pub kind: Option<FailureKind>,
+    /// Error message, without the resctl-bench stderr
+    pub message: String,
+    /// Last lines of the resctl-bench stderr, if it caused the error
+    pub excerpt: Option<String>,
+}
+
+/// Returns the last lines of `stderr`, cut to a readable length
+fn excerpt(stderr: &str) -> String {
+    let lines: Vec<&str> = stderr.trim_end().lines().collect();
+    let mut excerpt: Vec<String> = lines[lines.len().saturating_sub(EXCERPT_LINES)..]
+        .iter()
+        .map(|line| match line.char_indices().nth(MAX_EXCERPT_LINE_LEN) {
+            Some((end, _)) => format!("{}...", &line[..end]),
+            None => line.to_string(),
+        })
+        .collect();
+    if lines.len() > EXCERPT_LINES {
+        excerpt.insert(0, "...".to_string());
+    }
+    excerpt.join("\n")
+}
+
+/// Classifies a validation `error` from its message and from the
+/// resctl-bench stderr in its chain
+pub fn diagnose(error: &anyhow::Error) -> Diagnosis {
+    let stderr = error
+        .chain()
+        .find_map(|e| e.downcast_ref::<Error>())
+        .and_then(|e| e.stderr())
+        .filter(|s| !s.trim().is_empty());
+    let mut message = format!("{:#}", error);
+    if let Some(stderr) = stderr {
+        message = message.replace(stderr, "").trim_end().trim_end_matches(':').to_string();
+    }
+    let text = format!("{:#}", error).to_lowercase();
+    let kind = PATTERNS
+        .iter()
+        .find(|(_, needles)| needles.iter().any(|n| text.contains(n)))
+        .map(|(kind, _)| *kind);
+    Diagnosis { kind, message, excerpt: stderr.map(excerpt) }
+}
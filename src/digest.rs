@@ -0,0 +1,239 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use lettre::message::{header::ContentType, Mailbox};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{Message, SmtpTransport, Transport};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+use crate::catalog::tr;
+use crate::common::BenchMerge;
+use crate::config::SmtpConfig;
+use crate::index::{DatabaseIndex, ModelEntry, ResultMetadata};
+use crate::result::FailedSubmission;
+
+/// Data points of the iocost-tune runs of the results of a model
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DataPointGain {
+    /// Data points of the results imported before the period
+    pub before: usize,
+    /// Data points of the results imported during the period
+    pub gained: usize,
+}
+
+impl DataPointGain {
+    /// Sums the data points of the results of `entry`, split at `cutoff`.
+    /// The results whose data points can't be read, e.g. of legacy
+    /// versions, are left out.
+    fn of_entry(entry: &ModelEntry, cutoff: DateTime<Utc>) -> Self {
+        let mut gain = DataPointGain::default();
+        for result in &entry.results {
+            let Ok(points) = BenchMerge::get_data_points(result) else {
+                continue;
+            };
+            let imported_at = ResultMetadata::load_for(result).and_then(|m| m.imported_at);
+            if is_recent(imported_at.as_deref(), cutoff) {
+                gain.gained += points;
+            } else {
+                gain.before += points;
+            }
+        }
+        gain
+    }
+
+    fn describe(&self) -> String {
+        tr(
+            "digest-data-point-gain",
+            &[
+                ("gained", self.gained.to_string()),
+                ("total", (self.before + self.gained).to_string()),
+            ],
+        )
+    }
+}
+
+/// Activity in the database over a period of time
+pub struct Digest {
+    pub days: i64,
+    /// New results per (model, version)
+    pub new_results: BTreeMap<(String, String), usize>,
+    /// Data points gained per (model, version) with new results
+    pub data_points: BTreeMap<(String, String), DataPointGain>,
+    pub contributors: BTreeSet<String>,
+    pub issues: BTreeSet<u64>,
+    pub failures: Vec<FailedSubmission>,
+}
+
+fn is_recent(date: Option<&str>, cutoff: DateTime<Utc>) -> bool {
+    date.and_then(|d| DateTime::parse_from_rfc3339(d).ok())
+        .map(|d| d >= cutoff)
+        .unwrap_or(false)
+}
+
+impl Digest {
+    /// Summarizes the imports and failed submissions of the last `days`
+    /// days in `database_dir`
+    pub fn new(database_dir: &Path, days: i64) -> Result<Self> {
+        let cutoff = Utc::now() - Duration::days(days);
+        let index = DatabaseIndex::load(database_dir)?;
+        let mut digest = Digest {
+            days,
+            new_results: BTreeMap::new(),
+            data_points: BTreeMap::new(),
+            contributors: BTreeSet::new(),
+            issues: BTreeSet::new(),
+            failures: vec![],
+        };
+        for metadata in index.entries.iter().flat_map(|e| e.metadata.iter()) {
+            if !is_recent(metadata.imported_at.as_deref(), cutoff) {
+                continue;
+            }
+            *digest
+                .new_results
+                .entry((metadata.model_name.clone(), metadata.version.clone()))
+                .or_default() += 1;
+            digest.contributors.extend(metadata.submitter.clone());
+            digest.issues.extend(metadata.issue);
+        }
+        for entry in &index.entries {
+            let key = (entry.model_name.clone(), entry.version.clone());
+            if digest.new_results.contains_key(&key) {
+                digest.data_points.insert(key, DataPointGain::of_entry(entry, cutoff));
+            }
+        }
+        digest.failures = FailedSubmission::load_all(database_dir)?
+            .into_iter()
+            .filter(|f| is_recent(Some(&f.failed_at), cutoff))
+            .collect();
+        Ok(digest)
+    }
+
+    pub fn subject(&self) -> String {
+        tr(
+            "digest-subject",
+            &[
+                ("count", self.new_results.values().sum::<usize>().to_string()),
+                ("days", self.days.to_string()),
+            ],
+        )
+    }
+
+    fn summary(&self) -> String {
+        tr(
+            "digest-summary",
+            &[
+                ("submissions", self.issues.len().to_string()),
+                ("contributors", self.contributors.len().to_string()),
+            ],
+        )
+    }
+
+    /// Describes the data points gained by the model and version of
+    /// `key`
+    fn gain_of(&self, key: &(String, String)) -> String {
+        self.data_points.get(key).map(DataPointGain::describe).unwrap_or_default()
+    }
+
+    /// Renders the digest as markdown
+    pub fn to_markdown(&self) -> String {
+        let mut text = format!("# {}\n\n{}\n\n", self.subject(), self.summary());
+        if !self.new_results.is_empty() {
+            text.push_str(&format!(
+                "## {0}\n\n| {1} | {2} | {0} | {3} |\n|---|---|---|---|\n",
+                tr("digest-new-results", &[]),
+                tr("digest-model", &[]),
+                tr("digest-version", &[]),
+                tr("digest-data-points", &[])
+            ));
+            for (key, count) in &self.new_results {
+                text.push_str(&format!("| {} | {} | {} | {} |\n", key.0, key.1, count, self.gain_of(key)));
+            }
+            text.push('\n');
+        }
+        if !self.contributors.is_empty() {
+            text.push_str(&format!("## {}\n\n", tr("digest-contributors", &[])));
+            for contributor in &self.contributors {
+                text.push_str(&format!("- @{}\n", contributor));
+            }
+            text.push('\n');
+        }
+        if !self.failures.is_empty() {
+            text.push_str(&format!("## {}\n\n", tr("digest-failed-submissions", &[])));
+            for failure in &self.failures {
+                let issue = failure.issue.map(|i| format!("#{}", i)).unwrap_or_default();
+                text.push_str(&format!("- {} {}\n", issue, failure.url));
+            }
+        }
+        text
+    }
+
+    /// Renders the digest as a minimal HTML document
+    pub fn to_html(&self) -> String {
+        let escape = |s: &str| s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;");
+        let mut html = format!(
+            "<html><body>\n<h1>{}</h1>\n<p>{}</p>\n",
+            escape(&self.subject()),
+            escape(&self.summary())
+        );
+        if !self.new_results.is_empty() {
+            html.push_str(&format!(
+                "<h2>{0}</h2>\n<table>\n<tr><th>{1}</th><th>{2}</th><th>{0}</th><th>{3}</th></tr>\n",
+                escape(&tr("digest-new-results", &[])),
+                escape(&tr("digest-model", &[])),
+                escape(&tr("digest-version", &[])),
+                escape(&tr("digest-data-points", &[]))
+            ));
+            for (key, count) in &self.new_results {
+                html.push_str(&format!(
+                    "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                    escape(&key.0),
+                    escape(&key.1),
+                    count,
+                    escape(&self.gain_of(key))
+                ));
+            }
+            html.push_str("</table>\n");
+        }
+        if !self.contributors.is_empty() {
+            html.push_str(&format!("<h2>{}</h2>\n<ul>\n", escape(&tr("digest-contributors", &[]))));
+            for contributor in &self.contributors {
+                html.push_str(&format!("<li>@{}</li>\n", escape(contributor)));
+            }
+            html.push_str("</ul>\n");
+        }
+        if !self.failures.is_empty() {
+            html.push_str(&format!(
+                "<h2>{}</h2>\n<ul>\n",
+                escape(&tr("digest-failed-submissions", &[]))
+            ));
+            for failure in &self.failures {
+                let issue = failure.issue.map(|i| format!("#{} ", i)).unwrap_or_default();
+                html.push_str(&format!("<li>{}{}</li>\n", issue, escape(&failure.url)));
+            }
+            html.push_str("</ul>\n");
+        }
+        html.push_str("</body></html>\n");
+        html
+    }
+
+    /// Sends the digest by email using the SMTP settings in `smtp`
+    pub fn send(&self, smtp: &SmtpConfig, html: bool) -> Result<()> {
+        let mut builder = Message::builder()
+            .from(smtp.from.parse::<Mailbox>().context("Invalid digest sender")?)
+            .subject(self.subject());
+        for to in &smtp.to {
+            builder = builder.to(to.parse::<Mailbox>().context("Invalid digest recipient")?);
+        }
+        let message = if html {
+            builder.header(ContentType::TEXT_HTML).body(self.to_html())?
+        } else {
+            builder.header(ContentType::TEXT_PLAIN).body(self.to_markdown())?
+        };
+        let mut transport = SmtpTransport::relay(&smtp.server)?.port(smtp.port.unwrap_or(587));
+        if let (Some(username), Some(password)) = (&smtp.username, &smtp.password) {
+            transport = transport.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+        transport.build().send(&message)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,245 @@
+use anyhow::Result;
+use serde::Serialize;
+use std::fs;
+
+use crate::archive::HWDB_ARCHIVE_DIR;
+use crate::common::{resctl_bench_path, run_resctl, BENCH_VERSIONS, RESCTL_SUPPORTED};
+use crate::config::TomlData;
+use crate::database::MERGED_RESULTS_DIR;
+use crate::exit::EnvironmentError;
+use crate::output::OutputFormat;
+use crate::workspace::{workspace, DATABASE_DIR, HWDB_INPUTS_DIR, PDFS_DIR};
+
+/// Directories the pipeline writes its outputs to
+static OUTPUT_DIRS: &[&str] = &[
+    DATABASE_DIR,
+    MERGED_RESULTS_DIR,
+    PDFS_DIR,
+    HWDB_INPUTS_DIR,
+    HWDB_ARCHIVE_DIR,
+];
+
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Ok,
+    Warn,
+    Fail,
+}
+
+/// Outcome of a single environment check
+#[derive(Debug, Serialize)]
+pub struct Check {
+    pub name: String,
+    pub status: CheckStatus,
+    pub detail: String,
+    /// What to do to fix a failed or suspicious check
+    pub fix: Option<String>,
+}
+
+impl Check {
+    fn ok(name: &str, detail: impl Into<String>) -> Self {
+        Check {
+            name: name.to_string(),
+            status: CheckStatus::Ok,
+            detail: detail.into(),
+            fix: None,
+        }
+    }
+
+    fn warn(name: &str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Check {
+            name: name.to_string(),
+            status: CheckStatus::Warn,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+
+    fn fail(name: &str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Check {
+            name: name.to_string(),
+            status: CheckStatus::Fail,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+}
+
+fn check_resctl_bench(version: &str) -> Check {
+    let name = format!("resctl-bench {}", version);
+    if !RESCTL_SUPPORTED {
+        return Check::warn(
+            &name,
+            "resctl-bench only runs on Linux, results can't be validated or merged here",
+            "Only use the inspection commands on this platform, or run on Linux",
+        );
+    }
+    let path = resctl_bench_path(version);
+    if !path.exists() {
+        return Check::fail(
+            &name,
+            format!("{} not found", path.display()),
+            format!(
+                "Build or download resctl-demo v{} and place its binaries in ./resctl-demo-v{}/",
+                version, version
+            ),
+        );
+    }
+    match run_resctl(version, &["--version"]) {
+        Ok(output) => Check::ok(&name, output.trim()),
+        Err(e) => Check::fail(
+            &name,
+            format!("{} --version failed: {}", path.display(), e),
+            "Check that the binary matches the runner architecture and its dependencies are installed",
+        ),
+    }
+}
+
+fn check_git_repo() -> Check {
+    let name = "git repository";
+    let repo = match workspace().repository() {
+        Ok(r) => r,
+        Err(e) => {
+            return Check::fail(
+                name,
+                e.message().to_string(),
+                "Run from the root of a checkout of the iocost-benchmarks repository, or pass it with --workspace",
+            )
+        }
+    };
+    let head = match repo.head().and_then(|h| h.peel_to_commit()) {
+        Ok(c) => c.id().to_string(),
+        Err(e) => {
+            return Check::fail(
+                name,
+                format!("HEAD can't be resolved: {}", e.message()),
+                "Make sure the checkout has at least one commit",
+            )
+        }
+    };
+    let mut options = git2::StatusOptions::new();
+    options.include_untracked(false);
+    let modified = match repo.statuses(Some(&mut options)) {
+        Ok(statuses) => statuses.len(),
+        Err(e) => {
+            return Check::fail(name, e.message().to_string(), "Check the repository is not corrupted")
+        }
+    };
+    if modified > 0 {
+        Check::warn(
+            name,
+            format!("HEAD at {}, {} modified files", head, modified),
+            "Commit or stash local changes, as they'll end up in the bot commits",
+        )
+    } else {
+        Check::ok(name, format!("HEAD at {}, clean", head))
+    }
+}
+
+fn check_config(path: Option<&str>) -> Check {
+    let name = "config file";
+    match path {
+        None => Check::ok(name, "none given, using defaults"),
+        Some(path) => match TomlData::load(path) {
+            Ok(_) => Check::ok(name, format!("{} is valid", path)),
+            Err(e) => Check::fail(
+                name,
+                format!("{:#}", e),
+                "Fix the config file, see config.toml in iocost-benchmarks-ci for the format",
+            ),
+        },
+    }
+}
+
+async fn check_github_token(token: Option<&str>) -> Check {
+    let name = "github token";
+    let token = match token {
+        Some(t) => t,
+        None => {
+            return Check::warn(
+                name,
+                "no token available",
+                "Set GITHUB_TOKEN to comment on issues and open pull requests",
+            )
+        }
+    };
+    let response = reqwest::Client::new()
+        .get("https://api.github.com/user")
+        .header(reqwest::header::AUTHORIZATION, format!("Bearer {}", token))
+        .header(reqwest::header::USER_AGENT, "iocost-ci")
+        .send()
+        .await;
+    let response = match response {
+        Ok(r) => r,
+        Err(e) => {
+            return Check::fail(name, format!("request failed: {}", e), "Check network access to api.github.com")
+        }
+    };
+    if !response.status().is_success() {
+        return Check::fail(
+            name,
+            format!("Github replied {}", response.status()),
+            "The token is invalid or expired, generate a new one",
+        );
+    }
+    // Fine-grained and App tokens don't report scopes.
+    match response
+        .headers()
+        .get("x-oauth-scopes")
+        .and_then(|h| h.to_str().ok())
+    {
+        Some(scopes) if scopes.split(", ").any(|s| s == "repo" || s == "public_repo") => {
+            Check::ok(name, format!("scopes: {}", scopes))
+        }
+        Some(scopes) => Check::fail(
+            name,
+            format!("scopes: {}", scopes),
+            "The token needs the public_repo (or repo) scope",
+        ),
+        None => Check::ok(name, "valid, scopes not reported (fine-grained token)"),
+    }
+}
+
+fn check_writable(dir: &str) -> Check {
+    let name = format!("{} directory", dir);
+    let result = fs::create_dir_all(dir).and_then(|_| tempfile::tempfile_in(dir).map(|_| ()));
+    match result {
+        Ok(()) => Check::ok(&name, "writable"),
+        Err(e) => Check::fail(&name, e.to_string(), format!("Make sure {} is writable by the current user", dir)),
+    }
+}
+
+/// Runs every environment check
+pub async fn run_checks(config_file: Option<&str>, github_token: Option<&str>) -> Vec<Check> {
+    let mut checks: Vec<Check> = BENCH_VERSIONS.iter().map(|v| check_resctl_bench(v)).collect();
+    checks.push(check_git_repo());
+    checks.push(check_config(config_file));
+    checks.push(check_github_token(github_token).await);
+    checks.extend(OUTPUT_DIRS.iter().map(|d| check_writable(d)));
+    checks
+}
+
+/// Prints the outcome of `checks`, with the fixes for the ones that
+/// didn't pass
+pub fn print_checks(checks: &[Check], format: OutputFormat) -> Result<()> {
+    format.print(checks, || {
+        for check in checks {
+            let status = match check.status {
+                CheckStatus::Ok => "ok",
+                CheckStatus::Warn => "WARN",
+                CheckStatus::Fail => "FAIL",
+            };
+            println!("[{:>4}] {}: {}", status, check.name, check.detail);
+            if let Some(fix) = &check.fix {
+                println!("       fix: {}", fix);
+            }
+        }
+        Ok(())
+    })?;
+    let failed = checks.iter().filter(|c| c.status == CheckStatus::Fail).count();
+    if failed > 0 {
+        return Err(EnvironmentError(format!("{} checks failed", failed)).into());
+    }
+    Ok(())
+}
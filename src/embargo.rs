@@ -0,0 +1,194 @@
+use anyhow::{bail, Context, Result};
+use chrono::NaiveDate;
+use serde::Serialize;
+use serde_with::{serde_as, DisplayFromStr};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::config::{set_once, EmbargoConfig};
+use crate::git::commit_to_branch;
+use crate::index::ResultMetadata;
+use crate::storage::{key_for, object_storage, object_storage_from_config, LocalStorage, Storage};
+use crate::workspace::workspace;
+
+/// Extension of the metadata files, which tell when the results they
+/// describe can be published
+const METADATA_EXTENSION: &str = ".json.metadata";
+
+/// Returns the date until which the results submitted in `body` are
+/// embargoed, from an `embargo-until: YYYY-MM-DD` line or from the
+/// "Embargo until" field of the issue form
+pub fn embargo_date(body: &str) -> Result<Option<NaiveDate>> {
+    let mut lines = body.lines().map(str::trim);
+    while let Some(line) = lines.next() {
+        let field = line.trim_start_matches('#').trim().to_lowercase();
+        let Some(rest) = field
+            .strip_prefix("embargo-until")
+            .or_else(|| field.strip_prefix("embargo until"))
+        else {
+            continue;
+        };
+        let mut value = rest.trim_start_matches(':').trim().to_string();
+        if value.is_empty() {
+            // Issue forms put the value in a paragraph after the field
+            value = lines.find(|l| !l.is_empty()).unwrap_or_default().to_string();
+        }
+        if value.is_empty() || value == "_no response_" {
+            return Ok(None);
+        }
+        let date = NaiveDate::parse_from_str(&value, "%Y-%m-%d")
+            .with_context(|| format!("Invalid embargo date {:?}, expected YYYY-MM-DD", value))?;
+        return Ok(Some(date));
+    }
+    Ok(None)
+}
+
+/// A result held in the staging area until the end of its embargo
+#[serde_as]
+#[derive(Debug, Serialize)]
+pub struct EmbargoedResult {
+    /// Storage key of the result file
+    pub key: String,
+    pub metadata: ResultMetadata,
+    #[serde_as(as = "DisplayFromStr")]
+    pub until: NaiveDate,
+}
+
+impl EmbargoedResult {
+    /// Storage key of the metadata file
+    pub fn metadata_key(&self) -> String {
+        format!("{}{}", self.key.trim_end_matches(".json.gz"), METADATA_EXTENSION)
+    }
+}
+
+/// Returns the results held in `staging`, by end of embargo
+pub fn held(staging: &dyn Storage) -> Result<Vec<EmbargoedResult>> {
+    let mut results = vec![];
+    for key in staging.list()? {
+        let Some(base) = key.strip_suffix(METADATA_EXTENSION) else {
+            continue;
+        };
+        let metadata: ResultMetadata = serde_json::from_slice(&staging.read(&key)?)
+            .with_context(|| format!("Invalid metadata {} in {}", key, staging.describe()))?;
+        let Some(until) = metadata.embargo_until.as_deref() else {
+            bail!("{} in {} has no embargo date", key, staging.describe());
+        };
+        let until = NaiveDate::parse_from_str(until, "%Y-%m-%d")
+            .with_context(|| format!("Invalid embargo date in {}", key))?;
+        results.push(EmbargoedResult { key: format!("{}.json.gz", base), metadata, until });
+    }
+    results.sort_by(|a, b| (a.until, &a.key).cmp(&(b.until, &b.key)));
+    Ok(results)
+}
+
+/// Copies the results of `staging` whose embargo ended by `today` to
+/// the database in `database_dir`. Returns them with the files written.
+pub fn release(
+    staging: &dyn Storage,
+    database_dir: &Path,
+    today: NaiveDate,
+) -> Result<(Vec<EmbargoedResult>, Vec<PathBuf>)> {
+    let database = LocalStorage::new(database_dir);
+    let mut released = vec![];
+    let mut files = vec![];
+    for result in held(staging)?.into_iter().filter(|r| r.until <= today) {
+        for key in [result.key.clone(), result.metadata_key()] {
+            database.write(&key, &staging.read(&key)?)?;
+            files.push(database_dir.join(&key));
+        }
+        released.push(result);
+    }
+    Ok((released, files))
+}
+
+/// Runs the `embargo release` command: publishes the results whose
+/// embargo ended to the database in `database_dir`. With `commit`, the
+/// files are committed to a branch for review, or uploaded if the
+/// database is kept in an object storage, and removed from the staging
+/// area.
+pub fn run_release(database_dir: &Path, commit: bool) -> Result<()> {
+    let staging = embargo_staging().context("No embargo staging area in the config file")?;
+    let today = chrono::Utc::now().date_naive();
+    let (released, files) = release(staging, database_dir, today)?;
+    if released.is_empty() {
+        println!("No embargo ended by {}", today);
+        return Ok(());
+    }
+    for result in &released {
+        println!(
+            "  {} ({}, {}), embargoed until {}",
+            result.key,
+            result.metadata.model_name,
+            result.metadata.version,
+            result.until
+        );
+    }
+    if !commit {
+        println!("Released {} results, they stay in {} until committed", released.len(), staging.describe());
+        return Ok(());
+    }
+    if let Some(storage) = object_storage() {
+        for file in &files {
+            storage.write(&key_for(database_dir, file).unwrap(), &std::fs::read(file)?)?;
+        }
+        storage.flush()?;
+        println!("Uploaded {} results to {}", released.len(), storage.describe());
+    } else {
+        let git_repo = workspace().repository()?;
+        let mut index = git_repo.index()?;
+        for file in &files {
+            index.add_path(file)?;
+        }
+        let branch = format!("iocost-bot/embargo-release-{}", today);
+        let message = format!(
+            "Release {} embargoed results\n\n{}",
+            released.len(),
+            released
+                .iter()
+                .map(|r| format!(
+                    "{} ({}) from #{}, embargoed until {}",
+                    r.metadata.model_name,
+                    r.metadata.version,
+                    r.metadata.issue.map_or("?".to_string(), |i| i.to_string()),
+                    r.until
+                ))
+                .collect::<Vec<_>>()
+                .join("\n")
+        );
+        commit_to_branch(&git_repo, &mut index, &message, &branch)?;
+        println!("Committed {} results to {}", released.len(), branch);
+    }
+    for result in &released {
+        staging.remove(&result.key)?;
+        staging.remove(&result.metadata_key())?;
+    }
+    staging.flush()?;
+    Ok(())
+}
+
+/// Creates the staging area of the [embargo] section of the config
+/// file: a private directory, or an object storage
+pub fn staging_from_config(config: &EmbargoConfig) -> Result<Box<dyn Storage>> {
+    if let Some(storage) = &config.storage {
+        if let Some(storage) = object_storage_from_config(storage)? {
+            return Ok(storage);
+        }
+    }
+    match &config.dir {
+        Some(dir) => Ok(Box::new(LocalStorage::new(dir))),
+        None => bail!("The embargo staging area needs a dir or an object storage"),
+    }
+}
+
+static EMBARGO_STAGING: OnceLock<Box<dyn Storage>> = OnceLock::new();
+
+/// Holds embargoed results in `staging`.
+pub fn set_embargo_staging(staging: Box<dyn Storage>) -> Result<()> {
+    set_once(&EMBARGO_STAGING, staging, "embargo staging area")
+}
+
+/// Returns the staging area of the embargoed results, None if embargoes
+/// are not supported
+pub fn embargo_staging() -> Option<&'static dyn Storage> {
+    EMBARGO_STAGING.get().map(|s| s.as_ref())
+}
@@ -0,0 +1,188 @@
+use std::path::PathBuf;
+
+use crate::exit::ExitStatus;
+
+/// Errors raised while running resctl-bench and processing its results.
+/// Each variant carries enough context (model, version, file) for the
+/// caller to report it or decide whether to skip, retry or abort.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// The resctl-bench version (major.minor) is not supported
+    #[error("Unsupported resctl-bench version {version}")]
+    UnsupportedVersion { version: String },
+
+    /// The resctl-bench binary could not be run
+    #[error("Error running resctl-bench {version} {args}: {error}")]
+    ResctlSpawn {
+        version: String,
+        args: String,
+        error: std::io::Error,
+    },
+
+    /// resctl-bench ran but reported an error
+    #[error("resctl-bench {version} {args} failed: {stderr}")]
+    ResctlFailed {
+        version: String,
+        args: String,
+        stderr: String,
+    },
+
+    /// resctl-bench doesn't run on the platform of the tools
+    #[error("resctl-bench {version} only runs on Linux")]
+    ResctlUnsupported { version: String },
+
+    /// The output of `resctl-bench --version` could not be parsed
+    #[error("Error parsing resctl-bench {version} --version output: {output}")]
+    ResctlVersion { version: String, output: String },
+
+    /// A result file could not be read or parsed
+    #[error("Invalid result file {file}: {reason}")]
+    InvalidResult { file: PathBuf, reason: String },
+
+    /// A merged result has no iocost-tune data
+    #[error("Could not find iocost-tune spec in merge file {file}")]
+    MissingIocostTune { file: PathBuf },
+
+    /// The iocost-tune result has no data points for a series, as laid
+    /// out by the supported versions
+    #[error("No {series} data points in the iocost-tune result of {file}")]
+    MissingDataSeries { file: PathBuf, series: String },
+
+    /// There are no results to merge for a model
+    #[error("No results for {model} ({version}) in {dir}")]
+    NoResults {
+        model: String,
+        version: String,
+        dir: PathBuf,
+    },
+
+    /// A model name, version or detail can't be used in database paths
+    #[error("Invalid {kind} name {name:?}")]
+    InvalidName { kind: &'static str, name: String },
+
+    /// The database layout can't be used by this version of the tools
+    #[error("Unusable database layout in {root}: {reason}")]
+    Layout { root: PathBuf, reason: String },
+
+    /// The contents of a result stored with Git LFS could not be fetched
+    #[error("Failed to fetch the LFS object of {file}: {reason}")]
+    Lfs { file: PathBuf, reason: String },
+
+    /// Filesystem error on a specific path
+    #[error("I/O error on {path}: {error}")]
+    Io {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+
+    /// Invalid glob pattern built from a database path
+    #[error(transparent)]
+    Pattern(#[from] glob::PatternError),
+
+    /// Merging the results of a model failed
+    #[error("Failed to merge {model} ({version}): {error}")]
+    Merge {
+        model: String,
+        version: String,
+        error: Box<Error>,
+    },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    /// Returns true if the error is caused by the environment (missing
+    /// or broken resctl-bench binaries, permissions) rather than by the
+    /// data being processed, so that retrying with other inputs is
+    /// pointless.
+    pub fn is_environment(&self) -> bool {
+        match self {
+            Error::UnsupportedVersion { .. }
+            | Error::ResctlSpawn { .. }
+            | Error::ResctlUnsupported { .. }
+            | Error::ResctlVersion { .. }
+            | Error::Layout { .. }
+            | Error::Lfs { .. }
+            | Error::Io { .. } => true,
+            Error::Merge { error, .. } => error.is_environment(),
+            _ => false,
+        }
+    }
+
+    /// Exit status of a command that fails with this error
+    pub fn exit_status(&self) -> ExitStatus {
+        match self {
+            Error::InvalidResult { .. }
+            | Error::MissingIocostTune { .. }
+            | Error::MissingDataSeries { .. }
+            | Error::InvalidName { .. } => {
+                ExitStatus::ValidationError
+            }
+            Error::Merge { error, .. } => error.exit_status(),
+            e if e.is_environment() => ExitStatus::EnvironmentError,
+            _ => ExitStatus::Failure,
+        }
+    }
+
+    /// Returns the stderr output of resctl-bench, if it caused the error
+    pub fn stderr(&self) -> Option<&str> {
+        match self {
+            Error::ResctlFailed { stderr, .. } => Some(stderr),
+            Error::Merge { error, .. } => error.stderr(),
+            _ => None,
+        }
+    }
+
+    /// Suggests how to fix the error
+    pub fn remediation(&self) -> String {
+        match self {
+            Error::UnsupportedVersion { version } => format!(
+                "Add resctl-bench {} to the supported versions and the workflow",
+                version
+            ),
+            Error::ResctlSpawn { version, .. } | Error::ResctlVersion { version, .. } => format!(
+                "Check that ./resctl-demo-v{}/resctl-bench exists and runs (see `iocost-ci doctor`)",
+                version
+            ),
+            Error::ResctlUnsupported { .. } => {
+                "Validate and merge the results on Linux, e.g. in the Github workflows".to_string()
+            }
+            Error::ResctlFailed { .. } => {
+                "Check the resctl-bench stderr and re-run the same command locally".to_string()
+            }
+            Error::InvalidResult { .. } => {
+                "Ask the submitter to upload the gzipped json result again".to_string()
+            }
+            Error::MissingIocostTune { .. } => {
+                "The results don't include an iocost-tune run: re-run the benchmark".to_string()
+            }
+            Error::MissingDataSeries { .. } => {
+                "Check that the resctl-bench version is supported and the merged results complete".to_string()
+            }
+            Error::InvalidName { .. } => {
+                "Only ASCII letters, digits and _.+- are allowed in model names".to_string()
+            }
+            Error::NoResults { dir, .. } => {
+                format!("Check the result files in {}", dir.display())
+            }
+            Error::Lfs { .. } => {
+                "Check access to the Git LFS server of the database repository, or run `git lfs pull`".to_string()
+            }
+            Error::Io { path, .. } => {
+                format!("Check the permissions and free space for {}", path.display())
+            }
+            Error::Pattern(_) => "Check the database path".to_string(),
+            Error::Layout { .. } => {
+                "Update the tools or run `iocost-ci migrate` on the database".to_string()
+            }
+            Error::Merge { error, .. } => error.remediation(),
+        }
+    }
+}
+
+/// Returns a closure that wraps an io::Error with the `path` it was
+/// raised on, to be used with `map_err`.
+pub fn io_error(path: impl Into<PathBuf>) -> impl FnOnce(std::io::Error) -> Error {
+    let path = path.into();
+    move |error| Error::Io { path, error }
+}
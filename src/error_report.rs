@@ -0,0 +1,94 @@
+use serde::Serialize;
+use serde_with::skip_serializing_none;
+use std::sync::{LazyLock, Mutex};
+
+use crate::error::Error;
+
+/// Name of the structured error report written when a run has failures
+pub static ERRORS_FILE: &str = "errors.json";
+
+/// Processing stage an error happened in
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Stage {
+    /// Validating a submitted result
+    Validation,
+    /// Merging the results of a model
+    Merge,
+    /// Generating the pdf or the hwdb input of a merge
+    Output,
+    /// Any other error, which stopped the run
+    Run,
+}
+
+/// An error of the run, with the context needed to debug it
+#[skip_serializing_none]
+#[derive(Debug, Serialize)]
+pub struct ErrorEntry {
+    pub stage: Stage,
+    /// Input file or URL
+    pub input: Option<String>,
+    pub model: Option<String>,
+    pub version: Option<String>,
+    pub message: String,
+    /// resctl-bench stderr output
+    pub stderr: Option<String>,
+    pub remediation: Option<String>,
+}
+
+impl ErrorEntry {
+    /// Creates an entry for `error`, filling the stderr output and the
+    /// suggested remediation from the typed error in its chain, if any
+    pub fn new(stage: Stage, error: &anyhow::Error) -> Self {
+        let typed = error.chain().find_map(|e| e.downcast_ref::<Error>());
+        let (model, version) = match typed {
+            Some(Error::Merge { model, version, .. }) => (Some(model.clone()), Some(version.clone())),
+            _ => (None, None),
+        };
+        ErrorEntry {
+            stage,
+            input: None,
+            model,
+            version,
+            message: format!("{:#}", error),
+            stderr: typed.and_then(|e| e.stderr()).map(str::to_string),
+            remediation: typed.map(|e| e.remediation()),
+        }
+    }
+
+    pub fn input(mut self, input: impl Into<String>) -> Self {
+        self.input = Some(input.into());
+        self
+    }
+
+    pub fn model(mut self, model: impl Into<String>, version: impl Into<String>) -> Self {
+        self.model = Some(model.into());
+        self.version = Some(version.into());
+        self
+    }
+}
+
+/// Errors collected during the run
+#[derive(Default)]
+pub struct ErrorReport {
+    entries: Mutex<Vec<ErrorEntry>>,
+}
+
+impl ErrorReport {
+    pub fn record(&self, entry: ErrorEntry) {
+        self.entries.lock().unwrap().push(entry);
+    }
+
+    /// Writes the collected errors to `errors.json`, if there are any
+    pub fn write(&self) -> anyhow::Result<()> {
+        let entries = self.entries.lock().unwrap();
+        if entries.is_empty() {
+            return Ok(());
+        }
+        std::fs::write(ERRORS_FILE, serde_json::to_string_pretty(&*entries)?)?;
+        eprintln!("Wrote {} errors to {}", entries.len(), ERRORS_FILE);
+        Ok(())
+    }
+}
+
+pub static ERROR_REPORT: LazyLock<ErrorReport> = LazyLock::new(ErrorReport::default);
@@ -0,0 +1,83 @@
+use std::fmt;
+use std::process::ExitCode;
+
+use crate::error_report::{ErrorEntry, Stage, ERROR_REPORT};
+use crate::resources::RESOURCES;
+
+/// Outcome of a command, mapped to the process exit code so that
+/// workflow steps can branch on it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitStatus {
+    /// Everything was processed
+    Success = 0,
+    /// Unclassified error
+    Failure = 1,
+    /// Some inputs were processed, others failed
+    PartialFailure = 2,
+    /// The inputs failed validation
+    ValidationError = 3,
+    /// The environment is not set up correctly (missing binaries,
+    /// variables, config or permissions)
+    EnvironmentError = 4,
+}
+
+/// Exit codes documentation appended to the `--help` output
+pub static EXIT_CODES_HELP: &str = "\
+Exit codes:
+  0  success
+  1  unclassified error
+  2  partial failure: some inputs were processed, others failed
+  3  validation error: the inputs failed validation
+  4  environment error: missing binaries, variables, config or permissions";
+
+/// Error raised when the inputs fail validation
+#[derive(Debug)]
+pub struct ValidationError(pub String);
+
+/// Error raised when the environment is not set up correctly
+#[derive(Debug)]
+pub struct EnvironmentError(pub String);
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl fmt::Display for EnvironmentError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for ValidationError {}
+impl std::error::Error for EnvironmentError {}
+
+/// Turns the result of a command into its exit code, printing the
+/// error if there is one and writing the errors of the run to
+/// `errors.json`
+pub fn exit_code(result: anyhow::Result<ExitStatus>) -> ExitCode {
+    let status = match result {
+        Ok(status) => status,
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+            ERROR_REPORT.record(ErrorEntry::new(Stage::Run, &e));
+            if let Some(e) = e.downcast_ref::<crate::error::Error>() {
+                e.exit_status()
+            } else if e.downcast_ref::<ValidationError>().is_some() {
+                ExitStatus::ValidationError
+            } else if e.downcast_ref::<EnvironmentError>().is_some() {
+                ExitStatus::EnvironmentError
+            } else {
+                ExitStatus::Failure
+            }
+        }
+    };
+    if let Err(e) = ERROR_REPORT.write() {
+        eprintln!("Failed to write the error report: {:#}", e);
+    }
+    if let Err(e) = RESOURCES.write_summary() {
+        eprintln!("Failed to write the resource usage summary: {:#}", e);
+    }
+    ExitCode::from(status as u8)
+}
@@ -0,0 +1,292 @@
+use anyhow::{bail, Result};
+use arrow_array::{ArrayRef, BooleanArray, Float64Array, RecordBatch, StringArray, UInt64Array};
+use arrow_schema::{DataType, Field, Schema};
+use clap::ValueEnum;
+use indicatif::ParallelProgressIterator;
+use json::JsonValue;
+use parquet::arrow::ArrowWriter;
+use rayon::prelude::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::common::load_json;
+use crate::index::{DatabaseIndex, ResultMetadata};
+use crate::progress::{bar, log};
+
+/// File format of an export
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    Csv,
+    Parquet,
+}
+
+impl ExportFormat {
+    /// Returns the format matching the extension of `path`, CSV if it
+    /// is not `.parquet`
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("parquet") => ExportFormat::Parquet,
+            _ => ExportFormat::Csv,
+        }
+    }
+}
+
+/// What each row of an export describes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Granularity {
+    /// One row per result, with its metadata and summary metrics
+    Result,
+    /// One row per data point of the iocost-tune series of each result
+    DataPoint,
+}
+
+/// Metadata and summary metrics of a result
+struct ResultRow {
+    version: String,
+    model_name: String,
+    file: String,
+    bench_version: Option<String>,
+    fwrev: Option<String>,
+    metadata: Option<ResultMetadata>,
+    data_points: u64,
+    outliers: u64,
+    mof_min: Option<f64>,
+    mof_max: Option<f64>,
+}
+
+/// A data point of an iocost-tune series: the value of the series at a
+/// given vrate
+struct PointRow {
+    series: String,
+    vrate: f64,
+    val: f64,
+    outlier: bool,
+}
+
+/// Everything exported from a result file
+struct ExportedResult {
+    row: ResultRow,
+    points: Vec<PointRow>,
+}
+
+/// A column of an export, in the types shared by CSV and Parquet
+enum Column {
+    Str(Vec<Option<String>>),
+    U64(Vec<Option<u64>>),
+    F64(Vec<Option<f64>>),
+    Bool(Vec<bool>),
+}
+
+impl Column {
+    fn csv_field(&self, row: usize) -> String {
+        match self {
+            Column::Str(v) => v[row].clone().unwrap_or_default(),
+            Column::U64(v) => v[row].map(|n| n.to_string()).unwrap_or_default(),
+            Column::F64(v) => v[row].map(|n| n.to_string()).unwrap_or_default(),
+            Column::Bool(v) => v[row].to_string(),
+        }
+    }
+
+    fn field(&self, name: &str) -> Field {
+        let data_type = match self {
+            Column::Str(_) => DataType::Utf8,
+            Column::U64(_) => DataType::UInt64,
+            Column::F64(_) => DataType::Float64,
+            Column::Bool(_) => DataType::Boolean,
+        };
+        Field::new(name, data_type, !matches!(self, Column::Bool(_)))
+    }
+
+    fn to_array(&self) -> ArrayRef {
+        match self {
+            Column::Str(v) => Arc::new(StringArray::from(v.clone())),
+            Column::U64(v) => Arc::new(UInt64Array::from(v.clone())),
+            Column::F64(v) => Arc::new(Float64Array::from(v.clone())),
+            Column::Bool(v) => Arc::new(BooleanArray::from(v.clone())),
+        }
+    }
+}
+
+/// Named columns of the same length
+struct Table {
+    columns: Vec<(&'static str, Column)>,
+    len: usize,
+}
+
+impl Table {
+    fn results(results: &[ExportedResult]) -> Self {
+        let rows: Vec<&ResultRow> = results.iter().map(|r| &r.row).collect();
+        let metadata = |f: fn(&ResultMetadata) -> Option<String>| {
+            Column::Str(rows.iter().map(|r| r.metadata.as_ref().and_then(f)).collect())
+        };
+        let columns = vec![
+            ("version", Column::Str(rows.iter().map(|r| Some(r.version.clone())).collect())),
+            ("model_name", Column::Str(rows.iter().map(|r| Some(r.model_name.clone())).collect())),
+            ("file", Column::Str(rows.iter().map(|r| Some(r.file.clone())).collect())),
+            ("bench_version", Column::Str(rows.iter().map(|r| r.bench_version.clone()).collect())),
+            ("fwrev", Column::Str(rows.iter().map(|r| r.fwrev.clone()).collect())),
+            (
+                "issue",
+                Column::U64(rows.iter().map(|r| r.metadata.as_ref().and_then(|m| m.issue)).collect()),
+            ),
+            ("url", metadata(|m| m.url.clone())),
+            ("submitter", metadata(|m| m.submitter.clone())),
+            ("imported_at", metadata(|m| m.imported_at.clone())),
+            ("data_points", Column::U64(rows.iter().map(|r| Some(r.data_points)).collect())),
+            ("outliers", Column::U64(rows.iter().map(|r| Some(r.outliers)).collect())),
+            ("mof_min", Column::F64(rows.iter().map(|r| r.mof_min).collect())),
+            ("mof_max", Column::F64(rows.iter().map(|r| r.mof_max).collect())),
+        ];
+        Table {
+            columns,
+            len: rows.len(),
+        }
+    }
+
+    fn data_points(results: &[ExportedResult]) -> Self {
+        let rows: Vec<(&ResultRow, &PointRow)> = results
+            .iter()
+            .flat_map(|r| r.points.iter().map(move |p| (&r.row, p)))
+            .collect();
+        let columns = vec![
+            ("version", Column::Str(rows.iter().map(|(r, _)| Some(r.version.clone())).collect())),
+            (
+                "model_name",
+                Column::Str(rows.iter().map(|(r, _)| Some(r.model_name.clone())).collect()),
+            ),
+            ("file", Column::Str(rows.iter().map(|(r, _)| Some(r.file.clone())).collect())),
+            ("fwrev", Column::Str(rows.iter().map(|(r, _)| r.fwrev.clone()).collect())),
+            (
+                "issue",
+                Column::U64(
+                    rows.iter()
+                        .map(|(r, _)| r.metadata.as_ref().and_then(|m| m.issue))
+                        .collect(),
+                ),
+            ),
+            ("series", Column::Str(rows.iter().map(|(_, p)| Some(p.series.clone())).collect())),
+            ("vrate", Column::F64(rows.iter().map(|(_, p)| Some(p.vrate)).collect())),
+            ("val", Column::F64(rows.iter().map(|(_, p)| Some(p.val)).collect())),
+            ("outlier", Column::Bool(rows.iter().map(|(_, p)| p.outlier).collect())),
+        ];
+        Table {
+            columns,
+            len: rows.len(),
+        }
+    }
+
+    fn write_csv(&self, path: &Path) -> Result<()> {
+        let mut writer = csv::Writer::from_path(path)?;
+        writer.write_record(self.columns.iter().map(|(name, _)| name))?;
+        for row in 0..self.len {
+            writer.write_record(self.columns.iter().map(|(_, column)| column.csv_field(row)))?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    fn write_parquet(&self, path: &Path) -> Result<()> {
+        let schema = Arc::new(Schema::new(
+            self.columns
+                .iter()
+                .map(|(name, column)| column.field(name))
+                .collect::<Vec<_>>(),
+        ));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            self.columns.iter().map(|(_, column)| column.to_array()).collect(),
+        )?;
+        let mut writer = ArrowWriter::try_new(fs::File::create(path)?, schema, None)?;
+        writer.write(&batch)?;
+        writer.close()?;
+        Ok(())
+    }
+}
+
+fn json_string(value: &JsonValue) -> Option<String> {
+    value.as_str().map(str::to_string)
+}
+
+/// Returns the (vrate, val) pairs of a list of iocost-tune data points
+fn series_points(points: &JsonValue) -> impl Iterator<Item = (f64, f64)> + '_ {
+    points
+        .members()
+        .filter_map(|p| Some((p["vrate"].as_f64()?, p["val"].as_f64()?)))
+}
+
+fn export_result(version: &str, model_name: &str, path: &Path) -> Result<ExportedResult> {
+    let result = load_json(&path.to_string_lossy())?;
+    let Some(tune) = result.members().find(|v| v["spec"]["kind"] == "iocost-tune") else {
+        bail!("No iocost-tune result in {}", path.display());
+    };
+    let mut points = vec![];
+    for (series, data) in tune["result"]["data"].entries() {
+        for (key, outlier) in [("data", false), ("outliers", true)] {
+            points.extend(series_points(&data[key]).map(|(vrate, val)| PointRow {
+                series: series.to_string(),
+                vrate,
+                val,
+                outlier,
+            }));
+        }
+    }
+    let mof: Vec<f64> = series_points(&tune["result"]["data"]["MOF"]["data"])
+        .map(|(_, val)| val)
+        .collect();
+    let row = ResultRow {
+        version: version.to_string(),
+        model_name: model_name.to_string(),
+        file: path.file_name().unwrap().to_string_lossy().to_string(),
+        bench_version: json_string(&result[0]["sysinfo"]["bench_version"]),
+        fwrev: json_string(&result[0]["sysinfo"]["sysreqs_report"]["scr_dev_fwrev"]),
+        metadata: ResultMetadata::load_for(path),
+        data_points: points.iter().filter(|p| !p.outlier).count() as u64,
+        outliers: points.iter().filter(|p| p.outlier).count() as u64,
+        mof_min: mof.iter().copied().reduce(f64::min),
+        mof_max: mof.iter().copied().reduce(f64::max),
+    };
+    Ok(ExportedResult { row, points })
+}
+
+/// Flattens the results in `database_dir` into a table with one row per
+/// `granularity`, written to `output` in `format`. Results that cannot be
+/// read are reported and skipped. Returns the number of rows written.
+pub fn export(
+    database_dir: &Path,
+    output: &Path,
+    format: ExportFormat,
+    granularity: Granularity,
+) -> Result<usize> {
+    let index = DatabaseIndex::load(database_dir)?;
+    let files: Vec<(&str, &str, &PathBuf)> = index
+        .entries
+        .iter()
+        .flat_map(|e| {
+            e.results
+                .iter()
+                .map(move |r| (e.version.as_str(), e.model_name.as_str(), r))
+        })
+        .collect();
+    let pb = bar(files.len() as u64, "Exporting");
+    let results: Vec<ExportedResult> = files
+        .par_iter()
+        .progress_with(pb.clone())
+        .filter_map(|(version, model_name, path)| {
+            export_result(version, model_name, path)
+                .map_err(|e| log(format!("Skipping {}: {:#}", path.display(), e)))
+                .ok()
+        })
+        .collect();
+    pb.finish_and_clear();
+
+    let table = match granularity {
+        Granularity::Result => Table::results(&results),
+        Granularity::DataPoint => Table::data_points(&results),
+    };
+    match format {
+        ExportFormat::Csv => table.write_csv(output)?,
+        ExportFormat::Parquet => table.write_parquet(output)?,
+    }
+    Ok(table.len)
+}
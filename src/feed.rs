@@ -0,0 +1,175 @@
+use anyhow::Result;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+
+use crate::archive::HwdbArchive;
+use crate::hwdb::{hwdb_section_for, HWDB_FILE};
+use crate::index::DatabaseIndex;
+use crate::manifest::{Manifest, MANIFEST_FILE};
+
+/// Atom feed of the new results and hwdb updates, written with the
+/// outputs of the merge runs
+pub static FEED_FILE: &str = "iocost-benchmarks.atom";
+
+/// Number of entries kept in the feed, the most recent ones
+const MAX_ENTRIES: usize = 50;
+
+/// Entry of the feed: a submission or a hwdb update
+#[derive(Debug)]
+pub struct FeedEntry {
+    pub id: String,
+    pub title: String,
+    pub link: String,
+    /// RFC 3339 date of the import or of the merge run
+    pub updated: String,
+    pub author: Option<String>,
+    pub summary: String,
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// Returns an entry per submission of the results of `index`, linking
+/// to the pull requests of the `repo` which imported them. Results added
+/// without a submission issue are grouped by day.
+pub fn submission_entries(index: &DatabaseIndex, repo: &str) -> Vec<FeedEntry> {
+    // Models, count, submitter and last import of each submission
+    type Submission = (BTreeSet<String>, usize, Option<String>, String);
+    let mut submissions: BTreeMap<(Option<u64>, String), Submission> = BTreeMap::new();
+    for metadata in index.entries.iter().flat_map(|e| &e.metadata) {
+        let Some(imported_at) = metadata.imported_at.clone() else {
+            continue;
+        };
+        let day = match metadata.issue {
+            Some(_) => String::new(),
+            None => imported_at.get(..10).unwrap_or_default().to_string(),
+        };
+        let submission = submissions.entry((metadata.issue, day)).or_default();
+        submission.0.insert(format!("{} ({})", metadata.model_name, metadata.version));
+        submission.1 += 1;
+        submission.2 = submission.2.take().or(metadata.submitter.clone());
+        if imported_at > submission.3 {
+            submission.3 = imported_at;
+        }
+    }
+    submissions
+        .into_iter()
+        .map(|((issue, day), (models, count, submitter, updated))| {
+            let models = models.into_iter().collect::<Vec<_>>().join(", ");
+            let (id, link, summary) = match issue {
+                Some(issue) => (
+                    format!("submission/{}", issue),
+                    format!(
+                        "https://github.com/{}/pulls?q=is%3Apr+head%3Aiocost-bot%2F{}",
+                        repo, issue
+                    ),
+                    format!("{} results submitted in #{}: {}", count, issue, models),
+                ),
+                None => (
+                    format!("results/{}", day),
+                    format!("https://github.com/{}", repo),
+                    format!("{} results added to the database: {}", count, models),
+                ),
+            };
+            FeedEntry {
+                id: format!("tag:github.com,{}:{}/{}", updated.get(..4).unwrap_or("1970"), repo, id),
+                title: format!("New results for {}", models),
+                link,
+                updated,
+                author: submitter,
+                summary,
+            }
+        })
+        .collect()
+}
+
+/// Returns an entry per archived merge run whose hwdb file changed the
+/// entries of some models, linking to the commit of the `repo` it was
+/// built from
+pub fn hwdb_entries(archive: &HwdbArchive, repo: &str) -> Result<Vec<FeedEntry>> {
+    let mut entries = vec![];
+    let mut previous: Option<(String, BTreeSet<String>)> = None;
+    for run in archive.load_index()? {
+        let dir = archive.root().join(&run.dir);
+        let Ok(hwdb) = fs::read_to_string(dir.join(HWDB_FILE)) else {
+            continue;
+        };
+        let models: BTreeSet<String> = fs::read_to_string(dir.join(MANIFEST_FILE))
+            .ok()
+            .and_then(|c| serde_json::from_str::<Manifest>(&c).ok())
+            .map(|m| m.candidates.into_iter().map(|c| c.model_name).collect())
+            .unwrap_or_default();
+        let changed: Vec<&String> = match &previous {
+            Some((previous_hwdb, previous_models)) => models
+                .union(previous_models)
+                .filter(|m| hwdb_section_for(&hwdb, m) != hwdb_section_for(previous_hwdb, m))
+                .collect(),
+            None => models.iter().collect(),
+        };
+        if !changed.is_empty() {
+            let changed = changed.iter().map(|m| m.as_str()).collect::<Vec<_>>().join(", ");
+            let link = match &run.commit {
+                Some(commit) => format!("https://github.com/{}/commit/{}", repo, commit),
+                None => format!("https://github.com/{}", repo),
+            };
+            entries.push(FeedEntry {
+                id: format!(
+                    "tag:github.com,{}:{}/hwdb/{}",
+                    run.date.get(..4).unwrap_or("1970"),
+                    repo,
+                    run.dir.display()
+                ),
+                title: format!("hwdb updated for {}", changed),
+                link,
+                updated: format!("{}T00:00:00Z", run.date),
+                author: None,
+                summary: format!("The hwdb entries of {} changed", changed),
+            });
+        }
+        previous = Some((hwdb, models));
+    }
+    Ok(entries)
+}
+
+/// Renders the most recent `entries` as an Atom feed of the `repo`
+pub fn render(repo: &str, mut entries: Vec<FeedEntry>) -> String {
+    entries.sort_by(|a, b| b.updated.cmp(&a.updated));
+    entries.truncate(MAX_ENTRIES);
+    let updated = entries.first().map_or("1970-01-01T00:00:00Z".to_string(), |e| e.updated.clone());
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    xml.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+    xml.push_str("  <title>iocost-benchmarks: new results and hwdb updates</title>\n");
+    xml.push_str(&format!("  <id>https://github.com/{}</id>\n", escape(repo)));
+    xml.push_str(&format!("  <link href=\"https://github.com/{}\"/>\n", escape(repo)));
+    xml.push_str("  <author><name>iocost-benchmarks</name></author>\n");
+    xml.push_str(&format!("  <updated>{}</updated>\n", updated));
+    for entry in &entries {
+        xml.push_str("  <entry>\n");
+        xml.push_str(&format!("    <id>{}</id>\n", escape(&entry.id)));
+        xml.push_str(&format!("    <title>{}</title>\n", escape(&entry.title)));
+        xml.push_str(&format!("    <link href=\"{}\"/>\n", escape(&entry.link)));
+        xml.push_str(&format!("    <updated>{}</updated>\n", entry.updated));
+        if let Some(author) = &entry.author {
+            xml.push_str(&format!("    <author><name>{}</name></author>\n", escape(author)));
+        }
+        xml.push_str(&format!("    <summary>{}</summary>\n  </entry>\n", escape(&entry.summary)));
+    }
+    xml.push_str("</feed>\n");
+    xml
+}
+
+/// Writes the feed of the `repo` to `path`, from the results of the
+/// database in `database_dir` and the runs of the hwdb `archive`.
+/// Returns the number of entries.
+pub fn write_feed(path: &Path, database_dir: &Path, archive: &HwdbArchive, repo: &str) -> Result<usize> {
+    let mut entries = vec![];
+    if database_dir.exists() {
+        entries.extend(submission_entries(&DatabaseIndex::load(database_dir)?, repo));
+    }
+    entries.extend(hwdb_entries(archive, repo)?);
+    let count = entries.len().min(MAX_ENTRIES);
+    fs::write(path, render(repo, entries))?;
+    Ok(count)
+}
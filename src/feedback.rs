@@ -0,0 +1,127 @@
+use anyhow::Result;
+use octocrab::models::CommentId;
+use octocrab::Octocrab;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::index::DatabaseIndex;
+use crate::templates::{Message, MESSAGES};
+
+/// Submissions with at least this many URLs get a progress comment
+pub const PROGRESS_COMMENT_MIN_URLS: usize = 3;
+/// Number of recent imports the per-file estimate is based on
+const ESTIMATE_SAMPLES: usize = 50;
+/// Per-file estimate used when the database has no recorded durations
+const DEFAULT_SECS_PER_FILE: f64 = 120.0;
+
+/// Returns the estimated seconds it takes to download and validate a
+/// result, based on the recent imports recorded in the database
+pub fn estimate_secs_per_file(database_path: &Path) -> f64 {
+    DatabaseIndex::load(database_path)
+        .ok()
+        .and_then(|index| index.mean_processing_secs(ESTIMATE_SAMPLES))
+        .unwrap_or(DEFAULT_SECS_PER_FILE)
+}
+
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match secs {
+        0..=59 => format!("{}s", secs),
+        60..=3599 => format!("{}m", secs.div_ceil(60)),
+        _ => format!("{}h{:02}m", secs / 3600, (secs % 3600) / 60),
+    }
+}
+
+/// Comment on a submission issue that reports the processing progress
+/// and the estimated time left, updated as the results are processed
+pub struct ProgressComment {
+    octocrab: Octocrab,
+    owner: String,
+    issue: u64,
+    id: CommentId,
+    total: usize,
+    done: usize,
+    failed: usize,
+    secs_per_file: f64,
+    started: Instant,
+}
+
+impl ProgressComment {
+    /// Posts the initial comment on `issue` for `total` results
+    pub async fn post(
+        token: &str,
+        owner: &str,
+        issue: u64,
+        total: usize,
+        secs_per_file: f64,
+    ) -> Result<Self> {
+        let octocrab = octocrab::OctocrabBuilder::new()
+            .personal_token(token.to_string())
+            .build()?;
+        let mut comment = ProgressComment {
+            octocrab,
+            owner: owner.to_string(),
+            issue,
+            id: CommentId(0),
+            total,
+            done: 0,
+            failed: 0,
+            secs_per_file,
+            started: Instant::now(),
+        };
+        comment.id = comment
+            .octocrab
+            .issues(owner, "iocost-benchmarks")
+            .create_comment(issue, comment.body()?)
+            .await?
+            .id;
+        Ok(comment)
+    }
+
+    fn body(&self) -> Result<String> {
+        let eta = (self.done < self.total).then(|| {
+            // Once some files are done, the actual pace is a better
+            // estimate than the historical one.
+            let secs_per_file = match self.done {
+                0 => self.secs_per_file,
+                done => self.started.elapsed().as_secs_f64() / done as f64,
+            };
+            format_duration(Duration::from_secs_f64(
+                secs_per_file * (self.total - self.done) as f64,
+            ))
+        });
+        MESSAGES.render(
+            Message::Progress,
+            &serde_json::json!({
+                "total": self.total,
+                "done": self.done,
+                "failed": self.failed,
+                "eta": eta,
+            }),
+        )
+    }
+
+    /// Records a processed file and updates the comment. Failures to
+    /// update it are reported but otherwise ignored.
+    pub async fn file_done(&mut self, failed: bool) {
+        self.done += 1;
+        if failed {
+            self.failed += 1;
+        }
+        let body = match self.body() {
+            Ok(body) => body,
+            Err(e) => {
+                eprintln!("Failed to render the progress comment: {:#}", e);
+                return;
+            }
+        };
+        if let Err(e) = self
+            .octocrab
+            .issues(&self.owner, "iocost-benchmarks")
+            .update_comment(self.id, body)
+            .await
+        {
+            eprintln!("Failed to update the progress comment on issue {}: {}", self.issue, e);
+        }
+    }
+}
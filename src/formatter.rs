@@ -0,0 +1,49 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::{LazyLock, Mutex};
+
+use crate::common::run_resctl;
+use crate::error::{io_error, Result};
+use crate::progress::debug;
+use crate::retry::with_retries;
+
+/// Outputs of `resctl-bench format` for the merged results. resctl-bench
+/// formats one spec per run, and the same merged file is formatted
+/// several times in a merge (high-level summary, hwdb, firmware
+/// comparison), so the outputs are cached by the md5 of the file
+/// contents rather than formatted again.
+#[derive(Default)]
+pub struct Formatter {
+    /// Outputs by (version, md5 of the result, format spec)
+    outputs: Mutex<HashMap<(String, String, String), String>>,
+}
+
+impl Formatter {
+    /// Returns the output of resctl-bench `version` formatting the
+    /// `result` file with the `spec` format, e.g. `iocost-tune:hwdb`.
+    /// Specs writing a file, like `iocost-tune:pdf=<path>`, are always
+    /// run as their output is the file.
+    pub fn format(&self, version: &str, result: &Path, spec: &str) -> Result<String> {
+        let path = result.to_string_lossy();
+        let what = format!("Formatting {} as {}", path, spec);
+        let run = || run_resctl(version, &["--result", &path, "format", spec]);
+        if spec.contains('=') {
+            return with_retries(&what, run);
+        }
+        let md5 = format!("{:x}", md5::compute(fs::read(result).map_err(io_error(result))?));
+        let key = (version.to_string(), md5, spec.to_string());
+        if let Some(output) = self.outputs.lock().unwrap().get(&key) {
+            debug(format!("Reusing the {} output of {}", spec, path));
+            return Ok(output.clone());
+        }
+        // The lock isn't held while resctl-bench runs, so that different
+        // files are formatted in parallel
+        let output = with_retries(&what, run)?;
+        self.outputs.lock().unwrap().insert(key, output.clone());
+        Ok(output)
+    }
+}
+
+/// Formatter of the current process
+pub static FORMATTER: LazyLock<Formatter> = LazyLock::new(Formatter::default);
@@ -0,0 +1,162 @@
+use anyhow::{bail, Result};
+use std::collections::BTreeMap;
+
+use crate::common::{BenchMerge, BenchVersion};
+use crate::database::Database;
+use crate::formatter::FORMATTER;
+use crate::hwdb::hwdb_properties;
+
+/// Relative change of a fitted parameter above which a firmware update
+/// is considered to meaningfully change the iocost behaviour
+pub const SIGNIFICANT_CHANGE: f64 = 0.10;
+
+/// Returns the numeric parameters of the iocost properties of a hwdb
+/// entry, e.g. `IOCOST_MODEL_NAIVE=rbps=... rseqiops=...`, keyed by
+/// `<property> <parameter>`
+fn fitted_params(hwdb: &str) -> BTreeMap<String, f64> {
+    let mut params = BTreeMap::new();
+    for (name, value) in hwdb_properties(hwdb) {
+        let name = name.trim_start_matches("IOCOST_");
+        for (param, number) in value.split_whitespace().filter_map(|p| p.split_once('=')) {
+            if let Ok(number) = number.parse::<f64>() {
+                params.insert(format!("{} {}", name, param), number);
+            }
+        }
+    }
+    params
+}
+
+/// Solution fitted on the results of a single firmware revision
+#[derive(Debug)]
+pub struct FirmwareSolution {
+    pub fwrev: String,
+    pub results: usize,
+    pub data_points: usize,
+    pub params: BTreeMap<String, f64>,
+}
+
+/// Solutions of a model fitted separately for each firmware revision,
+/// oldest first
+#[derive(Debug)]
+pub struct FirmwareComparison {
+    pub model_name: String,
+    pub version: String,
+    pub solutions: Vec<FirmwareSolution>,
+}
+
+impl FirmwareComparison {
+    /// Merges the results of `model_name` for a resctl-bench `version`
+    /// separately for each firmware revision and fits their solutions
+    pub fn compute(database: &Database, version: &str, model_name: &str) -> Result<Self> {
+        if !BenchVersion::new(version)?.has_hwdb() {
+            bail!("resctl-bench {} can't generate the solutions to compare", version);
+        }
+        let directory = database.model_dir(version, model_name)?;
+        let merge_dir = tempfile::tempdir()?;
+        let mut solutions = vec![];
+        for (fwrev, files) in BenchMerge::results_by_fwrev(&directory)? {
+            let merged = merge_dir.path().join(format!("{}.json.gz", solutions.len()));
+            BenchMerge::merge_files(version, &files, &merged)?;
+            let hwdb = FORMATTER.format(version, &merged, "iocost-tune:hwdb")?;
+            solutions.push(FirmwareSolution {
+                fwrev,
+                results: files.len(),
+                data_points: BenchMerge::get_data_points(&merged)?,
+                params: fitted_params(&hwdb),
+            });
+        }
+        Ok(FirmwareComparison {
+            model_name: model_name.to_string(),
+            version: version.to_string(),
+            solutions,
+        })
+    }
+
+    /// Returns the largest relative change of each parameter compared to
+    /// the oldest firmware revision, largest changes first
+    pub fn changes(&self) -> Vec<(&str, f64)> {
+        let Some((oldest, newer)) = self.solutions.split_first() else {
+            return vec![];
+        };
+        let mut changes: Vec<(&str, f64)> = oldest
+            .params
+            .iter()
+            .filter(|(_, base)| **base != 0.0)
+            .map(|(name, base)| {
+                let change = newer
+                    .iter()
+                    .filter_map(|s| s.params.get(name))
+                    .map(|value| (value - base) / base)
+                    .fold(0.0, |max: f64, change| match change.abs() > max.abs() {
+                        true => change,
+                        false => max,
+                    });
+                (name.as_str(), change)
+            })
+            .collect();
+        changes.sort_by(|a, b| b.1.abs().total_cmp(&a.1.abs()));
+        changes
+    }
+
+    /// Whether some parameter changes significantly across the firmware
+    /// revisions
+    pub fn is_significant(&self) -> bool {
+        self.changes()
+            .first()
+            .is_some_and(|(_, change)| change.abs() >= SIGNIFICANT_CHANGE)
+    }
+
+    /// Returns a one-line summary of the comparison, for pull request
+    /// descriptions
+    pub fn summary(&self) -> String {
+        let fwrevs: Vec<&str> = self.solutions.iter().map(|s| s.fwrev.as_str()).collect();
+        let largest = match self.changes().first() {
+            Some((name, change)) => format!("largest change: {} {:+.1}%", name, change * 100.0),
+            None => "no comparable parameters".to_string(),
+        };
+        format!(
+            "Firmware revisions {}: {} ({})",
+            fwrevs.join(", "),
+            match self.is_significant() {
+                true => "the fitted parameters differ significantly",
+                false => "no significant difference",
+            },
+            largest
+        )
+    }
+
+    /// Renders the parameters of every firmware revision as a markdown
+    /// table, with their largest change from the oldest revision
+    pub fn to_markdown(&self) -> String {
+        let mut md = format!("## {} ({})\n\n", self.model_name, self.version);
+        if self.solutions.len() < 2 {
+            md.push_str("Only one firmware revision has results, nothing to compare.\n");
+            return md;
+        }
+        md.push_str("| Parameter |");
+        for s in &self.solutions {
+            md.push_str(&format!(" {} |", s.fwrev));
+        }
+        md.push_str(" Change |\n|---|");
+        md.push_str(&"---|".repeat(self.solutions.len() + 1));
+        md.push('\n');
+        for (name, change) in self.changes() {
+            md.push_str(&format!("| {} |", name));
+            for s in &self.solutions {
+                match s.params.get(name) {
+                    Some(value) => md.push_str(&format!(" {} |", value)),
+                    None => md.push_str(" - |"),
+                }
+            }
+            md.push_str(&format!(" {:+.1}% |\n", change * 100.0));
+        }
+        md.push_str("| Results |");
+        for s in &self.solutions {
+            md.push_str(&format!(" {} ({} data points) |", s.results, s.data_points));
+        }
+        md.push_str(" |\n\n");
+        md.push_str(&self.summary());
+        md.push('\n');
+        md
+    }
+}
@@ -0,0 +1,113 @@
+use anyhow::{anyhow, bail, Result};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::OnceLock;
+
+use crate::common::MINIMUM_DATA_POINTS;
+use crate::config::{set_once, FwMergeConfig, FwMergePolicyConfig};
+
+/// When the latest firmware revision of a model gets a merge, and a hwdb
+/// section, of its own rather than sharing the generic one. The merge
+/// needs at least `MINIMUM_DATA_POINTS` in any case, and other revisions
+/// to have results, as it would be the generic merge otherwise.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FwMergePolicy {
+    /// Its data points differ by at least this many from the generic
+    /// merge
+    MinDelta(u64),
+    /// Its data points differ by at least this fraction of those of the
+    /// generic merge
+    MinRatio(f64),
+    /// It has at least this many data points, whatever the generic
+    /// merge has, for the well-sampled drives
+    MinPoints(usize),
+}
+
+impl Default for FwMergePolicy {
+    fn default() -> Self {
+        FwMergePolicy::MinDelta(1)
+    }
+}
+
+impl fmt::Display for FwMergePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FwMergePolicy::MinDelta(delta) => write!(f, "min_delta={}", delta),
+            FwMergePolicy::MinRatio(ratio) => write!(f, "min_ratio={}", ratio),
+            FwMergePolicy::MinPoints(points) => write!(f, "min_points={}", points),
+        }
+    }
+}
+
+impl FwMergePolicy {
+    fn from_config(config: &FwMergePolicyConfig) -> anyhow::Result<Option<Self>> {
+        match (config.min_delta, config.min_ratio, config.min_points) {
+            (None, None, None) => Ok(None),
+            (Some(delta), None, None) => Ok(Some(FwMergePolicy::MinDelta(delta))),
+            (None, Some(ratio), None) if (0.0..=1.0).contains(&ratio) => Ok(Some(FwMergePolicy::MinRatio(ratio))),
+            (None, Some(ratio), None) => bail!("min_ratio {} is not between 0 and 1", ratio),
+            (None, None, Some(points)) => Ok(Some(FwMergePolicy::MinPoints(points))),
+            _ => bail!("min_delta, min_ratio and min_points are mutually exclusive"),
+        }
+    }
+
+    /// Whether the merge of the latest firmware revision, with
+    /// `data_points`, is kept next to the generic merge, with
+    /// `common_data_points`
+    pub fn selects(&self, data_points: usize, common_data_points: usize) -> bool {
+        let delta = data_points.abs_diff(common_data_points);
+        if data_points < MINIMUM_DATA_POINTS || delta == 0 {
+            return false;
+        }
+        match *self {
+            FwMergePolicy::MinDelta(min_delta) => delta as u64 >= min_delta,
+            FwMergePolicy::MinRatio(min_ratio) => delta as f64 >= min_ratio * common_data_points as f64,
+            FwMergePolicy::MinPoints(min_points) => data_points >= min_points,
+        }
+    }
+}
+
+/// Policies of the firmware-specific merges, by default or per model
+#[derive(Debug, Default)]
+pub struct FwMergePolicies {
+    default: FwMergePolicy,
+    models: HashMap<String, FwMergePolicy>,
+}
+
+impl FwMergePolicies {
+    /// Builds the policies from the [fwmerge] section of the config file
+    pub fn from_config(config: &FwMergeConfig) -> anyhow::Result<Self> {
+        let mut models = HashMap::new();
+        for (model, policy) in &config.models {
+            if let Some(policy) = FwMergePolicy::from_config(policy)
+                .map_err(|e| anyhow!("Invalid fwmerge policy for {}: {}", model, e))?
+            {
+                models.insert(model.clone(), policy);
+            }
+        }
+        Ok(FwMergePolicies {
+            default: FwMergePolicy::from_config(&config.policy)
+                .map_err(|e| anyhow!("Invalid fwmerge policy: {}", e))?
+                .unwrap_or_default(),
+            models,
+        })
+    }
+
+    /// Returns the policy of `model_name`
+    pub fn policy_for(&self, model_name: &str) -> FwMergePolicy {
+        self.models.get(model_name).copied().unwrap_or(self.default)
+    }
+}
+
+static FW_MERGE_POLICIES: OnceLock<FwMergePolicies> = OnceLock::new();
+
+/// Sets the policies of the firmware-specific merges.
+pub fn set_fw_merge_policies(policies: FwMergePolicies) -> Result<()> {
+    set_once(&FW_MERGE_POLICIES, policies, "firmware merge policies")
+}
+
+/// Returns the policies of the firmware-specific merges, the default
+/// one for all the models if none were set
+pub fn fw_merge_policies() -> &'static FwMergePolicies {
+    FW_MERGE_POLICIES.get_or_init(FwMergePolicies::default)
+}
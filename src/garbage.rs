@@ -0,0 +1,144 @@
+use anyhow::{bail, Result};
+use git2::Repository;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::database::MERGED_RESULTS_DIR;
+use crate::index::ResultMetadata;
+use crate::workspace::{workspace, HWDB_INPUTS_DIR, PDFS_DIR};
+
+/// Directories of generated files, which are rebuilt on every run and
+/// never committed
+static GENERATED_DIRS: &[&str] = &[MERGED_RESULTS_DIR, PDFS_DIR, HWDB_INPUTS_DIR];
+
+/// Kinds of files that don't belong in the database repository
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum GarbageKind {
+    /// A result without its metadata file
+    ResultWithoutMetadata,
+    /// A metadata file without its result
+    OrphanedMetadata,
+    /// A file in a model directory which is neither a result nor
+    /// metadata
+    Unexpected,
+    /// A merged result, pdf or hwdb input committed by accident
+    GeneratedLeftover,
+}
+
+impl fmt::Display for GarbageKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GarbageKind::ResultWithoutMetadata => write!(f, "result without metadata"),
+            GarbageKind::OrphanedMetadata => write!(f, "metadata without result"),
+            GarbageKind::Unexpected => write!(f, "unexpected file"),
+            GarbageKind::GeneratedLeftover => write!(f, "generated file"),
+        }
+    }
+}
+
+/// A tracked file to clean up
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Garbage {
+    pub kind: GarbageKind,
+    pub path: PathBuf,
+}
+
+impl Garbage {
+    /// Stages the fix of the file in `index`: the missing metadata is
+    /// added for results, every other file is removed
+    fn fix(&self, index: &mut git2::Index) -> Result<()> {
+        match self.kind {
+            GarbageKind::ResultWithoutMetadata => {
+                ResultMetadata::unknown_origin(&self.path).save_for(&self.path)?;
+                index.add_path(&ResultMetadata::path_for(&self.path))?;
+            }
+            _ => {
+                if self.path.exists() {
+                    fs::remove_file(&self.path)?;
+                }
+                index.remove_path(&self.path)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns the result file of a `metadata` file
+fn result_for(metadata: &Path) -> PathBuf {
+    metadata.with_extension("").with_extension("json.gz")
+}
+
+fn is_generated(path: &Path) -> bool {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    GENERATED_DIRS.iter().any(|d| path.starts_with(d))
+        || name.ends_with("-merged-results.json.gz")
+        || name.ends_with(".pdf")
+}
+
+/// Finds the files tracked in `git_repo` that don't belong in it: results
+/// and metadata files in `database_dir` missing their counterpart,
+/// unexpected files in its model directories and generated files
+/// committed by accident. Paths are relative to the repository root.
+pub fn find_garbage(git_repo: &Repository, database_dir: &Path) -> Result<Vec<Garbage>> {
+    let tracked: BTreeSet<PathBuf> = git_repo
+        .index()?
+        .iter()
+        .map(|e| PathBuf::from(String::from_utf8_lossy(&e.path).to_string()))
+        .collect();
+    let database_dir = database_dir.strip_prefix(".").unwrap_or(database_dir);
+    let mut garbage = vec![];
+    for path in &tracked {
+        let kind = if is_generated(path) {
+            Some(GarbageKind::GeneratedLeftover)
+        } else {
+            // Only the files of the model directories are checked, the
+            // database root has the layout file and failure ledger.
+            match path.strip_prefix(database_dir) {
+                Ok(relative) if relative.components().count() == 3 => {
+                    let name = path.file_name().unwrap().to_string_lossy();
+                    if name.starts_with("result-") && name.ends_with(".json.gz") {
+                        (!tracked.contains(&ResultMetadata::path_for(path)))
+                            .then_some(GarbageKind::ResultWithoutMetadata)
+                    } else if name.ends_with(".json.metadata") {
+                        (!tracked.contains(&result_for(path))).then_some(GarbageKind::OrphanedMetadata)
+                    } else {
+                        Some(GarbageKind::Unexpected)
+                    }
+                }
+                _ => None,
+            }
+        };
+        if let Some(kind) = kind {
+            garbage.push(Garbage { kind, path: path.clone() });
+        }
+    }
+    garbage.sort();
+    Ok(garbage)
+}
+
+/// Runs the `gc` command: lists the garbage of the repository of the
+/// workspace and, with `fix`, stages its cleanup. Fails if there
+/// is garbage left.
+pub fn run_gc(database_dir: &Path, fix: bool) -> Result<()> {
+    let git_repo = workspace().repository()?;
+    let garbage = find_garbage(&git_repo, database_dir)?;
+    if garbage.is_empty() {
+        println!("No garbage found");
+        return Ok(());
+    }
+    for g in &garbage {
+        println!("{:<24} {}", g.kind.to_string(), g.path.display());
+    }
+    if !fix {
+        bail!("Found {} files to clean up, run with --fix to stage the cleanup", garbage.len());
+    }
+    let mut index = git_repo.index()?;
+    for g in &garbage {
+        g.fix(&mut index)?;
+    }
+    index.write()?;
+    println!("Staged the cleanup of {} files", garbage.len());
+    Ok(())
+}
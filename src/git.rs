@@ -0,0 +1,318 @@
+use anyhow::{bail, Context, Result};
+use git2::build::CheckoutBuilder;
+use git2::{
+    BranchType, Commit, Cred, Delta, ErrorCode, FetchOptions, Oid, PushOptions, RebaseOptions, RemoteCallbacks,
+    Repository, ResetType,
+};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use crate::commits::commit_settings;
+use crate::index::ResultMetadata;
+use crate::review::PENDING_DIR;
+
+/// Commits the tree staged in `index` on top of HEAD and points
+/// `branch_name` to the new commit, replacing it if it already exists.
+/// HEAD is left as is, so that the branches of long-lived checkouts
+/// don't stack the commits of one another.
+pub fn commit_to_branch(
+    git_repo: &Repository,
+    index: &mut git2::Index,
+    message: &str,
+    branch_name: &str,
+) -> Result<Oid> {
+    let sig = git2::Signature::now("iocost bot", "iocost-bot@has.no.email")?;
+    let parent_commit = git_repo.head()?.peel_to_commit()?;
+    let oid = index.write_tree()?;
+    let tree = git_repo.find_tree(oid)?;
+    let commit = git_repo.commit(
+        None,
+        &sig,
+        &sig,
+        message,
+        &tree,
+        &[&parent_commit],
+    )?;
+    git_repo.branch(branch_name, &git_repo.find_commit(commit)?, true)?;
+    Ok(commit)
+}
+
+/// Resets the index and the tracked files to HEAD and removes the
+/// untracked files of `database_path`, e.g. the results an import staged
+/// for its branch, so that the next event of a long-lived checkout
+/// starts from a clean tree
+pub fn reset_to_head(git_repo: &Repository, database_path: &Path) -> Result<()> {
+    let head = git_repo.head()?.peel_to_commit()?;
+    git_repo.reset(head.as_object(), ResetType::Hard, None)?;
+    git_repo.checkout_head(Some(
+        CheckoutBuilder::new()
+            .force()
+            .remove_untracked(true)
+            .path(git_path(database_path)),
+    ))?;
+    Ok(())
+}
+
+/// Fast-forwards HEAD, and the branch it points to if any, to `target`,
+/// checking out the changed files. Returns whether HEAD moved, failing
+/// if it has commits `target` doesn't have.
+pub fn fast_forward_head(git_repo: &Repository, target: &Commit) -> Result<bool> {
+    let head = git_repo.head()?;
+    let current = head.peel_to_commit()?;
+    if current.id() == target.id() || git_repo.graph_descendant_of(current.id(), target.id())? {
+        return Ok(false);
+    }
+    if !git_repo.graph_descendant_of(target.id(), current.id())? {
+        bail!("HEAD ({}) diverged from {}, it can't be fast-forwarded", current.id(), target.id());
+    }
+    git_repo.checkout_tree(target.as_object(), Some(CheckoutBuilder::new().safe()))?;
+    match head.name().filter(|_| head.is_branch()) {
+        Some(name) => {
+            git_repo.reference(name, target.id(), true, "fast-forward")?;
+        }
+        None => git_repo.set_head_detached(target.id())?,
+    }
+    Ok(true)
+}
+
+/// Rebases the commits of the local `branch_name` missing from `onto` on
+/// top of it, without touching HEAD or the working tree. Returns whether
+/// the branch moved, failing without changing it if a commit conflicts.
+pub fn rebase_branch(git_repo: &Repository, branch_name: &str, onto: Oid) -> Result<bool> {
+    let branch = git_repo.find_reference(&format!("refs/heads/{}", branch_name))?;
+    let tip = branch.peel_to_commit()?.id();
+    if tip == onto || git_repo.graph_descendant_of(tip, onto)? {
+        return Ok(false);
+    }
+    let sig = git2::Signature::now("iocost bot", "iocost-bot@has.no.email")?;
+    let mut options = RebaseOptions::new();
+    options.inmemory(true);
+    let mut rebase = git_repo.rebase(
+        Some(&git_repo.reference_to_annotated_commit(&branch)?),
+        Some(&git_repo.find_annotated_commit(onto)?),
+        None,
+        Some(&mut options),
+    )?;
+    let mut rebased = onto;
+    while let Some(operation) = rebase.next() {
+        let operation = operation?;
+        if rebase.inmemory_index()?.has_conflicts() {
+            rebase.abort()?;
+            bail!("{} conflicts with {} in commit {}", branch_name, onto, operation.id());
+        }
+        match rebase.commit(None, &sig, None) {
+            Ok(commit) => rebased = commit,
+            // The change of the commit is already in `onto`
+            Err(e) if e.code() == ErrorCode::Applied => (),
+            Err(e) => return Err(e.into()),
+        }
+    }
+    rebase.finish(None)?;
+    git_repo.reference(&format!("refs/heads/{}", branch_name), rebased, true, &format!("rebase onto {}", onto))?;
+    Ok(true)
+}
+
+/// Force-pushes `branch_name` to the Github repository `repo`
+/// (owner/name) over HTTPS, authenticating with `token`.
+pub fn push_branch(git_repo: &Repository, repo: &str, branch_name: &str, token: &str) -> Result<()> {
+    push_refspec(git_repo, repo, &format!("+refs/heads/{0}:refs/heads/{0}", branch_name), token)
+}
+
+/// Pushes `branch_name` to the Github repository `repo` (owner/name)
+/// without forcing it, failing if the remote branch moved since it was
+/// fetched
+pub fn fast_forward_branch(git_repo: &Repository, repo: &str, branch_name: &str, token: &str) -> Result<()> {
+    push_refspec(git_repo, repo, &format!("refs/heads/{0}:refs/heads/{0}", branch_name), token)
+}
+
+/// Deletes `branch_name` from the Github repository `repo` (owner/name)
+pub fn delete_remote_branch(git_repo: &Repository, repo: &str, branch_name: &str, token: &str) -> Result<()> {
+    push_refspec(git_repo, repo, &format!(":refs/heads/{}", branch_name), token)
+}
+
+/// Fetches `branch_name` of the Github repository `repo` (owner/name) to
+/// `origin/<branch_name>`, if it exists
+pub fn fetch_branch(git_repo: &Repository, repo: &str, branch_name: &str, token: &str) -> Result<()> {
+    let url = format!("https://github.com/{}.git", repo);
+    let refspec = format!("+refs/heads/{0}:refs/remotes/origin/{0}", branch_name);
+    let mut options = FetchOptions::new();
+    options.remote_callbacks(token_callbacks(token));
+    git_repo
+        .remote_anonymous(&url)?
+        .fetch(&[&refspec], Some(&mut options), None)
+        .with_context(|| format!("Failed to fetch {} from {}", refspec, url))
+}
+
+/// Checks out `git_ref`, a branch, tag or commit of the repository at
+/// `url`, in `dir`, fetching it in the repository already there if any
+pub fn checkout_remote_ref(dir: &Path, url: &str, git_ref: &str) -> Result<Oid> {
+    let git_repo = match Repository::open(dir) {
+        Ok(git_repo) => git_repo,
+        Err(_) => Repository::init(dir)?,
+    };
+    git_repo
+        .remote_anonymous(url)?
+        .fetch(&[git_ref], None, None)
+        .with_context(|| format!("Failed to fetch {} from {}", git_ref, url))?;
+    let commit = git_repo.find_reference("FETCH_HEAD")?.peel_to_commit()?;
+    git_repo.checkout_tree(commit.as_object(), Some(CheckoutBuilder::new().force()))?;
+    git_repo.set_head_detached(commit.id())?;
+    Ok(commit.id())
+}
+
+/// Returns the callbacks authenticating to Github with `token`
+fn token_callbacks(token: &str) -> RemoteCallbacks<'_> {
+    let mut callbacks = RemoteCallbacks::new();
+    callbacks.credentials(|_, _, _| Cred::userpass_plaintext("x-access-token", token));
+    // Refs the server refuses to update don't fail the push otherwise
+    callbacks.push_update_reference(|name, status| match status {
+        Some(status) => Err(git2::Error::from_str(&format!("{} rejected: {}", name, status))),
+        None => Ok(()),
+    });
+    callbacks
+}
+
+fn push_refspec(git_repo: &Repository, repo: &str, refspec: &str, token: &str) -> Result<()> {
+    let url = format!("https://github.com/{}.git", repo);
+    let mut remote = git_repo.remote_anonymous(&url)?;
+    let mut options = PushOptions::new();
+    options.remote_callbacks(token_callbacks(token));
+    remote
+        .push(&[refspec], Some(&mut options))
+        .with_context(|| format!("Failed to push {} to {}", refspec, url))
+}
+
+/// Returns the `iocost-bot/*` and submission branches (local or fetched
+/// from origin) that are either already merged into HEAD or haven't been updated in
+/// `max_age_days`. Remote branches are returned without the remote
+/// name prefix, along with whether they are local.
+pub fn stale_bot_branches(git_repo: &Repository, max_age_days: i64) -> Result<Vec<(String, BranchType)>> {
+    let head = git_repo.head()?.peel_to_commit()?;
+    let cutoff = chrono::Utc::now().timestamp() - max_age_days * 24 * 3600;
+    let mut stale = vec![];
+    for branch in git_repo.branches(None)? {
+        let (branch, branch_type) = branch?;
+        let name = match branch.name()? {
+            Some(n) => n.to_string(),
+            None => continue,
+        };
+        let name = match branch_type {
+            BranchType::Local => name,
+            BranchType::Remote => match name.strip_prefix("origin/") {
+                Some(n) => n.to_string(),
+                None => continue,
+            },
+        };
+        if !name.starts_with("iocost-bot/") && commit_settings().issue_of(&name).is_none() {
+            continue;
+        }
+        let tip = branch.get().peel_to_commit()?;
+        let merged = tip.id() == head.id() || git_repo.graph_descendant_of(head.id(), tip.id())?;
+        if merged || tip.time().seconds() < cutoff {
+            stale.push((name, branch_type));
+        }
+    }
+    Ok(stale)
+}
+
+/// Returns the metadata of the results added to `database_path`, or to
+/// the pending area of the review, by the branches of the submissions
+/// (`iocost-bot/<issue>` by default) not merged yet, local or fetched
+/// from origin, i.e. of the submissions being imported
+pub fn pending_submissions(git_repo: &Repository, database_path: &Path) -> Result<Vec<ResultMetadata>> {
+    let head = git_repo.head()?.peel_to_commit()?;
+    let mut pending = vec![];
+    for branch in git_repo.branches(None)? {
+        let (branch, _) = branch?;
+        let name = branch.name()?.unwrap_or_default();
+        if commit_settings().issue_of(name.trim_start_matches("origin/")).is_none() {
+            continue;
+        }
+        let tip = branch.get().peel_to_commit()?;
+        if tip.id() == head.id() || git_repo.graph_descendant_of(head.id(), tip.id())? {
+            continue;
+        }
+        let diff = git_repo.diff_tree_to_tree(Some(&head.tree()?), Some(&tip.tree()?), None)?;
+        for delta in diff.deltas().filter(|d| d.status() == Delta::Added) {
+            let added = delta.new_file();
+            let is_metadata = added.path().is_some_and(|p| {
+                (p.starts_with(database_path) || p.starts_with(PENDING_DIR))
+                    && p.to_string_lossy().ends_with(".json.metadata")
+            });
+            if !is_metadata {
+                continue;
+            }
+            let blob = git_repo.find_blob(added.id())?;
+            if let Ok(metadata) = serde_json::from_slice(blob.content()) {
+                pending.push(metadata);
+            }
+        }
+    }
+    Ok(pending)
+}
+
+/// Returns the files added between commit `base` and HEAD
+pub fn added_files(git_repo: &Repository, base: &str) -> Result<Vec<PathBuf>> {
+    let base_tree = git_repo
+        .find_commit(Oid::from_str(base)?)
+        .with_context(|| format!("Can't find base commit {}", base))?
+        .tree()?;
+    let head_tree = git_repo.head()?.peel_to_tree()?;
+    let diff = git_repo.diff_tree_to_tree(Some(&base_tree), Some(&head_tree), None)?;
+    Ok(diff
+        .deltas()
+        .filter(|d| d.status() == Delta::Added)
+        .filter_map(|d| d.new_file().path().map(PathBuf::from))
+        .collect())
+}
+
+/// Returns the (version, model) pairs whose files in `database_path`
+/// changed between commits `from` and `to`
+pub fn changed_models(
+    git_repo: &Repository,
+    from: &str,
+    to: &str,
+    database_path: &Path,
+) -> Result<BTreeSet<(String, String)>> {
+    let tree_for = |sha: &str| -> Result<git2::Tree> {
+        Ok(git_repo
+            .find_commit(Oid::from_str(sha)?)
+            .with_context(|| format!("Can't find commit {}", sha))?
+            .tree()?)
+    };
+    let diff = git_repo.diff_tree_to_tree(Some(&tree_for(from)?), Some(&tree_for(to)?), None)?;
+    let mut models = BTreeSet::new();
+    for delta in diff.deltas() {
+        for path in [delta.old_file().path(), delta.new_file().path()].into_iter().flatten() {
+            let mut components = match path.strip_prefix(database_path) {
+                Ok(p) => p.components(),
+                Err(_) => continue,
+            };
+            // Expect <version>/<model>/<file>
+            if let (Some(version), Some(model), Some(_)) =
+                (components.next(), components.next(), components.next())
+            {
+                models.insert((
+                    version.as_os_str().to_string_lossy().to_string(),
+                    model.as_os_str().to_string_lossy().to_string(),
+                ));
+            }
+        }
+    }
+    Ok(models)
+}
+
+/// Returns the sha of the HEAD commit of the repository containing
+/// `path`, like `git rev-parse HEAD`
+pub fn head_commit(path: &Path) -> Result<String> {
+    let git_repo = Repository::discover(path)
+        .with_context(|| format!("{} is not in a git repository", path.display()))?;
+    let commit = git_repo.head()?.peel_to_commit()?;
+    Ok(commit.id().to_string())
+}
+
+/// Returns `path` as a path of the git index and trees, which are
+/// separated by `/` whatever the platform
+pub fn git_path(path: &Path) -> String {
+    path.iter().map(|c| c.to_string_lossy()).collect::<Vec<_>>().join("/")
+}
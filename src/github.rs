@@ -0,0 +1,130 @@
+use anyhow::Result;
+
+/// Returns the URL of the pull request opened in the Github repository
+/// `repo` (owner/name) from `branch_name`, if any
+pub async fn find_pull_request(token: &str, repo: &str, branch_name: &str) -> Result<Option<String>> {
+    let (owner, name) = repo
+        .split_once('/')
+        .ok_or_else(|| anyhow::anyhow!("Malformed repository name: {}", repo))?;
+    let page = octocrab::OctocrabBuilder::new()
+        .personal_token(token.to_string())
+        .build()?
+        .pulls(owner, name)
+        .list()
+        .state(octocrab::params::State::All)
+        .head(format!("{}:{}", owner, branch_name))
+        .send()
+        .await?;
+    Ok(page
+        .items
+        .into_iter()
+        .next()
+        .and_then(|pr| pr.html_url)
+        .map(|u| u.to_string()))
+}
+
+/// Opens a pull request in the Github repository `repo` (owner/name)
+/// from `branch_name` into `base`, as a `draft` if set, and returns its
+/// URL.
+pub async fn open_pull_request(
+    token: &str,
+    repo: &str,
+    branch_name: &str,
+    base: &str,
+    title: &str,
+    body: &str,
+    draft: bool,
+) -> Result<String> {
+    let (owner, name) = repo
+        .split_once('/')
+        .ok_or_else(|| anyhow::anyhow!("Malformed repository name: {}", repo))?;
+    let pr = octocrab::OctocrabBuilder::new()
+        .personal_token(token.to_string())
+        .build()?
+        .pulls(owner, name)
+        .create(title, branch_name, base)
+        .body(body)
+        .draft(draft)
+        .send()
+        .await?;
+    Ok(pr.html_url.map(|u| u.to_string()).unwrap_or_default())
+}
+
+/// Updates the title and description of the open pull request of the
+/// Github repository `repo` (owner/name) from `branch_name`, if there is
+/// one. Returns its URL.
+pub async fn update_pull_request(
+    token: &str,
+    repo: &str,
+    branch_name: &str,
+    title: &str,
+    body: &str,
+) -> Result<Option<String>> {
+    let (owner, name) = repo
+        .split_once('/')
+        .ok_or_else(|| anyhow::anyhow!("Malformed repository name: {}", repo))?;
+    let octocrab = octocrab::OctocrabBuilder::new()
+        .personal_token(token.to_string())
+        .build()?;
+    let page = octocrab
+        .pulls(owner, name)
+        .list()
+        .head(format!("{}:{}", owner, branch_name))
+        .send()
+        .await?;
+    let Some(pr) = page.items.into_iter().next() else {
+        return Ok(None);
+    };
+    let pr = octocrab.pulls(owner, name).update(pr.number).title(title).body(body).send().await?;
+    Ok(Some(pr.html_url.map(|u| u.to_string()).unwrap_or_default()))
+}
+
+/// Posts `body` as a review comment, neither approving nor requesting
+/// changes, on pull request `number` of the Github repository `repo`
+/// (owner/name)
+pub async fn post_review(token: &str, repo: &str, number: u64, body: &str) -> Result<()> {
+    let (owner, name) = repo
+        .split_once('/')
+        .ok_or_else(|| anyhow::anyhow!("Malformed repository name: {}", repo))?;
+    let review = serde_json::json!({ "body": body, "event": "COMMENT" });
+    let _: serde_json::Value = octocrab::OctocrabBuilder::new()
+        .personal_token(token.to_string())
+        .build()?
+        .post(format!("/repos/{}/{}/pulls/{}/reviews", owner, name, number), Some(&review))
+        .await?;
+    Ok(())
+}
+
+/// Marks the draft pull request opened in the Github repository `repo`
+/// (owner/name) from `branch_name` as ready for review. Returns its URL,
+/// if there is one.
+pub async fn mark_ready_for_review(token: &str, repo: &str, branch_name: &str) -> Result<Option<String>> {
+    let (owner, name) = repo
+        .split_once('/')
+        .ok_or_else(|| anyhow::anyhow!("Malformed repository name: {}", repo))?;
+    let octocrab = octocrab::OctocrabBuilder::new()
+        .personal_token(token.to_string())
+        .build()?;
+    let page = octocrab
+        .pulls(owner, name)
+        .list()
+        .head(format!("{}:{}", owner, branch_name))
+        .send()
+        .await?;
+    let Some(pr) = page.items.into_iter().next() else {
+        return Ok(None);
+    };
+    // Only the GraphQL API can take a pull request out of draft
+    if let (Some(true), Some(id)) = (pr.draft, &pr.node_id) {
+        let query = serde_json::json!({
+            "query": "mutation($id: ID!) { markPullRequestReadyForReview(input: {pullRequestId: $id}) \
+                      { pullRequest { id } } }",
+            "variables": { "id": id },
+        });
+        let response: serde_json::Value = octocrab.post("graphql", Some(&query)).await?;
+        if let Some(errors) = response.get("errors") {
+            anyhow::bail!("Failed to mark the pull request of {} ready for review: {}", branch_name, errors);
+        }
+    }
+    Ok(pr.html_url.map(|u| u.to_string()))
+}
@@ -0,0 +1,141 @@
+use anyhow::{bail, Result};
+use json::JsonValue;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// QoS parameters of the targets, in the order they are shown
+const QOS_PARAMS: &[&str] = &["rpct", "rlat", "wpct", "wlat", "min", "max"];
+
+/// Fit metrics of the solutions stored in the results, as fractions
+const FIT_METRICS: &[&str] = &["isol", "lat_imp", "work_csv", "missing"];
+
+/// A QoS target of the iocost-tune high-level summary, e.g. `naive` or
+/// `isolated-bandwidth`, with the iocost parameters found for it
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct QosTarget {
+    pub name: String,
+    /// iocost QoS parameters: rpct, rlat, wpct, wlat, min and max
+    pub qos: BTreeMap<String, f64>,
+    /// iocost model parameters: rbps, rseqiops, rrandiops, etc.
+    pub model: BTreeMap<String, f64>,
+    /// How well the solution fits the target, e.g. isol, lat_imp or
+    /// work_csv, in percents
+    pub fit: BTreeMap<String, f64>,
+}
+
+/// Parsed output of `resctl-bench format iocost-tune:high-level`, which
+/// lists the QoS targets as:
+///
+/// ```text
+/// [isolated-bandwidth]
+///   info: isol=91.02% lat_imp=13.57% work_csv=71.27% missing=0.20%
+///   model: rbps=1407126476 rseqiops=296434 rrandiops=324186 ...
+///   qos: rpct=95.00 rlat=1234 wpct=95.00 wlat=2345 min=64.80 max=64.80
+/// ```
+///
+/// Lines which aren't part of a target, or values which aren't numbers,
+/// are ignored.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct HighLevelSummary {
+    /// Targets in the order of the output
+    pub targets: Vec<QosTarget>,
+}
+
+/// Returns the numeric members of the JSON object `params`
+fn json_params(params: &JsonValue) -> BTreeMap<String, f64> {
+    params
+        .entries()
+        .filter_map(|(key, value)| Some((key.to_string(), value.as_f64()?)))
+        .collect()
+}
+
+/// Parses the `key=value` pairs of `line`, without the percent signs
+fn parse_params(line: &str) -> BTreeMap<String, f64> {
+    line.split_whitespace()
+        .filter_map(|param| {
+            let (key, value) = param.split_once('=')?;
+            Some((key.to_string(), value.trim_end_matches('%').parse().ok()?))
+        })
+        .collect()
+}
+
+impl HighLevelSummary {
+    /// Parses the high-level `output` of resctl-bench, failing if it has
+    /// no QoS target, e.g. for the versions without the high-level format
+    pub fn parse(output: &str) -> Result<Self> {
+        let mut targets: Vec<QosTarget> = vec![];
+        for line in output.lines() {
+            let trimmed = line.trim();
+            if let Some(name) = trimmed.strip_prefix('[').and_then(|l| l.split_once(']')) {
+                targets.push(QosTarget { name: name.0.trim().to_string(), ..Default::default() });
+                continue;
+            }
+            let (Some(target), Some((label, params))) = (targets.last_mut(), trimmed.split_once(':')) else {
+                continue;
+            };
+            let params = parse_params(params);
+            match label.trim() {
+                "qos" => target.qos.extend(params),
+                "model" => target.model.extend(params),
+                _ => target.fit.extend(params),
+            }
+        }
+        // Headings without parameters, e.g. of the whole output, aren't
+        // targets
+        targets.retain(|t| !(t.qos.is_empty() && t.model.is_empty() && t.fit.is_empty()));
+        if targets.is_empty() {
+            bail!("No QoS target in the high-level summary");
+        }
+        Ok(HighLevelSummary { targets })
+    }
+
+    /// Builds the summary from the `solutions` of an iocost-tune result,
+    /// which both 2.1 and 2.2 store by target name, for the versions
+    /// without the high-level format
+    pub fn from_solutions(solutions: &JsonValue) -> Self {
+        let targets = solutions
+            .entries()
+            .map(|(name, solution)| QosTarget {
+                name: name.to_string(),
+                qos: json_params(&solution["qos"]),
+                model: json_params(&solution["model"]),
+                fit: FIT_METRICS
+                    .iter()
+                    .filter_map(|m| Some((m.to_string(), solution[*m].as_f64()? * 100.0)))
+                    .collect(),
+            })
+            .collect();
+        HighLevelSummary { targets }
+    }
+
+    /// Returns the target named `name`
+    pub fn target(&self, name: &str) -> Option<&QosTarget> {
+        self.targets.iter().find(|t| t.name == name)
+    }
+
+    /// Renders the QoS parameters and the fit metrics of the targets as
+    /// a Markdown table, a row per target
+    pub fn to_markdown(&self) -> String {
+        let qos: Vec<&str> = QOS_PARAMS
+            .iter()
+            .copied()
+            .filter(|p| self.targets.iter().any(|t| t.qos.contains_key(*p)))
+            .collect();
+        let fit: BTreeSet<&str> = self.targets.iter().flat_map(|t| t.fit.keys().map(String::as_str)).collect();
+        let columns: Vec<&str> = qos.iter().chain(&fit).copied().collect();
+        let mut table = format!("| target | {} |\n", columns.join(" | "));
+        table.push_str(&format!("|---{}|\n", "|---:".repeat(columns.len())));
+        for target in &self.targets {
+            let values: Vec<String> = columns
+                .iter()
+                .map(|c| match target.qos.get(*c).or(target.fit.get(*c)) {
+                    Some(value) if fit.contains(c) => format!("{:.2}%", value),
+                    Some(value) => value.to_string(),
+                    None => "-".to_string(),
+                })
+                .collect();
+            table.push_str(&format!("| {} | {} |\n", target.name, values.join(" | ")));
+        }
+        table
+    }
+}
@@ -0,0 +1,189 @@
+use anyhow::Result;
+use git2::{Commit, IndexEntry, IndexTime, Repository};
+use serde::{Deserialize, Serialize};
+use serde_with::skip_serializing_none;
+use std::path::PathBuf;
+
+use crate::actions::ContextPayload;
+use crate::git::{fast_forward_branch, fetch_branch, git_path};
+use crate::workspace::workspace;
+
+/// Branch of the database repository the histories of the submissions
+/// are committed to, apart from the database itself
+pub static HISTORY_BRANCH: &str = "iocost-history";
+
+/// Directory of the history branch with a directory per issue
+pub static HISTORY_DIR: &str = "submissions";
+
+/// Name of the history file in the directory of an issue
+pub static HISTORY_FILE: &str = "history.jsonl";
+
+/// An event of the processing of a submission, as a line of its
+/// history file
+#[skip_serializing_none]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEvent {
+    /// Time of the event, in RFC 3339 format
+    pub at: String,
+    /// Github event and action that triggered the run, e.g.
+    /// `issues/opened`
+    pub event: String,
+    /// User who triggered the event
+    pub user: Option<String>,
+    /// What came out of it, e.g. `imported`, `queued` or `error`
+    pub outcome: String,
+    pub imported: Option<usize>,
+    pub failed: Option<usize>,
+    /// Branch the changes were committed to
+    pub branch: Option<String>,
+    /// Error or details of the outcome
+    pub detail: Option<String>,
+}
+
+impl HistoryEvent {
+    /// Creates the event of the `outcome` of the run triggered by the
+    /// event of `context`
+    pub fn new(context: &ContextPayload, outcome: &str) -> Self {
+        let event = match &context.event.action {
+            Some(action) => format!("{}/{}", context.event_name, action),
+            None => context.event_name.clone(),
+        };
+        HistoryEvent {
+            at: chrono::Utc::now().to_rfc3339(),
+            event,
+            user: context.event.sender.as_ref().map(|s| s.login.clone()),
+            outcome: outcome.to_string(),
+            imported: None,
+            failed: None,
+            branch: None,
+            detail: None,
+        }
+    }
+}
+
+impl std::fmt::Display for HistoryEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} {}", self.at, self.event)?;
+        if let Some(user) = &self.user {
+            write!(f, " by @{}", user)?;
+        }
+        write!(f, ": {}", self.outcome)?;
+        if let (Some(imported), Some(failed)) = (self.imported, self.failed) {
+            write!(f, " ({} imported, {} failed)", imported, failed)?;
+        }
+        if let Some(branch) = &self.branch {
+            write!(f, " to {}", branch)?;
+        }
+        if let Some(detail) = &self.detail {
+            write!(f, " - {}", detail)?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the path of the history file of `issue` in the history branch
+fn history_path(issue: u64) -> PathBuf {
+    PathBuf::from(HISTORY_DIR).join(issue.to_string()).join(HISTORY_FILE)
+}
+
+/// Returns the tip of the history branch: the local one if it has all
+/// the commits of the one fetched from origin, the fetched one otherwise
+fn history_tip(git_repo: &Repository) -> Result<Option<Commit<'_>>> {
+    let tip = |name: &str| git_repo.find_reference(name).and_then(|r| r.peel_to_commit()).ok();
+    let local = tip(&format!("refs/heads/{}", HISTORY_BRANCH));
+    let remote = tip(&format!("refs/remotes/origin/{}", HISTORY_BRANCH));
+    Ok(match (local, remote) {
+        (Some(local), Some(remote)) => {
+            if local.id() == remote.id() || git_repo.graph_descendant_of(local.id(), remote.id())? {
+                Some(local)
+            } else {
+                Some(remote)
+            }
+        }
+        (local, remote) => local.or(remote),
+    })
+}
+
+/// Loads the history of `issue` from the history branch of `git_repo`
+pub fn load_history(git_repo: &Repository, issue: u64) -> Result<Vec<HistoryEvent>> {
+    let Some(tip) = history_tip(git_repo)? else {
+        return Ok(vec![]);
+    };
+    let entry = match tip.tree()?.get_path(&history_path(issue)) {
+        Ok(entry) => entry,
+        Err(_) => return Ok(vec![]),
+    };
+    let blob = git_repo.find_blob(entry.id())?;
+    Ok(String::from_utf8_lossy(blob.content())
+        .lines()
+        .filter_map(|l| serde_json::from_str(l).ok())
+        .collect())
+}
+
+/// Commits `event` at the end of the history of `issue` in the local
+/// history branch, without touching HEAD or the working tree
+fn commit_event(git_repo: &Repository, issue: u64, event: &HistoryEvent) -> Result<()> {
+    let tip = history_tip(git_repo)?;
+    let path = history_path(issue);
+    let mut index = git2::Index::new()?;
+    let mut contents = vec![];
+    if let Some(tip) = &tip {
+        let tree = tip.tree()?;
+        index.read_tree(&tree)?;
+        if let Ok(entry) = tree.get_path(&path) {
+            contents = git_repo.find_blob(entry.id())?.content().to_vec();
+        }
+    }
+    contents.extend(format!("{}\n", serde_json::to_string(event)?).into_bytes());
+    let path = git_path(&path);
+    index.add(&IndexEntry {
+        ctime: IndexTime::new(0, 0),
+        mtime: IndexTime::new(0, 0),
+        dev: 0,
+        ino: 0,
+        mode: 0o100644,
+        uid: 0,
+        gid: 0,
+        file_size: contents.len() as u32,
+        id: git_repo.blob(&contents)?,
+        flags: path.len().min(0xfff) as u16,
+        flags_extended: 0,
+        path: path.into_bytes(),
+    })?;
+    let tree = git_repo.find_tree(index.write_tree_to(git_repo)?)?;
+    let sig = git2::Signature::now("iocost bot", "iocost-bot@has.no.email")?;
+    let message = format!("#{}: {}", issue, event.outcome);
+    let parents: Vec<&Commit> = tip.iter().collect();
+    let commit = git_repo.commit(None, &sig, &sig, &message, &tree, &parents)?;
+    git_repo.reference(&format!("refs/heads/{}", HISTORY_BRANCH), commit, true, &message)?;
+    Ok(())
+}
+
+/// Appends `event` to the history of `issue` in the history branch of
+/// the repository of the workspace, and pushes it to the
+/// repository of the `context` if it has a token. The branch is fetched
+/// first, and again if another run pushed to it in the meantime.
+pub fn record_event(context: &ContextPayload, issue: u64, event: &HistoryEvent) -> Result<()> {
+    let git_repo = workspace().repository()?;
+    let (repo, token) = match (&context.repository, &context.token) {
+        (Some(repo), Some(token)) => (repo, token),
+        _ => {
+            commit_event(&git_repo, issue, event)?;
+            println!("No repository or token in the context, the history of #{} is only kept locally", issue);
+            return Ok(());
+        }
+    };
+    // The local commit is redone on top of the fetched branch if it moved
+    let mut retried = false;
+    loop {
+        fetch_branch(&git_repo, repo, HISTORY_BRANCH, token)?;
+        commit_event(&git_repo, issue, event)?;
+        match fast_forward_branch(&git_repo, repo, HISTORY_BRANCH, token) {
+            Err(e) if !retried => {
+                eprintln!("Retrying to push the history of #{}: {:#}", issue, e);
+                retried = true;
+            }
+            result => return result,
+        }
+    }
+}
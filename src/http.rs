@@ -0,0 +1,164 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use crate::config::{set_once, HostConfig, HttpConfig};
+
+/// Settings of the HTTP client the submitted result files are downloaded
+/// with, for environments which only reach S3 and Github through a proxy
+#[derive(Debug, Default, Clone)]
+pub struct HttpSettings {
+    /// Proxy URL of every request. Without it, the proxy of the
+    /// HTTPS_PROXY, HTTP_PROXY and NO_PROXY environment variables is used.
+    pub proxy: Option<String>,
+    /// PEM file with root certificates to trust on top of the system
+    /// ones, such as the private CA of a proxy
+    pub ca_certificates: Option<PathBuf>,
+    /// Private hosts and their credentials
+    pub hosts: Vec<HostConfig>,
+}
+
+impl HttpSettings {
+    /// Creates the settings of the [http] section of the config file
+    pub fn from_config(config: &HttpConfig) -> Self {
+        HttpSettings {
+            proxy: config.proxy.clone(),
+            ca_certificates: config.ca_certificates.clone(),
+            hosts: config.hosts.clone(),
+        }
+    }
+
+    /// Returns the private host `url` is under, if any
+    pub fn host_for(&self, url: &str) -> Option<&HostConfig> {
+        self.hosts.iter().find(|h| h.prefix.starts_with("https://") && url.starts_with(&h.prefix))
+    }
+
+    /// Builds a client with the settings
+    pub fn client(&self) -> Result<reqwest::Client> {
+        let mut builder = reqwest::Client::builder();
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(reqwest::Proxy::all(proxy).with_context(|| format!("Invalid proxy {:?}", proxy))?);
+        }
+        if let Some(path) = &self.ca_certificates {
+            let pem = fs::read(path).with_context(|| format!("Can't read CA certificates {:?}", path))?;
+            let certificates = reqwest::Certificate::from_pem_bundle(&pem)
+                .with_context(|| format!("Invalid CA certificates in {:?}", path))?;
+            for certificate in certificates {
+                builder = builder.add_root_certificate(certificate);
+            }
+        }
+        Ok(builder.build()?)
+    }
+}
+
+static HTTP_SETTINGS: OnceLock<HttpSettings> = OnceLock::new();
+static DOWNLOAD_CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+
+/// Sets the settings of the download client.
+pub fn set_http_settings(settings: HttpSettings) -> Result<()> {
+    set_once(&HTTP_SETTINGS, settings, "settings of the download client")
+}
+
+/// Returns the settings of the download client, the default ones if
+/// none were set
+pub fn http_settings() -> &'static HttpSettings {
+    HTTP_SETTINGS.get_or_init(HttpSettings::default)
+}
+
+/// Returns the client to download the submitted result files with,
+/// built from the settings on first use
+pub fn download_client() -> Result<reqwest::Client> {
+    if let Some(client) = DOWNLOAD_CLIENT.get() {
+        return Ok(client.clone());
+    }
+    let client = http_settings().client()?;
+    Ok(DOWNLOAD_CLIENT.get_or_init(|| client).clone())
+}
+
+/// Returns the request for `url` with `method`, fetching it with the
+/// credentials of its host if it is a private one
+pub fn download_request(method: reqwest::Method, url: &str) -> Result<reqwest::RequestBuilder> {
+    let client = download_client()?;
+    let Some(host) = http_settings().host_for(url) else {
+        return Ok(client.request(method, url));
+    };
+    let url = match &host.url_template {
+        Some(template) => template.replace("{url}", url),
+        None => url.to_string(),
+    };
+    let mut request = client.request(method, &url);
+    if let Some(authorization) = &host.authorization {
+        request = request.header(reqwest::header::AUTHORIZATION, authorization);
+    }
+    Ok(request)
+}
+
+/// Runs `future` to completion from synchronous code, whether or not it
+/// runs in an async runtime, on a thread of its own
+pub fn block_on<F: Future + Send>(future: F) -> F::Output
+where
+    F::Output: Send,
+{
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .expect("Failed to start a runtime for the HTTP requests")
+                .block_on(future)
+        })
+        .join()
+        .unwrap()
+    })
+}
+
+/// Decodes standard base64 with padding
+fn decode_base64(s: &str) -> Option<Vec<u8>> {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let s = s.trim().trim_end_matches('=');
+    let mut bytes = vec![];
+    let (mut acc, mut bits) = (0u32, 0);
+    for c in s.bytes() {
+        acc = (acc << 6) | ALPHABET.iter().position(|&a| a == c)? as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((acc >> bits) as u8);
+            acc &= (1 << bits) - 1;
+        }
+    }
+    Some(bytes)
+}
+
+/// Returns the MD5 checksum the host of `response` advertises for its
+/// body, as the header it came from and the hex digest: the Content-MD5
+/// header (S3 and Azure), the md5 of the x-goog-hash header (GCS) or,
+/// from S3, the ETag of objects uploaded in a single part.
+/// Other hosts don't derive their ETags from the contents.
+pub fn advertised_md5(response: &reqwest::Response) -> Option<(&'static str, String)> {
+    let header = |name| response.headers().get(name).and_then(|v| v.to_str().ok());
+    if let Some(md5) = header(reqwest::header::HeaderName::from_static("content-md5"))
+        .and_then(decode_base64)
+        .filter(|md5| md5.len() == 16)
+    {
+        return Some(("content-md5", hex::encode(md5)));
+    }
+    let goog_md5 = response
+        .headers()
+        .get_all("x-goog-hash")
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .find_map(|hash| hash.trim().strip_prefix("md5="))
+        .and_then(decode_base64)
+        .filter(|md5| md5.len() == 16);
+    if let Some(md5) = goog_md5 {
+        return Some(("x-goog-hash", hex::encode(md5)));
+    }
+    let from_s3 = response.url().host_str().is_some_and(|h| h.ends_with(".amazonaws.com"));
+    let etag = header(reqwest::header::ETAG)?.trim_matches('"');
+    (from_s3 && etag.len() == 32 && etag.chars().all(|c| c.is_ascii_hexdigit()))
+        .then(|| ("etag", etag.to_lowercase()))
+}
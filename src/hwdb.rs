@@ -0,0 +1,363 @@
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::common::BenchMerge;
+use crate::database::Database;
+use crate::index::DatabaseIndex;
+use crate::legacy::legacy_versions;
+use crate::progress::log;
+use crate::vendor_overrides::VendorOverrides;
+
+/// Name of the final hwdb file, combining the entries of every model
+pub static HWDB_FILE: &str = "90-iocost-tune.hwdb";
+
+/// Older firmware revisions of a model whose results are superseded by
+/// those of a newer one for the hwdb
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SupersededFirmware {
+    pub model_name: String,
+    pub version: String,
+    pub fwrev: String,
+    pub superseded: Vec<String>,
+}
+
+/// Results of the merge of a model signed by their vendor, which the
+/// header of the hwdb lists as the provenance of the entries
+#[derive(Debug, Clone, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub struct SignedResults {
+    pub model_name: String,
+    pub version: String,
+    /// Results of the merge
+    pub results: usize,
+    /// Signed results, by vendor
+    pub signed_by: BTreeMap<String, usize>,
+}
+
+/// The hwdb input generated from a merge, among which the final hwdb
+/// entry of a model is selected
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HwdbCandidate {
+    pub model_name: String,
+    pub version: String,
+    pub data_points: usize,
+    /// Path of the hwdb input, relative to the hwdb inputs directory
+    pub hwdb_input: PathBuf,
+    pub superseded: Option<SupersededFirmware>,
+    #[serde(default)]
+    pub signed: Option<SignedResults>,
+}
+
+/// A hwdb candidate passed over because its input couldn't be used
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SkippedCandidate {
+    pub version: String,
+    pub hwdb_input: PathBuf,
+    pub reason: String,
+}
+
+/// Candidates of a model passed over for the final hwdb file, and the
+/// one whose entry was used instead, if any
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HwdbFallback {
+    pub model_name: String,
+    pub skipped: Vec<SkippedCandidate>,
+    pub selected: Option<PathBuf>,
+}
+
+/// Returns whether `model_name` lost its entry, none of its candidates
+/// having a usable hwdb input according to `fallbacks`
+pub fn has_no_entry(fallbacks: &[HwdbFallback], model_name: &str) -> bool {
+    fallbacks.iter().any(|f| f.model_name == model_name && f.selected.is_none())
+}
+
+/// Returns the hwdb entry of the best of `alternatives` with a usable
+/// input in `inputs_dir`: the one with the most data points, then the
+/// next ones when it has no input, e.g. for the versions of resctl-bench
+/// without hwdb support or when its generation failed. The candidates
+/// passed over are added to `skipped`.
+fn best_entry(
+    inputs_dir: &Path,
+    alternatives: &[HwdbCandidate],
+    skipped: &mut Vec<SkippedCandidate>,
+) -> Option<(PathBuf, String)> {
+    // Of the candidates with as many data points, the last one was
+    // always preferred
+    let mut ranked: Vec<&HwdbCandidate> = alternatives.iter().rev().collect();
+    ranked.sort_by_key(|c| Reverse(c.data_points));
+    for candidate in ranked {
+        let path = inputs_dir.join(&candidate.hwdb_input);
+        let reason = match fs::read_to_string(&path) {
+            Ok(contents) if !contents.trim().is_empty() => {
+                log(format!("{:>2} datapoints:\t{}", candidate.data_points, candidate.hwdb_input.display()));
+                return Some((candidate.hwdb_input.clone(), contents));
+            }
+            Ok(_) => "the hwdb input is empty".to_string(),
+            Err(e) => format!("no usable hwdb input: {}", e),
+        };
+        log(format!("Skipping {} for {}: {}", candidate.hwdb_input.display(), candidate.model_name, reason));
+        skipped.push(SkippedCandidate {
+            version: candidate.version.clone(),
+            hwdb_input: candidate.hwdb_input.clone(),
+            reason,
+        });
+    }
+    None
+}
+
+/// Writes a hwdb header in `hwdb_file` containing data information, a
+/// reference to `commit_id`, the list of the applied vendor overrides,
+/// of the superseded firmware revisions and of the results signed by
+/// their vendor
+pub fn write_hwdb_file_header(
+    hwdb_file: &mut fs::File,
+    commit_id: Option<String>,
+    vendor_overrides: &VendorOverrides,
+    superseded: &[SupersededFirmware],
+    signed: &[SignedResults],
+) -> Result<()> {
+    let mut hwdb_text = format!("# This file was auto-generated on {}.\n",
+        chrono::Utc::now().to_rfc2822());
+    if let Some(id) = commit_id {
+        hwdb_text.push_str(&format!(r#"# From the following commit:
+# https://github.com/iocost-benchmark/iocost-benchmarks/commit/{}
+"#, id));
+    }
+    hwdb_text.push_str(&vendor_overrides.header());
+    if !superseded.is_empty() {
+        hwdb_text.push_str("#\n# Superseded firmware revisions (still used for the generic entries):\n");
+        for s in superseded {
+            hwdb_text.push_str(&format!(
+                "#   {} ({}): {} superseded by {}\n",
+                s.model_name,
+                s.version,
+                s.superseded.join(", "),
+                s.fwrev
+            ));
+        }
+    }
+    if !signed.is_empty() {
+        hwdb_text.push_str("#\n# Results signed by their vendor:\n");
+        for s in signed {
+            let vendors: Vec<String> = s.signed_by.iter().map(|(v, n)| format!("{} by {}", n, v)).collect();
+            hwdb_text.push_str(&format!(
+                "#   {} ({}): {} of {} results signed, {}\n",
+                s.model_name,
+                s.version,
+                s.signed_by.values().sum::<usize>(),
+                s.results,
+                vendors.join(", ")
+            ));
+        }
+    }
+    hwdb_text.push_str(r#"#
+# Match key format:
+# block:<devpath>:name:<model name>:fwrev:<firmware revision>:
+
+"#);
+    writeln!(hwdb_file, "{}", hwdb_text)?;
+    Ok(())
+}
+
+/// Writes the final hwdb file to `path` from the hwdb inputs in
+/// `inputs_dir`, with a header referring to `commit_id`. The entry of
+/// each model is the input of its candidate with the most data points
+/// among its `alternatives` that has one, with the vendor overrides
+/// applied. Returns the fallbacks to other candidates, models without
+/// any usable input having no entry.
+pub fn write_hwdb_file(
+    path: &Path,
+    inputs_dir: &Path,
+    alternatives: &BTreeMap<String, Vec<HwdbCandidate>>,
+    commit_id: Option<String>,
+    vendor_overrides: &VendorOverrides,
+) -> Result<Vec<HwdbFallback>> {
+    let mut hwdb_file =
+        fs::File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+    let mut superseded: Vec<SupersededFirmware> = alternatives
+        .values()
+        .flatten()
+        .filter_map(|c| c.superseded.clone())
+        .collect();
+    superseded.sort_by(|a, b| (&a.model_name, &a.version).cmp(&(&b.model_name, &b.version)));
+    let mut signed: Vec<SignedResults> =
+        alternatives.values().flatten().filter_map(|c| c.signed.clone()).collect();
+    signed.sort_by(|a, b| (&a.model_name, &a.version).cmp(&(&b.model_name, &b.version)));
+    write_hwdb_file_header(&mut hwdb_file, commit_id, vendor_overrides, &superseded, &signed)?;
+
+    let mut fallbacks = vec![];
+    for (model, alternatives) in alternatives {
+        // To override the hwdb file that is selected, you need to set
+        // the variable with the name of the model with all dashes
+        // replaced with underscores to a value that is the preferred
+        // filename. For instance:
+        //
+        // OVERRIDE_BEST_HFS256GD9TNG_62A0A_2022_09_19UTC=iocost-tune-2.2-HFS256GD9TNG-62A0A-2022-09-19UTC.hwdb
+        let override_var = format!("OVERRIDE_BEST_{}", model.replace('-', "_"));
+
+        // If override is available, select it, otherwise select the
+        // merge with the highest number of data points.
+        let mut skipped = vec![];
+        let best = match std::env::var(&override_var) {
+            Err(std::env::VarError::NotPresent) => best_entry(inputs_dir, alternatives, &mut skipped),
+            Err(e) => bail!("Failed to interpret variable {}: {}", override_var, e),
+            Ok(best) => {
+                if !Path::new(&best).exists() {
+                    bail!("Failed to find override file: {}", best);
+                }
+                log(format!("override:\t{}", best));
+                let best_hwdb = inputs_dir.join(&best);
+                let contents = fs::read_to_string(&best_hwdb)
+                    .with_context(|| format!("Failed to read input hwdb file {}", best_hwdb.display()))?;
+                Some((PathBuf::from(best), contents))
+            }
+        };
+        if !skipped.is_empty() {
+            fallbacks.push(HwdbFallback {
+                model_name: model.clone(),
+                skipped,
+                selected: best.as_ref().map(|(input, _)| input.clone()),
+            });
+        }
+        match best {
+            Some((_, contents)) => writeln!(hwdb_file, "{}", vendor_overrides.apply(model, &contents))?,
+            None => log(format!("No hwdb input could be used for {}, it has no entry", model)),
+        }
+    }
+    // The overrides of the models left without an entry are written
+    // like those of models without results
+    let models: Vec<String> = alternatives.keys().filter(|m| !has_no_entry(&fallbacks, m)).cloned().collect();
+    for entry in vendor_overrides.unmatched_entries(&models) {
+        writeln!(hwdb_file, "{}", entry)?;
+    }
+    Ok(fallbacks)
+}
+
+/// Merges the results of every model of the `database` and writes their
+/// hwdb inputs in `inputs_dir`, then the final hwdb file to `path` with
+/// a header referring to `commit_id`. Unlike merge-results, no pdf or
+/// other output is generated. Returns the models with an entry.
+pub fn export_hwdb(
+    database: &Database,
+    inputs_dir: &Path,
+    path: &Path,
+    commit_id: Option<String>,
+    vendor_overrides: &VendorOverrides,
+) -> Result<Vec<String>> {
+    database.ensure_layout()?;
+    let index = DatabaseIndex::load(database.root())?;
+    let mut alternatives: BTreeMap<String, Vec<HwdbCandidate>> = BTreeMap::new();
+    for entry in index.entries.iter().filter(|e| !legacy_versions().contains(&e.version)) {
+        log(format!("Merging {} ({})...", entry.model_name, entry.version));
+        let merge = BenchMerge::merge(database, entry.version.clone(), entry.model_name.clone())?;
+        if merge.create_hwdb_in(inputs_dir)?.is_some() {
+            alternatives
+                .entry(merge.model_name.clone())
+                .or_default()
+                .push(merge.hwdb_candidate());
+        }
+    }
+    let fallbacks = write_hwdb_file(path, inputs_dir, &alternatives, commit_id, vendor_overrides)?;
+    Ok(alternatives
+        .into_keys()
+        .filter(|model| !has_no_entry(&fallbacks, model))
+        .collect())
+}
+
+/// Returns the properties set in a hwdb file, keyed by name. Only the
+/// first value of a property set in several sections is kept.
+pub fn hwdb_properties(hwdb: &str) -> BTreeMap<String, String> {
+    let mut properties = BTreeMap::new();
+    for line in hwdb.lines().filter(|l| l.starts_with(char::is_whitespace)) {
+        if let Some((name, value)) = line.trim().split_once('=') {
+            properties
+                .entry(name.to_string())
+                .or_insert_with(|| value.to_string());
+        }
+    }
+    properties
+}
+
+/// Returns the match key of a hwdb section, after its comments
+fn section_key(section: &str) -> Option<&str> {
+    section.lines().find(|l| !l.starts_with('#'))
+}
+
+/// Extracts the sections of a hwdb file whose match key refers to
+/// `model_name`. Model names in the database have their spaces replaced
+/// with underscores, so both spellings are accepted.
+pub fn hwdb_section_for(hwdb: &str, model_name: &str) -> Option<String> {
+    let names = [
+        format!(":name:{}:", model_name),
+        format!(":name:{}:", model_name.replace('_', " ")),
+    ];
+    let sections: Vec<&str> = hwdb
+        .split("\n\n")
+        .map(|s| s.trim_matches('\n'))
+        .filter(|s| {
+            section_key(s)
+                .map(|key| names.iter().any(|n| key.contains(n.as_str())))
+                .unwrap_or(false)
+        })
+        .collect();
+    if sections.is_empty() {
+        None
+    } else {
+        Some(sections.join("\n\n"))
+    }
+}
+
+/// Returns a unified-style line diff of two hwdb sections, with the
+/// removed lines prefixed with `-` and the added ones with `+`, or None
+/// if they are the same
+pub fn diff_sections(old: &str, new: &str) -> Option<String> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    if old == new {
+        return None;
+    }
+    // Longest common subsequence of the lines, from the end
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = match old[i] == new[j] {
+                true => lcs[i + 1][j + 1] + 1,
+                false => lcs[i + 1][j].max(lcs[i][j + 1]),
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut diff = vec![];
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            diff.push(format!(" {}", old[i]));
+            (i, j) = (i + 1, j + 1);
+        } else if i < old.len() && (j == new.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            diff.push(format!("-{}", old[i]));
+            i += 1;
+        } else {
+            diff.push(format!("+{}", new[j]));
+            j += 1;
+        }
+    }
+    Some(diff.join("\n"))
+}
+
+/// Returns the sections of a hwdb file that don't refer to any of the
+/// `model_names`, skipping the header comments.
+pub fn hwdb_sections_except(hwdb: &str, model_names: &[&str]) -> Vec<String> {
+    hwdb.split("\n\n")
+        .map(|s| s.trim_matches('\n'))
+        .filter(|s| section_key(s).is_some())
+        .filter(|s| {
+            !model_names
+                .iter()
+                .any(|m| hwdb_section_for(s, m).is_some())
+        })
+        .map(str::to_string)
+        .collect()
+}
@@ -1,416 +1,304 @@
-use anyhow::{bail, Result, Context};
-use common::{load_json, merged_file, save_pdf_to, BenchMerge};
-use serde::{Serialize, Deserialize};
-use serde_with::skip_serializing_none;
-use std::collections::HashMap;
-use std::fs;
+use anyhow::Result;
+use std::fs::OpenOptions;
 use std::io::Write;
-use std::path::{Path, PathBuf};
-use std::process::exit;
-use semver::VersionReq;
+use std::path::{absolute, Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Duration;
 use clap::Parser;
 
-use crate::common::{database_directory, run_resctl, BenchVersion};
+use iocost_benchmarks::actions::ContextPayload;
+use iocost_benchmarks::bot_commands::BotCommand;
+use iocost_benchmarks::catalog::set_locale;
+use iocost_benchmarks::commits::{set_commit_settings, CommitSettings};
+use iocost_benchmarks::config::TomlData;
+use iocost_benchmarks::exit::{exit_code, EnvironmentError, ExitStatus, ValidationError, EXIT_CODES_HELP};
+use iocost_benchmarks::fw_policy::{set_fw_merge_policies, FwMergePolicies};
+use iocost_benchmarks::http::{download_client, set_http_settings, HttpSettings};
+use iocost_benchmarks::labels::{set_issue_settings, IssueSettings};
+use iocost_benchmarks::legacy::{set_legacy_versions, LegacyVersions};
+use iocost_benchmarks::lfs::{set_lfs_settings, LfsSettings};
+use iocost_benchmarks::rules::{set_rules, Rules, RULES_FILE};
+use iocost_benchmarks::url_extract::{set_url_extractor, UrlExtractor};
+use iocost_benchmarks::storage::{object_storage_from_config, set_object_storage, sync_from_object_storage};
+use iocost_benchmarks::merge_window::{set_merge_window, MergeWindow};
+use iocost_benchmarks::metrics::push_metrics;
+use iocost_benchmarks::notifier::{Notifier, NotifyEvent};
+use iocost_benchmarks::policy::set_policies;
+use iocost_benchmarks::progress::{log, VerbosityArgs};
+use iocost_benchmarks::retry::{set_retry_settings, RetrySettings};
+use iocost_benchmarks::review::{set_review_settings, ReviewSettings};
+use iocost_benchmarks::vendors::VENDORS_FILE;
+use iocost_benchmarks::watch::{watch, WatchConfig};
+use iocost_benchmarks::workflow::{
+    collect_result_files, import_local_files, process_bot_command, process_dispatch_event, process_issue_event,
+    process_pull_request_event, process_push_event, process_schedule_event, publish_branch,
+};
+use iocost_benchmarks::workspace::{set_workspace, Workspace, DATABASE_DIR};
 
-mod common;
-
-static ALLOWED_PREFIXES: &[&str] = &[
-    "https://github.com/",
-    "https://iocost-submit-us-east-1.s3.us-east-1.amazonaws.com/",
-    "https://iocost-submit.s3.eu-north-1.amazonaws.com/",
-];
 static GH_CONTEXT_ENVVAR: &str = "GITHUB_CONTEXT";
-
-/// Returns `true` if the URL specified in `link` is allowed according
-/// to its domain name. Returns `false` otherwise.
-fn is_url_allowlisted(link: &str) -> bool {
-    for prefix in ALLOWED_PREFIXES {
-        if link.starts_with(prefix) {
-            return true;
-        }
-    }
-    false
-}
-
-/// Extracts the URLs found in a Github issue context.
-/// Only open and unlocked issues are processed
-fn get_urls(context: &json::JsonValue) -> Result<Vec<String>> {
-    let issue = &context["event"]["issue"];
-
-    // The workflow should already filter this out, but double-check.
-    if issue["locked"].as_bool().unwrap() || issue["state"] != "open" {
-        panic!("Issue is either locked or not in the open state, workflow should filter this...");
-    }
-
-    // created is always for comments, opened is always for issues.
-    let body = match context["event"]["action"].as_str().unwrap() {
-        "created" => context["event"]["comment"]["body"].as_str(),
-        "opened" => issue["body"].as_str(),
-        "edited" => {
-            if context["event_name"] == "issue_comment" {
-                context["event"]["comment"]["body"].as_str()
-            } else {
-                issue["body"].as_str()
-            }
-        }
-        _ => bail!(
-            "Called for event we do not handle: {} / {}",
-            context["event_name"],
-            context["event"]["action"]
-        ),
+static GH_OUTPUT_ENVVAR: &str = "GITHUB_OUTPUT";
+
+/// Sets the output `name` of the workflow step to `value`, when running
+/// in a Github workflow
+fn set_step_output(name: &str, value: &str) -> Result<()> {
+    if let Ok(path) = std::env::var(GH_OUTPUT_ENVVAR) {
+        let mut file = OpenOptions::new().append(true).create(true).open(path)?;
+        writeln!(file, "{}={}", name, value)?;
     }
-    .expect("Could not obtain the contents of the issue or comment");
-
-    let mut urls = vec![];
-    for link in linkify::LinkFinder::new().links(body) {
-        let link = link.as_str();
-        if is_url_allowlisted(link) && link.ends_with(".json.gz") {
-            println!("URL found: {}", link);
-            urls.push(link.to_string());
-        } else {
-            println!(
-                "URL ignored due to not having a allowlisted prefix: {}",
-                link
-            );
-        }
-    }
-    Ok(urls)
-}
-
-async fn download_url(url: &str) -> Result<String> {
-    let response = reqwest::get(url).await?;
-    let contents = response.bytes().await?;
-    // Use md5sum of the data as filename, we only care about exact duplicates.
-    let path = format!("result-{:x}.json.gz", md5::compute(&contents));
-    let mut file = fs::File::create(&path)?;
-    file.write_all(&contents)?;
-    Ok(path)
+    Ok(())
 }
 
-
-#[skip_serializing_none]
-#[derive(Serialize)]
-struct BenchResult {
-    /// Drive model name
-    model_name: String,
-    /// Path to the result directory in the database
-    #[serde(skip_serializing)]
-    dir: String,
-    /// Path to the source result file
-    #[serde(skip_serializing)]
-    result_file: String,
-    /// resctl-bench version used to generate the result (major.minor)
-    version: String,
-    /// Github issue the result is related to, if any
-    issue: Option<u64>,
-    /// Result file url, if provided through a Github issue
-    url: Option<String>,
+/// Pushes `branch_name` with the token of the Github context `payload`
+/// and opens or updates its pull request, for the --push option
+async fn publish(
+    payload: &ContextPayload,
+    branch_name: &str,
+    title: &str,
+    description: &str,
+    draft: bool,
+) -> Result<()> {
+    let (Some(token), Some(repo)) = (&payload.token, &payload.repository) else {
+        return Err(EnvironmentError("--push needs a token and a repository in the Github context".into()).into());
+    };
+    let base = payload
+        .event
+        .repository
+        .as_ref()
+        .and_then(|r| r.default_branch.as_deref())
+        .unwrap_or("main");
+    let (url, opened) = publish_branch(token, repo, base, branch_name, title, description, draft).await?;
+    println!("{} {} for {}", if opened { "Opened" } else { "Updated" }, url, branch_name);
+    set_step_output("pull_request", &url)
 }
 
-impl BenchResult {
-    /// Creates a BenchResult extracting the model and version info from
-    /// a json file (`json_result_file`) and set it to store the output
-    /// data into `database_path`
-    async fn new(json_result_file: &str, database_path: &str)
-    -> Result<Self>
-    {
-        let result = load_json(&json_result_file)
-            .expect(&format!("Error parsing json file {}", &json_result_file));
-        let full_version = result[0]["sysinfo"]["bench_version"]
-            .to_string()
-            .split_whitespace()
-            .collect::<Vec<_>>()[0]
-            .to_string();
-        let version = {
-            let v = semver::Version::parse(&full_version)?;
-            format!("{}.{}", v.major, v.minor)
-        };
-        semver::Version::parse(&full_version)?;
-        let model_name = result[0]["sysinfo"]["sysreqs_report"]["scr_dev_model"]
-            .to_string()
-            .replace(" ", "_");
-        let dir = PathBuf::from(database_path)
-            .join(&version)
-            .join(&model_name)
-            .into_os_string()
-            .into_string().unwrap();
-        Ok(BenchResult {
-            model_name,
-            dir,
-            result_file: json_result_file.to_string(),
-            version,
-            issue: None,
-            url: None
-        })
-    }
-
-    /// Runs resctl-demo to validate the file in self.path.
-    fn validate(&self) -> Result<()> {
-        run_resctl(
-            &self.version,
-            &["--result", "/tmp/result.json", "merge", &self.result_file],
-        )?;
-        Ok(())
-    }
-
-    /// Returns a path for the result file in the DB
-    fn db_file(&self) -> PathBuf {
-        PathBuf::from(&self.dir).join(&self.result_file)
-    }
-
-    /// Returns a path for the metadata file in the DB
-    fn metadata_file_path(&self) -> PathBuf {
-        let basename = Path::new(&self.result_file)
-            .with_extension("")
-            .with_extension("")
-            .to_str()
-            .unwrap()
-            .to_string();
-        let mut metadata_filepath = PathBuf::from(&self.dir).join(basename);
-        metadata_filepath.set_extension("json.metadata");
-        metadata_filepath
-    }
-
-    /// Processes the result and stores the output files in the DB.
-    /// If an `id` string is provided, it'll be used to name the
-    /// directory for the pdf outputs
-    fn add_to_database(&self, id: Option<&str>) -> Result<()> {
-        let pdfs_dir = match id {
-            Some(id) => PathBuf::from(".")
-                .join(&format!("pdfs-for-{}", id)),
-            None => {
-                PathBuf::from(".")
-                    .join(&format!("pdfs-for-{}-{}", &self.model_name, &self.version))
+async fn run_as_gh_workflow(
+    database_path: &str,
+    notifier: &Notifier,
+    strict_context: bool,
+    push: bool,
+) -> Result<ExitStatus>{
+    let envvar_contents = std::env::var(GH_CONTEXT_ENVVAR).map_err(|_| {
+        EnvironmentError(format!("Can't read environment variable {}", GH_CONTEXT_ENVVAR))
+    })?;
+    let payload = ContextPayload::parse(&envvar_contents, strict_context)?;
+    if let Some(command) = BotCommand::from_context(&payload) {
+        if let Some(outcome) = process_bot_command(&payload, database_path, command?).await? {
+            println!("Committed {} to {}", outcome.title, outcome.branch_name);
+            if push {
+                publish(&payload, &outcome.branch_name, &outcome.title, &outcome.description, false).await?;
             }
-        };
-        save_pdf_to(&self.version, &PathBuf::from(&self.result_file), &pdfs_dir, None)?;
-        // Generate DB directory and place the result file there
-        fs::create_dir_all(&self.dir).ok();
-        fs::rename(&self.result_file, &self.db_file())?;
-        // Create metadata file and save it in the DB dir
-        let mut metadata_file = fs::File::create(&self.metadata_file_path())?;
-        write!(metadata_file, "{}", serde_json::to_string(self)?)?;
-        Ok(())
-    }
-}
-
-/// Models a resctl-bench high-level summary output
-struct HighLevel {
-    version: String,
-    model_name: String,
-    new_files: u64,
-}
-
-impl HighLevel {
-    fn new(version: &str, model_name: &str) -> Self {
-        HighLevel {
-            version: version.to_string(),
-            model_name: model_name.to_string(),
-            new_files: 0,
         }
+        return Ok(ExitStatus::Success);
     }
-
-    fn increment(&mut self) {
-        self.new_files += 1;
-    }
-
-    /// Runs resctl-bench to generate a high-level summary, if
-    /// available, and returns it as a String.
-    fn format_high_level(&self) -> String {
-        // Get the high-level summary only if `resctl-bench` supports
-        // that option (available since v2.2.3)
-        let resctl_bench_version = BenchVersion::new(&self.version);
-        if VersionReq::parse("<2.2.3")
-            .unwrap()
-            .matches(&resctl_bench_version.semver)
-        {
-            return String::new();
-        }
-
-        let path = merged_file(&self.version, &self.model_name, None);
-        BenchMerge::do_merge(
-            &self.version,
-            &database_directory(&self.version, &self.model_name),
-            &path,
-        )
-        .expect("Failed to do the merge for obtaining high level summary");
-
-        run_resctl(
-            &self.version,
-            &[
-                "--result",
-                &path.to_string_lossy(),
-                "format",
-                "iocost-tune:high-level",
-            ],
-        )
-        .expect("Failed to run resctl-bench to format high level")
-    }
-}
-
-async fn run_as_gh_workflow(database_path: &str) -> Result<()>{
-    let envvar_contents = std::env::var(GH_CONTEXT_ENVVAR)
-        .context(format!("Can't read environment variable {}", GH_CONTEXT_ENVVAR))?;
-    let context = json::parse(&envvar_contents)?;
-    let issue_id = context["event"]["issue"]["number"].as_u64().unwrap();
-    let git_repo = git2::Repository::open(".")?;
-    let mut index = git_repo.index()?;
-    // HashMap to keep the complete set of results
-    let mut merged = HashMap::new();
-
-    // Download and validate all provided URLs.
-    let urls = get_urls(&context)?;
-    let mut errors = vec![];
-    for url in urls {
-        // Download resctl-bench result, process and validate it,
-        // and add it to the database and the repo
-        let path = download_url(&url).await?;
-        let mut result = BenchResult::new(&path, database_path).await?;
-        result.issue = Some(issue_id);
-        result.url = Some(url.clone());
-        if let Err(e) = result.validate() {
-            errors.push(
-                format!("File {} failed validation: \n\n{}", url, e)
-            );
-            continue;
+    match payload.event_name.as_str() {
+        "repository_dispatch" => process_dispatch_event(&payload, database_path)?,
+        "schedule" => process_schedule_event(&payload, database_path).await?,
+        "pull_request" => process_pull_request_event(&payload, database_path).await?,
+        "push" => process_push_event(&payload, database_path)?,
+        _ => {
+            let outcome = process_issue_event(&payload, database_path).await?;
+            if let Some(branch_name) = outcome.branch_name.clone() {
+                let issue = payload.event.issue.as_ref().map(|i| i.number).unwrap_or_default();
+                notifier
+                    .notify(&NotifyEvent::Imported { issue, branch_name })
+                    .await;
+            }
+            // The workflow opens the pull request of results pending
+            // review as a draft.
+            if outcome.pending {
+                set_step_output("pending", "true")?;
+            }
+            if let (true, Some(branch_name), Some(title), Some(description)) =
+                (push, &outcome.branch_name, &outcome.title, &outcome.description)
+            {
+                publish(&payload, branch_name, title, description, outcome.pending).await?;
+            }
+            // Without --push, the rest of the process happens in the
+            // workflow.
+            return Ok(outcome.exit_status());
         }
-        result.add_to_database(Some(&issue_id.to_string()))?;
-        index.add_path(&result.db_file())?;
-        index.add_path(&result.metadata_file_path())?;
-        merged
-            .entry(format!("{}-{}", &result.version, &result.model_name))
-            .or_insert_with(|| HighLevel::new(&result.version, &result.model_name))
-            .increment();
-    }
-
-    if !errors.is_empty() {
-        octocrab::OctocrabBuilder::new()
-            .personal_token(context["token"].as_str().unwrap().to_string())
-            .build()?
-            .issues(
-                context["repository_owner"].as_str().unwrap(),
-                "iocost-benchmarks",
-            )
-            .create_comment(issue_id, errors.join("\n\n"))
-            .await?;
-    }
-    if merged.is_empty() {
-        println!("Found no new results files to merge...");
-        return Ok(());
     }
-
-    // Commit the new and changed files.
-    let sig = git2::Signature::now("iocost bot", "iocost-bot@has.no.email")?;
-    let parent_commit = git_repo.head()?.peel_to_commit()?;
-    let oid = index.write_tree()?;
-    let tree = git_repo.find_tree(oid)?;
-    let description = format!(
-        "Closes #{}\n\n{}",
-        issue_id,
-        merged
-            .iter()
-            .map(|(_, v)| format!(
-                "[{} ({})] {} new files\n{}",
-                v.model_name,
-                v.version,
-                v.new_files,
-                v.format_high_level()
-            ))
-            .collect::<Vec<String>>()
-            .join("\n")
-    );
-    let commit_title = format!("Automated update from issue {}", issue_id);
-    let commit_message = format!("{commit_title}\n\n{description}");
-    let commit = git_repo.commit(
-        Some("HEAD"),
-        &sig,
-        &sig,
-        &commit_message,
-        &tree,
-        &[&parent_commit],
-    )?;
-    let branch_name = format!("iocost-bot/{}", issue_id);
-    git_repo.branch(&branch_name, &git_repo.find_commit(commit)?, true)?;
-
-    // The rest of the process happens in the workflow.
-    Ok(())
-}
-
-
-/// Top-level struct to parse the config toml file
-#[derive(Debug, Deserialize)]
-struct TomlData {
-    config: Config,
-}
-
-/// Struct to parse the [config] section of the config toml file
-#[derive(Debug, Deserialize)]
-struct Config {
-    database_dir: Option<String>,
+    Ok(ExitStatus::Success)
 }
 
 #[derive(Parser, Debug)]
-#[command(version, about)]
+#[command(version, about, after_help = EXIT_CODES_HELP)]
 /// Imports resctl-bench results into a common database
 ///
 /// By default, it runs as part of a Github workflow, reading the input
 /// from an environment variable (GITHUB_CONTEXT). Run with -r
-/// (--result) to specify a local result file.
+/// (--result) to specify local result files instead.
 struct Cli {
     /// Path of the toml config file to load
     #[arg(short, long, value_name = "FILE")]
     config_file: Option<String>,
 
-    /// Result file to process
-    #[arg(short, long, value_name = "FILE.json.gz")]
-    result: Option<String>,
+    /// Result files to process: files, glob patterns or directories
+    /// to search for *.json.gz files
+    #[arg(short, long, value_name = "FILE.json.gz", num_args = 1..)]
+    result: Vec<String>,
+
+    /// Watch a directory and import the result files that appear in
+    /// it, committing them in batches
+    #[arg(short, long, value_name = "DIR", conflicts_with = "result")]
+    watch: Option<PathBuf>,
+
+    /// Seconds without new files in the watched directory before
+    /// importing a batch
+    #[arg(long, value_name = "SECS", default_value_t = 30, requires = "watch")]
+    batch_secs: u64,
 
     /// Output database dir
-    #[arg(short, long, value_name = "DIR", default_value = "database")]
+    #[arg(short, long, value_name = "DIR", default_value = DATABASE_DIR)]
     database_dir: Option<String>,
+
+    /// Root of the checkout of the database repository to work in, the
+    /// database and output dirs being relative to it [default: the
+    /// current directory]
+    #[arg(long, value_name = "DIR", env = "IOCOST_WORKSPACE")]
+    workspace: Option<PathBuf>,
+
+    /// Fail if the Github context has fields unknown to the typed
+    /// model, to detect schema changes
+    #[arg(long)]
+    strict_context: bool,
+
+    /// Push the branch of the results with the token of the Github
+    /// context (e.g. an App installation token) and open its pull
+    /// request, rather than leaving it to the next workflow steps
+    #[arg(long, conflicts_with_all = ["result", "watch"])]
+    push: bool,
+
+    /// Result files of a submission validated in parallel [default: the
+    /// number of CPUs]
+    #[arg(short, long, value_name = "N", value_parser = clap::value_parser!(u16).range(1..))]
+    jobs: Option<u16>,
+
+    #[command(flatten)]
+    verbosity: VerbosityArgs,
 }
 
 #[tokio::main]
-async fn main() -> Result<()> {
-    let args = Cli::parse();
+async fn main() -> ExitCode {
+    exit_code(run(Cli::parse()).await)
+}
 
+async fn run(mut args: Cli) -> Result<ExitStatus> {
+    args.verbosity.apply();
     // Load config from toml file, if specified
     let config: Option<TomlData> = match args.config_file {
         Some(path) => {
-            match fs::read_to_string(&path) {
-                Ok(contents) => {
-                    toml::from_str(&contents)
-                        .expect(&format!("Error parsing toml file {}", &path))
-                },
-                Err(_) => {
-                    eprintln!("Can't open config file: {}", &path);
-                    exit(1);
-                }
+            match TomlData::load(&path) {
+                Ok(config) => Some(config),
+                Err(e) => return Err(EnvironmentError(format!("{:#}", e)).into()),
             }
         },
         None => None,
     };
+    if let Some(jobs) = args.jobs {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(jobs.into())
+            .build_global()
+            .map_err(|e| EnvironmentError(format!("Failed to start {} validation jobs: {}", jobs, e)))?;
+    }
+    if let Some(root) = &args.workspace {
+        // The inputs are named relative to the current directory
+        args.result = args
+            .result
+            .iter()
+            .map(|r| Ok(absolute(r)?.to_string_lossy().to_string()))
+            .collect::<Result<_>>()?;
+        args.watch = args.watch.as_deref().map(absolute).transpose()?;
+        set_workspace(Workspace::enter(root).map_err(|e| EnvironmentError(format!("{:#}", e)))?)?;
+    }
 
     // Process general parameters
+    if let Some(locale) = config.as_ref().and_then(|c| c.config.locale.as_deref()) {
+        set_locale(locale)?;
+    }
+    if let Some(merge) = config.as_ref().and_then(|c| c.merge.as_ref()) {
+        set_merge_window(
+            MergeWindow::from_config(merge).map_err(|e| EnvironmentError(format!("{:#}", e)))?,
+        )?;
+    }
+    if let Some(fwmerge) = config.as_ref().and_then(|c| c.fwmerge.as_ref()) {
+        set_fw_merge_policies(
+            FwMergePolicies::from_config(fwmerge).map_err(|e| EnvironmentError(format!("{:#}", e)))?,
+        )?;
+    }
+    if let Some(config) = &config {
+        set_legacy_versions(
+            LegacyVersions::from_config(&config.config).map_err(|e| EnvironmentError(format!("{:#}", e)))?,
+        )?;
+    }
+    if let Some(config) = &config {
+        set_policies(config).map_err(|e| EnvironmentError(format!("{:#}", e)))?;
+    }
+    if let Some(http) = config.as_ref().and_then(|c| c.http.as_ref()) {
+        set_http_settings(HttpSettings::from_config(http))?;
+    }
+    download_client().map_err(|e| EnvironmentError(format!("{:#}", e)))?;
+    if let Some(urls) = config.as_ref().and_then(|c| c.urls.as_ref()) {
+        set_url_extractor(UrlExtractor::from_config(urls).map_err(|e| EnvironmentError(format!("{:#}", e)))?)?;
+    }
+    if let Some(lfs) = config.as_ref().and_then(|c| c.lfs.as_ref()) {
+        set_lfs_settings(LfsSettings::from_config(lfs))?;
+    }
+    if let Some(issues) = config.as_ref().and_then(|c| c.issues.as_ref()) {
+        set_issue_settings(IssueSettings::from_config(issues))?;
+    }
+    if let Some(review) = config.as_ref().and_then(|c| c.review.as_ref()) {
+        set_review_settings(ReviewSettings::from_config(review))?;
+    }
+    if let Some(commits) = config.as_ref().and_then(|c| c.commits.as_ref()) {
+        set_commit_settings(
+            CommitSettings::from_config(commits).map_err(|e| EnvironmentError(format!("{:#}", e)))?,
+        )?;
+    }
+    if let Some(retry) = config.as_ref().and_then(|c| c.retry.as_ref()) {
+        set_retry_settings(RetrySettings::from_config(retry))?;
+    }
+    set_rules(
+        Rules::load(Path::new(RULES_FILE), Path::new(VENDORS_FILE))
+            .map_err(|e| EnvironmentError(format!("{:#}", e)))?,
+    )?;
+    if let Some(storage) = config.as_ref().and_then(|c| c.storage.as_ref()) {
+        if let Some(storage) =
+            object_storage_from_config(storage).map_err(|e| EnvironmentError(format!("{:#}", e)))?
+        {
+            set_object_storage(storage)?;
+        }
+    }
     let database_dir;
-    if let Some(config) = config {
-        database_dir = config.config.database_dir.unwrap_or(args.database_dir.unwrap());
+    let notifier = Notifier::new(config.as_ref().and_then(|c| c.notifications.as_ref()));
+    let metrics_config = config.as_ref().and_then(|c| c.metrics.as_ref());
+    if let Some(config) = &config {
+        database_dir = config.config.database_dir.clone().unwrap_or(args.database_dir.unwrap());
     } else {
         database_dir = args.database_dir.unwrap()
     }
+    sync_from_object_storage(Path::new(&database_dir))?;
 
-    if let Some(result_file) = args.result {
-        // Run with result file as input
-        let bench_result = BenchResult::new(
-            &result_file,
-            &database_dir).await?;
-        bench_result.validate()
-            .expect(&format!("File {} failed validation", &result_file));
-        return bench_result.add_to_database(None);
+    if let Some(dir) = args.watch {
+        watch(WatchConfig {
+            dir,
+            database_dir,
+            batch_interval: Duration::from_secs(args.batch_secs),
+        })
+        .await?;
+        Ok(ExitStatus::Success)
+    } else if !args.result.is_empty() {
+        // Run with result files as input
+        let files = collect_result_files(&args.result)
+            .map_err(|e| ValidationError(format!("{:#}", e)))?;
+        let (outcome, _) = import_local_files(&files, &database_dir).await?;
+        Ok(outcome.exit_status())
     } else {
         // Run as part of a Github workflow
-        println!("No result file specified: reading result info from \
-                  Github workflow ({} envvar)", GH_CONTEXT_ENVVAR);
-        return run_as_gh_workflow(&database_dir).await;
+        log(format!("No result file specified: reading result info from \
+                     Github workflow ({} envvar)", GH_CONTEXT_ENVVAR));
+        let result = run_as_gh_workflow(&database_dir, &notifier, args.strict_context, args.push).await;
+        push_metrics(metrics_config).await;
+        result
     }
 }
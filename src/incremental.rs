@@ -0,0 +1,108 @@
+use anyhow::{Context, Result};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::actions::ContextPayload;
+use crate::archive::HwdbArchive;
+use crate::git::changed_models;
+use crate::hwdb::HwdbCandidate;
+use crate::manifest::{Artifact, ArtifactKind, Manifest, MANIFEST_FILE};
+use crate::workspace::workspace;
+
+/// Restricts a merge run to the models whose database files changed in
+/// the commits of a push or pull request. The hwdb inputs of the other
+/// models are reused from the last archived run, so that the final hwdb
+/// file is the same as the one of a full merge.
+#[derive(Debug)]
+pub struct IncrementalMerge {
+    /// (version, model) pairs to re-merge
+    changed: BTreeSet<(String, String)>,
+    /// Archive directory of the run the hwdb inputs are reused from
+    previous_dir: PathBuf,
+    previous: Vec<HwdbCandidate>,
+}
+
+impl IncrementalMerge {
+    /// Prepares the incremental merge of the database in `database_root`
+    /// for the event of `context`. Returns None when every model needs to
+    /// be merged: for scheduled and manual runs, when the commit range
+    /// can't be diffed or when no archived run recorded its hwdb inputs.
+    pub fn new(context: &ContextPayload, database_root: &Path, archive: &HwdbArchive) -> Option<Self> {
+        let (from, to) = context.commit_range()?;
+        let changed = workspace().repository()
+            .map_err(anyhow::Error::from)
+            .and_then(|repo| changed_models(&repo, &from, &to, database_root));
+        let changed = match changed {
+            Ok(changed) => changed,
+            Err(e) => {
+                println!("Can't diff {}..{}, merging every model: {:#}", from, to, e);
+                return None;
+            }
+        };
+        let manifest_path = match archive.latest_file(MANIFEST_FILE) {
+            Ok(Some(path)) => path,
+            Ok(None) => {
+                println!("No archived run to reuse, merging every model");
+                return None;
+            }
+            Err(e) => {
+                println!("Can't read the hwdb archive, merging every model: {:#}", e);
+                return None;
+            }
+        };
+        let manifest: Manifest = match fs::read_to_string(&manifest_path)
+            .map_err(anyhow::Error::from)
+            .and_then(|contents| Ok(serde_json::from_str(&contents)?))
+        {
+            Ok(manifest) => manifest,
+            Err(e) => {
+                println!("Can't load {:?}, merging every model: {:#}", manifest_path, e);
+                return None;
+            }
+        };
+        if manifest.candidates.is_empty() {
+            println!("{:?} has no hwdb inputs to reuse, merging every model", manifest_path);
+            return None;
+        }
+        Some(IncrementalMerge {
+            changed,
+            previous_dir: manifest_path.parent().unwrap().to_path_buf(),
+            previous: manifest.candidates,
+        })
+    }
+
+    /// Number of (version, model) pairs to re-merge
+    pub fn changed_count(&self) -> usize {
+        self.changed.len()
+    }
+
+    /// Whether the results of `model_name` for `version` need merging
+    pub fn includes(&self, version: &str, model_name: &str) -> bool {
+        self.changed.contains(&(version.to_string(), model_name.to_string()))
+    }
+
+    /// Copies the hwdb inputs of the models that are not re-merged from
+    /// the archived run into `hwdb_inputs_dir`, adds them to `manifest`
+    /// and returns their candidates
+    pub fn reuse_unchanged(&self, hwdb_inputs_dir: &Path, manifest: &mut Manifest) -> Result<Vec<HwdbCandidate>> {
+        fs::create_dir_all(hwdb_inputs_dir)?;
+        let mut reused = vec![];
+        for candidate in &self.previous {
+            if self.includes(&candidate.version, &candidate.model_name) {
+                continue;
+            }
+            let source = self.previous_dir.join(&candidate.hwdb_input);
+            let path = hwdb_inputs_dir.join(&candidate.hwdb_input);
+            fs::copy(&source, &path).with_context(|| format!("Failed to reuse archived hwdb input {:?}", source))?;
+            manifest.push(Artifact::new(
+                ArtifactKind::HwdbInput,
+                &path,
+                Some(&candidate.model_name),
+                Some(&candidate.version),
+            )?);
+            reused.push(candidate.clone());
+        }
+        Ok(reused)
+    }
+}
@@ -0,0 +1,346 @@
+use anyhow::Result;
+use glob::glob;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::common::{BenchMerge, SeriesPoints};
+use crate::database::Database;
+use crate::vendors::Vendors;
+
+/// Results found in the database for a model under a given
+/// resctl-bench version
+#[derive(Debug, Serialize)]
+pub struct ModelEntry {
+    pub model_name: String,
+    /// resctl-bench version (major.minor)
+    pub version: String,
+    pub results: Vec<PathBuf>,
+    /// Github issues the results were submitted through
+    pub issues: Vec<u64>,
+    /// Contents of the metadata files of the results
+    pub metadata: Vec<ResultMetadata>,
+}
+
+impl ModelEntry {
+    /// Summarizes the results of the entry, reading their firmware
+    /// revisions from the result files
+    pub fn summary(&self) -> Result<ModelSummary> {
+        let firmware = match self.results.first().and_then(|r| r.parent()) {
+            Some(dir) => BenchMerge::results_by_fwrev(dir)?
+                .into_keys()
+                .map(|fwrev| fwrev.trim_matches('"').to_string())
+                .collect(),
+            None => vec![],
+        };
+        Ok(ModelSummary {
+            model_name: self.model_name.clone(),
+            version: self.version.clone(),
+            results: self.results.len(),
+            issues: self.issues.len(),
+            firmware,
+            last_updated: self.metadata.iter().filter_map(|m| m.imported_at.clone()).max(),
+        })
+    }
+}
+
+/// Results of a model for a resctl-bench version, as listed by
+/// `iocost-ci list`
+#[derive(Debug, Serialize)]
+pub struct ModelSummary {
+    pub model_name: String,
+    pub version: String,
+    pub results: usize,
+    pub issues: usize,
+    /// Firmware revisions of the results, oldest first
+    pub firmware: Vec<String>,
+    /// When the last result was imported, if it was recorded
+    pub last_updated: Option<String>,
+}
+
+/// Metadata stored next to each result in the database
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResultMetadata {
+    pub model_name: String,
+    pub version: String,
+    pub issue: Option<u64>,
+    pub url: Option<String>,
+    pub submitter: Option<String>,
+    pub imported_at: Option<String>,
+    /// Seconds it took to download and validate the result
+    pub processing_secs: Option<f64>,
+    /// Checksum advertised by the host of `url` the download was
+    /// verified against, as `<header>:<md5>`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verified_checksum: Option<String>,
+    /// Identifying fields scrubbed from the result before storing it
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub scrubbed: Vec<String>,
+    /// Date (YYYY-MM-DD) until which the result was held back from the
+    /// database at the request of the submitter
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub embargo_until: Option<String>,
+    /// Newer firmware revision whose results supersede this one for the
+    /// hwdb. Superseded results are still used for the generic merges.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub superseded_by: Option<String>,
+    /// Set by maintainers to leave the result out of the merges, with
+    /// `/exclude <md5> reason=...`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub exclude: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exclude_reason: Option<String>,
+    /// Number of times the result is passed to the merges, to give it
+    /// more (or, with 0, no) influence. Defaults to 1.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weight: Option<u32>,
+    /// Vendor whose key made the signature submitted with the result
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signed_by: Option<String>,
+}
+
+impl ResultMetadata {
+    /// Returns the path of the metadata file of the `result` file
+    pub fn path_for(result: &Path) -> PathBuf {
+        result.with_extension("").with_extension("json.metadata")
+    }
+
+    /// Returns the metadata of a `result` file of unknown origin, with
+    /// the model and version of the database directory it is in
+    pub fn unknown_origin(result: &Path) -> Self {
+        let model_dir = result.parent().unwrap();
+        ResultMetadata {
+            model_name: model_dir.file_name().unwrap().to_string_lossy().to_string(),
+            version: model_dir
+                .parent()
+                .and_then(|p| p.file_name())
+                .unwrap()
+                .to_string_lossy()
+                .to_string(),
+            issue: None,
+            url: None,
+            submitter: None,
+            imported_at: None,
+            processing_secs: None,
+            verified_checksum: None,
+            scrubbed: vec![],
+            embargo_until: None,
+            superseded_by: None,
+            exclude: false,
+            exclude_reason: None,
+            weight: None,
+            signed_by: None,
+        }
+    }
+
+    /// Loads the metadata of the `result` file in the database, if it
+    /// has any
+    pub fn load_for(result: &Path) -> Option<Self> {
+        fs::read_to_string(Self::path_for(result))
+            .ok()
+            .and_then(|c| serde_json::from_str(&c).ok())
+    }
+
+    /// Saves the metadata of the `result` file in the database
+    pub fn save_for(&self, result: &Path) -> std::io::Result<()> {
+        fs::write(Self::path_for(result), serde_json::to_string(self)?)
+    }
+}
+
+/// Number of results and submissions per model and version
+#[derive(Debug, Serialize)]
+pub struct CoverageStats {
+    pub generated: String,
+    pub models: usize,
+    pub results: usize,
+    /// Totals of the models of each vendor of vendors.toml
+    pub vendors: Vec<VendorCoverage>,
+    pub entries: Vec<CoverageEntry>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct VendorCoverage {
+    pub vendor: String,
+    pub models: usize,
+    pub results: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CoverageEntry {
+    pub model_name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vendor: Option<String>,
+    pub version: String,
+    pub results: usize,
+    pub issues: usize,
+    /// Results superseded by those of a newer firmware revision
+    pub superseded: usize,
+    /// Data points of the iocost-tune series of the merged results, by
+    /// series, if the model was merged
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub series: BTreeMap<String, SeriesPoints>,
+}
+
+impl CoverageStats {
+    /// Adds the data points of the series of the merged results of
+    /// `database` to the entries of the models that were merged
+    pub fn add_series_points(&mut self, database: &Database) {
+        for entry in &mut self.entries {
+            let merged = database.merged_path(&entry.version, &entry.model_name, None);
+            if let Some(series) = merged
+                .ok()
+                .filter(|path| path.exists())
+                .and_then(|path| BenchMerge::get_series_points(&path).ok())
+            {
+                entry.series = series;
+            }
+        }
+    }
+
+    /// Returns the entries with a series that has too few data points
+    pub fn starved(&self) -> impl Iterator<Item = &CoverageEntry> {
+        self.entries.iter().filter(|e| e.series.values().any(SeriesPoints::is_starved))
+    }
+}
+
+/// In-memory index of the database contents, built from the directory
+/// layout and the metadata files stored next to each result.
+#[derive(Debug, Default, Serialize)]
+pub struct DatabaseIndex {
+    pub entries: Vec<ModelEntry>,
+}
+
+impl DatabaseIndex {
+    /// Scans `database_dir`, expecting the
+    /// `<version>/<model>/result-<md5>.json.gz` layout.
+    pub fn load(database_dir: &Path) -> Result<Self> {
+        let mut entries = vec![];
+        let pattern = database_dir.join("*").join("*");
+        for model_dir in glob(&pattern.to_string_lossy())?.flatten().filter(|p| p.is_dir()) {
+            let model_name = model_dir.file_name().unwrap().to_string_lossy().to_string();
+            let version = model_dir
+                .parent()
+                .and_then(|p| p.file_name())
+                .unwrap()
+                .to_string_lossy()
+                .to_string();
+            let mut results: Vec<PathBuf> =
+                glob(&model_dir.join("*.json.gz").to_string_lossy())?
+                    .flatten()
+                    .collect();
+            results.sort();
+            let metadata: Vec<ResultMetadata> =
+                glob(&model_dir.join("*.json.metadata").to_string_lossy())?
+                    .flatten()
+                    .filter_map(|p| fs::read_to_string(p).ok())
+                    .filter_map(|c| serde_json::from_str(&c).ok())
+                    .collect();
+            let mut issues: Vec<u64> = metadata.iter().filter_map(|m| m.issue).collect();
+            issues.sort_unstable();
+            issues.dedup();
+            entries.push(ModelEntry {
+                model_name,
+                version,
+                results,
+                issues,
+                metadata,
+            });
+        }
+        entries.sort_by(|a, b| (&a.model_name, &a.version).cmp(&(&b.model_name, &b.version)));
+        Ok(DatabaseIndex { entries })
+    }
+
+    /// Returns the entries for `model_name`, one per version
+    pub fn model(&self, model_name: &str) -> Vec<&ModelEntry> {
+        self.entries
+            .iter()
+            .filter(|e| e.model_name == model_name)
+            .collect()
+    }
+
+    /// Computes the coverage statistics of the database, grouped by the
+    /// `vendors` of the models
+    pub fn coverage_stats(&self, vendors: &Vendors) -> CoverageStats {
+        let mut by_vendor: BTreeMap<&str, (BTreeSet<&str>, usize)> = BTreeMap::new();
+        for entry in &self.entries {
+            if let Some((vendor, _)) = vendors.vendor_for(&entry.model_name) {
+                let totals = by_vendor.entry(vendor).or_default();
+                totals.0.insert(&entry.model_name);
+                totals.1 += entry.results.len();
+            }
+        }
+        CoverageStats {
+            generated: chrono::Utc::now().to_rfc3339(),
+            models: self.models().len(),
+            results: self.entries.iter().map(|e| e.results.len()).sum(),
+            vendors: by_vendor
+                .into_iter()
+                .map(|(vendor, (models, results))| VendorCoverage {
+                    vendor: vendor.to_string(),
+                    models: models.len(),
+                    results,
+                })
+                .collect(),
+            entries: self
+                .entries
+                .iter()
+                .map(|e| CoverageEntry {
+                    model_name: e.model_name.clone(),
+                    vendor: vendors.vendor_for(&e.model_name).map(|(v, _)| v.to_string()),
+                    version: e.version.clone(),
+                    results: e.results.len(),
+                    issues: e.issues.len(),
+                    superseded: e.metadata.iter().filter(|m| m.superseded_by.is_some()).count(),
+                    series: BTreeMap::new(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Returns the average time it took to download and validate the
+    /// `last` most recently imported results, if any was recorded
+    pub fn mean_processing_secs(&self, last: usize) -> Option<f64> {
+        let mut timed: Vec<(&str, f64)> = self
+            .entries
+            .iter()
+            .flat_map(|e| &e.metadata)
+            .filter_map(|m| Some((m.imported_at.as_deref()?, m.processing_secs?)))
+            .collect();
+        if timed.is_empty() {
+            return None;
+        }
+        timed.sort_by(|a, b| b.0.cmp(a.0));
+        timed.truncate(last);
+        Some(timed.iter().map(|(_, secs)| secs).sum::<f64>() / timed.len() as f64)
+    }
+
+    /// Returns the issues `submitter` got results imported from on
+    /// `date`, in UTC
+    pub fn issues_submitted_on(&self, submitter: &str, date: chrono::NaiveDate) -> BTreeSet<u64> {
+        self.entries
+            .iter()
+            .flat_map(|e| &e.metadata)
+            .filter(|m| m.submitter.as_deref() == Some(submitter))
+            .filter(|m| {
+                m.imported_at
+                    .as_deref()
+                    .and_then(|t| chrono::DateTime::parse_from_rfc3339(t).ok())
+                    .is_some_and(|t| t.with_timezone(&chrono::Utc).date_naive() == date)
+            })
+            .filter_map(|m| m.issue)
+            .collect()
+    }
+
+    /// Returns the versions each model has results for, keyed by model
+    pub fn models(&self) -> BTreeMap<&str, Vec<&str>> {
+        let mut models: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+        for entry in &self.entries {
+            models
+                .entry(entry.model_name.as_str())
+                .or_default()
+                .push(entry.version.as_str());
+        }
+        models
+    }
+}
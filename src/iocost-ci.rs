@@ -0,0 +1,911 @@
+use anyhow::{bail, Context, Result};
+use clap::{Parser, Subcommand};
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use iocost_benchmarks::archive::{print_model_history, HwdbArchive};
+use iocost_benchmarks::catalog::set_locale;
+use iocost_benchmarks::common::{format_result, BenchMerge};
+use iocost_benchmarks::config::TomlData;
+use iocost_benchmarks::digest::Digest;
+use iocost_benchmarks::doctor::{print_checks, run_checks};
+use iocost_benchmarks::database::{Database, MERGED_RESULTS_DIR};
+use iocost_benchmarks::embargo::{embargo_staging, held, run_release, set_embargo_staging, staging_from_config};
+use iocost_benchmarks::exit::{exit_code, EnvironmentError, ExitStatus, EXIT_CODES_HELP};
+use iocost_benchmarks::export::{export, ExportFormat, Granularity};
+use iocost_benchmarks::fw_compare::FirmwareComparison;
+use iocost_benchmarks::garbage::run_gc;
+use iocost_benchmarks::git::head_commit;
+use iocost_benchmarks::history::{load_history, HISTORY_BRANCH};
+use iocost_benchmarks::hwdb::{export_hwdb, HWDB_FILE};
+use iocost_benchmarks::index::DatabaseIndex;
+use iocost_benchmarks::issue_form::IssueForm;
+use iocost_benchmarks::layout::run_migrate;
+use iocost_benchmarks::legacy::{run_archive_version, set_legacy_versions, LegacyVersions};
+use iocost_benchmarks::output::OutputArgs;
+use iocost_benchmarks::plot::plot;
+use iocost_benchmarks::policy::set_policies;
+use iocost_benchmarks::progress::VerbosityArgs;
+use iocost_benchmarks::retract::run_retract;
+use iocost_benchmarks::rules::{set_rules, Rules, RULES_FILE};
+use iocost_benchmarks::serve::{serve, ServeConfig, SubmitConfig};
+use iocost_benchmarks::self_test::run_self_test;
+use iocost_benchmarks::setup::{run_setup, SetupSettings};
+use iocost_benchmarks::show::ResultFacts;
+use iocost_benchmarks::storage::{object_storage_from_config, pull, push, set_object_storage, Storage};
+use iocost_benchmarks::vendor_overrides::{VendorOverrides, VENDOR_OVERRIDES_DIR};
+use iocost_benchmarks::vendors::VENDORS_FILE;
+use iocost_benchmarks::webhook::{serve_webhooks, WebhookConfig};
+use iocost_benchmarks::workspace::{set_workspace, Workspace, DATABASE_DIR, HWDB_INPUTS_DIR};
+
+#[derive(Parser, Debug)]
+#[command(version, about, after_help = EXIT_CODES_HELP)]
+/// Maintenance and inspection tool for the iocost-benchmarks database
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+
+    /// Root of the checkout of the database repository to work in, the
+    /// paths given to the commands being relative to it [default: the
+    /// current directory]
+    #[arg(long, global = true, value_name = "DIR", env = "IOCOST_WORKSPACE")]
+    workspace: Option<PathBuf>,
+
+    #[command(flatten)]
+    verbosity: VerbosityArgs,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Inspect generated hwdb files
+    Hwdb {
+        #[command(subcommand)]
+        command: HwdbCommand,
+    },
+    /// Serve an HTTP API over the database contents
+    Serve {
+        /// Address to listen on
+        #[arg(short, long, value_name = "ADDR", default_value = "127.0.0.1:8080")]
+        listen: String,
+
+        /// Database dir
+        #[arg(short, long, value_name = "DIR", default_value = DATABASE_DIR)]
+        database_dir: PathBuf,
+
+        /// Final hwdb file to serve the model entries from
+        #[arg(long, value_name = "FILE", default_value = "90-iocost-tune.hwdb")]
+        hwdb_file: PathBuf,
+
+        /// Bearer token for the POST /submit endpoint, which is only
+        /// enabled if this is set
+        #[arg(long, value_name = "TOKEN", env = "IOCOST_SUBMIT_TOKEN", hide_env_values = true)]
+        submit_token: Option<String>,
+
+        /// Github token used to push submission branches and open pull
+        /// requests
+        #[arg(long, value_name = "TOKEN", env = "GITHUB_TOKEN", hide_env_values = true)]
+        github_token: Option<String>,
+
+        /// Database repository pull requests are opened in
+        #[arg(long, value_name = "OWNER/NAME", default_value = "iocost-benchmark/iocost-benchmarks")]
+        github_repo: String,
+
+        /// Branch pull requests are opened against
+        #[arg(long, value_name = "BRANCH", default_value = "main")]
+        base_branch: String,
+
+        /// Path of the toml config file with the policies the submitted
+        /// results are checked against, as by import-results
+        #[arg(short, long, value_name = "FILE")]
+        config_file: Option<String>,
+    },
+    /// Move the tree of a legacy version out of the database, to the
+    /// archive directory, and regenerate the support matrix and the
+    /// coverage statistics without it
+    ArchiveVersion {
+        /// resctl-bench version (X.Y) to archive, which must be one of the
+        /// legacy versions of the config file
+        version: String,
+
+        /// Database dir
+        #[arg(short, long, value_name = "DIR", default_value = DATABASE_DIR)]
+        database_dir: PathBuf,
+
+        /// Path of the toml config file with the legacy versions
+        #[arg(short, long, value_name = "FILE")]
+        config_file: Option<String>,
+
+        /// Commit the archival to a branch for review
+        #[arg(long)]
+        commit: bool,
+    },
+    /// Check the environment the pipeline runs in, suggesting fixes for
+    /// the problems found
+    Doctor {
+        /// Path of the toml config file to check
+        #[arg(short, long, value_name = "FILE")]
+        config_file: Option<String>,
+
+        /// Github token to check
+        #[arg(long, value_name = "TOKEN", env = "GITHUB_TOKEN", hide_env_values = true)]
+        github_token: Option<String>,
+
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Summarize the recent submission activity, optionally sending it
+    /// by email
+    Digest {
+        /// Number of days to summarize
+        #[arg(long, default_value_t = 7)]
+        days: i64,
+
+        /// Database dir
+        #[arg(short, long, value_name = "DIR", default_value = DATABASE_DIR)]
+        database_dir: PathBuf,
+
+        /// Render the digest as HTML instead of markdown
+        #[arg(long)]
+        html: bool,
+
+        /// Send the digest using the SMTP settings in the config file
+        #[arg(long, requires = "config_file")]
+        send: bool,
+
+        /// Path of the toml config file to load
+        #[arg(short, long, value_name = "FILE")]
+        config_file: Option<String>,
+    },
+    /// Export the results metrics and metadata as a CSV or Parquet
+    /// dataset for offline analysis
+    Export {
+        /// Database dir
+        #[arg(short, long, value_name = "DIR", default_value = DATABASE_DIR)]
+        database_dir: PathBuf,
+
+        /// File to write the dataset to
+        #[arg(short, long, value_name = "FILE")]
+        output: PathBuf,
+
+        /// Format of the dataset [default: from the output extension,
+        /// csv if not .parquet]
+        #[arg(short, long)]
+        format: Option<ExportFormat>,
+
+        /// What each row describes
+        #[arg(long, value_enum, default_value_t = Granularity::Result)]
+        per: Granularity,
+    },
+    /// Generate the final hwdb file and the hwdb inputs of every model
+    /// of the database, without the Github environment merge-results
+    /// runs in
+    ExportHwdb {
+        /// Database dir
+        #[arg(short, long, value_name = "DIR", default_value = DATABASE_DIR)]
+        database_dir: PathBuf,
+
+        /// Directory to write the hwdb file, the hwdb inputs and the
+        /// merged results in
+        #[arg(short, long, value_name = "DIR", default_value = ".")]
+        output_dir: PathBuf,
+
+        /// Commit referenced in the header of the hwdb file [default:
+        /// HEAD of the database repository]
+        #[arg(long, value_name = "SHA")]
+        commit: Option<String>,
+    },
+    /// Compare the solutions fitted separately on the results of each
+    /// firmware revision of a model
+    FwCompare {
+        /// Drive model name, as found in the database
+        model: String,
+
+        /// Database dir
+        #[arg(short, long, value_name = "DIR", default_value = DATABASE_DIR)]
+        database_dir: PathBuf,
+
+        /// Only compare the results of this resctl-bench version (X.Y)
+        #[arg(long, value_name = "VERSION")]
+        version: Option<String>,
+    },
+    /// Find the files of the database repository that don't belong in
+    /// it: results without metadata, metadata without results and
+    /// generated files committed by accident
+    Gc {
+        /// Database dir
+        #[arg(short, long, value_name = "DIR", default_value = DATABASE_DIR)]
+        database_dir: PathBuf,
+
+        /// Stage the cleanup: the removal of the files, or the missing
+        /// metadata of the results
+        #[arg(long)]
+        fix: bool,
+    },
+    /// Show what happened to a submission: the runs of its issue and
+    /// their outcome, from the history branch of the database repository
+    History {
+        /// Number of the submission issue
+        issue: u64,
+
+        /// Root of the database repository, with the history branch
+        /// fetched
+        #[arg(long, value_name = "DIR", default_value = ".")]
+        repo_dir: PathBuf,
+
+        /// Print the events as JSON lines
+        #[arg(long, conflicts_with = "format")]
+        json: bool,
+
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Generate the issue form of the submissions from the settings the
+    /// importer runs with
+    IssueForm {
+        /// Path of the toml config file of the importer
+        #[arg(short, long, value_name = "FILE")]
+        config_file: Option<String>,
+
+        /// Root of the database repository
+        #[arg(long, value_name = "DIR", default_value = ".")]
+        repo_dir: PathBuf,
+
+        /// Only check that the form in the repository is up to date
+        #[arg(long)]
+        check: bool,
+    },
+    /// List the models of the database with their number of results,
+    /// firmware revisions and last update
+    List {
+        /// Database dir
+        #[arg(short, long, value_name = "DIR", default_value = DATABASE_DIR)]
+        database_dir: PathBuf,
+
+        /// Only list the models of this resctl-bench version (X.Y)
+        #[arg(long, value_name = "VERSION")]
+        version: Option<String>,
+
+        /// Only list the models matching this glob pattern
+        #[arg(long, value_name = "GLOB")]
+        model: Option<String>,
+
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Upgrade the database to the current layout version
+    Migrate {
+        /// Database dir
+        #[arg(short, long, value_name = "DIR", default_value = DATABASE_DIR)]
+        database_dir: PathBuf,
+
+        /// Only show what would change
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Commit the changes to a branch for review
+        #[arg(long, conflicts_with = "dry_run")]
+        commit: bool,
+    },
+    /// Chart a series of the iocost-tune results of a model against the
+    /// vrate, with the solutions, to compare firmware revisions or
+    /// models on the same chart
+    Plot {
+        /// Drive model name, as found in the database
+        #[arg(long)]
+        model: String,
+
+        /// Other models to plot on the same chart
+        #[arg(long, value_name = "MODEL")]
+        compare: Vec<String>,
+
+        /// resctl-bench version (X.Y) of the results
+        #[arg(long, value_name = "VERSION", default_value = "2.2")]
+        version: String,
+
+        /// Plot a merge per firmware revision of each model
+        #[arg(long)]
+        by_fwrev: bool,
+
+        /// iocost-tune series to plot, such as MOF, aMOF or isol-01
+        #[arg(long, default_value = "MOF")]
+        series: String,
+
+        /// SVG or PNG file to write the chart to, by its extension
+        #[arg(short, long, value_name = "FILE")]
+        output: PathBuf,
+
+        /// Database dir
+        #[arg(short, long, value_name = "DIR", default_value = DATABASE_DIR)]
+        database_dir: PathBuf,
+    },
+    /// Remove a result from the database and regenerate the merge, pdf
+    /// and hwdb entry of its model
+    Retract {
+        /// md5 checksum of the result, as in its result-<md5>.json.gz
+        /// file name
+        #[arg(long, value_name = "HASH")]
+        md5: String,
+
+        /// Why the result is retracted, recorded in the commit
+        #[arg(long)]
+        reason: String,
+
+        /// Database dir
+        #[arg(short, long, value_name = "DIR", default_value = DATABASE_DIR)]
+        database_dir: PathBuf,
+
+        /// Commit the removal to a branch for review
+        #[arg(long)]
+        commit: bool,
+    },
+    /// Run the import, merge and hwdb generation on bundled results in a
+    /// temporary workspace and check the outputs against the expected
+    /// ones
+    SelfTest {
+        /// Run the resctl-bench binaries of the workspace rather than the
+        /// mock, only checking that every model gets a hwdb entry
+        #[arg(long)]
+        real: bool,
+
+        /// Keep the temporary workspace for inspection
+        #[arg(long)]
+        keep: bool,
+
+        /// Overwrite the golden files of the source tree with the
+        /// outputs, after an intended change of them
+        #[arg(long, conflicts_with = "real")]
+        update_golden: bool,
+    },
+    /// Prepare the resctl-bench binaries of the runner, building the
+    /// missing ones with --build, verify them and print the manifest of
+    /// the setup as JSON
+    Setup {
+        /// Only set up this resctl-bench version (X.Y), e.g. in a job of
+        /// a matrix over the versions [default: all the known versions]
+        #[arg(long, value_name = "VERSION")]
+        version: Vec<String>,
+
+        /// Fetch and build the binaries which aren't in the workspace
+        #[arg(long)]
+        build: bool,
+
+        /// Only print the versions to set up as a JSON array, for the
+        /// matrix strategy of a workflow
+        #[arg(long, conflicts_with = "build")]
+        matrix: bool,
+
+        /// Also write the manifest to FILE
+        #[arg(long, value_name = "FILE")]
+        manifest: Option<PathBuf>,
+
+        /// Path of the toml config file with the [setup] section
+        #[arg(short, long, value_name = "FILE")]
+        config_file: Option<String>,
+    },
+    /// Print the key facts of a result file, without running resctl-bench,
+    /// to check it before submitting it
+    Show {
+        /// Result file (.json.gz)
+        file: PathBuf,
+
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Print the resctl-bench summary of the merged results of a model,
+    /// merging them first if there is no merge yet
+    Summary {
+        /// Drive model name, as found in the database
+        #[arg(long)]
+        model: String,
+
+        /// resctl-bench version (X.Y) of the results
+        #[arg(long, value_name = "VERSION", default_value = "2.2")]
+        version: String,
+
+        /// Merge the results again even if there is a merge
+        #[arg(long)]
+        merge: bool,
+
+        /// Print the full formatted results instead of the summary
+        #[arg(long)]
+        full: bool,
+
+        /// Database dir
+        #[arg(short, long, value_name = "DIR", default_value = DATABASE_DIR)]
+        database_dir: PathBuf,
+    },
+    /// Receive Github webhooks and process submissions directly, as an
+    /// alternative to running as a Github workflow
+    Webhook {
+        /// Address to listen on
+        #[arg(short, long, value_name = "ADDR", default_value = "127.0.0.1:8081")]
+        listen: String,
+
+        /// Secret configured for the webhook
+        #[arg(long, value_name = "SECRET", env = "IOCOST_WEBHOOK_SECRET", hide_env_values = true)]
+        secret: String,
+
+        /// Github token used to comment, push branches and open pull
+        /// requests
+        #[arg(long, value_name = "TOKEN", env = "GITHUB_TOKEN", hide_env_values = true)]
+        github_token: String,
+
+        /// Database dir
+        #[arg(short, long, value_name = "DIR", default_value = DATABASE_DIR)]
+        database_dir: String,
+
+        /// Database repository pull requests are opened in
+        #[arg(long, value_name = "OWNER/NAME", default_value = "iocost-benchmark/iocost-benchmarks")]
+        github_repo: String,
+
+        /// Branch pull requests are opened against
+        #[arg(long, value_name = "BRANCH", default_value = "main")]
+        base_branch: String,
+    },
+    /// Inspect and publish the results held back until the end of their
+    /// embargo
+    Embargo {
+        #[command(subcommand)]
+        command: EmbargoCommand,
+    },
+    /// Synchronize the database directory with the object storage of the
+    /// [storage] section of the config file
+    Storage {
+        #[command(subcommand)]
+        command: StorageCommand,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum HwdbCommand {
+    /// Show how the hwdb entry of a model evolved over time
+    History {
+        /// Drive model name, as found in the database
+        model: String,
+
+        /// Directory holding the archived hwdb outputs
+        #[arg(long, value_name = "DIR", default_value = "hwdb-archive")]
+        archive_dir: String,
+
+        /// Name of the final hwdb file in each archived run
+        #[arg(long, value_name = "FILE", default_value = "90-iocost-tune.hwdb")]
+        hwdb_file: String,
+
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum StorageCommand {
+    /// Download the files of the object storage missing from the database
+    /// dir and remove those it doesn't have anymore
+    Pull {
+        /// Path of the toml config file to load
+        #[arg(short, long, value_name = "FILE")]
+        config_file: String,
+
+        /// Database dir
+        #[arg(short, long, value_name = "DIR", default_value = DATABASE_DIR)]
+        database_dir: PathBuf,
+    },
+    /// Upload the files of the database dir missing from the object
+    /// storage, to move a git database to it
+    Push {
+        /// Path of the toml config file to load
+        #[arg(short, long, value_name = "FILE")]
+        config_file: String,
+
+        /// Database dir
+        #[arg(short, long, value_name = "DIR", default_value = DATABASE_DIR)]
+        database_dir: PathBuf,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum EmbargoCommand {
+    /// List the held results and the end of their embargo
+    List {
+        /// Path of the toml config file to load
+        #[arg(short, long, value_name = "FILE")]
+        config_file: String,
+
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Add the results whose embargo ended to the database
+    Release {
+        /// Path of the toml config file to load
+        #[arg(short, long, value_name = "FILE")]
+        config_file: String,
+
+        /// Database dir
+        #[arg(short, long, value_name = "DIR", default_value = DATABASE_DIR)]
+        database_dir: PathBuf,
+
+        /// Commit the results to a branch for review (or upload them to
+        /// the object storage of the database) and remove them from the
+        /// staging area
+        #[arg(long)]
+        commit: bool,
+    },
+}
+
+/// Loads the config file in `path` and sets up the embargo staging area
+/// and the object storage of the database
+fn load_embargo_config(path: &str) -> Result<()> {
+    let config = TomlData::load(path)?;
+    let embargo = config.embargo.context("No [embargo] section in the config file")?;
+    set_embargo_staging(staging_from_config(&embargo)?)?;
+    if let Some(storage) = config.storage.as_ref().map(object_storage_from_config).transpose()?.flatten() {
+        set_object_storage(storage)?;
+    }
+    Ok(())
+}
+
+/// Returns the object storage of the config file in `path`
+fn load_object_storage(path: &str) -> Result<Box<dyn Storage>> {
+    let config = TomlData::load(path)?;
+    let storage = config.storage.context("No [storage] section in the config file")?;
+    object_storage_from_config(&storage)?.context("The database is stored in git, not in an object storage")
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    exit_code(run(Cli::parse()).await.map(|_| ExitStatus::Success))
+}
+
+async fn run(args: Cli) -> Result<()> {
+    args.verbosity.apply();
+    if let Some(root) = &args.workspace {
+        set_workspace(Workspace::enter(root).map_err(|e| EnvironmentError(format!("{:#}", e)))?)?;
+    }
+    match args.command {
+        Command::Hwdb { command } => match command {
+            HwdbCommand::History {
+                model,
+                archive_dir,
+                hwdb_file,
+                output,
+            } => print_model_history(&HwdbArchive::new(archive_dir), &model, &hwdb_file, output.format),
+        },
+        Command::Serve {
+            listen,
+            database_dir,
+            hwdb_file,
+            submit_token,
+            github_token,
+            github_repo,
+            base_branch,
+            config_file,
+        } => {
+            if let Some(path) = config_file {
+                set_policies(&TomlData::load(&path)?)?;
+            }
+            set_rules(Rules::load(Path::new(RULES_FILE), Path::new(VENDORS_FILE))?)?;
+            let submit = submit_token.map(|token| SubmitConfig {
+                token,
+                github_token,
+                repo: github_repo,
+                base_branch,
+            });
+            serve(
+                &listen,
+                ServeConfig {
+                    database_dir,
+                    hwdb_file,
+                    submit,
+                },
+            )
+            .await
+        }
+        Command::ArchiveVersion {
+            version,
+            database_dir,
+            config_file,
+            commit,
+        } => {
+            if let Some(path) = config_file {
+                set_legacy_versions(LegacyVersions::from_config(&TomlData::load(&path)?.config)?)?;
+            }
+            run_archive_version(&database_dir, &version, commit)
+        }
+        Command::Doctor {
+            config_file,
+            github_token,
+            output,
+        } => print_checks(&run_checks(config_file.as_deref(), github_token.as_deref()).await, output.format),
+        Command::Digest {
+            days,
+            database_dir,
+            html,
+            send,
+            config_file,
+        } => {
+            let config = config_file.map(|path| TomlData::load(&path)).transpose()?;
+            if let Some(locale) = config.as_ref().and_then(|c| c.config.locale.as_deref()) {
+                set_locale(locale)?;
+            }
+            let digest = Digest::new(&database_dir, days)?;
+            if send {
+                let smtp = config
+                    .unwrap()
+                    .digest
+                    .and_then(|d| d.smtp)
+                    .context("No [digest.smtp] section in the config file")?;
+                digest.send(&smtp, html)?;
+                println!("Digest sent to {}", smtp.to.join(", "));
+            } else if html {
+                print!("{}", digest.to_html());
+            } else {
+                print!("{}", digest.to_markdown());
+            }
+            Ok(())
+        }
+        Command::Export {
+            database_dir,
+            output,
+            format,
+            per,
+        } => {
+            let format = format.unwrap_or_else(|| ExportFormat::from_path(&output));
+            let rows = export(&database_dir, &output, format, per)?;
+            println!("Exported {} rows to {}", rows, output.display());
+            Ok(())
+        }
+        Command::ExportHwdb {
+            database_dir,
+            output_dir,
+            commit,
+        } => {
+            let commit = match commit {
+                Some(commit) => commit,
+                None => head_commit(&database_dir)?,
+            };
+            let database = Database::new(&database_dir).with_merged_dir(output_dir.join(MERGED_RESULTS_DIR));
+            let vendor_overrides = VendorOverrides::load(&PathBuf::from(VENDOR_OVERRIDES_DIR))?;
+            let hwdb = output_dir.join(HWDB_FILE);
+            let models = export_hwdb(
+                &database,
+                &output_dir.join(HWDB_INPUTS_DIR),
+                &hwdb,
+                Some(commit),
+                &vendor_overrides,
+            )?;
+            println!("Wrote the entries of {} models to {}", models.len(), hwdb.display());
+            Ok(())
+        }
+        Command::FwCompare {
+            model,
+            database_dir,
+            version,
+        } => {
+            let index = DatabaseIndex::load(&database_dir)?;
+            let versions: Vec<&str> = index
+                .model(&model)
+                .into_iter()
+                .map(|e| e.version.as_str())
+                .filter(|v| version.as_deref().is_none_or(|version| version == *v))
+                .collect();
+            if versions.is_empty() {
+                bail!("No results for {} in {}", model, database_dir.display());
+            }
+            let database = Database::new(&database_dir);
+            for version in versions {
+                print!("{}", FirmwareComparison::compute(&database, version, &model)?.to_markdown());
+            }
+            Ok(())
+        }
+        Command::Gc { database_dir, fix } => run_gc(&database_dir, fix),
+        Command::History {
+            issue,
+            repo_dir,
+            json,
+            output,
+        } => {
+            let git_repo = git2::Repository::discover(&repo_dir)
+                .with_context(|| format!("{} is not in a git repository", repo_dir.display()))?;
+            let events = load_history(&git_repo, issue)?;
+            if events.is_empty() {
+                bail!(
+                    "No history of #{} in the {} branch, fetch it with `git fetch origin {}`",
+                    issue,
+                    HISTORY_BRANCH,
+                    HISTORY_BRANCH
+                );
+            }
+            if json {
+                for event in &events {
+                    println!("{}", serde_json::to_string(event)?);
+                }
+                return Ok(());
+            }
+            output.format.print(&events, || {
+                events.iter().for_each(|event| println!("{}", event));
+                Ok(())
+            })
+        }
+        Command::List {
+            database_dir,
+            version,
+            model,
+            output,
+        } => {
+            let pattern = model.as_deref().map(glob::Pattern::new).transpose()?;
+            let summaries = DatabaseIndex::load(&database_dir)?
+                .entries
+                .iter()
+                .filter(|e| version.as_deref().is_none_or(|v| v == e.version))
+                .filter(|e| pattern.as_ref().is_none_or(|p| p.matches(&e.model_name)))
+                .map(|e| e.summary())
+                .collect::<Result<Vec<_>, _>>()?;
+            output.format.print(&summaries, || {
+                println!(
+                    "{:<40} {:>7} {:>7} {:>6}  {:<10}  firmware",
+                    "model", "version", "results", "issues", "updated"
+                );
+                for summary in &summaries {
+                    println!(
+                        "{:<40} {:>7} {:>7} {:>6}  {:<10}  {}",
+                        summary.model_name,
+                        summary.version,
+                        summary.results,
+                        summary.issues,
+                        summary.last_updated.as_deref().map_or("-", |t| t.get(..10).unwrap_or(t)),
+                        summary.firmware.join(", ")
+                    );
+                }
+                Ok(())
+            })
+        }
+        Command::IssueForm {
+            config_file,
+            repo_dir,
+            check,
+        } => {
+            let config = config_file.map(|path| TomlData::load(&path)).transpose()?;
+            let form = IssueForm::from_config(config.as_ref());
+            let path = repo_dir.join(form.path());
+            if check {
+                if fs::read_to_string(&path).ok() != Some(form.render()) {
+                    bail!("{} is out of date, run `iocost-ci issue-form` to regenerate it", path.display());
+                }
+                println!("{} is up to date", path.display());
+            } else if form.write_in(&repo_dir)? {
+                println!("Wrote {}", path.display());
+            }
+            Ok(())
+        }
+        Command::Migrate {
+            database_dir,
+            dry_run,
+            commit,
+        } => run_migrate(&database_dir, dry_run, commit),
+        Command::Plot {
+            model,
+            compare,
+            version,
+            by_fwrev,
+            series,
+            output,
+            database_dir,
+        } => {
+            let database = Database::new(&database_dir);
+            let merges = plot(&database, &version, &model, &compare, by_fwrev, &series, &output)?;
+            println!("Plotted {} merges to {}", merges, output.display());
+            Ok(())
+        }
+        Command::Retract {
+            md5,
+            reason,
+            database_dir,
+            commit,
+        } => run_retract(&database_dir, &md5, &reason, commit),
+        Command::Summary {
+            model,
+            version,
+            merge,
+            full,
+            database_dir,
+        } => {
+            let database = Database::new(&database_dir);
+            let mut merged = database.merged_path(&version, &model, None)?;
+            if merge || !merged.exists() {
+                database.ensure_layout()?;
+                merged = BenchMerge::merge(&database, version.clone(), model.clone())?.path;
+            }
+            print!("{}", format_result(&version, &merged, full)?);
+            Ok(())
+        }
+        Command::SelfTest {
+            real,
+            keep,
+            update_golden,
+        } => run_self_test(real, keep, update_golden).await,
+        Command::Setup {
+            version,
+            build,
+            matrix,
+            manifest,
+            config_file,
+        } => {
+            let config = config_file.map(|path| TomlData::load(&path)).transpose()?;
+            let settings = match config.as_ref().and_then(|c| c.setup.as_ref()) {
+                Some(setup) => SetupSettings::from_config(setup),
+                None => SetupSettings::default(),
+            };
+            run_setup(&settings, &version, build, matrix, manifest.as_deref())
+        }
+        Command::Show { file, output } => {
+            let facts = ResultFacts::load(&file)?;
+            output.format.print(&facts, || {
+                print!("{}", facts.to_text());
+                Ok(())
+            })
+        }
+        Command::Embargo { command } => match command {
+            EmbargoCommand::List { config_file, output } => {
+                load_embargo_config(&config_file)?;
+                let results = held(embargo_staging().unwrap())?;
+                output.format.print(&results, || {
+                    for result in &results {
+                        println!(
+                            "{}  {} ({}) from #{}: {}",
+                            result.until,
+                            result.metadata.model_name,
+                            result.metadata.version,
+                            result.metadata.issue.map_or("?".to_string(), |i| i.to_string()),
+                            result.key
+                        );
+                    }
+                    Ok(())
+                })
+            }
+            EmbargoCommand::Release {
+                config_file,
+                database_dir,
+                commit,
+            } => {
+                load_embargo_config(&config_file)?;
+                run_release(&database_dir, commit)
+            }
+        },
+        Command::Storage { command } => match command {
+            StorageCommand::Pull { config_file, database_dir } => {
+                let storage = load_object_storage(&config_file)?;
+                let (downloaded, removed) = pull(storage.as_ref(), &database_dir)?;
+                println!("{} files downloaded, {} removed", downloaded, removed);
+                Ok(())
+            }
+            StorageCommand::Push { config_file, database_dir } => {
+                let storage = load_object_storage(&config_file)?;
+                let uploaded = push(storage.as_ref(), &database_dir)?;
+                println!("{} files uploaded to {}", uploaded, storage.describe());
+                Ok(())
+            }
+        },
+        Command::Webhook {
+            listen,
+            secret,
+            github_token,
+            database_dir,
+            github_repo,
+            base_branch,
+        } => {
+            set_rules(Rules::load(Path::new(RULES_FILE), Path::new(VENDORS_FILE))?)?;
+            serve_webhooks(
+                &listen,
+                WebhookConfig {
+                    secret,
+                    github_token,
+                    database_dir,
+                    repo: github_repo,
+                    base_branch,
+                },
+            )
+            .await
+        }
+    }
+}
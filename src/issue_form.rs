@@ -0,0 +1,138 @@
+use anyhow::Result;
+use indicatif::HumanBytes;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::TomlData;
+use crate::consent::ConsentSettings;
+use crate::labels::IssueSettings;
+use crate::limits::SubmissionLimits;
+use crate::workflow::{allowed_url_prefixes, RESULT_SUFFIX};
+
+/// Directory of the database repository with the issue forms
+pub static ISSUE_TEMPLATE_DIR: &str = ".github/ISSUE_TEMPLATE";
+
+/// File name of the issue form when the config doesn't name one
+pub static DEFAULT_FORM: &str = "submission.yml";
+
+/// Label of the embargo field, as parsed by `embargo_date`
+const EMBARGO_LABEL: &str = "Embargo until";
+
+/// Issue form of the submissions, generated from the settings the
+/// importer parses the issues with, so that the two don't drift apart
+#[derive(Debug)]
+pub struct IssueForm {
+    /// File name of the form in the issue templates directory
+    pub name: String,
+    /// URL prefixes the result files can be downloaded from
+    pub hosts: Vec<String>,
+    pub limits: SubmissionLimits,
+    pub consent: Option<ConsentSettings>,
+    /// Whether the submissions need an explicit sign-off
+    pub signoff: bool,
+    /// Whether the results can be embargoed
+    pub embargo: bool,
+}
+
+/// Returns `s` as a double-quoted YAML string
+fn quote(s: &str) -> String {
+    serde_json::to_string(s).unwrap()
+}
+
+impl IssueForm {
+    /// Creates the form matching the `config` the importer runs with
+    pub fn from_config(config: Option<&TomlData>) -> Self {
+        let mut hosts: Vec<String> = allowed_url_prefixes();
+        if let Some(http) = config.and_then(|c| c.http.as_ref()) {
+            hosts.extend(http.hosts.iter().map(|h| h.prefix.clone()));
+        }
+        let issues = config.and_then(|c| c.issues.as_ref()).map(IssueSettings::from_config);
+        IssueForm {
+            name: issues.and_then(|i| i.form).unwrap_or(DEFAULT_FORM.to_string()),
+            hosts,
+            limits: config
+                .and_then(|c| c.limits.as_ref())
+                .map(SubmissionLimits::from_config)
+                .unwrap_or_default(),
+            consent: config.and_then(|c| c.consent.as_ref()).map(ConsentSettings::from_config),
+            signoff: config.and_then(|c| c.signoff.as_ref()).is_some_and(|s| s.required),
+            embargo: config.is_some_and(|c| c.embargo.is_some()),
+        }
+    }
+
+    /// Returns the path of the form in the database repository
+    pub fn path(&self) -> PathBuf {
+        Path::new(ISSUE_TEMPLATE_DIR).join(&self.name)
+    }
+
+    pub fn render(&self) -> String {
+        let mut instructions = format!(
+            "Upload the `{}` result files of resctl-bench, at most {} files and {} in total, and \
+             post their links below. The files can be attached to this issue or hosted on:\n",
+            RESULT_SUFFIX,
+            self.limits.max_urls,
+            HumanBytes(self.limits.max_total_bytes)
+        );
+        for host in &self.hosts {
+            instructions.push_str(&format!("- `{}`\n", host));
+        }
+        let mut yaml = String::from("# Generated by `iocost-ci issue-form`, changes will be overwritten\n");
+        yaml.push_str("name: Benchmark results submission\n");
+        yaml.push_str("description: Submit resctl-bench results to the iocost-benchmarks database\n");
+        yaml.push_str("body:\n");
+        yaml.push_str("  - type: markdown\n    attributes:\n");
+        yaml.push_str(&format!("      value: {}\n", quote(&instructions)));
+        yaml.push_str("  - type: textarea\n    id: results\n    attributes:\n");
+        yaml.push_str("      label: Result links\n");
+        yaml.push_str(&format!(
+            "      description: {}\n",
+            quote(&format!("One link to a `{}` result file per line", RESULT_SUFFIX))
+        ));
+        yaml.push_str("    validations:\n      required: true\n");
+        if self.embargo {
+            yaml.push_str("  - type: input\n    id: embargo\n    attributes:\n");
+            yaml.push_str(&format!("      label: {}\n", EMBARGO_LABEL));
+            yaml.push_str(
+                "      description: Date (YYYY-MM-DD) until which the results are held back from the database\n",
+            );
+            yaml.push_str("      placeholder: YYYY-MM-DD\n");
+        }
+        if self.signoff {
+            yaml.push_str("  - type: input\n    id: signoff\n    attributes:\n");
+            yaml.push_str("      label: Sign-off\n");
+            yaml.push_str("      description: Certify that you can submit these results\n");
+            yaml.push_str("      placeholder: \"Signed-off-by: Name <email>\"\n");
+            yaml.push_str("    validations:\n      required: true\n");
+        }
+        if let Some(consent) = &self.consent {
+            // The license has to be in the label of the checked box
+            let license = match &consent.license_url {
+                Some(url) => format!("[{}]({})", consent.license, url),
+                None => consent.license.clone(),
+            };
+            yaml.push_str("  - type: checkboxes\n    id: consent\n    attributes:\n");
+            yaml.push_str("      label: Data license\n      options:\n");
+            yaml.push_str(&format!(
+                "        - label: {}\n",
+                quote(&format!("I agree to publish these results under the {} license", license))
+            ));
+            yaml.push_str("          required: true\n");
+        }
+        yaml
+    }
+
+    /// Writes the form in the database repository at `root` if it
+    /// changed. Returns whether it was written.
+    pub fn write_in(&self, root: &Path) -> Result<bool> {
+        let path = root.join(self.path());
+        let text = self.render();
+        if fs::read_to_string(&path).is_ok_and(|current| current == text) {
+            return Ok(false);
+        }
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&path, text)?;
+        Ok(true)
+    }
+}
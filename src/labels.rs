@@ -0,0 +1,106 @@
+use anyhow::Result;
+use std::collections::BTreeSet;
+use std::sync::OnceLock;
+
+use crate::config::{set_once, IssuesConfig};
+
+/// Maximum length of a Github label
+const MAX_LABEL_LEN: usize = 50;
+
+/// Label of the issues opened without any result link
+pub const NEEDS_LINKS_LABEL: &str = "needs-links";
+
+/// Label of the submissions queued until the next day, over the daily
+/// limit of their submitter
+pub const QUEUED_LABEL: &str = "queued";
+
+/// How the submission issues are labelled and titled after the models
+/// of their results, to make the submissions searchable by device
+#[derive(Debug, Clone)]
+pub struct IssueSettings {
+    /// Whether to add a `model/<model>` label per model
+    pub model_labels: bool,
+    /// Whether to append the models and versions to the issue title
+    pub retitle: bool,
+    /// File name of the issue form of the submissions
+    pub form: Option<String>,
+    /// URLs of the PDF report and of the hwdb entry of a model, with
+    /// `{model}` and `{version}` placeholders
+    pub pdf_url: Option<String>,
+    pub hwdb_url: Option<String>,
+}
+
+impl Default for IssueSettings {
+    fn default() -> Self {
+        IssueSettings {
+            model_labels: true,
+            retitle: false,
+            form: None,
+            pdf_url: None,
+            hwdb_url: None,
+        }
+    }
+}
+
+impl IssueSettings {
+    /// Creates the settings of the [issues] section of the config file
+    pub fn from_config(config: &IssuesConfig) -> Self {
+        let default = IssueSettings::default();
+        IssueSettings {
+            model_labels: config.model_labels.unwrap_or(default.model_labels),
+            retitle: config.retitle.unwrap_or(default.retitle),
+            form: config.form.clone(),
+            pdf_url: config.pdf_url.clone(),
+            hwdb_url: config.hwdb_url.clone(),
+        }
+    }
+
+    /// Returns the URLs of the PDF report and of the hwdb entry of
+    /// `model_name` for a resctl-bench `version`, if configured
+    pub fn output_urls(&self, model_name: &str, version: &str) -> (Option<String>, Option<String>) {
+        let expand = |url: &String| url.replace("{model}", model_name).replace("{version}", version);
+        (self.pdf_url.as_ref().map(expand), self.hwdb_url.as_ref().map(expand))
+    }
+
+    /// Returns the URL opening a new submission issue in the
+    /// iocost-benchmarks repository of `owner`, with the issue form if
+    /// there is one
+    pub fn form_url(&self, owner: &str) -> String {
+        let new_issue = format!("https://github.com/{}/iocost-benchmarks/issues/new", owner);
+        match &self.form {
+            Some(form) => format!("{}?template={}", new_issue, form),
+            None => format!("{}/choose", new_issue),
+        }
+    }
+}
+
+/// Returns the label of the issues with results of `model_name`
+pub fn model_label(model_name: &str) -> String {
+    format!("model/{}", model_name).chars().take(MAX_LABEL_LEN).collect()
+}
+
+/// Returns `title` with the `models` (model, version) appended between
+/// brackets, merged with those a previous submission to the issue
+/// appended. Returns None if the title already lists them all.
+pub fn submission_title(title: &str, models: &BTreeSet<(String, String)>) -> Option<String> {
+    let (base, mut tags) = match title.trim_end().strip_suffix(']').and_then(|t| t.rsplit_once(" [")) {
+        Some((base, tags)) => (base, tags.split(", ").map(str::to_string).collect::<BTreeSet<_>>()),
+        None => (title.trim_end(), BTreeSet::new()),
+    };
+    let count = tags.len();
+    tags.extend(models.iter().map(|(model, version)| format!("{} {}", model, version)));
+    (tags.len() > count).then(|| format!("{} [{}]", base, tags.into_iter().collect::<Vec<_>>().join(", ")))
+}
+
+static ISSUE_SETTINGS: OnceLock<IssueSettings> = OnceLock::new();
+
+/// Sets how the submission issues are labelled.
+pub fn set_issue_settings(settings: IssueSettings) -> Result<()> {
+    set_once(&ISSUE_SETTINGS, settings, "issue settings")
+}
+
+/// Returns how the submission issues are labelled, the default settings
+/// if none were set
+pub fn issue_settings() -> &'static IssueSettings {
+    ISSUE_SETTINGS.get_or_init(IssueSettings::default)
+}
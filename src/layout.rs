@@ -0,0 +1,158 @@
+use anyhow::Result;
+use glob::glob;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::database::Database;
+use crate::git::commit_to_branch;
+use crate::index::ResultMetadata;
+use crate::workspace::workspace;
+
+/// File at the database root declaring its layout version
+pub static LAYOUT_FILE: &str = "db-layout.json";
+
+/// Layout version written by this version of the tools. Databases
+/// without a layout file are version 1.
+pub const CURRENT_LAYOUT_VERSION: u32 = 2;
+
+/// Contents of the layout file
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LayoutInfo {
+    pub version: u32,
+    /// Date of the last migration (RFC 3339)
+    pub migrated_at: Option<String>,
+}
+
+/// Returns the layout version of the database in `root`
+pub fn layout_version(root: &Path) -> Result<u32> {
+    let path = root.join(LAYOUT_FILE);
+    if !path.exists() {
+        return Ok(1);
+    }
+    let info: LayoutInfo = serde_json::from_str(&fs::read_to_string(path)?)?;
+    Ok(info.version)
+}
+
+/// Writes the layout file declaring `version` in `root` and returns its
+/// path
+pub fn write_layout_version(root: &Path, version: u32) -> Result<PathBuf> {
+    let path = root.join(LAYOUT_FILE);
+    let info = LayoutInfo {
+        version,
+        migrated_at: Some(chrono::Utc::now().to_rfc3339()),
+    };
+    fs::write(&path, serde_json::to_string_pretty(&info)? + "\n")?;
+    Ok(path)
+}
+
+/// Files added/changed and removed by a migration
+#[derive(Debug, Default)]
+pub struct MigrationChanges {
+    pub changed: Vec<PathBuf>,
+    pub removed: Vec<PathBuf>,
+}
+
+/// An upgrade of the database layout from `from` to `from + 1`
+struct Migration {
+    from: u32,
+    description: &'static str,
+    run: fn(&Database, dry_run: bool) -> Result<MigrationChanges>,
+}
+
+static MIGRATIONS: &[Migration] = &[Migration {
+    from: 1,
+    description: "add the metadata files missing for results imported before they existed",
+    run: add_missing_metadata,
+}];
+
+/// v1 -> v2: every result has a metadata file next to it
+fn add_missing_metadata(database: &Database, dry_run: bool) -> Result<MigrationChanges> {
+    let mut changes = MigrationChanges::default();
+    let pattern = database.root().join("*").join("*").join("*.json.gz");
+    for result in glob(&pattern.to_string_lossy())?.flatten() {
+        let metadata_path = ResultMetadata::path_for(&result);
+        if metadata_path.exists() {
+            continue;
+        }
+        let metadata = ResultMetadata::unknown_origin(&result);
+        if !dry_run {
+            fs::write(&metadata_path, serde_json::to_string(&metadata)?)?;
+        }
+        changes.changed.push(metadata_path);
+    }
+    Ok(changes)
+}
+
+/// Upgrades the database layout to the current version, running the
+/// pending migrations in order. With `dry_run`, only reports what would
+/// change. Returns the original version and the changes.
+pub fn migrate(database: &Database, dry_run: bool) -> Result<(u32, MigrationChanges)> {
+    let from = layout_version(database.root())?;
+    let mut changes = MigrationChanges::default();
+    for migration in MIGRATIONS.iter().filter(|m| m.from >= from) {
+        println!(
+            "Layout v{} -> v{}: {}",
+            migration.from,
+            migration.from + 1,
+            migration.description
+        );
+        let mut step = (migration.run)(database, dry_run)?;
+        changes.changed.append(&mut step.changed);
+        changes.removed.append(&mut step.removed);
+    }
+    if from < CURRENT_LAYOUT_VERSION {
+        let path = database.root().join(LAYOUT_FILE);
+        if !dry_run {
+            write_layout_version(database.root(), CURRENT_LAYOUT_VERSION)?;
+        }
+        changes.changed.push(path);
+    }
+    Ok((from, changes))
+}
+
+/// Branch migrations are committed to, for review
+pub fn migration_branch() -> String {
+    format!("iocost-bot/migrate-layout-v{}", CURRENT_LAYOUT_VERSION)
+}
+
+/// Runs the `migrate` command: upgrades the database in `database_dir`
+/// and, with `commit`, commits all the changes in a single commit on the
+/// migration branch
+pub fn run_migrate(database_dir: &Path, dry_run: bool, commit: bool) -> Result<()> {
+    let database = Database::new(database_dir);
+    let (from, changes) = migrate(&database, dry_run)?;
+    if from >= CURRENT_LAYOUT_VERSION {
+        println!("Database {} is already at layout v{}", database_dir.display(), from);
+        return Ok(());
+    }
+    for path in &changes.changed {
+        println!("  M {}", path.display());
+    }
+    for path in &changes.removed {
+        println!("  D {}", path.display());
+    }
+    if dry_run || !commit {
+        return Ok(());
+    }
+    let git_repo = workspace().repository()?;
+    let mut index = git_repo.index()?;
+    for path in &changes.changed {
+        index.add_path(path)?;
+    }
+    for path in &changes.removed {
+        index.remove_path(path)?;
+    }
+    index.write()?;
+    let message = format!(
+        "Migrate the database layout from v{} to v{}\n\n{} files changed, {} removed",
+        from,
+        CURRENT_LAYOUT_VERSION,
+        changes.changed.len(),
+        changes.removed.len()
+    );
+    let branch = migration_branch();
+    commit_to_branch(&git_repo, &mut index, &message, &branch)?;
+    println!("Committed the migration to {}", branch);
+    Ok(())
+}
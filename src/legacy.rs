@@ -0,0 +1,183 @@
+use anyhow::{anyhow, bail, Result};
+use glob::glob;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::archive::HwdbArchive;
+use crate::config::{set_once, Config};
+use crate::database::validate_version;
+use crate::git::commit_to_branch;
+use crate::index::DatabaseIndex;
+use crate::support_matrix::{SupportMatrix, SUPPORT_MATRIX_FILE};
+use crate::vendors::{Vendors, VENDORS_FILE};
+use crate::workflow::{write_coverage_stats, COVERAGE_STATS_FILE};
+use crate::workspace::{workspace, LEGACY_ARCHIVE_DIR};
+
+/// Legacy versions when the config file names none: resctl-bench 2.1
+/// doesn't generate hwdb files
+static DEFAULT_LEGACY_VERSIONS: &[&str] = &["2.1"];
+
+/// resctl-bench versions whose results are kept in the database, and
+/// compared with those of the newer versions, but no longer merged into
+/// the hwdb
+#[derive(Debug, Clone)]
+pub struct LegacyVersions {
+    versions: Vec<String>,
+}
+
+impl Default for LegacyVersions {
+    fn default() -> Self {
+        LegacyVersions {
+            versions: DEFAULT_LEGACY_VERSIONS.iter().map(|v| v.to_string()).collect(),
+        }
+    }
+}
+
+impl LegacyVersions {
+    /// Reads the legacy versions of the [config] section of the config
+    /// file, the default ones if it names none
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let Some(versions) = &config.legacy_versions else {
+            return Ok(LegacyVersions::default());
+        };
+        for version in versions {
+            validate_version(version).map_err(|e| anyhow!("Invalid legacy version: {}", e))?;
+        }
+        Ok(LegacyVersions { versions: versions.clone() })
+    }
+
+    pub fn contains(&self, version: &str) -> bool {
+        self.versions.iter().any(|v| v == version)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.versions.iter().map(String::as_str)
+    }
+}
+
+static LEGACY_VERSIONS: OnceLock<LegacyVersions> = OnceLock::new();
+
+/// Sets the legacy versions of the database.
+pub fn set_legacy_versions(versions: LegacyVersions) -> Result<()> {
+    set_once(&LEGACY_VERSIONS, versions, "legacy versions")
+}
+
+/// Returns the legacy versions of the database, the default ones if none
+/// were set
+pub fn legacy_versions() -> &'static LegacyVersions {
+    LEGACY_VERSIONS.get_or_init(LegacyVersions::default)
+}
+
+/// A legacy tree moved out of the database
+#[derive(Debug)]
+pub struct VersionArchival {
+    pub version: String,
+    pub models: usize,
+    /// Files of the tree, with where they were moved to
+    pub moved: Vec<(PathBuf, PathBuf)>,
+    /// Documents and statistics regenerated without the tree
+    pub regenerated: Vec<PathBuf>,
+}
+
+impl VersionArchival {
+    /// Branch the archival is committed to, for review
+    pub fn branch(&self) -> String {
+        format!("iocost-bot/archive-{}", self.version)
+    }
+
+    /// Commits the moves and the regenerated support matrix to the
+    /// archival branch and returns the branch
+    pub fn commit(&self) -> Result<String> {
+        let git_repo = workspace().repository()?;
+        let mut index = git_repo.index()?;
+        for (from, to) in &self.moved {
+            index.remove_path(from)?;
+            index.add_path(to)?;
+        }
+        // The coverage statistics are a workflow artifact, not part of
+        // the database
+        for path in self.regenerated.iter().filter(|p| !p.ends_with(COVERAGE_STATS_FILE)) {
+            index.add_path(path)?;
+        }
+        index.write()?;
+        let message = format!(
+            "Archive the {} results\n\nThe {} models of {} were moved to {}, which the merges don't scan.",
+            self.version,
+            self.models,
+            self.version,
+            Path::new(LEGACY_ARCHIVE_DIR).join(&self.version).display()
+        );
+        let branch = self.branch();
+        commit_to_branch(&git_repo, &mut index, &message, &branch)?;
+        Ok(branch)
+    }
+}
+
+/// Moves the tree of the legacy `version` of the database in
+/// `database_dir` to the archive, then regenerates the support matrix
+/// and the coverage statistics without it
+pub fn archive_version(database_dir: &Path, version: &str) -> Result<VersionArchival> {
+    validate_version(version)?;
+    if !legacy_versions().contains(version) {
+        bail!("{} isn't a legacy version, add it to legacy_versions in the config file first", version);
+    }
+    let tree = database_dir.join(version);
+    if !tree.is_dir() {
+        bail!("No {} tree in {}", version, database_dir.display());
+    }
+    let target = Path::new(LEGACY_ARCHIVE_DIR).join(version);
+    if target.exists() {
+        bail!("{} is already archived in {}", version, target.display());
+    }
+    let models = fs::read_dir(&tree)?.flatten().filter(|e| e.path().is_dir()).count();
+    let mut moved = vec![];
+    for path in glob(&tree.join("**").join("*").to_string_lossy())?.flatten().filter(|p| p.is_file()) {
+        let to = target.join(path.strip_prefix(&tree)?);
+        moved.push((path, to));
+    }
+    fs::create_dir_all(LEGACY_ARCHIVE_DIR)?;
+    fs::rename(&tree, &target)?;
+
+    let mut regenerated = vec![];
+    // The legacy versions have no hwdb entry, those of the last merge
+    // run are still current
+    let candidates = HwdbArchive::new(workspace().hwdb_archive_dir())
+        .latest_manifest()
+        .map(|m| m.candidates)
+        .unwrap_or_default();
+    let index = DatabaseIndex::load(database_dir)?;
+    let vendors = Vendors::load(Path::new(VENDORS_FILE))?;
+    if SupportMatrix::build(&index, &vendors, &candidates)?.write_in(database_dir)? {
+        regenerated.push(database_dir.join(SUPPORT_MATRIX_FILE));
+    }
+    write_coverage_stats(database_dir)?;
+    regenerated.push(PathBuf::from(COVERAGE_STATS_FILE));
+    Ok(VersionArchival {
+        version: version.to_string(),
+        models,
+        moved,
+        regenerated,
+    })
+}
+
+/// Runs the `archive-version` command: archives the legacy `version` of
+/// the database in `database_dir` and, with `commit`, commits it to the
+/// archival branch
+pub fn run_archive_version(database_dir: &Path, version: &str, commit: bool) -> Result<()> {
+    let archival = archive_version(database_dir, version)?;
+    println!(
+        "Moved the {} models of {} to {}",
+        archival.models,
+        version,
+        Path::new(LEGACY_ARCHIVE_DIR).join(version).display()
+    );
+    for path in &archival.regenerated {
+        println!("  M {}", path.display());
+    }
+    if commit {
+        let branch = archival.commit()?;
+        println!("Committed the archival to {}", branch);
+    }
+    Ok(())
+}
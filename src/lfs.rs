@@ -0,0 +1,363 @@
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::config::{set_once, LfsConfig};
+use crate::error::Error;
+use crate::http::{block_on, download_client};
+use crate::workspace::workspace;
+
+/// First line of the LFS pointer files
+static POINTER_VERSION: &str = "version https://git-lfs.github.com/spec/v1";
+
+/// Local store of the LFS objects, in the repository the tools run from
+static OBJECTS_DIR: &str = ".git/lfs/objects";
+
+/// Git attributes storing the result files of the database with LFS
+static RESULT_ATTRIBUTES: &str = "result-*.json.gz filter=lfs diff=lfs merge=lfs -text";
+
+/// Pointer files are small, anything larger is the contents itself
+const MAX_POINTER_SIZE: u64 = 1024;
+
+static LFS_MEDIA_TYPE: &str = "application/vnd.git-lfs+json";
+
+/// Git LFS storage of the result files
+#[derive(Debug, Default, Clone)]
+pub struct LfsSettings {
+    /// Whether new results are stored with LFS. Results already stored
+    /// with LFS are read either way.
+    pub enabled: bool,
+    /// LFS server of the database repository, by default the one of
+    /// the Github repository in $GITHUB_REPOSITORY or of the origin
+    /// remote
+    pub url: Option<String>,
+}
+
+impl LfsSettings {
+    /// Creates the settings of the [lfs] section of the config file,
+    /// which enables LFS storage
+    pub fn from_config(config: &LfsConfig) -> Self {
+        LfsSettings {
+            enabled: true,
+            url: config.url.clone(),
+        }
+    }
+
+    /// Returns the LFS server to download the objects from
+    fn endpoint(&self) -> Option<String> {
+        if let Some(url) = &self.url {
+            return Some(url.trim_end_matches('/').to_string());
+        }
+        if let Ok(repo) = std::env::var("GITHUB_REPOSITORY") {
+            return Some(endpoint_for(&repo));
+        }
+        let git_repo = workspace().repository().ok()?;
+        let origin = git_repo.find_remote("origin").ok()?;
+        let url = origin.url()?.trim_end_matches('/').trim_end_matches(".git");
+        url.starts_with("https://").then(|| format!("{}.git/info/lfs", url))
+    }
+}
+
+static LFS_SETTINGS: OnceLock<LfsSettings> = OnceLock::new();
+
+/// Sets the LFS settings.
+pub fn set_lfs_settings(settings: LfsSettings) -> Result<()> {
+    set_once(&LFS_SETTINGS, settings, "LFS settings")
+}
+
+/// Returns the LFS settings, with LFS storage disabled if none were set
+pub fn lfs_settings() -> &'static LfsSettings {
+    LFS_SETTINGS.get_or_init(LfsSettings::default)
+}
+
+/// Returns the LFS server of the Github repository `repo` (owner/name)
+pub fn endpoint_for(repo: &str) -> String {
+    format!("https://github.com/{}.git/info/lfs", repo)
+}
+
+/// Pointer to an LFS object, committed in place of its contents
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LfsPointer {
+    pub oid: String,
+    pub size: u64,
+}
+
+impl fmt::Display for LfsPointer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}\noid sha256:{}\nsize {}\n", POINTER_VERSION, self.oid, self.size)
+    }
+}
+
+impl LfsPointer {
+    pub fn for_contents(contents: &[u8]) -> Self {
+        LfsPointer {
+            oid: format!("{:x}", Sha256::digest(contents)),
+            size: contents.len() as u64,
+        }
+    }
+
+    /// Parses the pointer file `contents`
+    pub fn parse(contents: &str) -> Option<Self> {
+        let mut lines = contents.lines();
+        if lines.next()? != POINTER_VERSION {
+            return None;
+        }
+        let (mut oid, mut size) = (None, None);
+        for line in lines {
+            match line.split_once(' ') {
+                Some(("oid", value)) => oid = value.strip_prefix("sha256:").map(str::to_string),
+                Some(("size", value)) => size = value.parse().ok(),
+                _ => {}
+            }
+        }
+        Some(LfsPointer { oid: oid?, size: size? })
+    }
+
+    /// Reads the pointer in the file at `path`, if it is one
+    pub fn read(path: &Path) -> Option<Self> {
+        let mut contents = String::new();
+        fs::File::open(path)
+            .ok()?
+            .take(MAX_POINTER_SIZE)
+            .read_to_string(&mut contents)
+            .ok()?;
+        Self::parse(&contents)
+    }
+
+    /// Path of the object in the local store
+    pub fn object_path(&self) -> PathBuf {
+        PathBuf::from(OBJECTS_DIR)
+            .join(&self.oid[..2])
+            .join(&self.oid[2..4])
+            .join(&self.oid)
+    }
+}
+
+/// Moves the contents of the result file at `path` to the local LFS
+/// store and replaces it with a pointer. The object has to be uploaded
+/// to the LFS server when pushing, which git does if LFS is installed.
+pub fn convert_to_pointer(path: &Path) -> Result<LfsPointer> {
+    let contents = fs::read(path)?;
+    let pointer = LfsPointer::for_contents(&contents);
+    let object = pointer.object_path();
+    fs::create_dir_all(object.parent().unwrap())?;
+    fs::write(&object, &contents)?;
+    fs::write(path, pointer.to_string())?;
+    Ok(pointer)
+}
+
+/// Adds the attributes storing the results with LFS to the
+/// .gitattributes file of `database_dir` if needed, and returns its path
+pub fn ensure_gitattributes(database_dir: &Path) -> Result<PathBuf> {
+    let path = database_dir.join(".gitattributes");
+    let mut contents = fs::read_to_string(&path).unwrap_or_default();
+    if !contents.lines().any(|l| l.trim() == RESULT_ATTRIBUTES) {
+        if !contents.is_empty() && !contents.ends_with('\n') {
+            contents.push('\n');
+        }
+        contents.push_str(RESULT_ATTRIBUTES);
+        contents.push('\n');
+        fs::write(&path, contents)?;
+    }
+    Ok(path)
+}
+
+/// Returns the path of the contents of the result file at `path`: the
+/// file itself or, for an LFS pointer, the object in the local store,
+/// downloaded from the LFS server if it isn't there yet
+pub fn resolve(path: &Path) -> crate::error::Result<PathBuf> {
+    let Some(pointer) = LfsPointer::read(path) else {
+        return Ok(path.to_path_buf());
+    };
+    let object = pointer.object_path();
+    if object.exists() {
+        return Ok(object);
+    }
+    let lfs_error = |e: anyhow::Error| Error::Lfs {
+        file: path.to_path_buf(),
+        reason: format!("{:#}", e),
+    };
+    let endpoint = lfs_settings()
+        .endpoint()
+        .ok_or_else(|| lfs_error(anyhow!("No LFS server known for the repository")))?;
+    let client = LfsClient::new(endpoint, std::env::var("GITHUB_TOKEN").ok());
+    block_on(client.download(std::slice::from_ref(&pointer))).map_err(lfs_error)?;
+    Ok(object)
+}
+
+/// Returns the LFS pointers of the local objects among the files changed
+/// by the last commit of `branch`
+fn branch_pointers(branch: &str) -> Result<Vec<LfsPointer>> {
+    let git_repo = workspace().repository()?;
+    let commit = git_repo
+        .find_branch(branch, git2::BranchType::Local)?
+        .get()
+        .peel_to_commit()?;
+    let parent_tree = commit.parent(0).ok().map(|p| p.tree()).transpose()?;
+    let diff = git_repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&commit.tree()?), None)?;
+    let mut pointers = vec![];
+    for delta in diff.deltas() {
+        let id = delta.new_file().id();
+        if id.is_zero() {
+            continue;
+        }
+        let blob = git_repo.find_blob(id)?;
+        if let Some(pointer) = std::str::from_utf8(blob.content()).ok().and_then(LfsPointer::parse) {
+            if pointer.object_path().exists() {
+                pointers.push(pointer);
+            }
+        }
+    }
+    Ok(pointers)
+}
+
+/// Uploads the LFS objects of the result files committed to `branch` to
+/// the LFS server of the Github repository `repo` (owner/name). Branches
+/// pushed with libgit2 need it, as the LFS git hooks don't run.
+pub async fn upload_branch_objects(repo: &str, branch: &str, token: &str) -> Result<()> {
+    let pointers = branch_pointers(branch)?;
+    if pointers.is_empty() {
+        return Ok(());
+    }
+    let endpoint = lfs_settings().url.clone().unwrap_or_else(|| endpoint_for(repo));
+    LfsClient::new(endpoint, Some(token.to_string())).upload(&pointers).await
+}
+
+#[derive(Debug, Serialize)]
+struct BatchRequest<'a> {
+    operation: &'a str,
+    transfers: [&'a str; 1],
+    objects: &'a [LfsPointer],
+}
+
+#[derive(Debug, Deserialize)]
+struct BatchResponse {
+    objects: Vec<BatchObject>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BatchObject {
+    oid: String,
+    #[serde(default)]
+    actions: HashMap<String, BatchAction>,
+    error: Option<BatchError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BatchAction {
+    href: String,
+    #[serde(default)]
+    header: HashMap<String, String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BatchError {
+    code: u16,
+    message: String,
+}
+
+/// Client of the batch API of an LFS server
+pub struct LfsClient {
+    endpoint: String,
+    token: Option<String>,
+}
+
+impl LfsClient {
+    pub fn new(endpoint: String, token: Option<String>) -> Self {
+        LfsClient { endpoint, token }
+    }
+
+    async fn batch(&self, operation: &str, objects: &[LfsPointer]) -> Result<Vec<BatchObject>> {
+        let mut request = download_client()?
+            .post(format!("{}/objects/batch", self.endpoint))
+            .header(reqwest::header::ACCEPT, LFS_MEDIA_TYPE)
+            .header(reqwest::header::CONTENT_TYPE, LFS_MEDIA_TYPE)
+            .body(serde_json::to_vec(&BatchRequest { operation, transfers: ["basic"], objects })?);
+        if let Some(token) = &self.token {
+            request = request.basic_auth("x-access-token", Some(token));
+        }
+        let body = request
+            .send()
+            .await?
+            .error_for_status()
+            .with_context(|| format!("LFS {} batch request to {} failed", operation, self.endpoint))?
+            .bytes()
+            .await?;
+        let response: BatchResponse = serde_json::from_slice(&body)?;
+        for object in &response.objects {
+            if let Some(error) = &object.error {
+                bail!("LFS object {}: {} ({})", object.oid, error.message, error.code);
+            }
+        }
+        Ok(response.objects)
+    }
+
+    fn action_request(
+        method: reqwest::Method,
+        action: &BatchAction,
+    ) -> Result<reqwest::RequestBuilder> {
+        let mut request = download_client()?.request(method, &action.href);
+        for (name, value) in &action.header {
+            request = request.header(name, value);
+        }
+        Ok(request)
+    }
+
+    /// Downloads the `objects` to the local store, checking their
+    /// contents against their oid
+    pub async fn download(&self, objects: &[LfsPointer]) -> Result<()> {
+        for object in self.batch("download", objects).await? {
+            let Some(action) = object.actions.get("download") else {
+                bail!("LFS server has no download for object {}", object.oid);
+            };
+            let contents = Self::action_request(reqwest::Method::GET, action)?
+                .send()
+                .await?
+                .error_for_status()?
+                .bytes()
+                .await?;
+            let pointer = LfsPointer::for_contents(&contents);
+            if pointer.oid != object.oid {
+                bail!("LFS object {} was downloaded with oid {}", object.oid, pointer.oid);
+            }
+            let path = pointer.object_path();
+            fs::create_dir_all(path.parent().unwrap())?;
+            fs::write(path, &contents)?;
+        }
+        Ok(())
+    }
+
+    /// Uploads the `objects` of the local store the server doesn't have
+    /// yet
+    pub async fn upload(&self, objects: &[LfsPointer]) -> Result<()> {
+        for object in self.batch("upload", objects).await? {
+            // Objects the server already has come without actions.
+            let Some(action) = object.actions.get("upload") else {
+                continue;
+            };
+            let Some(pointer) = objects.iter().find(|p| p.oid == object.oid) else {
+                bail!("LFS server asked for unknown object {}", object.oid);
+            };
+            Self::action_request(reqwest::Method::PUT, action)?
+                .body(fs::read(pointer.object_path())?)
+                .send()
+                .await?
+                .error_for_status()?;
+            if let Some(verify) = object.actions.get("verify") {
+                Self::action_request(reqwest::Method::POST, verify)?
+                    .header(reqwest::header::CONTENT_TYPE, LFS_MEDIA_TYPE)
+                    .body(serde_json::to_vec(pointer)?)
+                    .send()
+                    .await?
+                    .error_for_status()?;
+            }
+        }
+        Ok(())
+    }
+}
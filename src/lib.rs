@@ -0,0 +1,79 @@
+//! Shared code for the iocost-benchmarks tooling: merging and validating
+//! resctl-bench results, the Github workflow pipeline and the services
+//! built on top of it. The `import-results`, `merge-results` and
+//! `iocost-ci` binaries are thin frontends over this library.
+
+pub mod actions;
+pub mod archive;
+pub mod bench_runner;
+pub mod bot_commands;
+pub mod catalog;
+pub mod checkpoint;
+pub mod commits;
+pub mod common;
+pub mod config;
+pub mod consent;
+pub mod database;
+pub mod diagnosis;
+pub mod digest;
+pub mod doctor;
+pub mod embargo;
+pub mod error;
+pub mod error_report;
+pub mod exit;
+pub mod export;
+pub mod feed;
+pub mod feedback;
+pub mod formatter;
+pub mod fw_compare;
+pub mod fw_policy;
+pub mod garbage;
+pub mod git;
+pub mod github;
+pub mod high_level;
+pub mod history;
+pub mod http;
+pub mod hwdb;
+pub mod incremental;
+pub mod index;
+pub mod issue_form;
+pub mod labels;
+pub mod layout;
+pub mod legacy;
+pub mod lfs;
+pub mod limits;
+pub mod manifest;
+pub mod merge_window;
+pub mod metrics;
+pub mod notifier;
+pub mod output;
+pub mod plot;
+pub mod policy;
+pub mod progress;
+pub mod readme;
+pub mod resources;
+pub mod result;
+pub mod retract;
+pub mod retry;
+pub mod review;
+pub mod rules;
+pub mod scrub;
+pub mod self_test;
+pub mod serve;
+pub mod setup;
+pub mod shard;
+pub mod show;
+pub mod signatures;
+pub mod signoff;
+pub mod storage;
+pub mod support_matrix;
+pub mod templates;
+pub mod url_extract;
+pub mod url_ledger;
+pub mod vendor_overrides;
+pub mod vendors;
+pub mod version_compare;
+pub mod watch;
+pub mod webhook;
+pub mod workflow;
+pub mod workspace;
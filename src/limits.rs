@@ -0,0 +1,107 @@
+use anyhow::Result;
+use std::sync::OnceLock;
+
+use crate::config::{set_once, LimitsConfig};
+use crate::http::download_request;
+
+/// Default maximum number of result URLs in a submission
+const DEFAULT_MAX_URLS: usize = 50;
+/// Default maximum total size of the result files of a submission
+const DEFAULT_MAX_TOTAL_BYTES: u64 = 1 << 30;
+
+/// Limits of a submission, which protect the runner from running out of
+/// disk and from abuse
+#[derive(Debug, Clone, Copy)]
+pub struct SubmissionLimits {
+    pub max_urls: usize,
+    pub max_total_bytes: u64,
+    /// Submissions of a user who is not a maintainer processed per day,
+    /// the next ones are queued until the day after
+    pub max_submissions_per_day: Option<usize>,
+}
+
+impl Default for SubmissionLimits {
+    fn default() -> Self {
+        SubmissionLimits {
+            max_urls: DEFAULT_MAX_URLS,
+            max_total_bytes: DEFAULT_MAX_TOTAL_BYTES,
+            max_submissions_per_day: None,
+        }
+    }
+}
+
+/// A submission over the limits
+#[derive(Debug)]
+pub struct LimitsExceeded {
+    pub urls: usize,
+    /// Total size advertised by the servers of the URLs, not counting
+    /// those which didn't tell
+    pub total_bytes: u64,
+}
+
+impl SubmissionLimits {
+    /// Creates the limits of the [limits] section of the config file,
+    /// using the default ones for the missing settings
+    pub fn from_config(config: &LimitsConfig) -> Self {
+        let default = SubmissionLimits::default();
+        SubmissionLimits {
+            max_urls: config.max_urls.unwrap_or(default.max_urls),
+            max_total_bytes: config.max_total_bytes.unwrap_or(default.max_total_bytes),
+            max_submissions_per_day: config.max_submissions_per_day,
+        }
+    }
+
+    /// Checks the submission of `urls` against the limits before
+    /// downloading them, from the sizes their servers advertise. URLs
+    /// whose size is unknown are only accounted for when downloaded.
+    pub async fn check(&self, urls: &[String]) -> Option<LimitsExceeded> {
+        let mut total_bytes = 0;
+        if urls.len() <= self.max_urls {
+            for url in urls {
+                total_bytes += advertised_size(url).await.unwrap_or(0);
+            }
+        }
+        if urls.len() > self.max_urls || total_bytes > self.max_total_bytes {
+            return Some(LimitsExceeded { urls: urls.len(), total_bytes });
+        }
+        None
+    }
+
+    /// Checks a result file of `bytes` uploaded directly against the
+    /// limits
+    pub fn check_upload(&self, bytes: u64) -> Option<LimitsExceeded> {
+        (bytes > self.max_total_bytes).then_some(LimitsExceeded { urls: 1, total_bytes: bytes })
+    }
+}
+
+/// Returns the Content-Length of `url` from a HEAD request, if the
+/// server sends one
+async fn advertised_size(url: &str) -> Option<u64> {
+    let response = download_request(reqwest::Method::HEAD, url)
+        .ok()?
+        .send()
+        .await
+        .ok()?
+        .error_for_status()
+        .ok()?;
+    response
+        .headers()
+        .get(reqwest::header::CONTENT_LENGTH)?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()
+}
+
+static SUBMISSION_LIMITS: OnceLock<SubmissionLimits> = OnceLock::new();
+
+/// Sets the limits of the submissions.
+pub fn set_submission_limits(limits: SubmissionLimits) -> Result<()> {
+    set_once(&SUBMISSION_LIMITS, limits, "submission limits")
+}
+
+/// Returns the limits of the submissions, the default ones if none were
+/// set
+pub fn submission_limits() -> &'static SubmissionLimits {
+    SUBMISSION_LIMITS.get_or_init(SubmissionLimits::default)
+}
@@ -0,0 +1,101 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::common::ExcludedResult;
+use crate::hwdb::{HwdbCandidate, HwdbFallback};
+
+/// File the manifest of a merge run is written to
+pub static MANIFEST_FILE: &str = "artifacts-manifest.json";
+
+/// Kinds of files produced by a merge run
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ArtifactKind {
+    Pdf,
+    HwdbInput,
+    Hwdb,
+}
+
+/// A single generated file, with enough information for downstream
+/// automation to verify it
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Artifact {
+    pub kind: ArtifactKind,
+    pub path: PathBuf,
+    pub sha256: String,
+    pub size: u64,
+    /// Drive model the artifact was generated for, if model-specific
+    pub model_name: Option<String>,
+    /// resctl-bench version (major.minor) used for the artifact, if any
+    pub version: Option<String>,
+}
+
+impl Artifact {
+    /// Creates an Artifact for the file in `path`, computing its
+    /// checksum and size
+    pub fn new(
+        kind: ArtifactKind,
+        path: &Path,
+        model_name: Option<&str>,
+        version: Option<&str>,
+    ) -> Result<Self> {
+        let contents = fs::read(path)?;
+        Ok(Artifact {
+            kind,
+            path: path.to_path_buf(),
+            sha256: format!("{:x}", Sha256::digest(&contents)),
+            size: contents.len() as u64,
+            model_name: model_name.map(str::to_string),
+            version: version.map(str::to_string),
+        })
+    }
+}
+
+/// List of every artifact generated by a merge run
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    /// Commit of the database repository the artifacts were built from
+    pub commit: Option<String>,
+    pub generated: String,
+    pub artifacts: Vec<Artifact>,
+    /// Results the maintainers left out of the merges
+    pub excluded: Vec<ExcludedResult>,
+    /// Hwdb inputs the final hwdb entries were selected from, which lets
+    /// later runs reuse those of the models they don't re-merge
+    #[serde(default)]
+    pub candidates: Vec<HwdbCandidate>,
+    /// Models whose best candidate had no usable hwdb input, with the
+    /// candidate used instead
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hwdb_fallbacks: Vec<HwdbFallback>,
+}
+
+impl Manifest {
+    pub fn new(commit: Option<String>) -> Self {
+        Manifest {
+            commit,
+            generated: chrono::Utc::now().to_rfc3339(),
+            artifacts: vec![],
+            excluded: vec![],
+            candidates: vec![],
+            hwdb_fallbacks: vec![],
+        }
+    }
+
+    pub fn push(&mut self, artifact: Artifact) {
+        self.artifacts.push(artifact);
+    }
+
+    /// Writes the manifest as json in `path`, with the artifacts and
+    /// exclusions and candidates sorted by path so that the output is stable across runs
+    pub fn write_to(&mut self, path: &Path) -> Result<()> {
+        self.artifacts.sort_by(|a, b| a.path.cmp(&b.path));
+        self.excluded.sort_by(|a, b| a.path.cmp(&b.path));
+        self.candidates.sort_by(|a, b| a.hwdb_input.cmp(&b.hwdb_input));
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
@@ -1,36 +1,228 @@
 use anyhow::Result;
+use chrono::NaiveDate;
+use clap::Parser;
 use dashmap::DashMap;
 use glob::glob;
+use indicatif::ParallelProgressIterator;
 use rayon::prelude::*;
-use std::io::Write;
-use std::{fs, path::PathBuf};
+use std::collections::BTreeMap;
+use std::process::ExitCode;
+use std::sync::Mutex;
+use std::path::{absolute, Path, PathBuf};
 
-use crate::common::BenchMerge;
+use iocost_benchmarks::actions::ContextPayload;
+use iocost_benchmarks::archive::HwdbArchive;
+use iocost_benchmarks::common::BenchMerge;
+use iocost_benchmarks::config::TomlData;
+use iocost_benchmarks::database::{Database, MERGED_RESULTS_DIR};
+use iocost_benchmarks::error::Error;
+use iocost_benchmarks::error_report::{ErrorEntry, Stage, ERROR_REPORT};
+use iocost_benchmarks::exit::{exit_code, EnvironmentError, ExitStatus, EXIT_CODES_HELP};
+use iocost_benchmarks::feed::{write_feed, FEED_FILE};
+use iocost_benchmarks::fw_policy::{set_fw_merge_policies, FwMergePolicies};
+use iocost_benchmarks::git::head_commit;
+use iocost_benchmarks::hwdb::{has_no_entry, write_hwdb_file, HwdbCandidate, HWDB_FILE};
+use iocost_benchmarks::incremental::IncrementalMerge;
+use iocost_benchmarks::index::DatabaseIndex;
+use iocost_benchmarks::labels::{issue_settings, set_issue_settings, IssueSettings};
+use iocost_benchmarks::legacy::{legacy_versions, set_legacy_versions, LegacyVersions};
+use iocost_benchmarks::lfs::{set_lfs_settings, LfsSettings};
+use iocost_benchmarks::storage::{
+    object_storage, object_storage_from_config, set_object_storage, sync_from_object_storage,
+};
+use iocost_benchmarks::manifest::{Artifact, ArtifactKind, Manifest, MANIFEST_FILE};
+use iocost_benchmarks::merge_window::{set_merge_window, Cutoff, MergeWindow};
+use iocost_benchmarks::metrics::push_metrics;
+use iocost_benchmarks::notifier::{Notifier, NotifyEvent};
+use iocost_benchmarks::progress::{bar, log, VerbosityArgs};
+use iocost_benchmarks::readme::ModelReadme;
+use iocost_benchmarks::resources::{PipelineStage, RESOURCES};
+use iocost_benchmarks::retry::{set_retry_settings, RetrySettings};
+use iocost_benchmarks::shard::{Shard, ShardSummary, SHARD_SUMMARY_FILE};
+use iocost_benchmarks::support_matrix::{SupportMatrix, SUPPORT_MATRIX_FILE};
+use iocost_benchmarks::vendor_overrides::{VendorOverrides, VENDOR_OVERRIDES_DIR};
+use iocost_benchmarks::vendors::{Vendors, VENDORS_FILE};
+use iocost_benchmarks::version_compare::{VersionComparison, VersionReport, VERSION_REPORT_FILE};
+use iocost_benchmarks::workspace::{
+    set_workspace, workspace, Workspace, DATABASE_DIR, HWDB_INPUTS_DIR, PDFS_DIR,
+};
 
-mod common;
+/// Database repository linked from the feed outside of Github workflows
+static DEFAULT_REPO: &str = "iocost-benchmark/iocost-benchmarks";
 
-/// Writes a hwdb header in `hwdb_file` containing data information and
-/// a reference to `commit_id`
-fn write_hwdb_file_header(hwdb_file: &mut fs::File, commit_id: Option<String>) -> Result<()> {
-    let mut hwdb_text = format!("# This file was auto-generated on {}.\n",
-        chrono::Utc::now().to_rfc2822());
-    if let Some(id) = commit_id {
-        hwdb_text.push_str(&format!(r#"# From the following commit:
-# https://github.com/iocost-benchmark/iocost-benchmarks/commit/{}
-"#, id));
-    }
-    hwdb_text.push_str(r#"#
-# Match key format:
-# block:<devpath>:name:<model name>:fwrev:<firmware revision>:
+#[derive(Parser, Debug)]
+#[command(version, about, after_help = EXIT_CODES_HELP)]
+/// Merges the results in the database and generates the pdfs and the
+/// final hwdb file
+struct Cli {
+    /// Path of the toml config file to load
+    #[arg(short, long, value_name = "FILE")]
+    config_file: Option<String>,
 
-"#);
-    writeln!(hwdb_file, "{}", hwdb_text)?;
-    Ok(())
+    /// Only merge the results of the last N months, overriding the
+    /// default window of the config file
+    #[arg(long, value_name = "N", conflicts_with = "since")]
+    max_age_months: Option<u32>,
+
+    /// Only merge the results since DATE (YYYY-MM-DD), overriding the
+    /// default window of the config file
+    #[arg(long, value_name = "DATE")]
+    since: Option<NaiveDate>,
+
+    /// Only merge the models of shard i of N (e.g. 2/4) and write their
+    /// hwdb candidates to shard-summary.json instead of generating the
+    /// final hwdb file
+    #[arg(long, value_name = "i/N")]
+    shard: Option<Shard>,
+
+    /// Generate the final hwdb file from the outputs of every shard of a
+    /// merge, given as the directories of their artifacts
+    #[arg(long, value_name = "DIR", num_args = 1.., conflicts_with = "shard")]
+    combine: Vec<PathBuf>,
+
+    /// Merge every model even on push and pull request runs, which
+    /// otherwise only re-merge the models changed by their commits
+    #[arg(long)]
+    full: bool,
+
+    /// Only print the firmware revisions of the models, with their
+    /// results, and which would get a hwdb section of their own under the
+    /// fwmerge policy, estimated from the result files without merging
+    #[arg(long, conflicts_with = "combine")]
+    plan: bool,
+
+    /// Commit of the database referenced by the hwdb file and the
+    /// archive [default: the commit of the Github event, or HEAD of the
+    /// database repository outside of Github]
+    #[arg(long, value_name = "SHA")]
+    commit: Option<String>,
+
+    /// Directory to write the pdfs, hwdb files, merged results, manifest
+    /// and feed in [default: the workspace]. The outputs are archived in
+    /// the hwdb-archive directory of the workspace, to be committed to
+    /// the database repository.
+    #[arg(short, long, value_name = "DIR")]
+    output_dir: Option<PathBuf>,
+
+    /// Root of the checkout of the database repository to work in
+    /// [default: the current directory]
+    #[arg(long, value_name = "DIR", env = "IOCOST_WORKSPACE")]
+    workspace: Option<PathBuf>,
+
+    #[command(flatten)]
+    verbosity: VerbosityArgs,
 }
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> ExitCode {
+    exit_code(run(Cli::parse()).await)
+}
+
+async fn run(mut args: Cli) -> Result<ExitStatus> {
+    args.verbosity.apply();
+    let config = match &args.config_file {
+        Some(path) => Some(TomlData::load(path).map_err(|e| EnvironmentError(format!("{:#}", e)))?),
+        None => None,
+    };
+    if let Some(root) = &args.workspace {
+        // The shard artifacts and outputs are named relative to the
+        // current directory
+        args.combine = args.combine.iter().map(absolute).collect::<std::io::Result<_>>()?;
+        args.output_dir = args.output_dir.as_deref().map(absolute).transpose()?;
+        set_workspace(Workspace::enter(root).map_err(|e| EnvironmentError(format!("{:#}", e)))?)?;
+    }
+    let mut window = match config.as_ref().and_then(|c| c.merge.as_ref()) {
+        Some(merge) => MergeWindow::from_config(merge).map_err(|e| EnvironmentError(format!("{:#}", e)))?,
+        None => MergeWindow::default(),
+    };
+    if let Some(months) = args.max_age_months {
+        window = window.with_default(Cutoff::MaxAgeMonths(months));
+    } else if let Some(since) = args.since {
+        window = window.with_default(Cutoff::Since(since));
+    }
+    set_merge_window(window)?;
+    if let Some(fwmerge) = config.as_ref().and_then(|c| c.fwmerge.as_ref()) {
+        set_fw_merge_policies(
+            FwMergePolicies::from_config(fwmerge).map_err(|e| EnvironmentError(format!("{:#}", e)))?,
+        )?;
+    }
+    if let Some(config) = &config {
+        set_legacy_versions(
+            LegacyVersions::from_config(&config.config).map_err(|e| EnvironmentError(format!("{:#}", e)))?,
+        )?;
+    }
+    if let Some(retry) = config.as_ref().and_then(|c| c.retry.as_ref()) {
+        set_retry_settings(RetrySettings::from_config(retry))?;
+    }
+    if let Some(lfs) = config.as_ref().and_then(|c| c.lfs.as_ref()) {
+        set_lfs_settings(LfsSettings::from_config(lfs))?;
+    }
+    if let Some(issues) = config.as_ref().and_then(|c| c.issues.as_ref()) {
+        set_issue_settings(IssueSettings::from_config(issues))?;
+    }
+    if let Some(storage) = config.as_ref().and_then(|c| c.storage.as_ref()) {
+        if let Some(storage) =
+            object_storage_from_config(storage).map_err(|e| EnvironmentError(format!("{:#}", e)))?
+        {
+            set_object_storage(storage)?;
+        }
+    }
+    let notifier = Notifier::new(config.as_ref().and_then(|c| c.notifications.as_ref()));
+    let vendor_overrides = VendorOverrides::load(&PathBuf::from(VENDOR_OVERRIDES_DIR))?;
+    let vendors = Vendors::load(&PathBuf::from(VENDORS_FILE))?;
+    let outputs = &args.output_dir.clone().unwrap_or_default();
+    let database = Database::new(
+        config
+            .as_ref()
+            .and_then(|c| c.config.database_dir.clone())
+            .unwrap_or(DATABASE_DIR.to_string()),
+    )
+    .with_merged_dir(outputs.join(MERGED_RESULTS_DIR));
+    if !args.combine.is_empty() {
+        let (candidates, mut manifest) = ShardSummary::combine(&args.combine)?;
+        log(format!("Combined {} shards", args.combine.len()));
+        if args.commit.is_some() {
+            manifest.commit = args.commit.clone();
+        }
+        publish(candidates, manifest, &database, outputs, &vendor_overrides, &vendors, &notifier).await?;
+        push_metrics(config.as_ref().and_then(|c| c.metrics.as_ref())).await;
+        return Ok(ExitStatus::Success);
+    }
+    database.ensure_layout()?;
+    sync_from_object_storage(database.root())?;
+    if args.plan {
+        print_plan(&database, args.shard)?;
+        return Ok(ExitStatus::Success);
+    }
+
     let merges: DashMap<String, Vec<BenchMerge>> = DashMap::new();
+    let failures: Mutex<Vec<NotifyEvent>> = Mutex::new(vec![]);
+    // Errors caused by the environment rather than by a model's
+    // results, which would make every other merge fail too.
+    let fatal: Mutex<Option<Error>> = Mutex::new(None);
+    let context = match std::env::var("GITHUB_CONTEXT") {
+        Ok(context_str) => Some(ContextPayload::parse(&context_str, false)?),
+        _ => None
+    };
+    let github_id = match (&args.commit, &context) {
+        (Some(commit), _) => Some(commit.clone()),
+        (None, Some(context)) => context.sha.clone(),
+        // Outside of Github, refer to the checked out database
+        (None, None) => head_commit(database.root()).ok(),
+    };
+    let manifest = Mutex::new(Manifest::new(github_id.clone()));
+    // Shards and full runs merge every model, other runs only those
+    // changed by the commits of their push or pull request if any. The
+    // commits don't tell the changes of an object storage.
+    let incremental = match &context {
+        Some(context) if !args.full && args.shard.is_none() && object_storage().is_none() => {
+            IncrementalMerge::new(context, database.root(), &HwdbArchive::new(workspace().hwdb_archive_dir()))
+        }
+        _ => None,
+    };
+    if let Some(incremental) = &incremental {
+        log(format!("Only merging the {} models changed since the last run", incremental.changed_count()));
+    }
     // Merge result files (`resctl-bench merge`), generate pdfs and hwdb
     // files and keep the results in the `merges` hash table.
     // This expects the results to be laid out in a structure like:
@@ -42,83 +234,304 @@ async fn main() -> Result<()> {
     //     - <version_b>
     //     - ...
     //     - <version_n>
-    for version_dir in glob("database/*").unwrap().into_iter().flatten() {
+    for version_dir in glob(&database.root().join("*").to_string_lossy()).unwrap().flatten() {
         let version = version_dir.file_name().unwrap().to_str().unwrap();
-        if version == "2.1" {
-            println!("Ignoring 2.1 version, since it does not generate hwdb files.");
+        if legacy_versions().contains(version) {
+            log(format!("Ignoring legacy version {}", version));
             continue;
         }
-        let paths: Vec<PathBuf> = glob(&format!("database/{}/*", version))
+        let paths: Vec<PathBuf> = glob(&version_dir.join("*").to_string_lossy())
             .unwrap()
-            .into_iter()
             .flatten()
             .collect();
-        paths.par_iter().for_each(|model_dir: &PathBuf| {
+        let pb = bar(paths.len() as u64, &format!("Merging {}", version));
+        paths.par_iter().progress_with(pb.clone()).for_each(|model_dir: &PathBuf| {
             if model_dir.is_dir() {
                 let model_name = model_dir.file_name().unwrap().to_str().unwrap();
-                let merge = BenchMerge::merge(version.to_string(), model_name.to_string())
-                    .expect("Failed to merge");
-                merge
-                    .save_pdf_in(&PathBuf::from("pdfs"))
-                    .expect("Failed to save PDF");
-                merge
-                    .create_hwdb_in(&PathBuf::from("hwdb-inputs"))
-                    .expect("Failed to create a hwdb file");
+                if args.shard.is_some_and(|shard| !shard.contains(model_name))
+                    || incremental.as_ref().is_some_and(|i| !i.includes(version, model_name))
+                {
+                    return;
+                }
+                let merge = match BenchMerge::merge(&database, version.to_string(), model_name.to_string()) {
+                    Ok(m) => m,
+                    Err(e) if e.is_environment() => {
+                        fatal.lock().unwrap().get_or_insert(e);
+                        return;
+                    }
+                    Err(e) => {
+                        let e = anyhow::Error::from(e);
+                        eprintln!("Failed to merge {} ({}): {:#}", model_name, version, e);
+                        ERROR_REPORT.record(
+                            ErrorEntry::new(Stage::Merge, &e)
+                                .input(model_dir.display().to_string()),
+                        );
+                        failures.lock().unwrap().push(NotifyEvent::MergeFailed {
+                            model_name: model_name.to_string(),
+                            version: version.to_string(),
+                            error: format!("{:#}", e),
+                            contacts: vendors.contacts(model_name),
+                        });
+                        return;
+                    }
+                };
+                // A model whose pdf or hwdb input fails is still merged,
+                // another of its candidates may provide its hwdb entry.
+                let output_error = |what: &str, e: Error| {
+                    let e = anyhow::Error::from(e);
+                    eprintln!("Failed to generate the {} of {} ({}): {:#}", what, model_name, version, e);
+                    ERROR_REPORT.record(ErrorEntry::new(Stage::Output, &e).model(model_name, version));
+                };
+                let pdf = merge
+                    .save_pdf_in(&outputs.join(PDFS_DIR))
+                    .map_err(|e| output_error("pdf", e))
+                    .ok();
+                let hwdb = merge
+                    .create_hwdb_in(&outputs.join(HWDB_INPUTS_DIR))
+                    .unwrap_or_else(|e| {
+                        output_error("hwdb input", e);
+                        None
+                    });
+                let (pdf_url, _) = issue_settings().output_urls(model_name, version);
+                match ModelReadme::for_merge(&merge, model_dir, pdf_url).and_then(|r| r.write_in(model_dir)) {
+                    Ok(true) => log(format!("Updated the README of {} ({})", model_name, version)),
+                    Ok(false) => {}
+                    Err(e) => eprintln!("Failed to write the README of {} ({}): {:#}", model_name, version, e),
+                }
+                let mut manifest = manifest.lock().unwrap();
+                manifest.excluded.extend(merge.excluded.iter().cloned());
+                for (kind, path) in [(ArtifactKind::Pdf, pdf), (ArtifactKind::HwdbInput, hwdb)] {
+                    if let Some(path) = path {
+                        manifest.push(
+                            Artifact::new(kind, &path, Some(model_name), Some(version))
+                                .expect("Failed to add artifact to the manifest"),
+                        );
+                    }
+                }
                 merges
                     .entry(merge.model_name.clone())
                     .or_insert(vec![])
                     .push(merge);
             }
         });
+        pb.finish_and_clear();
+    }
+
+    if let Some(e) = fatal.into_inner().unwrap() {
+        return Err(e.into());
     }
 
-    println!("Generating final hwdb file...");
-    let github_id = match std::env::var("GITHUB_CONTEXT") {
-        Ok(context_str) => {
-            let context = json::parse(&context_str)?;
-            Some(context["sha"].to_string())
+    let failures = failures.into_inner().unwrap();
+    if !failures.is_empty() {
+        for failure in &failures {
+            notifier.notify(failure).await;
         }
-        _ => None
-    };
-    let mut hwdb_file =
-        fs::File::create("90-iocost-tune.hwdb").expect("Failed to create hwdb file");
-    write_hwdb_file_header(&mut hwdb_file, github_id)?;
-
-    let models: Vec<String> = merges.iter().map(|m| m.key().clone()).collect();
-    for model in models {
-        // To override the hwdb file that is selected, you need to set
-        // the variable with the name of the model with all dashes
-        // replaced with underscores to a value that is the preferred
-        // filename. For instance:
-        //
-        // OVERRIDE_BEST_HFS256GD9TNG_62A0A_2022_09_19UTC=iocost-tune-2.2-HFS256GD9TNG-62A0A-2022-09-19UTC.hwdb
-        let override_var = format!("OVERRIDE_BEST_{}", model.replace('-', "_"));
-
-        let alternatives = merges.get(&model).unwrap();
-        let alternatives = alternatives.value();
-
-        // If override is available, select it, otherwise select the
-        // merge with the highest number of data points.
-        let best = match std::env::var(&override_var) {
-            Err(std::env::VarError::NotPresent) => {
-                let merge = alternatives.iter().max_by_key(|x| x.data_points).unwrap();
-                let best = merge.build_descriptive_filename("hwdb", None);
-                println!("{:>2} datapoints:\t{}", merge.data_points, best);
-                best
-            }
-            Err(e) => panic!("Failed to interpret variable {}: {}", override_var, e),
-            Ok(best) => {
-                if !std::path::Path::exists(&PathBuf::from(&best)) {
-                    panic!("Failed to find override file: {}", best);
+        push_metrics(config.as_ref().and_then(|c| c.metrics.as_ref())).await;
+        eprintln!("Failed to merge {} models", failures.len());
+        return Ok(ExitStatus::PartialFailure);
+    }
+
+    // Compare the solutions of the models which still have results in
+    // a legacy tree, to tell when it can be retired.
+    let mut legacy: Vec<(String, String, String, PathBuf)> = merges
+        .iter()
+        .flat_map(|m| {
+            m.value()
+                .iter()
+                .filter_map(|b| {
+                    let legacy_version = VersionComparison::legacy_version_of(&database, &b.model_name)?;
+                    Some((b.model_name.clone(), legacy_version, b.version_str.clone(), b.path.clone()))
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    if !legacy.is_empty() {
+        legacy.sort();
+        let pb = bar(legacy.len() as u64, "Comparing with the legacy versions");
+        let comparisons: Vec<_> = legacy
+            .par_iter()
+            .progress_with(pb.clone())
+            .map(|(model_name, legacy_version, version, path)| {
+                let comparison = VersionComparison::compute(&database, model_name, legacy_version, version, path);
+                (model_name, legacy_version, comparison)
+            })
+            .collect();
+        pb.finish_and_clear();
+        let mut report = VersionReport::default();
+        for (model_name, legacy_version, comparison) in comparisons {
+            match comparison {
+                Ok(comparison) => report.comparisons.push(comparison),
+                Err(e) => {
+                    eprintln!("Failed to compare {} with its {} solutions: {:#}", model_name, legacy_version, e);
+                    report.failures.push((model_name.clone(), format!("{:#}", e)));
                 }
-                println!("override:\t{}", best);
-                best
             }
-        };
-        let best_hwdb = PathBuf::from("hwdb-inputs").join(best);
-        let contents = fs::read_to_string(best_hwdb).expect("Failed to read input hwdb file");
-        writeln!(hwdb_file, "{}", contents)?;
+        }
+        report.write_in(outputs)?;
+        log(format!("Wrote the comparisons with the legacy versions to {}", VERSION_REPORT_FILE));
+    }
+
+    let mut candidates: Vec<HwdbCandidate> = merges
+        .iter()
+        .flat_map(|m| m.value().iter().map(|b| b.hwdb_candidate()).collect::<Vec<_>>())
+        .collect();
+    let mut manifest = manifest.into_inner().unwrap();
+    if let Some(incremental) = &incremental {
+        let reused = incremental.reuse_unchanged(&outputs.join(HWDB_INPUTS_DIR), &mut manifest)?;
+        log(format!("Reused the hwdb inputs of {} unchanged models", reused.len()));
+        candidates.extend(reused);
+    }
+    if let Some(shard) = args.shard {
+        log(format!("Writing the summary of shard {} to {}...", shard, SHARD_SUMMARY_FILE));
+        ShardSummary { shard, candidates, manifest }.write_to(&outputs.join(SHARD_SUMMARY_FILE))?;
+        push_metrics(config.as_ref().and_then(|c| c.metrics.as_ref())).await;
+        return Ok(ExitStatus::Success);
+    }
+    publish(candidates, manifest, &database, outputs, &vendor_overrides, &vendors, &notifier).await?;
+    push_metrics(config.as_ref().and_then(|c| c.metrics.as_ref())).await;
+
+    Ok(ExitStatus::Success)
+}
+
+/// Generates the final hwdb file from the hwdb inputs of the merges,
+/// selecting the best `candidates` of each model, then writes the
+/// `manifest` of the run, archives its outputs, refreshes the support
+/// matrix of the `database` and writes the feed of the changes. The
+/// outputs are written in `outputs`.
+async fn publish(
+    candidates: Vec<HwdbCandidate>,
+    mut manifest: Manifest,
+    database: &Database,
+    outputs: &Path,
+    vendor_overrides: &VendorOverrides,
+    vendors: &Vendors,
+    notifier: &Notifier,
+) -> Result<()> {
+    let github_id = manifest.commit.clone();
+    let mut alternatives: BTreeMap<String, Vec<HwdbCandidate>> = BTreeMap::new();
+    for candidate in candidates {
+        alternatives.entry(candidate.model_name.clone()).or_default().push(candidate);
+    }
+
+    log("Generating final hwdb file...");
+    let hwdb_stage = RESOURCES.start(PipelineStage::Hwdb);
+    let fallbacks = write_hwdb_file(
+        &outputs.join(HWDB_FILE),
+        &outputs.join(HWDB_INPUTS_DIR),
+        &alternatives,
+        github_id.clone(),
+        vendor_overrides,
+    )?;
+    drop(hwdb_stage);
+    let models: Vec<String> = alternatives.keys().filter(|m| !has_no_entry(&fallbacks, m)).cloned().collect();
+
+    log(format!("Writing artifacts manifest to {}...", MANIFEST_FILE));
+    manifest.candidates = alternatives.into_values().flatten().collect();
+    manifest.hwdb_fallbacks = fallbacks;
+    manifest.push(Artifact::new(ArtifactKind::Hwdb, &outputs.join(HWDB_FILE), None, None)?);
+    manifest.write_to(&outputs.join(MANIFEST_FILE))?;
+
+    if database.root().exists() {
+        let index = DatabaseIndex::load(database.root())?;
+        // Only the candidates whose input made it to the hwdb file
+        let used: Vec<HwdbCandidate> = manifest
+            .candidates
+            .iter()
+            .filter(|c| {
+                !manifest.hwdb_fallbacks.iter().flat_map(|f| &f.skipped).any(|s| s.hwdb_input == c.hwdb_input)
+            })
+            .cloned()
+            .collect();
+        if SupportMatrix::build(&index, vendors, &used)?.write_in(database.root())? {
+            log(format!("Updated {}", SUPPORT_MATRIX_FILE));
+        }
     }
 
+    // Let the vendors know about the models the new hwdb file lost
+    // compared with the last archived one.
+    let archive = HwdbArchive::new(workspace().hwdb_archive_dir());
+    for model_name in dropped_models(&archive, &models) {
+        log(format!("{} has no entry in the new hwdb file", model_name));
+        notifier
+            .notify(&NotifyEvent::ModelDropped {
+                contacts: vendors.contacts(&model_name),
+                model_name,
+            })
+            .await;
+    }
+
+    // Keep a copy of the outputs so that the evolution of the hwdb
+    // entries can be tracked over time.
+    let mut archived: Vec<PathBuf> = manifest
+        .artifacts
+        .iter()
+        .filter(|a| !matches!(a.kind, ArtifactKind::Pdf))
+        .map(|a| a.path.clone())
+        .collect();
+    archived.push(outputs.join(MANIFEST_FILE));
+    let entry = archive.store(github_id.as_deref(), &archived)?;
+    log(format!("Archived hwdb outputs in {}", archive.root().join(&entry.dir).display()));
+
+    let repo = std::env::var("GITHUB_REPOSITORY").unwrap_or(DEFAULT_REPO.to_string());
+    let entries = write_feed(&outputs.join(FEED_FILE), database.root(), &archive, &repo)?;
+    log(format!("Wrote {} entries to {}", entries, FEED_FILE));
+
+    notifier
+        .notify(&NotifyEvent::HwdbPublished {
+            commit: github_id,
+            models: models.len(),
+        })
+        .await;
+    Ok(())
+}
+
+/// Returns the models of the last archived run which are not in `models`
+fn dropped_models(archive: &HwdbArchive, models: &[String]) -> Vec<String> {
+    let mut dropped: Vec<String> = archive
+        .latest_manifest()
+        .map(|m| m.candidates)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|c| c.model_name)
+        .filter(|model_name| !models.contains(model_name))
+        .collect();
+    dropped.sort();
+    dropped.dedup();
+    dropped
+}
+
+/// Prints the merge plans of the models of `database`, or of those of
+/// `shard`
+fn print_plan(database: &Database, shard: Option<Shard>) -> Result<()> {
+    for version_dir in glob(&database.root().join("*").to_string_lossy())?.flatten().filter(|p| p.is_dir()) {
+        let version = version_dir.file_name().unwrap().to_string_lossy().to_string();
+        if legacy_versions().contains(&version) {
+            continue;
+        }
+        for model_dir in glob(&version_dir.join("*").to_string_lossy())?.flatten().filter(|p| p.is_dir()) {
+            let model_name = model_dir.file_name().unwrap().to_string_lossy().to_string();
+            if shard.is_some_and(|shard| !shard.contains(&model_name)) {
+                continue;
+            }
+            let plan = match BenchMerge::plan(database, &version, &model_name) {
+                Ok(plan) => plan,
+                Err(e) => {
+                    eprintln!("{} ({}): {}", model_name, version, e);
+                    continue;
+                }
+            };
+            println!(
+                "{} ({}): ~{} data points, fwmerge policy {}",
+                plan.model_name, plan.version, plan.data_points, plan.policy
+            );
+            for fwrev in &plan.fwrevs {
+                println!("  {}: {} results, ~{} data points", fwrev.fwrev, fwrev.results, fwrev.data_points);
+            }
+            match &plan.specific {
+                Some(fwrev) => println!("  => {} gets a hwdb section of its own", fwrev),
+                None => println!("  => generic hwdb section only"),
+            }
+        }
+    }
     Ok(())
 }
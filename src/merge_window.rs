@@ -0,0 +1,130 @@
+use anyhow::Result;
+use chrono::{DateTime, Months, NaiveDate, Utc};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::common::load_json;
+use crate::config::{set_once, MergeConfig, MergeWindowConfig};
+use crate::index::ResultMetadata;
+use crate::progress::log;
+
+/// Oldest results included in the merges
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cutoff {
+    /// Results older than this many months are left out
+    MaxAgeMonths(u32),
+    /// Results from before this date are left out
+    Since(NaiveDate),
+}
+
+impl Cutoff {
+    fn from_config(config: &MergeWindowConfig) -> anyhow::Result<Option<Self>> {
+        match (config.max_age_months, &config.since) {
+            (Some(_), Some(_)) => anyhow::bail!("max_age_months and since are mutually exclusive"),
+            (Some(months), None) => Ok(Some(Cutoff::MaxAgeMonths(months))),
+            (None, Some(since)) => Ok(Some(Cutoff::Since(since.parse()?))),
+            (None, None) => Ok(None),
+        }
+    }
+
+    fn date(&self) -> DateTime<Utc> {
+        match self {
+            Cutoff::MaxAgeMonths(months) => Utc::now()
+                .checked_sub_months(Months::new(*months))
+                .unwrap_or(DateTime::<Utc>::MIN_UTC),
+            Cutoff::Since(date) => date.and_hms_opt(0, 0, 0).unwrap().and_utc(),
+        }
+    }
+}
+
+/// Restricts the merges to the recent results, by default or per model.
+/// Results aged out are kept in the database.
+#[derive(Debug, Default)]
+pub struct MergeWindow {
+    default: Option<Cutoff>,
+    models: HashMap<String, Cutoff>,
+}
+
+static MERGE_WINDOW: OnceLock<MergeWindow> = OnceLock::new();
+
+/// Sets the window of the merges.
+pub fn set_merge_window(window: MergeWindow) -> Result<()> {
+    set_once(&MERGE_WINDOW, window, "merge window")
+}
+
+/// Returns the window of the merges, including all the results if
+/// none was set
+pub fn merge_window() -> &'static MergeWindow {
+    MERGE_WINDOW.get_or_init(MergeWindow::default)
+}
+
+/// Returns the date of a result: when the benchmark was run, or when it
+/// was imported for results which don't record it
+fn result_date(path: &Path) -> crate::error::Result<Option<DateTime<Utc>>> {
+    let result = load_json(&path.to_string_lossy())?;
+    let run_at = result[0]["period"][0]
+        .as_i64()
+        .and_then(|secs| DateTime::from_timestamp(secs, 0));
+    Ok(run_at.or_else(|| {
+        ResultMetadata::load_for(path)
+            .and_then(|m| m.imported_at)
+            .and_then(|d| DateTime::parse_from_rfc3339(&d).ok())
+            .map(|d| d.to_utc())
+    }))
+}
+
+impl MergeWindow {
+    /// Builds the window from the [merge] section of the config file
+    pub fn from_config(config: &MergeConfig) -> anyhow::Result<Self> {
+        let mut models = HashMap::new();
+        for (model, window) in &config.models {
+            if let Some(cutoff) = Cutoff::from_config(window)
+                .map_err(|e| anyhow::anyhow!("Invalid merge window for {}: {}", model, e))?
+            {
+                models.insert(model.clone(), cutoff);
+            }
+        }
+        Ok(MergeWindow {
+            default: Cutoff::from_config(&config.window)
+                .map_err(|e| anyhow::anyhow!("Invalid merge window: {}", e))?,
+            models,
+        })
+    }
+
+    /// Sets the cutoff of the models without a specific one
+    pub fn with_default(mut self, cutoff: Cutoff) -> Self {
+        self.default = Some(cutoff);
+        self
+    }
+
+    fn cutoff_for(&self, model_name: &str) -> Option<&Cutoff> {
+        self.models.get(model_name).or(self.default.as_ref())
+    }
+
+    /// Returns the result `files` of `model_name` inside the window.
+    /// Results without a known date are kept.
+    pub fn filter(&self, model_name: &str, files: Vec<PathBuf>) -> crate::error::Result<Vec<PathBuf>> {
+        let Some(cutoff) = self.cutoff_for(model_name) else {
+            return Ok(files);
+        };
+        let cutoff = cutoff.date();
+        let total = files.len();
+        let mut kept = vec![];
+        for file in files {
+            if result_date(&file)?.is_none_or(|date| date >= cutoff) {
+                kept.push(file);
+            }
+        }
+        if kept.len() < total {
+            log(format!(
+                "Model {}: left {} of {} results from before {} out of the merge",
+                model_name,
+                total - kept.len(),
+                total,
+                cutoff.format("%Y-%m-%d")
+            ));
+        }
+        Ok(kept)
+    }
+}
@@ -0,0 +1,88 @@
+use anyhow::Result;
+use prometheus::{
+    Encoder, Histogram, HistogramOpts, HistogramVec, IntCounter, Registry, TextEncoder,
+};
+use std::sync::LazyLock;
+
+/// Counters and histograms describing a pipeline run
+pub struct Metrics {
+    registry: Registry,
+    /// Result files downloaded from submissions
+    pub downloads: IntCounter,
+    /// Result files that failed validation
+    pub validation_failures: IntCounter,
+    /// Time spent merging the results of a model
+    pub merge_duration: Histogram,
+    /// Time spent in resctl-bench subprocesses, by subcommand
+    pub resctl_duration: HistogramVec,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new_custom(Some("iocost_ci".to_string()), None)
+            .expect("Failed to create metrics registry");
+        let downloads = IntCounter::new("downloads_total", "Result files downloaded").unwrap();
+        let validation_failures =
+            IntCounter::new("validation_failures_total", "Result files that failed validation")
+                .unwrap();
+        let merge_duration = Histogram::with_opts(
+            HistogramOpts::new("merge_duration_seconds", "Time spent merging a model")
+                .buckets(prometheus::exponential_buckets(1.0, 2.0, 10).unwrap()),
+        )
+        .unwrap();
+        let resctl_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "resctl_bench_duration_seconds",
+                "Time spent in resctl-bench subprocesses",
+            )
+            .buckets(prometheus::exponential_buckets(0.1, 2.0, 12).unwrap()),
+            &["subcommand"],
+        )
+        .unwrap();
+        registry.register(Box::new(downloads.clone())).unwrap();
+        registry.register(Box::new(validation_failures.clone())).unwrap();
+        registry.register(Box::new(merge_duration.clone())).unwrap();
+        registry.register(Box::new(resctl_duration.clone())).unwrap();
+        Metrics {
+            registry,
+            downloads,
+            validation_failures,
+            merge_duration,
+            resctl_duration,
+        }
+    }
+
+    /// Renders the metrics in the Prometheus text format
+    pub fn render(&self) -> Result<String> {
+        let mut buf = vec![];
+        TextEncoder::new().encode(&self.registry.gather(), &mut buf)?;
+        Ok(String::from_utf8(buf)?)
+    }
+
+    /// Pushes the metrics to a Prometheus pushgateway under `job`
+    pub async fn push(&self, pushgateway: &str, job: &str) -> Result<()> {
+        let url = format!("{}/metrics/job/{}", pushgateway.trim_end_matches('/'), job);
+        reqwest::Client::new()
+            .put(url)
+            .header(reqwest::header::CONTENT_TYPE, TextEncoder::new().format_type())
+            .body(self.render()?)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// Metrics of the current process
+pub static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+/// Pushes the metrics of the current process if a pushgateway is set in
+/// the [metrics] config section. Failing to push doesn't fail the run.
+pub async fn push_metrics(config: Option<&crate::config::MetricsConfig>) {
+    if let Some(config) = config {
+        let job = config.job.as_deref().unwrap_or("iocost-benchmarks");
+        if let Err(e) = METRICS.push(&config.pushgateway, job).await {
+            eprintln!("Failed to push metrics to {}: {}", config.pushgateway, e);
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use anyhow::Result;
+use reqwest::header::{AUTHORIZATION, CONTENT_TYPE};
+
+use crate::config::{MatrixConfig, NotificationsConfig};
+
+/// Pipeline events maintainers get notified about
+pub enum NotifyEvent {
+    /// Results from a submission issue were imported into a branch
+    Imported { issue: u64, branch_name: String },
+    /// Merging the results of a model failed
+    MergeFailed {
+        model_name: String,
+        version: String,
+        error: String,
+        /// Contacts of the vendor of the model
+        contacts: Vec<String>,
+    },
+    /// A model of the previous hwdb file has no entry in the new one
+    ModelDropped {
+        model_name: String,
+        contacts: Vec<String>,
+    },
+    /// A new final hwdb file was generated
+    HwdbPublished { commit: Option<String>, models: usize },
+}
+
+/// Mentions the `contacts` of a vendor at the end of a message
+fn cc(contacts: &[String]) -> String {
+    match contacts.is_empty() {
+        true => String::new(),
+        false => format!(" (cc {})", contacts.join(" ")),
+    }
+}
+
+impl NotifyEvent {
+    fn message(&self) -> String {
+        match self {
+            NotifyEvent::Imported { issue, branch_name } => format!(
+                "iocost-bot: imported results from issue #{} into {}",
+                issue, branch_name
+            ),
+            NotifyEvent::MergeFailed {
+                model_name,
+                version,
+                error,
+                contacts,
+            } => format!(
+                "iocost-bot: merge failed for {} ({}): {}{}",
+                model_name,
+                version,
+                error,
+                cc(contacts)
+            ),
+            NotifyEvent::ModelDropped { model_name, contacts } => format!(
+                "iocost-bot: {} has no entry in the new hwdb anymore{}",
+                model_name,
+                cc(contacts)
+            ),
+            NotifyEvent::HwdbPublished { commit, models } => format!(
+                "iocost-bot: new hwdb generated for {} models from commit {}",
+                models,
+                commit.as_deref().unwrap_or("(local)")
+            ),
+        }
+    }
+}
+
+/// Posts short messages about pipeline events to the Slack and Matrix
+/// webhooks set in the [notifications] config section. Notifications
+/// are best-effort: failures are logged but never fail the run.
+pub struct Notifier {
+    slack_webhook: Option<String>,
+    matrix: Option<MatrixConfig>,
+    client: reqwest::Client,
+}
+
+impl Notifier {
+    pub fn new(config: Option<&NotificationsConfig>) -> Self {
+        Notifier {
+            slack_webhook: config.and_then(|c| c.slack_webhook.clone()),
+            matrix: config.and_then(|c| c.matrix.clone()),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    pub async fn notify(&self, event: &NotifyEvent) {
+        let message = event.message();
+        if let Some(url) = &self.slack_webhook {
+            if let Err(e) = self.post_slack(url, &message).await {
+                eprintln!("Failed to send Slack notification: {}", e);
+            }
+        }
+        if let Some(matrix) = &self.matrix {
+            if let Err(e) = self.post_matrix(matrix, &message).await {
+                eprintln!("Failed to send Matrix notification: {}", e);
+            }
+        }
+    }
+
+    async fn post_slack(&self, url: &str, message: &str) -> Result<()> {
+        let body = serde_json::json!({ "text": message });
+        self.client
+            .post(url)
+            .header(CONTENT_TYPE, "application/json")
+            .body(body.to_string())
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    async fn post_matrix(&self, matrix: &MatrixConfig, message: &str) -> Result<()> {
+        // Matrix requires a transaction id unique per access token.
+        let txn_id = format!("iocost-{}", chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default());
+        let url = format!(
+            "{}/_matrix/client/v3/rooms/{}/send/m.room.message/{}",
+            matrix.homeserver.trim_end_matches('/'),
+            matrix.room_id,
+            txn_id
+        );
+        let body = serde_json::json!({ "msgtype": "m.text", "body": message });
+        self.client
+            .put(url)
+            .header(AUTHORIZATION, format!("Bearer {}", matrix.access_token))
+            .header(CONTENT_TYPE, "application/json")
+            .body(body.to_string())
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,99 @@
+use anyhow::Result;
+use clap::{Args, ValueEnum};
+use serde::Serialize;
+use serde_json::Value;
+
+/// Words YAML 1.1 parsers read as booleans or null rather than strings,
+/// whatever their case
+static RESERVED_WORDS: &[&str] = &["y", "n", "yes", "no", "true", "false", "on", "off", "null"];
+
+/// Format the inspection commands print their records in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable text
+    #[default]
+    Table,
+    Json,
+    Yaml,
+}
+
+/// Output options of the inspection commands
+#[derive(Args, Debug)]
+pub struct OutputArgs {
+    /// Format of the output, json and yaml being meant for other tools
+    /// to consume
+    #[arg(long, value_enum, default_value_t)]
+    pub format: OutputFormat,
+}
+
+impl OutputFormat {
+    /// Prints `records` in the machine-readable formats, or calls `table`
+    /// to print them as text
+    pub fn print<T: Serialize + ?Sized>(&self, records: &T, table: impl FnOnce() -> Result<()>) -> Result<()> {
+        match self {
+            OutputFormat::Table => table()?,
+            OutputFormat::Json => println!("{}", serde_json::to_string_pretty(records)?),
+            OutputFormat::Yaml => print!("{}", to_yaml(&serde_json::to_value(records)?)),
+        }
+        Ok(())
+    }
+}
+
+/// Returns `value` on a single line if it is a scalar or an empty
+/// collection. Strings are quoted as in JSON, which YAML accepts.
+fn inline(value: &Value) -> Option<String> {
+    match value {
+        Value::Array(items) if !items.is_empty() => None,
+        Value::Object(fields) if !fields.is_empty() => None,
+        value => Some(value.to_string()),
+    }
+}
+
+/// Returns `key` as a YAML mapping key. It is quoted unless no parser
+/// can read it as anything but a string: a word starting with a letter
+/// or an underscore, unlike numbers, which isn't reserved.
+fn yaml_key(key: &str) -> String {
+    let plain = key.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        && !RESERVED_WORDS.contains(&key.to_ascii_lowercase().as_str());
+    match plain {
+        true => key.to_string(),
+        false => Value::from(key).to_string(),
+    }
+}
+
+fn write_yaml(yaml: &mut String, value: &Value, indent: usize) {
+    let pad = " ".repeat(indent);
+    match value {
+        Value::Array(items) if !items.is_empty() => {
+            for item in items {
+                match inline(item) {
+                    Some(item) => yaml.push_str(&format!("{}- {}\n", pad, item)),
+                    None => {
+                        yaml.push_str(&format!("{}-\n", pad));
+                        write_yaml(yaml, item, indent + 2);
+                    }
+                }
+            }
+        }
+        Value::Object(fields) if !fields.is_empty() => {
+            for (key, field) in fields {
+                match inline(field) {
+                    Some(field) => yaml.push_str(&format!("{}{}: {}\n", pad, yaml_key(key), field)),
+                    None => {
+                        yaml.push_str(&format!("{}{}:\n", pad, yaml_key(key)));
+                        write_yaml(yaml, field, indent + 2);
+                    }
+                }
+            }
+        }
+        value => yaml.push_str(&format!("{}{}\n", pad, value)),
+    }
+}
+
+/// Renders `value` as a block style YAML document
+pub fn to_yaml(value: &Value) -> String {
+    let mut yaml = String::from("---\n");
+    write_yaml(&mut yaml, value, 0);
+    yaml
+}
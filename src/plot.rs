@@ -0,0 +1,240 @@
+use anyhow::{anyhow, bail, Context, Result};
+use plotters::coord::Shift;
+use plotters::prelude::*;
+use std::path::{Path, PathBuf};
+
+use crate::common::{load_json, BenchMerge};
+use crate::database::Database;
+
+/// Size of the charts, in pixels
+const SIZE: (u32, u32) = (900, 560);
+
+/// Colors of the plotted merges, in order
+const PALETTE: &[RGBColor] = &[
+    RGBColor(0x1f, 0x77, 0xb4),
+    RGBColor(0xd6, 0x27, 0x28),
+    RGBColor(0x2c, 0xa0, 0x2c),
+    RGBColor(0xff, 0x7f, 0x0e),
+    RGBColor(0x94, 0x67, 0xbd),
+    RGBColor(0x8c, 0x56, 0x4b),
+    RGBColor(0xe3, 0x77, 0xc2),
+    RGBColor(0x17, 0xbe, 0xcf),
+];
+
+/// A series of the iocost-tune result of a merge, e.g. MOF vs vrate,
+/// with the vrates of its QoS solutions
+#[derive(Debug)]
+pub struct PlotSeries {
+    /// Name of the merge in the legend, the model and firmware revision
+    pub label: String,
+    /// (vrate, value) points
+    pub points: Vec<(f64, f64)>,
+    pub outliers: Vec<(f64, f64)>,
+    /// Name and vrate of each QoS solution
+    pub solutions: Vec<(String, f64)>,
+}
+
+impl PlotSeries {
+    /// Reads the `series` of the iocost-tune result in the `merged`
+    /// result file
+    pub fn load(merged: &Path, series: &str, label: &str) -> Result<Self> {
+        let result = load_json(&merged.to_string_lossy())?;
+        let Some(tune) = result.members().find(|v| v["spec"]["kind"] == "iocost-tune") else {
+            bail!("No iocost-tune result in {}", merged.display());
+        };
+        let data = &tune["result"]["data"][series];
+        if data.is_null() {
+            bail!("No {} series in {}", series, merged.display());
+        }
+        let points = |key: &str| -> Vec<(f64, f64)> {
+            data[key]
+                .members()
+                .filter_map(|p| Some((p["vrate"].as_f64()?, p["val"].as_f64()?)))
+                .collect()
+        };
+        // Solutions cap the vrate to the one they found
+        let solutions = tune["result"]["solutions"]
+            .entries()
+            .filter_map(|(name, solution)| {
+                let qos = &solution["qos"];
+                Some((name.to_string(), qos["max"].as_f64().or(qos["min"].as_f64())?))
+            })
+            .collect();
+        Ok(PlotSeries {
+            label: label.to_string(),
+            points: points("data"),
+            outliers: points("outliers"),
+            solutions,
+        })
+    }
+}
+
+/// Formats of the charts, by the extension of their file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChartFormat {
+    Svg,
+    Png,
+}
+
+impl ChartFormat {
+    /// Returns the format of the chart file `path`
+    pub fn of(path: &Path) -> Result<Self> {
+        match path.extension().and_then(|e| e.to_str()).map(str::to_lowercase).as_deref() {
+            Some("svg") => Ok(ChartFormat::Svg),
+            Some("png") => Ok(ChartFormat::Png),
+            _ => bail!("Unsupported chart format, {} should end with .svg or .png", path.display()),
+        }
+    }
+}
+
+/// Draws the `series` named `series_name` on `root` as a scatter chart of
+/// their value against the vrate, with dashed lines at the vrates of
+/// their solutions
+fn draw<DB: DrawingBackend>(
+    root: DrawingArea<DB, Shift>,
+    title: &str,
+    series_name: &str,
+    series: &[PlotSeries],
+) -> Result<()> {
+    let error = |e: DrawingAreaErrorKind<DB::ErrorType>| anyhow!("Failed to draw the chart: {}", e);
+    let all = || series.iter().flat_map(|s| s.points.iter().chain(&s.outliers));
+    let (mut x_max, mut y_min, mut y_max) = (0f64, f64::MAX, f64::MIN);
+    for (x, y) in all() {
+        x_max = x_max.max(*x);
+        y_min = y_min.min(*y);
+        y_max = y_max.max(*y);
+    }
+    for (_, vrate) in series.iter().flat_map(|s| &s.solutions) {
+        x_max = x_max.max(*vrate);
+    }
+    if y_min > y_max {
+        (y_min, y_max) = (0.0, 1.0);
+    }
+    let y_min = y_min.min(0.0);
+    let (x_max, y_max) = (x_max.max(1.0) * 1.05, y_max.max(y_min + 1e-9) * 1.05);
+
+    root.fill(&WHITE).map_err(error)?;
+    let mut chart = ChartBuilder::on(&root)
+        .caption(title, ("sans-serif", 16))
+        .margin(15)
+        .x_label_area_size(40)
+        .y_label_area_size(60)
+        .build_cartesian_2d(0f64..x_max, y_min..y_max)
+        .map_err(error)?;
+    chart
+        .configure_mesh()
+        .light_line_style(RGBColor(0xee, 0xee, 0xee))
+        .x_desc("vrate (%)")
+        .y_desc(series_name)
+        .draw()
+        .map_err(error)?;
+    // Data points, outliers hollow, and the solutions of each merge
+    for (i, s) in series.iter().enumerate() {
+        let color = PALETTE[i % PALETTE.len()];
+        chart
+            .draw_series(s.points.iter().map(|p| Circle::new(*p, 3, color.filled())))
+            .map_err(error)?
+            .label(&s.label)
+            .legend(move |p| Circle::new(p, 5, color.filled()));
+        chart
+            .draw_series(s.outliers.iter().map(|p| Circle::new(*p, 3, color.stroke_width(1))))
+            .map_err(error)?;
+        for (j, (name, vrate)) in s.solutions.iter().enumerate() {
+            let line = [(*vrate, y_min), (*vrate, y_max)];
+            chart.draw_series(DashedLineSeries::new(line, 4, 3, color.into())).map_err(error)?;
+            // The names are staggered so that close solutions stay legible
+            let label_y = y_max - (y_max - y_min) * (0.03 + 0.04 * ((i * s.solutions.len() + j) % 20) as f64);
+            let font = ("sans-serif", 10).into_font().color(&color);
+            chart.draw_series([Text::new(name.clone(), (*vrate, label_y), font)]).map_err(error)?;
+        }
+    }
+    chart
+        .configure_series_labels()
+        .position(SeriesLabelPosition::LowerRight)
+        .background_style(WHITE.mix(0.8))
+        .border_style(BLACK)
+        .draw()
+        .map_err(error)?;
+    root.present().map_err(error)?;
+    Ok(())
+}
+
+/// Renders the `series` named `series_name` to the chart file `output`,
+/// in the format of its extension
+pub fn render(output: &Path, title: &str, series_name: &str, series: &[PlotSeries]) -> Result<()> {
+    match ChartFormat::of(output)? {
+        ChartFormat::Svg => draw(SVGBackend::new(output, SIZE).into_drawing_area(), title, series_name, series),
+        ChartFormat::Png => draw(BitMapBackend::new(output, SIZE).into_drawing_area(), title, series_name, series),
+    }
+    .with_context(|| format!("Failed to write {}", output.display()))
+}
+
+/// Returns the merges of `model_name` for a resctl-bench `version` to
+/// plot, with their label: a merge per firmware revision with
+/// `by_fwrev`, otherwise the merged result of the last merge run, or a
+/// new merge if there is none. New merges are written in `merge_dir`.
+fn merges_of(
+    database: &Database,
+    version: &str,
+    model_name: &str,
+    by_fwrev: bool,
+    merge_dir: &Path,
+) -> Result<Vec<(String, PathBuf)>> {
+    let directory = database.model_dir(version, model_name)?;
+    if !directory.is_dir() {
+        bail!("No results for {} ({}) in {}", model_name, version, database.root().display());
+    }
+    let new_merge = |files: &[PathBuf], name: String| -> Result<PathBuf> {
+        let path = merge_dir.join(format!("{}.json.gz", name));
+        BenchMerge::merge_files(version, files, &path)?;
+        Ok(path)
+    };
+    if by_fwrev {
+        let mut merges = vec![];
+        for (fwrev, files) in BenchMerge::results_by_fwrev(&directory)? {
+            let fwrev = fwrev.trim_matches('"').to_string();
+            let path = new_merge(&files, format!("{}-{}", model_name, merges.len()))?;
+            merges.push((format!("{} {}", model_name, fwrev), path));
+        }
+        return Ok(merges);
+    }
+    let merged = database.merged_path(version, model_name, None)?;
+    if merged.exists() {
+        return Ok(vec![(model_name.to_string(), merged)]);
+    }
+    let files: Vec<PathBuf> = BenchMerge::results_by_fwrev(&directory)?
+        .into_values()
+        .flatten()
+        .collect();
+    Ok(vec![(model_name.to_string(), new_merge(&files, model_name.to_string())?)])
+}
+
+/// Runs the `plot` command: charts the `series` of the merges of
+/// `model_name` and of the `compare` models for a resctl-bench
+/// `version`, split by firmware revision with `by_fwrev`, to the SVG or
+/// PNG file `output`
+pub fn plot(
+    database: &Database,
+    version: &str,
+    model_name: &str,
+    compare: &[String],
+    by_fwrev: bool,
+    series: &str,
+    output: &Path,
+) -> Result<usize> {
+    // Fail before the merges for unsupported files
+    ChartFormat::of(output)?;
+    let merge_dir = tempfile::tempdir()?;
+    let mut plotted = vec![];
+    for model in std::iter::once(model_name).chain(compare.iter().map(String::as_str)) {
+        for (label, path) in merges_of(database, version, model, by_fwrev, merge_dir.path())? {
+            plotted.push(PlotSeries::load(&path, series, &label)?);
+        }
+    }
+    let title = match compare.is_empty() {
+        true => format!("{} (resctl-bench {}): {} vs vrate", model_name, version, series),
+        false => format!("{} vs vrate (resctl-bench {})", series, version),
+    };
+    render(output, &title, series, &plotted)?;
+    Ok(plotted.len())
+}
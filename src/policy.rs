@@ -0,0 +1,113 @@
+use anyhow::Result;
+use chrono::NaiveDate;
+use std::fmt;
+
+use crate::config::TomlData;
+use crate::consent::{consent_settings, set_consent_settings, ConsentSettings};
+use crate::embargo::{embargo_date, embargo_staging, set_embargo_staging, staging_from_config};
+use crate::limits::{set_submission_limits, SubmissionLimits};
+use crate::scrub::{set_scrubber, Scrubber};
+use crate::signatures::{set_signature_settings, SignatureSettings};
+use crate::signoff::{set_signoff_settings, signoff_settings, SignOff, SignOffError, SignOffSettings};
+
+/// What the submitter of results wrote with them, which the policies of
+/// the database are checked against
+pub struct Statement<'a> {
+    /// Texts of the submission: the bodies of the issue and of the
+    /// comment, or the statement of an upload
+    pub bodies: &'a [&'a str],
+    /// Github login of the author of the issue
+    pub author: &'a str,
+    /// Github login of the user who submitted the results
+    pub submitter: &'a str,
+}
+
+/// Why results are refused before any of them is processed
+#[derive(Debug)]
+pub enum Refusal {
+    /// The submitter didn't agree to the data license
+    LicenseRequired(&'static ConsentSettings),
+    /// The submission has no valid sign-off
+    SignOffRequired(SignOffError),
+    /// The results are embargoed until the date, with no staging area to
+    /// hold them back in
+    Embargoed(NaiveDate),
+}
+
+impl fmt::Display for Refusal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Refusal::LicenseRequired(consent) => write!(
+                f,
+                "No agreement to the {} license of the database, such as \"{}\"",
+                consent.license, consent.phrase
+            ),
+            Refusal::SignOffRequired(SignOffError::Missing) => write!(f, "No Signed-off-by line of the submitter"),
+            Refusal::SignOffRequired(SignOffError::NotAuthor { author }) => {
+                write!(f, "Only {} can sign off the results", author)
+            }
+            Refusal::Embargoed(date) => {
+                write!(f, "The results are embargoed until {}, the database can't hold them back", date)
+            }
+        }
+    }
+}
+
+/// Outcome of the policy checks of a submission
+#[derive(Debug)]
+pub enum Verdict {
+    /// The results can be processed, with the sign-off to record and
+    /// the end of their embargo, if any, before which they are held back
+    Accepted { signoff: SignOff, embargo: Option<NaiveDate> },
+    Refused(Refusal),
+}
+
+/// Checks a submission against the policies of the database, whichever
+/// way the results came in: agreement to the data license, sign-off and
+/// embargo
+pub fn check_statement(statement: &Statement) -> Result<Verdict> {
+    if let Some(consent) = consent_settings() {
+        if !statement.bodies.iter().any(|body| consent.is_given_in(body)) {
+            return Ok(Verdict::Refused(Refusal::LicenseRequired(consent)));
+        }
+    }
+    let signoff = match signoff_settings().verify(statement.bodies, statement.author, statement.submitter) {
+        Ok(signoff) => signoff,
+        Err(e) => return Ok(Verdict::Refused(Refusal::SignOffRequired(e))),
+    };
+    let mut embargo = None;
+    for body in statement.bodies {
+        embargo = embargo.or(embargo_date(body)?);
+    }
+    // Only embargoes in the future hold the results back
+    let embargo = embargo.filter(|date| *date > chrono::Utc::now().date_naive());
+    if let (Some(date), None) = (embargo, embargo_staging()) {
+        return Ok(Verdict::Refused(Refusal::Embargoed(date)));
+    }
+    Ok(Verdict::Accepted { signoff, embargo })
+}
+
+/// Sets the policies of the [limits], [consent], [signoff], [signatures],
+/// [embargo] and [scrub] sections of `config`, which the results are
+/// checked against whichever way they are submitted
+pub fn set_policies(config: &TomlData) -> Result<()> {
+    if let Some(limits) = &config.limits {
+        set_submission_limits(SubmissionLimits::from_config(limits))?;
+    }
+    if let Some(consent) = &config.consent {
+        set_consent_settings(ConsentSettings::from_config(consent))?;
+    }
+    if let Some(signoff) = &config.signoff {
+        set_signoff_settings(SignOffSettings::from_config(signoff))?;
+    }
+    if let Some(signatures) = &config.signatures {
+        set_signature_settings(SignatureSettings::from_config(signatures)?)?;
+    }
+    if let Some(embargo) = &config.embargo {
+        set_embargo_staging(staging_from_config(embargo)?)?;
+    }
+    if let Some(scrub) = &config.scrub {
+        set_scrubber(Scrubber::from_config(scrub))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,129 @@
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::LazyLock;
+use std::time::Duration;
+
+/// How much output the commands print
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    /// Only errors
+    Quiet = 0,
+    /// Progress and summaries
+    Normal = 1,
+    /// Also the resctl-bench invocations
+    Verbose = 2,
+}
+
+/// Verbosity flags shared by all commands
+#[derive(clap::Args, Debug)]
+pub struct VerbosityArgs {
+    /// Print only errors
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    pub quiet: bool,
+
+    /// Print more details about what is being run
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    pub verbose: u8,
+}
+
+impl VerbosityArgs {
+    /// Sets the process-wide verbosity from the flags
+    pub fn apply(&self) {
+        let verbosity = match (self.quiet, self.verbose) {
+            (true, _) => Verbosity::Quiet,
+            (false, 0) => Verbosity::Normal,
+            _ => Verbosity::Verbose,
+        };
+        VERBOSITY.store(verbosity as u8, Ordering::Relaxed);
+    }
+}
+
+static VERBOSITY: AtomicU8 = AtomicU8::new(Verbosity::Normal as u8);
+
+/// All progress bars are drawn through this, so that log lines can be
+/// printed without garbling them.
+static PROGRESS: LazyLock<MultiProgress> = LazyLock::new(MultiProgress::new);
+
+pub fn verbosity() -> Verbosity {
+    match VERBOSITY.load(Ordering::Relaxed) {
+        0 => Verbosity::Quiet,
+        1 => Verbosity::Normal,
+        _ => Verbosity::Verbose,
+    }
+}
+
+/// Progress bars are only drawn on an interactive terminal: not when
+/// running quietly, with stderr redirected or in Github Actions.
+fn bars_enabled() -> bool {
+    verbosity() > Verbosity::Quiet
+        && std::io::stderr().is_terminal()
+        && std::env::var_os("GITHUB_ACTIONS").is_none()
+}
+
+/// Returns a progress bar for `len` steps labelled with `prefix`, or a
+/// hidden one if progress bars are disabled.
+pub fn bar(len: u64, prefix: &str) -> ProgressBar {
+    if !bars_enabled() {
+        return ProgressBar::hidden();
+    }
+    let style = ProgressStyle::with_template(
+        "{prefix:>12} [{bar:40}] {pos}/{len} ({elapsed}) {wide_msg}",
+    )
+    .unwrap()
+    .progress_chars("=> ");
+    PROGRESS.add(
+        ProgressBar::new(len)
+            .with_style(style)
+            .with_prefix(prefix.to_string()),
+    )
+}
+
+/// Returns a download progress bar for `len` bytes, or a spinner if
+/// the length is unknown.
+pub fn download_bar(len: Option<u64>, msg: &str) -> ProgressBar {
+    if !bars_enabled() {
+        return ProgressBar::hidden();
+    }
+    let pb = match len {
+        Some(len) => ProgressBar::new(len).with_style(
+            ProgressStyle::with_template(
+                "{prefix:>12} [{bar:40}] {bytes}/{total_bytes} ({bytes_per_sec}) {wide_msg}",
+            )
+            .unwrap()
+            .progress_chars("=> "),
+        ),
+        None => ProgressBar::new_spinner(),
+    };
+    PROGRESS.add(pb.with_prefix("Downloading").with_message(msg.to_string()))
+}
+
+/// Returns a spinner for a single long running step, or a hidden one
+/// if progress bars are disabled.
+pub fn spinner(prefix: &str, msg: &str) -> ProgressBar {
+    if !bars_enabled() {
+        return ProgressBar::hidden();
+    }
+    let pb = PROGRESS.add(
+        ProgressBar::new_spinner()
+            .with_style(ProgressStyle::with_template("{prefix:>12} {spinner} {wide_msg}").unwrap())
+            .with_prefix(prefix.to_string())
+            .with_message(msg.to_string()),
+    );
+    pb.enable_steady_tick(Duration::from_millis(100));
+    pb
+}
+
+/// Prints `msg` unless running quietly
+pub fn log(msg: impl AsRef<str>) {
+    if verbosity() >= Verbosity::Normal {
+        PROGRESS.suspend(|| println!("{}", msg.as_ref()));
+    }
+}
+
+/// Prints `msg` only when running verbosely
+pub fn debug(msg: impl AsRef<str>) {
+    if verbosity() >= Verbosity::Verbose {
+        PROGRESS.suspend(|| println!("{}", msg.as_ref()));
+    }
+}
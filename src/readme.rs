@@ -0,0 +1,85 @@
+use anyhow::Result;
+use glob::glob;
+use std::fs;
+use std::path::Path;
+
+use crate::common::BenchMerge;
+use crate::index::ResultMetadata;
+
+/// README of the model directories of the database, giving context to
+/// the people browsing the database on Github
+pub static MODEL_README: &str = "README.md";
+
+/// Summary of the results of a model for a resctl-bench version
+#[derive(Debug)]
+pub struct ModelReadme {
+    pub model_name: String,
+    pub version: String,
+    pub results: usize,
+    pub data_points: usize,
+    /// Firmware revisions of the results, with their number of results
+    pub fwrevs: Vec<(String, usize)>,
+    /// Date (YYYY-MM-DD) the latest result was merged into the database
+    pub last_merged: Option<String>,
+    pub pdf_url: Option<String>,
+}
+
+impl ModelReadme {
+    /// Summarizes the results of `merge`, found in `model_dir`
+    pub fn for_merge(merge: &BenchMerge, model_dir: &Path, pdf_url: Option<String>) -> Result<Self> {
+        let fwrevs: Vec<(String, usize)> = BenchMerge::results_by_fwrev(model_dir)?
+            .into_iter()
+            .map(|(fwrev, files)| (fwrev.trim_matches('"').to_string(), files.len()))
+            .collect();
+        let last_merged = glob(&model_dir.join("*.json.metadata").to_string_lossy())?
+            .flatten()
+            .filter_map(|p| fs::read_to_string(p).ok())
+            .filter_map(|c| serde_json::from_str::<ResultMetadata>(&c).ok())
+            .filter_map(|m| m.imported_at.and_then(|at| at.get(..10).map(str::to_string)))
+            .max();
+        Ok(ModelReadme {
+            model_name: merge.model_name.clone(),
+            version: merge.version_str.clone(),
+            results: fwrevs.iter().map(|(_, count)| count).sum(),
+            data_points: merge.data_points,
+            fwrevs,
+            last_merged,
+            pdf_url,
+        })
+    }
+
+    pub fn render(&self) -> String {
+        let mut text = format!(
+            "<!-- Generated by merge-results, changes will be overwritten -->\n\
+             # {} (resctl-bench {})\n\n\
+             | | |\n|---|---|\n\
+             | Results | {} |\n\
+             | Data points | {} |\n",
+            self.model_name, self.version, self.results, self.data_points
+        );
+        if let Some(date) = &self.last_merged {
+            text.push_str(&format!("| Last merged | {} |\n", date));
+        }
+        if let Some(url) = &self.pdf_url {
+            text.push_str(&format!("| Report | [PDF]({}) |\n", url));
+        }
+        text.push_str("\n## Firmware revisions\n\n| Revision | Results |\n|---|---:|\n");
+        for (fwrev, count) in &self.fwrevs {
+            text.push_str(&format!("| {} | {} |\n", fwrev, count));
+        }
+        text
+    }
+
+    /// Writes the README in `model_dir` if it changed, so that runs
+    /// without new results leave the database untouched. Returns whether
+    /// it was written.
+    pub fn write_in(&self, model_dir: &Path) -> Result<bool> {
+        let path = model_dir.join(MODEL_README);
+        let text = self.render();
+        if fs::read_to_string(&path).is_ok_and(|current| current == text) {
+            return Ok(false);
+        }
+        fs::write(&path, text)?;
+        Ok(true)
+    }
+}
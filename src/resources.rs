@@ -0,0 +1,228 @@
+use anyhow::Result;
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io::{self, Read, Write};
+#[cfg(unix)]
+use std::os::unix::process::ExitStatusExt;
+use std::process::{Command, Output, Stdio};
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+/// Environment variable with the path of the Github job summary
+static JOB_SUMMARY_ENVVAR: &str = "GITHUB_STEP_SUMMARY";
+
+#[cfg(target_os = "linux")]
+const RUSAGE_CURRENT_THREAD: libc::c_int = libc::RUSAGE_THREAD;
+#[cfg(all(unix, not(target_os = "linux")))]
+const RUSAGE_CURRENT_THREAD: libc::c_int = libc::RUSAGE_SELF;
+
+/// Pipeline stages the resource usage is accounted for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PipelineStage {
+    Download,
+    Validate,
+    Merge,
+    Pdf,
+    Hwdb,
+}
+
+impl fmt::Display for PipelineStage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PipelineStage::Download => write!(f, "download"),
+            PipelineStage::Validate => write!(f, "validate"),
+            PipelineStage::Merge => write!(f, "merge"),
+            PipelineStage::Pdf => write!(f, "pdf"),
+            PipelineStage::Hwdb => write!(f, "hwdb"),
+        }
+    }
+}
+
+/// Resources used by a stage, over all its runs
+#[derive(Debug, Default, Clone, Copy)]
+pub struct StageUsage {
+    pub runs: u64,
+    /// Time spent in the stage, summed over the threads running it
+    pub wall: Duration,
+    /// CPU time of the threads running the stage and of the resctl-bench
+    /// subprocesses they started
+    pub cpu: Duration,
+    /// Largest peak RSS of the subprocesses of the stage and of the
+    /// process itself, whose peak is over the whole run so far
+    pub peak_rss_kb: u64,
+}
+
+thread_local! {
+    /// Stage running on the current thread, to account the subprocesses
+    /// it starts to
+    static CURRENT_STAGE: Cell<Option<PipelineStage>> = const { Cell::new(None) };
+}
+
+#[cfg(unix)]
+fn timeval(tv: libc::timeval) -> Duration {
+    Duration::from_secs(tv.tv_sec as u64) + Duration::from_micros(tv.tv_usec as u64)
+}
+
+/// Returns the CPU time and peak RSS (KiB) reported by getrusage(2) for
+/// the current thread, or for the whole process with `process`
+#[cfg(unix)]
+fn rusage(process: bool) -> (Duration, u64) {
+    let who = if process { libc::RUSAGE_SELF } else { RUSAGE_CURRENT_THREAD };
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    if unsafe { libc::getrusage(who, &mut usage) } != 0 {
+        return (Duration::ZERO, 0);
+    }
+    (
+        timeval(usage.ru_utime) + timeval(usage.ru_stime),
+        usage.ru_maxrss as u64,
+    )
+}
+
+/// Without getrusage(2), only the wall time of the stages is accounted
+#[cfg(not(unix))]
+fn rusage(_process: bool) -> (Duration, u64) {
+    (Duration::ZERO, 0)
+}
+
+/// Resources used by each stage of the run
+#[derive(Default)]
+pub struct ResourceAccounting {
+    stages: Mutex<BTreeMap<PipelineStage, StageUsage>>,
+}
+
+/// Accounts the resources used by a stage from its creation until it is
+/// dropped
+pub struct StageGuard {
+    stage: PipelineStage,
+    previous: Option<PipelineStage>,
+    started: Instant,
+    cpu: Duration,
+}
+
+impl Drop for StageGuard {
+    fn drop(&mut self) {
+        let (cpu, _) = rusage(false);
+        let (_, peak_rss_kb) = rusage(true);
+        RESOURCES.record(self.stage, |usage| {
+            usage.runs += 1;
+            usage.wall += self.started.elapsed();
+            usage.cpu += cpu.saturating_sub(self.cpu);
+            usage.peak_rss_kb = usage.peak_rss_kb.max(peak_rss_kb);
+        });
+        CURRENT_STAGE.set(self.previous);
+    }
+}
+
+impl ResourceAccounting {
+    /// Starts accounting the resources of `stage` on the current thread
+    pub fn start(&self, stage: PipelineStage) -> StageGuard {
+        StageGuard {
+            stage,
+            previous: CURRENT_STAGE.replace(Some(stage)),
+            started: Instant::now(),
+            cpu: rusage(false).0,
+        }
+    }
+
+    fn record(&self, stage: PipelineStage, update: impl FnOnce(&mut StageUsage)) {
+        update(self.stages.lock().unwrap().entry(stage).or_default());
+    }
+
+    /// Returns the usage of the stages that ran
+    pub fn usage(&self) -> BTreeMap<PipelineStage, StageUsage> {
+        self.stages.lock().unwrap().clone()
+    }
+
+    /// Renders the usage as a markdown table
+    pub fn to_markdown(&self) -> String {
+        let mut md = String::from("### Resource usage\n\n");
+        md.push_str("| Stage | Runs | Wall time | CPU time | Peak RSS |\n");
+        md.push_str("|---|---:|---:|---:|---:|\n");
+        for (stage, usage) in self.usage() {
+            md.push_str(&format!(
+                "| {} | {} | {:.1}s | {:.1}s | {:.1} MiB |\n",
+                stage,
+                usage.runs,
+                usage.wall.as_secs_f64(),
+                usage.cpu.as_secs_f64(),
+                usage.peak_rss_kb as f64 / 1024.0
+            ));
+        }
+        md
+    }
+
+    /// Appends the usage to the Github job summary, or prints it when not
+    /// running in a workflow. Does nothing if no stage ran.
+    pub fn write_summary(&self) -> Result<()> {
+        if self.stages.lock().unwrap().is_empty() {
+            return Ok(());
+        }
+        if !append_job_summary(&self.to_markdown())? {
+            eprintln!("{}", self.to_markdown());
+        }
+        Ok(())
+    }
+}
+
+/// Appends `markdown` to the Github job summary, returning false when not
+/// running in a workflow
+pub fn append_job_summary(markdown: &str) -> Result<bool> {
+    match std::env::var(JOB_SUMMARY_ENVVAR) {
+        Ok(path) => {
+            let mut file = fs::OpenOptions::new().create(true).append(true).open(path)?;
+            writeln!(file, "{}", markdown)?;
+            Ok(true)
+        }
+        Err(_) => Ok(false),
+    }
+}
+
+/// Resource usage of the current process
+pub static RESOURCES: LazyLock<ResourceAccounting> = LazyLock::new(ResourceAccounting::default);
+
+/// Runs `command` like `Command::output`, accounting the CPU time and
+/// peak RSS of the subprocess to the stage running on the current thread
+#[cfg(unix)]
+pub fn output_accounted(command: &mut Command) -> io::Result<Output> {
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let mut stderr_pipe = child.stderr.take().unwrap();
+    let stderr_reader = std::thread::spawn(move || {
+        let mut stderr = vec![];
+        stderr_pipe.read_to_end(&mut stderr).map(|_| stderr)
+    });
+    let mut stdout = vec![];
+    child.stdout.take().unwrap().read_to_end(&mut stdout)?;
+    let stderr = stderr_reader.join().unwrap()?;
+
+    // Reap the child with wait4(2) rather than Child::wait, to get its
+    // resource usage.
+    let mut status = 0;
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    if unsafe { libc::wait4(child.id() as libc::pid_t, &mut status, 0, &mut usage) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    if let Some(stage) = CURRENT_STAGE.get() {
+        RESOURCES.record(stage, |stage_usage| {
+            stage_usage.cpu += timeval(usage.ru_utime) + timeval(usage.ru_stime);
+            stage_usage.peak_rss_kb = stage_usage.peak_rss_kb.max(usage.ru_maxrss as u64);
+        });
+    }
+    Ok(Output {
+        status: std::process::ExitStatus::from_raw(status),
+        stdout,
+        stderr,
+    })
+}
+
+/// Runs `command` like `Command::output`, without accounting the
+/// resources of the subprocess where wait4(2) isn't available
+#[cfg(not(unix))]
+pub fn output_accounted(command: &mut Command) -> io::Result<Output> {
+    command.stdin(Stdio::null()).output()
+}
@@ -0,0 +1,416 @@
+use anyhow::{bail, Context, Result};
+use semver::VersionReq;
+use serde::Serialize;
+use serde_with::skip_serializing_none;
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::common::{
+    load_json, run_resctl, save_pdf_to, BenchMerge, BenchVersion, SeriesPoints, MINIMUM_DATA_POINTS,
+};
+use crate::database::Database;
+use crate::formatter::FORMATTER;
+use crate::lfs::{self, lfs_settings};
+use crate::resources::{PipelineStage, RESOURCES};
+use crate::rules::rules;
+use crate::scrub::scrubber;
+use crate::signatures::signature_settings;
+use crate::storage::{key_for, Storage};
+
+/// Media types of the pages hosts serve in place of the files, such as
+/// their login, error or redirect pages
+static PAGE_CONTENT_TYPES: &[&str] = &["text/html", "application/xhtml+xml"];
+
+/// Largest ratio of the decompressed size of a result file to its size.
+/// The json of the results compresses about 10 times.
+const MAX_COMPRESSION_RATIO: u64 = 200;
+
+/// Checks that the `contents` of a result file, served with
+/// `content_type` if known, are gzipped json of a sane size once
+/// decompressed, so that the pages served in place of the files are
+/// refused with a clear error rather than failing validation
+pub fn check_result_contents(contents: &[u8], content_type: Option<&str>) -> Result<()> {
+    let media_type = content_type.and_then(|t| t.split(';').next()).map(|t| t.trim().to_lowercase());
+    if media_type.as_deref().is_some_and(|t| PAGE_CONTENT_TYPES.contains(&t))
+        || contents.trim_ascii_start().starts_with(b"<")
+    {
+        bail!(
+            "The link serves a web page ({}) rather than the result file, such as a login, error or \
+             redirect page",
+            content_type.unwrap_or("HTML")
+        );
+    }
+    if !contents.starts_with(&[0x1f, 0x8b]) {
+        bail!(
+            "The file isn't gzipped ({}), resctl-bench results are .json.gz files",
+            content_type.unwrap_or("unknown content type")
+        );
+    }
+    let limit = contents.len() as u64 * MAX_COMPRESSION_RATIO;
+    let mut decoder = libflate::gzip::Decoder::new(contents)
+        .context("The file isn't valid gzip")?
+        .take(limit + 1);
+    let mut chunk = vec![0; 64 * 1024];
+    let (mut size, mut first) = (0, None);
+    loop {
+        let read = decoder.read(&mut chunk).context("The file is corrupted or truncated")?;
+        if read == 0 {
+            break;
+        }
+        first = first.or(chunk[..read].iter().find(|b| !b.is_ascii_whitespace()).copied());
+        size += read as u64;
+    }
+    if size > limit {
+        bail!("The file decompresses to over {} times its size, unlike any result", MAX_COMPRESSION_RATIO);
+    }
+    if !matches!(first, Some(b'[' | b'{')) {
+        bail!("The file doesn't hold json once decompressed");
+    }
+    Ok(())
+}
+
+/// Saves the contents of a result file in the current directory and
+/// returns its path. The md5sum of the data is used as filename, we
+/// only care about exact duplicates.
+pub fn save_result_file(contents: &[u8]) -> Result<String> {
+    let path = format!("result-{:x}.json.gz", md5::compute(contents));
+    let mut file = fs::File::create(&path)?;
+    file.write_all(contents)?;
+    Ok(path)
+}
+
+#[skip_serializing_none]
+#[derive(Serialize)]
+pub struct BenchResult {
+    /// Drive model name
+    pub model_name: String,
+    /// Path to the result directory in the database
+    #[serde(skip_serializing)]
+    pub dir: String,
+    /// Path to the source result file
+    #[serde(skip_serializing)]
+    pub result_file: String,
+    /// resctl-bench version used to generate the result (major.minor)
+    pub version: String,
+    /// Github issue the result is related to, if any
+    pub issue: Option<u64>,
+    /// Result file url, if provided through a Github issue
+    pub url: Option<String>,
+    /// Github user who submitted the result, if any
+    pub submitter: Option<String>,
+    /// Date the result was imported (RFC 3339)
+    pub imported_at: Option<String>,
+    /// Seconds it took to download and validate the result
+    pub processing_secs: Option<f64>,
+    /// Checksum advertised by the host of `url` the download was
+    /// verified against, as `<header>:<md5>`
+    pub verified_checksum: Option<String>,
+    /// Identifying fields scrubbed from the result before storing it
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub scrubbed: Vec<String>,
+    /// Date (YYYY-MM-DD) until which the result is held back from the
+    /// database
+    pub embargo_until: Option<String>,
+    /// Detached signature submitted with the result, verified along with
+    /// it
+    #[serde(skip_serializing)]
+    pub signature_file: Option<String>,
+    /// Vendor whose key made the signature of the result
+    pub signed_by: Option<String>,
+}
+
+impl BenchResult {
+    /// Creates a BenchResult extracting the model and version info from
+    /// a json file (`json_result_file`) and set it to store the output
+    /// data into `database_path`
+    pub async fn new(json_result_file: &str, database_path: &str)
+    -> Result<Self>
+    {
+        let _stage = RESOURCES.start(PipelineStage::Validate);
+        let result = load_json(json_result_file)?;
+        let full_version = result[0]["sysinfo"]["bench_version"]
+            .to_string()
+            .split_whitespace()
+            .collect::<Vec<_>>()[0]
+            .to_string();
+        let version = {
+            let v = semver::Version::parse(&full_version)?;
+            format!("{}.{}", v.major, v.minor)
+        };
+        semver::Version::parse(&full_version)?;
+        let model_name = result[0]["sysinfo"]["sysreqs_report"]["scr_dev_model"]
+            .to_string()
+            .replace(" ", "_");
+        let dir = Database::new(database_path)
+            .model_dir(&version, &model_name)?
+            .to_string_lossy()
+            .to_string();
+        Ok(BenchResult {
+            model_name,
+            dir,
+            result_file: json_result_file.to_string(),
+            version,
+            issue: None,
+            url: None,
+            submitter: None,
+            imported_at: Some(chrono::Utc::now().to_rfc3339()),
+            processing_secs: None,
+            verified_checksum: None,
+            scrubbed: vec![],
+            embargo_until: None,
+            signature_file: None,
+            signed_by: None,
+        })
+    }
+
+    /// Verifies the detached signature submitted with the result, if any,
+    /// recording the vendor who made it. It has to run before the result
+    /// is scrubbed, which changes its contents.
+    pub fn verify_signature(&mut self) -> Result<()> {
+        let Some(signature) = self.signature_file.take() else {
+            return Ok(());
+        };
+        let verified = match signature_settings() {
+            Some(settings) => settings.verify(Path::new(&self.result_file), Path::new(&signature)),
+            None => Err(anyhow::anyhow!("No vendor keys to verify the signature of the result against")),
+        };
+        fs::remove_file(&signature).ok();
+        self.signed_by = Some(verified?);
+        Ok(())
+    }
+
+    /// Removes or hashes the identifying fields of the result file
+    /// before it is validated and stored, recording the scrubbed ones
+    pub fn scrub(&mut self) -> Result<()> {
+        self.scrubbed = scrubber().scrub_file(Path::new(&self.result_file))?;
+        Ok(())
+    }
+
+    /// Runs resctl-demo to validate the file in self.path, checks that it
+    /// has an iocost-tune run, then checks it against the rules of the
+    /// database.
+    pub fn validate(&self) -> Result<()> {
+        let _stage = RESOURCES.start(PipelineStage::Validate);
+        // resctl-bench needs a file to merge into, of its own so that
+        // validations can run concurrently
+        let merge_dir = tempfile::tempdir()?;
+        let merged = merge_dir.path().join("result.json");
+        run_resctl(
+            &self.version,
+            &[OsStr::new("--result"), merged.as_os_str(), OsStr::new("merge"), OsStr::new(&self.result_file)],
+        )?;
+        let result = load_json(&self.result_file)?;
+        if !result.members().any(|v| v["spec"]["kind"] == "iocost-tune") {
+            bail!("The result has no iocost-tune run");
+        }
+        let violations = rules().evaluate(&self.model_name, &result);
+        if !violations.is_empty() {
+            bail!("Rejected by the rules of the database: {}", violations.join(", "));
+        }
+        Ok(())
+    }
+
+    /// Runs the checks of every submitted result, whichever way it came
+    /// in: verifies its signature, scrubs and validates it
+    pub fn check(&mut self) -> Result<()> {
+        self.verify_signature()?;
+        self.scrub()?;
+        self.validate()
+    }
+
+    /// Returns a path for the result file in the DB
+    pub fn db_file(&self) -> PathBuf {
+        PathBuf::from(&self.dir).join(&self.result_file)
+    }
+
+    /// Returns a path for the metadata file in the DB
+    pub fn metadata_file_path(&self) -> PathBuf {
+        let basename = Path::new(&self.result_file)
+            .with_extension("")
+            .with_extension("")
+            .to_str()
+            .unwrap()
+            .to_string();
+        let mut metadata_filepath = PathBuf::from(&self.dir).join(basename);
+        metadata_filepath.set_extension("json.metadata");
+        metadata_filepath
+    }
+
+    /// Processes the result and stores the output files in the DB.
+    /// If an `id` string is provided, it'll be used to name the
+    /// directory for the pdf outputs. With LFS storage enabled, the
+    /// result file is stored as an LFS pointer. Returns the files of the
+    /// DB to stage.
+    pub fn add_to_database(&self, id: Option<&str>) -> Result<Vec<PathBuf>> {
+        let pdfs_dir = match id {
+            Some(id) => PathBuf::from(".")
+                .join(format!("pdfs-for-{}", id)),
+            None => {
+                PathBuf::from(".")
+                    .join(format!("pdfs-for-{}-{}", &self.model_name, &self.version))
+            }
+        };
+        save_pdf_to(&self.version, &PathBuf::from(&self.result_file), &pdfs_dir, None)?;
+        // Generate DB directory and place the result file there
+        fs::create_dir_all(&self.dir).ok();
+        fs::rename(&self.result_file, self.db_file())?;
+        // Create metadata file and save it in the DB dir
+        let mut metadata_file = fs::File::create(self.metadata_file_path())?;
+        write!(metadata_file, "{}", serde_json::to_string(self)?)?;
+        let mut files = vec![self.db_file(), self.metadata_file_path()];
+        if lfs_settings().enabled {
+            lfs::convert_to_pointer(&self.db_file())?;
+            let database_dir = Path::new(&self.dir).parent().and_then(Path::parent).unwrap();
+            files.push(lfs::ensure_gitattributes(database_dir)?);
+        }
+        Ok(files)
+    }
+
+    /// Moves the result file and its metadata to the `staging` area of
+    /// the embargoed results, under the keys they will have in the
+    /// database
+    pub fn hold_back(&self, staging: &dyn Storage) -> Result<()> {
+        let database_dir = Path::new(&self.dir).parent().and_then(Path::parent).unwrap();
+        let key = |path: &Path| key_for(database_dir, path).unwrap();
+        staging.write(&key(&self.metadata_file_path()), serde_json::to_string(self)?.as_bytes())?;
+        staging.write(&key(&self.db_file()), &fs::read(&self.result_file)?)?;
+        staging.flush()?;
+        fs::remove_file(&self.result_file)?;
+        Ok(())
+    }
+}
+
+/// Models a resctl-bench high-level summary output
+pub struct HighLevel {
+    pub version: String,
+    pub model_name: String,
+    pub new_files: u64,
+    /// New files signed by their vendor, by vendor
+    pub signed_by: BTreeMap<String, u64>,
+}
+
+impl HighLevel {
+    pub fn new(version: &str, model_name: &str) -> Self {
+        HighLevel {
+            version: version.to_string(),
+            model_name: model_name.to_string(),
+            new_files: 0,
+            signed_by: BTreeMap::new(),
+        }
+    }
+
+    pub fn increment(&mut self) {
+        self.new_files += 1;
+    }
+
+    /// Counts one of the new files as signed by `vendor`
+    pub fn add_signed(&mut self, vendor: &str) {
+        *self.signed_by.entry(vendor.to_string()).or_default() += 1;
+    }
+
+    /// Runs resctl-bench to generate a high-level summary, if
+    /// available, and returns it as a String. The layout of `database`
+    /// must exist.
+    pub fn format_high_level(&self, database: &Database) -> Result<String> {
+        match self.has_high_level()? {
+            true => Ok(self.summarize(database)?.high_level),
+            false => Ok(String::new()),
+        }
+    }
+
+    /// Whether `resctl-bench` can generate the high-level summary
+    /// (available since v2.2.3)
+    fn has_high_level(&self) -> Result<bool> {
+        let resctl_bench_version = BenchVersion::new(&self.version)?;
+        Ok(!VersionReq::parse("<2.2.3").unwrap().matches(&resctl_bench_version.semver))
+    }
+
+    /// Merges the results of the model to summarize them: the high-level
+    /// summary of resctl-bench, if available, and the data points of the
+    /// iocost-tune series. The layout of `database` must exist.
+    pub fn summarize(&self, database: &Database) -> Result<MergeSummary> {
+        // Merged apart from the merged results of the database, which
+        // other runs may be writing
+        let merge_dir = tempfile::tempdir()?;
+        let path = merge_dir.path().join("merged.json.gz");
+        BenchMerge::do_merge(
+            &self.version,
+            &database.model_dir(&self.version, &self.model_name)?,
+            &path,
+        )?;
+
+        let high_level = match self.has_high_level()? {
+            true => FORMATTER.format(&self.version, &path, "iocost-tune:high-level")?,
+            false => String::new(),
+        };
+        Ok(MergeSummary {
+            high_level,
+            series: BenchMerge::get_series_points(&path)?,
+        })
+    }
+}
+
+/// Summary of the merged results of a model
+pub struct MergeSummary {
+    /// High-level summary of resctl-bench, empty before v2.2.3
+    pub high_level: String,
+    /// Data points of the iocost-tune series, by series
+    pub series: BTreeMap<String, SeriesPoints>,
+}
+
+impl MergeSummary {
+    /// Lists the data points of the series, flagging the ones with too
+    /// few of them
+    pub fn format_series(&self) -> String {
+        let mut text = String::from("Data points per series:\n");
+        for (name, points) in &self.series {
+            text.push_str(&format!("- {}: {} data points, {} outliers", name, points.data, points.outliers));
+            if points.is_starved() {
+                text.push_str(&format!(" (fewer than {})", MINIMUM_DATA_POINTS));
+            }
+            text.push('\n');
+        }
+        text
+    }
+}
+
+/// Name of the ledger of submissions that failed validation, stored at
+/// the root of the database
+pub static FAILED_SUBMISSIONS_FILE: &str = "failed-submissions.jsonl";
+
+/// A submission that failed validation
+#[derive(Debug, Serialize, serde::Deserialize)]
+pub struct FailedSubmission {
+    pub issue: Option<u64>,
+    pub url: String,
+    pub submitter: Option<String>,
+    pub failed_at: String,
+    pub error: String,
+}
+
+impl FailedSubmission {
+    /// Appends the failure to the ledger in `database_path` and returns
+    /// the ledger path
+    pub fn record(&self, database_path: &str) -> Result<PathBuf> {
+        let path = PathBuf::from(database_path).join(FAILED_SUBMISSIONS_FILE);
+        fs::create_dir_all(database_path)?;
+        let mut file = fs::OpenOptions::new().create(true).append(true).open(&path)?;
+        writeln!(file, "{}", serde_json::to_string(self)?)?;
+        Ok(path)
+    }
+
+    /// Loads every failure recorded in the ledger in `database_path`
+    pub fn load_all(database_path: &Path) -> Result<Vec<Self>> {
+        let path = database_path.join(FAILED_SUBMISSIONS_FILE);
+        if !path.exists() {
+            return Ok(vec![]);
+        }
+        Ok(fs::read_to_string(path)?
+            .lines()
+            .filter_map(|l| serde_json::from_str(l).ok())
+            .collect())
+    }
+}
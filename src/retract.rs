@@ -0,0 +1,137 @@
+use anyhow::{bail, Result};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::bot_commands::find_result;
+use crate::git::commit_to_branch;
+use crate::index::ResultMetadata;
+use crate::readme::MODEL_README;
+use crate::workflow::regenerate_models;
+use crate::workspace::workspace;
+
+/// A result removed from the database
+#[derive(Debug)]
+pub struct Retraction {
+    pub md5: String,
+    pub model_name: String,
+    pub version: String,
+    pub reason: String,
+    /// Metadata of the result, to tell where it came from
+    pub metadata: Option<ResultMetadata>,
+    /// Files removed from the database
+    pub removed: Vec<PathBuf>,
+}
+
+impl Retraction {
+    /// Branch the retraction is committed to, for review
+    pub fn branch(&self) -> String {
+        format!("iocost-bot/retract-{}", self.md5)
+    }
+
+    pub fn title(&self) -> String {
+        format!(
+            "Retract result {} of {} ({})",
+            self.md5, self.model_name, self.version
+        )
+    }
+
+    /// Documents the retraction: its reason, who asked for it and the
+    /// submission the result came from
+    pub fn description(&self, requested_by: Option<&str>) -> String {
+        let mut description = format!("Reason: {}\n", self.reason);
+        if let Some(requested_by) = requested_by {
+            description.push_str(&format!("Requested by: {}\n", requested_by));
+        }
+        let metadata = self.metadata.as_ref();
+        if let Some(issue) = metadata.and_then(|m| m.issue) {
+            description.push_str(&format!("Submitted in: #{}\n", issue));
+        }
+        if let Some(url) = metadata.and_then(|m| m.url.as_deref()) {
+            description.push_str(&format!("Original file: {}\n", url));
+        }
+        description.push_str(
+            "\nThe merge, pdf and hwdb entry of the model were regenerated without the result.",
+        );
+        description
+    }
+
+    /// Commits the removal of the files to the retraction branch and
+    /// returns the branch
+    pub fn commit(&self, requested_by: Option<&str>) -> Result<String> {
+        let git_repo = workspace().repository()?;
+        let mut index = git_repo.index()?;
+        for path in &self.removed {
+            index.remove_path(path)?;
+        }
+        index.write()?;
+        let message = format!("{}\n\n{}", self.title(), self.description(requested_by));
+        let branch = self.branch();
+        commit_to_branch(&git_repo, &mut index, &message, &branch)?;
+        Ok(branch)
+    }
+}
+
+/// Removes the result with the `md5` checksum and its metadata from the
+/// database in `database_dir`, then regenerates the merge, pdf and hwdb
+/// entry of its model
+pub fn retract(database_dir: &Path, md5: &str, reason: &str) -> Result<Retraction> {
+    if reason.trim().is_empty() {
+        bail!("A reason is needed to retract a result");
+    }
+    let result = find_result(database_dir, md5)?;
+    let model_dir = result.parent().unwrap().to_path_buf();
+    let model_name = model_dir.file_name().unwrap().to_string_lossy().to_string();
+    let version = model_dir
+        .parent()
+        .and_then(|p| p.file_name())
+        .unwrap()
+        .to_string_lossy()
+        .to_string();
+    let metadata = ResultMetadata::load_for(&result);
+
+    let mut removed = vec![result.clone()];
+    let metadata_path = ResultMetadata::path_for(&result);
+    if metadata_path.exists() {
+        removed.push(metadata_path);
+    }
+    for path in &removed {
+        fs::remove_file(path)?;
+    }
+    // The README alone doesn't keep the model directory
+    let readme = model_dir.join(MODEL_README);
+    if readme.exists() && fs::read_dir(&model_dir)?.count() == 1 {
+        fs::remove_file(&readme)?;
+        removed.push(readme);
+    }
+    if fs::read_dir(&model_dir)?.next().is_none() {
+        println!("No results left for {} ({})", model_name, version);
+        fs::remove_dir(&model_dir)?;
+    }
+
+    let changed = BTreeSet::from([(version.clone(), model_name.clone())]);
+    regenerate_models(&database_dir.to_string_lossy(), &changed, None)?;
+    Ok(Retraction {
+        md5: md5.to_lowercase(),
+        model_name,
+        version,
+        reason: reason.trim().to_string(),
+        metadata,
+        removed,
+    })
+}
+
+/// Runs the `retract` command: retracts the result with the `md5`
+/// checksum and, with `commit`, commits the removal to the retraction
+/// branch
+pub fn run_retract(database_dir: &Path, md5: &str, reason: &str, commit: bool) -> Result<()> {
+    let retraction = retract(database_dir, md5, reason)?;
+    for path in &retraction.removed {
+        println!("  D {}", path.display());
+    }
+    if commit {
+        let branch = retraction.commit(None)?;
+        println!("Committed the retraction to {}", branch);
+    }
+    Ok(())
+}
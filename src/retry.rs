@@ -0,0 +1,76 @@
+use std::sync::OnceLock;
+use std::thread::sleep;
+use std::time::Duration;
+
+use crate::config::{set_once, RetryConfig};
+use crate::error::{Error, Result};
+use crate::progress::log;
+
+/// How the resctl-bench merge and format runs are retried, as they
+/// occasionally fail transiently, e.g. when runs overlap
+#[derive(Debug, Clone)]
+pub struct RetrySettings {
+    /// Runs of a merge or format before it is declared failed
+    pub attempts: u32,
+    /// Time to wait before the next attempt
+    pub delay: Duration,
+}
+
+impl Default for RetrySettings {
+    fn default() -> Self {
+        RetrySettings {
+            attempts: 2,
+            delay: Duration::from_secs(5),
+        }
+    }
+}
+
+impl RetrySettings {
+    /// Creates the settings of the [retry] section of the config file
+    pub fn from_config(config: &RetryConfig) -> Self {
+        let default = RetrySettings::default();
+        RetrySettings {
+            attempts: config.attempts.unwrap_or(default.attempts).max(1),
+            delay: config.delay_secs.map_or(default.delay, Duration::from_secs),
+        }
+    }
+}
+
+static RETRY_SETTINGS: OnceLock<RetrySettings> = OnceLock::new();
+
+/// Sets how the resctl-bench runs are retried.
+pub fn set_retry_settings(settings: RetrySettings) -> anyhow::Result<()> {
+    set_once(&RETRY_SETTINGS, settings, "retry settings")
+}
+
+/// Returns how the resctl-bench runs are retried, the default settings
+/// if none were set
+pub fn retry_settings() -> &'static RetrySettings {
+    RETRY_SETTINGS.get_or_init(RetrySettings::default)
+}
+
+/// Calls `run` until it succeeds, the attempts are exhausted or it fails
+/// otherwise than with resctl-bench reporting an error, as a missing
+/// binary or an invalid file won't get any better. Each failed attempt
+/// is logged with `what` was run.
+pub fn with_retries<T>(what: &str, mut run: impl FnMut() -> Result<T>) -> Result<T> {
+    let settings = retry_settings();
+    let mut attempt = 1;
+    loop {
+        match run() {
+            Err(e @ Error::ResctlFailed { .. }) if attempt < settings.attempts => {
+                log(format!(
+                    "{} failed (attempt {}/{}), retrying in {}s: {}",
+                    what,
+                    attempt,
+                    settings.attempts,
+                    settings.delay.as_secs(),
+                    e
+                ));
+                sleep(settings.delay);
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use anyhow::{bail, Result};
+use git2::Repository;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::config::{set_once, ReviewConfig};
+use crate::git::git_path;
+use crate::index::DatabaseIndex;
+use crate::storage::{key_for, object_storage};
+
+/// Directory of the repository the results awaiting the review of a
+/// maintainer are imported into, with the layout of the database
+pub static PENDING_DIR: &str = "pending";
+
+/// Settings of the review of the submissions
+#[derive(Debug, Default, Clone)]
+pub struct ReviewSettings {
+    /// Whether the results of first-time contributors are held in the
+    /// pending area until a maintainer accepts them
+    pub first_time_contributors: bool,
+}
+
+impl ReviewSettings {
+    /// Creates the settings of the [review] section of the config file
+    pub fn from_config(config: &ReviewConfig) -> Self {
+        ReviewSettings { first_time_contributors: config.first_time_contributors }
+    }
+
+    /// Whether the results `submitter` submits need to be reviewed:
+    /// they never got results in the database of `index`
+    pub fn needs_review(&self, index: &DatabaseIndex, submitter: &str) -> bool {
+        self.first_time_contributors
+            && !index
+                .entries
+                .iter()
+                .flat_map(|e| &e.metadata)
+                .any(|m| m.submitter.as_deref() == Some(submitter))
+    }
+}
+
+static REVIEW_SETTINGS: OnceLock<ReviewSettings> = OnceLock::new();
+
+/// Sets the settings of the review.
+pub fn set_review_settings(settings: ReviewSettings) -> Result<()> {
+    set_once(&REVIEW_SETTINGS, settings, "review settings")
+}
+
+/// Returns the settings of the review, the default ones if none were set
+pub fn review_settings() -> &'static ReviewSettings {
+    REVIEW_SETTINGS.get_or_init(ReviewSettings::default)
+}
+
+/// Returns the directory of the pending area matching the `dir` of the
+/// database in `database_path`
+pub fn pending_dir(database_path: &str, dir: &str) -> PathBuf {
+    Path::new(PENDING_DIR).join(Path::new(dir).strip_prefix(database_path).unwrap_or(Path::new(dir)))
+}
+
+/// Moves the pending results of the branch `branch_name` into the
+/// database in `database_path`, or into its object storage if it has one,
+/// with a new commit on top of the branch. The local branch is updated
+/// from `tip`, its last commit. Returns the moved files.
+pub fn accept_pending(
+    git_repo: &Repository,
+    branch_name: &str,
+    tip: &git2::Commit,
+    database_path: &str,
+    message: &str,
+) -> Result<Vec<PathBuf>> {
+    let mut index = git2::Index::new()?;
+    index.read_tree(&tip.tree()?)?;
+    let pending: Vec<git2::IndexEntry> = index
+        .iter()
+        .filter(|e| Path::new(&*String::from_utf8_lossy(&e.path)).starts_with(PENDING_DIR))
+        .collect();
+    if pending.is_empty() {
+        bail!("{} has no results pending review", branch_name);
+    }
+    let mut moved = vec![];
+    for mut entry in pending {
+        let path = PathBuf::from(String::from_utf8_lossy(&entry.path).to_string());
+        let target = Path::new(database_path).join(path.strip_prefix(PENDING_DIR)?);
+        index.remove_path(&path)?;
+        match (object_storage(), key_for(Path::new(database_path), &target)) {
+            (Some(storage), Some(key)) => storage.write(&key, git_repo.find_blob(entry.id)?.content())?,
+            _ => {
+                let target_bytes = git_path(&target).into_bytes();
+                entry.flags = (entry.flags & !0xfff) | target_bytes.len().min(0xfff) as u16;
+                entry.path = target_bytes;
+                index.add(&entry)?;
+            }
+        }
+        moved.push(target);
+    }
+    if let Some(storage) = object_storage() {
+        storage.flush()?;
+    }
+    let tree = git_repo.find_tree(index.write_tree_to(git_repo)?)?;
+    let sig = git2::Signature::now("iocost bot", "iocost-bot@has.no.email")?;
+    let commit = git_repo.commit(None, &sig, &sig, message, &tree, &[tip])?;
+    git_repo.branch(branch_name, &git_repo.find_commit(commit)?, true)?;
+    Ok(moved)
+}
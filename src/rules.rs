@@ -0,0 +1,169 @@
+use anyhow::{bail, Context, Result};
+use json::JsonValue;
+use semver::{Version, VersionReq};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use crate::config::set_once;
+use crate::vendors::Vendors;
+
+/// File of the database repository declaring the acceptance criteria of
+/// the results, so that the policy can change without a new release of
+/// the pipeline
+pub static RULES_FILE: &str = "rules.toml";
+
+/// Acceptance criteria of the results, e.g.:
+///
+/// ```toml
+/// [default]
+/// required_sysreqs = ["IoCost", "NoWbt"]
+/// kernels = ">=5.10"
+/// max_outlier_ratio = 0.2
+///
+/// [vendors.Samsung]
+/// max_outlier_ratio = 0.3
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Criteria {
+    /// System requirements resctl-bench must have found satisfied, as
+    /// named in the sysreqs report of the results
+    pub required_sysreqs: Option<Vec<String>>,
+    /// Versions of the kernels the benchmarks may run on, as a semver
+    /// requirement
+    pub kernels: Option<String>,
+    /// Maximum ratio of outliers among the MOF data points of the
+    /// iocost-tune result
+    pub max_outlier_ratio: Option<f64>,
+}
+
+impl Criteria {
+    /// Returns the criteria with those set in `exception` replaced
+    fn with(&self, exception: &Criteria) -> Criteria {
+        Criteria {
+            required_sysreqs: exception.required_sysreqs.clone().or(self.required_sysreqs.clone()),
+            kernels: exception.kernels.clone().or(self.kernels.clone()),
+            max_outlier_ratio: exception.max_outlier_ratio.or(self.max_outlier_ratio),
+        }
+    }
+}
+
+/// Rules of the rules file: the default criteria and the exceptions of
+/// the vendors of the vendors file
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rules {
+    /// Criteria of the models without exceptions
+    #[serde(default)]
+    pub default: Criteria,
+    /// Criteria of the models of a vendor replacing the default ones
+    #[serde(default)]
+    pub vendors: BTreeMap<String, Criteria>,
+    #[serde(skip)]
+    vendor_models: Vendors,
+}
+
+/// Returns the major.minor.patch version at the start of a kernel
+/// release string, e.g. 6.1.0 for 6.1.0-rc1_fbk3
+fn kernel_version(release: &str) -> Option<Version> {
+    let numbers: Vec<u64> = release
+        .split(|c: char| !c.is_ascii_digit() && c != '.')
+        .next()?
+        .split('.')
+        .map_while(|n| n.parse().ok())
+        .collect();
+    match numbers[..] {
+        [major, minor, patch, ..] => Some(Version::new(major, minor, patch)),
+        [major, minor] => Some(Version::new(major, minor, 0)),
+        _ => None,
+    }
+}
+
+impl Rules {
+    /// Loads the rules file in `path`, if there is one, with the vendors
+    /// of the vendors file in `vendors_path` its exceptions refer to
+    pub fn load(path: &Path, vendors_path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Rules::default());
+        }
+        let contents = fs::read_to_string(path).with_context(|| format!("Failed to read {:?}", path))?;
+        let mut rules: Rules = toml::from_str(&contents).with_context(|| format!("Failed to parse {:?}", path))?;
+        rules.vendor_models = Vendors::load(vendors_path)?;
+        if let Some(vendor) = rules.vendors.keys().find(|v| !rules.vendor_models.vendors.contains_key(*v)) {
+            bail!("Unknown vendor {} in {:?}, not in {:?}", vendor, path, vendors_path);
+        }
+        let criteria = std::iter::once(&rules.default).chain(rules.vendors.values());
+        for kernels in criteria.filter_map(|c| c.kernels.as_ref()) {
+            VersionReq::parse(kernels)
+                .with_context(|| format!("Invalid kernels requirement {:?} in {:?}", kernels, path))?;
+        }
+        Ok(rules)
+    }
+
+    /// Returns the criteria applying to `model_name`: those of its
+    /// vendor if it has exceptions, the default ones otherwise
+    pub fn criteria_for(&self, model_name: &str) -> Criteria {
+        match self
+            .vendor_models
+            .vendor_for(model_name)
+            .and_then(|(vendor, _)| self.vendors.get(vendor))
+        {
+            Some(exception) => self.default.with(exception),
+            None => self.default.clone(),
+        }
+    }
+
+    /// Checks the `result` file contents of `model_name` against the
+    /// rules. Returns the reasons it is rejected, if any.
+    pub fn evaluate(&self, model_name: &str, result: &JsonValue) -> Vec<String> {
+        let criteria = self.criteria_for(model_name);
+        let report = &result[0]["sysinfo"]["sysreqs_report"];
+        let mut violations = vec![];
+        for sysreq in criteria.required_sysreqs.iter().flatten() {
+            if !report["satisfied"].members().any(|s| s == sysreq.as_str()) {
+                violations.push(format!("required sysreq {} is not satisfied", sysreq));
+            }
+        }
+        if let Some(kernels) = criteria.kernels.as_deref().and_then(|k| VersionReq::parse(k).ok()) {
+            let release = report["kernel_version"].as_str().unwrap_or_default();
+            match kernel_version(release) {
+                Some(version) if kernels.matches(&version) => (),
+                Some(_) => violations.push(format!("kernel {} is not in the allowed range {}", release, kernels)),
+                None => violations.push(format!("unknown kernel version {:?}", release)),
+            }
+        }
+        if let Some(max_ratio) = criteria.max_outlier_ratio {
+            let mof = result
+                .members()
+                .find(|v| v["spec"]["kind"] == "iocost-tune")
+                .map(|tune| &tune["result"]["data"]["MOF"]);
+            if let Some(mof) = mof {
+                let outliers = mof["outliers"].len();
+                let total = mof["data"].len() + outliers;
+                if total > 0 && outliers as f64 / total as f64 > max_ratio {
+                    violations.push(format!(
+                        "{} of the {} data points are outliers, over the maximum ratio of {}",
+                        outliers, total, max_ratio
+                    ));
+                }
+            }
+        }
+        violations
+    }
+}
+
+static RULES: OnceLock<Rules> = OnceLock::new();
+
+/// Sets the rules the results are checked against.
+pub fn set_rules(rules: Rules) -> Result<()> {
+    set_once(&RULES, rules, "rules")
+}
+
+/// Returns the rules the results are checked against, none if they were
+/// not set
+pub fn rules() -> &'static Rules {
+    RULES.get_or_init(Rules::default)
+}
@@ -0,0 +1,149 @@
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::io::{Read, Write};
+use std::path::Path;
+use std::sync::{LazyLock, OnceLock};
+
+use crate::config::{set_once, ScrubConfig};
+
+/// Fields removed from the results by default
+const DEFAULT_REMOVE: &[&str] = &["hostname", "nodename", "username"];
+/// Fields hashed in the results by default
+const DEFAULT_HASH: &[&str] = &["serial", "serial_number", "scr_dev_serial", "machine_id", "product_uuid"];
+
+/// User name of the home directory paths
+static HOME_PATH: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"/home/([^/\s]+)").unwrap());
+
+/// Removes or hashes the potentially identifying fields of the submitted
+/// results before they are stored in the public database. Fields are
+/// matched by name at any depth of the result.
+#[derive(Debug, Clone)]
+pub struct Scrubber {
+    /// Fields removed from the results
+    pub remove: Vec<String>,
+    /// Fields whose value is replaced with a hash of it, so that the
+    /// results of the same machine can still be told apart
+    pub hash: Vec<String>,
+    /// Whether to hash the user names of the /home/<user> paths found in
+    /// the string values
+    pub home_paths: bool,
+}
+
+impl Default for Scrubber {
+    fn default() -> Self {
+        Scrubber {
+            remove: DEFAULT_REMOVE.iter().map(|f| f.to_string()).collect(),
+            hash: DEFAULT_HASH.iter().map(|f| f.to_string()).collect(),
+            home_paths: true,
+        }
+    }
+}
+
+/// Returns a short hash of `value`
+fn hash(value: &str) -> String {
+    format!("sha256:{}", &hex::encode(Sha256::digest(value.as_bytes()))[..16])
+}
+
+/// Whether `value` is a hash of a scrubbed field
+fn is_hash(value: &str) -> bool {
+    value.starts_with("sha256:")
+}
+
+impl Scrubber {
+    /// Creates the scrubber of the [scrub] section of the config file,
+    /// using the default fields for the missing settings
+    pub fn from_config(config: &ScrubConfig) -> Self {
+        let default = Scrubber::default();
+        Scrubber {
+            remove: config.remove.clone().unwrap_or(default.remove),
+            hash: config.hash.clone().unwrap_or(default.hash),
+            home_paths: config.home_paths.unwrap_or(default.home_paths),
+        }
+    }
+
+    /// Scrubs `value`, found at `path` of the result, adding the paths
+    /// of the scrubbed fields to `scrubbed`
+    fn scrub_value(&self, value: &mut Value, path: &str, scrubbed: &mut Vec<String>) {
+        let child = |key: &str| match path {
+            "" => key.to_string(),
+            _ => format!("{}.{}", path, key),
+        };
+        match value {
+            Value::Object(fields) => {
+                fields.retain(|key, _| {
+                    let remove = self.remove.contains(key);
+                    if remove {
+                        scrubbed.push(child(key));
+                    }
+                    !remove
+                });
+                for (key, field) in fields.iter_mut() {
+                    // Results are scrubbed again when an interrupted
+                    // import resumes, hashes are left as they are.
+                    let hashed = field.as_str().is_some_and(is_hash);
+                    if self.hash.contains(key) && !field.is_null() && !hashed {
+                        let contents = match &*field {
+                            Value::String(s) => s.clone(),
+                            other => other.to_string(),
+                        };
+                        *field = Value::String(hash(&contents));
+                        scrubbed.push(child(key));
+                    } else {
+                        self.scrub_value(field, &child(key), scrubbed);
+                    }
+                }
+            }
+            Value::Array(items) => {
+                for (i, item) in items.iter_mut().enumerate() {
+                    self.scrub_value(item, &child(&i.to_string()), scrubbed);
+                }
+            }
+            Value::String(s) if self.home_paths => {
+                let replaced = HOME_PATH.replace_all(s, |c: &regex::Captures| match is_hash(&c[1]) {
+                    true => c[0].to_string(),
+                    false => format!("/home/{}", hash(&c[1])),
+                });
+                if replaced != *s {
+                    *s = replaced.to_string();
+                    scrubbed.push(path.to_string());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Scrubs the gzipped result file in `path` in place. Returns the
+    /// paths of the scrubbed fields, the file is left untouched if there
+    /// are none.
+    pub fn scrub_file(&self, path: &Path) -> Result<Vec<String>> {
+        let mut contents = vec![];
+        libflate::gzip::Decoder::new(std::fs::File::open(path)?)
+            .and_then(|mut d| d.read_to_end(&mut contents))
+            .with_context(|| format!("Can't decompress {:?}", path))?;
+        let mut result: Value =
+            serde_json::from_slice(&contents).with_context(|| format!("{:?} is not valid json", path))?;
+        let mut scrubbed = vec![];
+        self.scrub_value(&mut result, "", &mut scrubbed);
+        if !scrubbed.is_empty() {
+            let mut encoder = libflate::gzip::Encoder::new(vec![])?;
+            encoder.write_all(serde_json::to_string(&result)?.as_bytes())?;
+            std::fs::write(path, encoder.finish().into_result()?)?;
+        }
+        Ok(scrubbed)
+    }
+}
+
+static SCRUBBER: OnceLock<Scrubber> = OnceLock::new();
+
+/// Sets the scrubber of the submitted results.
+pub fn set_scrubber(scrubber: Scrubber) -> Result<()> {
+    set_once(&SCRUBBER, scrubber, "scrubber")
+}
+
+/// Returns the scrubber of the submitted results, the default one if
+/// none was set
+pub fn scrubber() -> &'static Scrubber {
+    SCRUBBER.get_or_init(Scrubber::default)
+}
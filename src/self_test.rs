@@ -0,0 +1,192 @@
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::bench_runner::{set_bench_runner, MockBench};
+use crate::common::{resctl_bench_path, BENCH_VERSIONS};
+use crate::database::Database;
+use crate::hwdb::{diff_sections, export_hwdb, HWDB_FILE};
+use crate::vendor_overrides::VendorOverrides;
+use crate::workflow::import_local_files;
+use crate::workspace::{set_workspace, Workspace, DATABASE_DIR, HWDB_INPUTS_DIR};
+
+/// Results the self-test imports, bundled in the binary so that it runs
+/// from anywhere
+static FIXTURES: &[(&str, &[u8])] = &[
+    ("samsung-x-fw1.json.gz", include_bytes!("../tests/fixtures/self-test/samsung-x-fw1.json.gz")),
+    ("samsung-x-fw2-a.json.gz", include_bytes!("../tests/fixtures/self-test/samsung-x-fw2-a.json.gz")),
+    ("samsung-x-fw2-b.json.gz", include_bytes!("../tests/fixtures/self-test/samsung-x-fw2-b.json.gz")),
+    ("wdc-y.json.gz", include_bytes!("../tests/fixtures/self-test/wdc-y.json.gz")),
+    ("wdc-y-2.1.json.gz", include_bytes!("../tests/fixtures/self-test/wdc-y-2.1.json.gz")),
+];
+
+/// Models of the fixtures expected to get a hwdb entry
+static EXPECTED_MODELS: &[&str] = &["SAMSUNG_X", "WDC_Y"];
+
+/// Golden files of the outputs, in the source tree
+static GOLDEN_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden");
+
+/// hwdb file the pipeline generates from the fixtures with the mock
+/// resctl-bench, without its generation date
+static GOLDEN_HWDB: (&str, &str) = ("self-test.hwdb", include_str!("../tests/golden/self-test.hwdb"));
+
+/// Files of the database the fixtures are imported into
+static GOLDEN_DATABASE: (&str, &str) =
+    ("self-test-database.txt", include_str!("../tests/golden/self-test-database.txt"));
+
+/// Returns `hwdb` without the line of its header with the generation
+/// date, which changes on every run
+fn strip_generation_date(hwdb: &str) -> String {
+    hwdb.lines()
+        .filter(|l| !l.starts_with("# This file was auto-generated on"))
+        .map(|l| format!("{}\n", l))
+        .collect()
+}
+
+/// Lists the files of the database in `database_dir`, one per line
+fn list_database(database_dir: &Path) -> Result<String> {
+    let mut files = vec![];
+    for path in glob::glob(&database_dir.join("**").join("*").to_string_lossy())?.flatten() {
+        if path.is_file() {
+            files.push(format!("{}\n", path.strip_prefix(database_dir)?.display()));
+        }
+    }
+    files.sort();
+    Ok(files.concat())
+}
+
+/// Outcome of a check of the self-test
+struct SelfTestCheck {
+    name: &'static str,
+    /// What differs from the expectation, if anything
+    mismatch: Option<String>,
+    /// Golden file the output was compared with, and the output
+    golden: Option<(&'static str, String)>,
+}
+
+impl SelfTestCheck {
+    /// Compares the `actual` output with the `golden` file, as (name,
+    /// contents)
+    fn golden(name: &'static str, golden: (&'static str, &str), actual: String) -> Self {
+        SelfTestCheck {
+            name,
+            mismatch: diff_sections(golden.1, &actual),
+            golden: Some((golden.0, actual)),
+        }
+    }
+}
+
+/// Runs the import, merge and hwdb generation of the pipeline on the
+/// bundled fixtures in a temporary workspace, and returns the checks of
+/// the outputs. With `real`, the resctl-bench binaries of `binaries_dir`
+/// run, and the outputs, which depend on their version, are only checked
+/// for an entry of each model; otherwise the mock runs and the outputs
+/// are compared with the golden files.
+async fn run_pipeline(workspace_dir: &Path, binaries_dir: &Path, real: bool) -> Result<Vec<SelfTestCheck>> {
+    if real {
+        for version in BENCH_VERSIONS {
+            let binary = binaries_dir.join(resctl_bench_path(version));
+            if !binary.exists() {
+                bail!("{} not found, run `iocost-ci setup` first or leave out --real", binary.display());
+            }
+            let dir = workspace_dir.join(resctl_bench_path(version));
+            fs::create_dir_all(dir.parent().unwrap())?;
+            fs::copy(&binary, &dir)?;
+        }
+    } else {
+        set_bench_runner(Box::new(MockBench))?;
+    }
+    set_workspace(Workspace::enter(workspace_dir)?)?;
+
+    let fixtures_dir = PathBuf::from("fixtures");
+    fs::create_dir_all(&fixtures_dir)?;
+    let mut files = vec![];
+    for (name, contents) in FIXTURES {
+        let path = fixtures_dir.join(name);
+        fs::write(&path, contents)?;
+        files.push(path);
+    }
+
+    let database = Database::new(DATABASE_DIR);
+    database.ensure_layout()?;
+    let (outcome, _) = import_local_files(&files, DATABASE_DIR).await.context("Import failed")?;
+    if outcome.failed > 0 {
+        bail!("{} of the {} fixtures failed validation", outcome.failed, FIXTURES.len());
+    }
+    let hwdb_path = PathBuf::from(HWDB_FILE);
+    let models = export_hwdb(
+        &database,
+        Path::new(HWDB_INPUTS_DIR),
+        &hwdb_path,
+        None,
+        &VendorOverrides::default(),
+    )
+    .context("hwdb generation failed")?;
+    let hwdb = strip_generation_date(&fs::read_to_string(&hwdb_path)?);
+
+    let mut checks = vec![SelfTestCheck {
+        name: "import",
+        mismatch: (outcome.imported != FIXTURES.len())
+            .then(|| format!("{} of the {} fixtures imported", outcome.imported, FIXTURES.len())),
+        golden: None,
+    }];
+    if real {
+        let missing: Vec<&str> = EXPECTED_MODELS
+            .iter()
+            .filter(|m| !models.iter().any(|model| model == *m))
+            .copied()
+            .collect();
+        checks.push(SelfTestCheck {
+            name: "hwdb entries",
+            mismatch: (!missing.is_empty()).then(|| format!("no entry for {}", missing.join(", "))),
+            golden: None,
+        });
+    } else {
+        checks.push(SelfTestCheck::golden("database", GOLDEN_DATABASE, list_database(Path::new(DATABASE_DIR))?));
+        checks.push(SelfTestCheck::golden("hwdb", GOLDEN_HWDB, hwdb));
+    }
+    Ok(checks)
+}
+
+/// Runs the `self-test` command: runs the pipeline on the bundled
+/// fixtures, with the mock resctl-bench or, with `real`, the binaries of
+/// the workspace, and fails if its outputs aren't the expected ones.
+/// With `keep`, the temporary workspace is left for inspection. With
+/// `update_golden`, the golden files of the source tree are overwritten
+/// with the outputs of the mock instead, after an intended change.
+pub async fn run_self_test(real: bool, keep: bool, update_golden: bool) -> Result<()> {
+    if real && update_golden {
+        bail!("The golden files are the outputs of the mock, --update-golden can't be used with --real");
+    }
+    let binaries_dir = std::env::current_dir()?;
+    let workspace_dir = tempfile::Builder::new().prefix("iocost-self-test-").tempdir()?;
+    let checks = run_pipeline(workspace_dir.path(), &binaries_dir, real).await;
+    // Leave the workspace before it is removed
+    std::env::set_current_dir(&binaries_dir)?;
+    if keep {
+        println!("Kept the self-test workspace in {}", workspace_dir.keep().display());
+    }
+    let mut checks = checks?;
+    if update_golden {
+        for check in checks.iter_mut() {
+            if let Some((file, output)) = &check.golden {
+                let path = Path::new(GOLDEN_DIR).join(file);
+                fs::write(&path, output).with_context(|| format!("Failed to write {}", path.display()))?;
+                check.mismatch = None;
+                println!("Updated {}", path.display());
+            }
+        }
+    }
+    for check in &checks {
+        match &check.mismatch {
+            None => println!("[ok]   {}", check.name),
+            Some(mismatch) => println!("[fail] {}\n{}", check.name, mismatch),
+        }
+    }
+    let failed = checks.iter().filter(|c| c.mismatch.is_some()).count();
+    if failed > 0 {
+        bail!("{} of the {} self-test checks failed", failed, checks.len());
+    }
+    println!("Self-test passed with the {} resctl-bench", if real { "real" } else { "mock" });
+    Ok(())
+}
@@ -0,0 +1,401 @@
+use anyhow::Result;
+use axum::body::Bytes;
+use axum::extract::{DefaultBodyLimit, Path as UrlPath, Query, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Json, Response};
+use axum::routing::{get, post};
+use axum::Router;
+use chrono::NaiveDate;
+use indicatif::HumanBytes;
+use serde::{Deserialize, Serialize};
+use serde_with::skip_serializing_none;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use subtle::ConstantTimeEq;
+use tokio::runtime::Handle;
+use tokio::sync::Mutex;
+
+use crate::database::Database;
+use crate::embargo::embargo_staging;
+use crate::hwdb::hwdb_section_for;
+use crate::git::{commit_to_branch, push_branch, reset_to_head};
+use crate::github::open_pull_request;
+use crate::index::{DatabaseIndex, ModelEntry};
+use crate::lfs::upload_branch_objects;
+use crate::limits::submission_limits;
+use crate::policy::{check_statement, Statement, Verdict};
+use crate::result::{check_result_contents, save_result_file, BenchResult, HighLevel};
+use crate::signatures::MAX_SIGNATURE_BYTES;
+use crate::signoff::SignOff;
+use crate::workspace::workspace;
+
+/// Maximum size accepted for an uploaded result file
+const MAX_UPLOAD_SIZE: usize = 64 * 1024 * 1024;
+
+/// Paths the API serves its data from
+pub struct ServeConfig {
+    pub database_dir: PathBuf,
+    /// Final hwdb file generated by merge-results
+    pub hwdb_file: PathBuf,
+    /// Enables the `POST /submit` endpoint when set
+    pub submit: Option<SubmitConfig>,
+}
+
+/// Settings for direct submissions through `POST /submit`
+pub struct SubmitConfig {
+    /// Bearer token clients must present to submit results
+    pub token: String,
+    /// Token used to push the branch and open the pull request. If not
+    /// set, the branch is only created locally.
+    pub github_token: Option<String>,
+    /// Database repository (owner/name)
+    pub repo: String,
+    /// Branch pull requests are opened against
+    pub base_branch: String,
+}
+
+struct ServeState {
+    config: ServeConfig,
+    /// Serializes submissions, as they all operate on the same git
+    /// working tree
+    submit_lock: Mutex<()>,
+}
+
+/// Query parameters of `POST /submit`
+#[derive(Deserialize)]
+struct SubmitParams {
+    /// Github login of the submitter, recorded with the result
+    submitter: Option<String>,
+    /// Text the policies of the database are checked against, as the
+    /// body of a submission issue: agreement to the data license,
+    /// `Signed-off-by` line, `embargo-until` date
+    statement: Option<String>,
+    /// ASCII-armored detached signature of the result by its vendor
+    signature: Option<String>,
+}
+
+/// Result uploaded through `POST /submit`, which passed the policy
+/// checks
+struct Upload {
+    contents: Bytes,
+    submitter: String,
+    signature: Option<String>,
+    signoff: SignOff,
+    /// End of the embargo of the result, until which it is held back
+    embargo: Option<NaiveDate>,
+}
+
+/// Where an uploaded result went
+enum Imported {
+    /// Committed to the branch
+    Branch(String),
+    /// Held back in the embargo staging area until the date
+    Held(NaiveDate),
+}
+
+#[skip_serializing_none]
+#[derive(Serialize)]
+struct SubmitResponse {
+    model_name: String,
+    version: String,
+    branch: Option<String>,
+    pull_request: Option<String>,
+    held_until: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ModelListItem<'a> {
+    model_name: &'a str,
+    versions: Vec<&'a str>,
+}
+
+#[derive(Serialize)]
+struct ModelSummary<'a> {
+    model_name: &'a str,
+    entries: Vec<&'a ModelEntry>,
+    hwdb: Option<String>,
+}
+
+/// Wraps errors raised while handling a request into a 500 response
+struct ServeError(anyhow::Error);
+
+impl IntoResponse for ServeError {
+    fn into_response(self) -> Response {
+        (StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", self.0)).into_response()
+    }
+}
+
+impl<E: Into<anyhow::Error>> From<E> for ServeError {
+    fn from(e: E) -> Self {
+        ServeError(e.into())
+    }
+}
+
+fn model_hwdb(config: &ServeConfig, model_name: &str) -> Option<String> {
+    fs::read_to_string(&config.hwdb_file)
+        .ok()
+        .and_then(|hwdb| hwdb_section_for(&hwdb, model_name))
+}
+
+/// GET /models
+async fn list_models(State(state): State<Arc<ServeState>>) -> Result<Response, ServeError> {
+    let index = DatabaseIndex::load(&state.config.database_dir)?;
+    let models: Vec<ModelListItem> = index
+        .models()
+        .into_iter()
+        .map(|(model_name, versions)| ModelListItem {
+            model_name,
+            versions,
+        })
+        .collect();
+    Ok(Json(models).into_response())
+}
+
+/// GET /models/{name}/hwdb
+async fn get_model_hwdb(
+    State(state): State<Arc<ServeState>>,
+    UrlPath(model_name): UrlPath<String>,
+) -> Response {
+    match model_hwdb(&state.config, &model_name) {
+        Some(hwdb) => hwdb.into_response(),
+        None => (
+            StatusCode::NOT_FOUND,
+            format!("No hwdb entry for {}", model_name),
+        )
+            .into_response(),
+    }
+}
+
+/// GET /models/{name}/summary
+async fn get_model_summary(
+    State(state): State<Arc<ServeState>>,
+    UrlPath(model_name): UrlPath<String>,
+) -> Result<Response, ServeError> {
+    let index = DatabaseIndex::load(&state.config.database_dir)?;
+    let entries = index.model(&model_name);
+    if entries.is_empty() {
+        return Ok((
+            StatusCode::NOT_FOUND,
+            format!("Model {} not found in the database", model_name),
+        )
+            .into_response());
+    }
+    let summary = ModelSummary {
+        model_name: &model_name,
+        entries,
+        hwdb: model_hwdb(&state.config, &model_name),
+    };
+    Ok(Json(summary).into_response())
+}
+
+/// Checks an uploaded result as the results submitted through Github
+/// issues are, then commits it to a new branch in the database
+/// repository, or holds it back until the end of its embargo. It runs
+/// resctl-bench and libgit2, so it has to run on a blocking thread, one
+/// submission at a time.
+fn import_upload(database_dir: &str, upload: &Upload) -> Result<(BenchResult, Imported)> {
+    check_result_contents(&upload.contents, None)?;
+    let git_repo = workspace().repository()?;
+    // Whatever the previous submission left, e.g. after failing
+    // half-way, doesn't end up in the branch of this one
+    reset_to_head(&git_repo, Path::new(database_dir))?;
+    // Named after its contents, as the downloads of the submissions
+    let path = save_result_file(&upload.contents)?;
+    let signature = match &upload.signature {
+        Some(signature) => {
+            let signature_path = format!("signature-{:x}.sig", md5::compute(signature));
+            fs::write(&signature_path, signature)?;
+            Some(signature_path)
+        }
+        None => None,
+    };
+    let checked = Handle::current()
+        .block_on(BenchResult::new(&path, database_dir))
+        .and_then(|mut result| {
+            result.submitter = Some(upload.submitter.clone());
+            result.signature_file = signature.clone();
+            result.check()?;
+            Ok(result)
+        });
+    let mut result = match checked {
+        Ok(result) => result,
+        Err(e) => {
+            fs::remove_file(&path).ok();
+            if let Some(signature) = &signature {
+                fs::remove_file(signature).ok();
+            }
+            return Err(e);
+        }
+    };
+    if let (Some(date), Some(staging)) = (upload.embargo, embargo_staging()) {
+        result.embargo_until = Some(date.to_string());
+        result.hold_back(staging)?;
+        return Ok((result, Imported::Held(date)));
+    }
+    let id = format!("upload-{:x}", md5::compute(&upload.contents));
+    let files = result.add_to_database(Some(&id))?;
+
+    let mut index = git_repo.index()?;
+    for file in &files {
+        index.add_path(file)?;
+    }
+    let mut high_level = HighLevel::new(&result.version, &result.model_name);
+    high_level.increment();
+    let database = Database::new(database_dir);
+    database.ensure_layout()?;
+    let summary = high_level.summarize(&database)?;
+    let message = format!(
+        "Automated update from direct submission\n\n[{} ({})] 1 new files\n{}{}\n\n{}",
+        result.model_name,
+        result.version,
+        summary.high_level,
+        summary.format_series(),
+        upload.signoff
+    );
+    let branch_name = format!("iocost-bot/{}", id);
+    commit_to_branch(&git_repo, &mut index, &message, &branch_name)?;
+    Ok((result, Imported::Branch(branch_name)))
+}
+
+/// POST /submit
+///
+/// Accepts a gzipped resctl-bench result as the request body, runs the
+/// same policy checks and validation as for the results submitted
+/// through Github issues and opens a pull request adding it to the
+/// database.
+async fn submit(
+    State(state): State<Arc<ServeState>>,
+    headers: HeaderMap,
+    Query(params): Query<SubmitParams>,
+    body: Bytes,
+) -> Result<Response, ServeError> {
+    let submit = match &state.config.submit {
+        Some(s) => s,
+        None => return Ok(StatusCode::NOT_FOUND.into_response()),
+    };
+    // Compared in constant time, not to tell how much of the token a
+    // guess got right
+    let authorized = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .map(|t| bool::from(t.as_bytes().ct_eq(submit.token.as_bytes())))
+        .unwrap_or(false);
+    if !authorized {
+        return Ok(StatusCode::UNAUTHORIZED.into_response());
+    }
+    let Some(submitter) = params.submitter else {
+        return Ok((StatusCode::BAD_REQUEST, "No submitter, the Github login of the submitter").into_response());
+    };
+    let limits = submission_limits();
+    let too_large = limits.check_upload(body.len() as u64).is_some()
+        || params.signature.as_ref().is_some_and(|s| s.len() as u64 > MAX_SIGNATURE_BYTES);
+    if too_large {
+        return Ok((
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!("The result is over the limit of {}", HumanBytes(limits.max_total_bytes)),
+        )
+            .into_response());
+    }
+    let bodies: Vec<&str> = params.statement.as_deref().into_iter().collect();
+    let statement = Statement { bodies: &bodies, author: &submitter, submitter: &submitter };
+    let (signoff, embargo) = match check_statement(&statement) {
+        Ok(Verdict::Accepted { signoff, embargo }) => (signoff, embargo),
+        Ok(Verdict::Refused(refusal)) => return Ok((StatusCode::FORBIDDEN, refusal.to_string()).into_response()),
+        Err(e) => return Ok((StatusCode::UNPROCESSABLE_ENTITY, format!("{:#}", e)).into_response()),
+    };
+    let upload = Upload {
+        contents: body,
+        submitter,
+        signature: params.signature,
+        signoff,
+        embargo,
+    };
+
+    // The lock is held until the branch is pushed, as the submissions
+    // all use the same checkout
+    let _guard = state.submit_lock.lock().await;
+    let database_dir = state.config.database_dir.to_string_lossy().to_string();
+    let imported = tokio::task::spawn_blocking(move || import_upload(&database_dir, &upload)).await?;
+    let (result, branch_name) = match imported {
+        Ok((result, Imported::Branch(branch_name))) => (result, branch_name),
+        Ok((result, Imported::Held(date))) => {
+            let response = SubmitResponse {
+                model_name: result.model_name,
+                version: result.version,
+                branch: None,
+                pull_request: None,
+                held_until: Some(date.to_string()),
+            };
+            return Ok((StatusCode::ACCEPTED, Json(response)).into_response());
+        }
+        Err(e) => {
+            return Ok((
+                StatusCode::UNPROCESSABLE_ENTITY,
+                format!("Result failed validation:\n\n{:#}", e),
+            )
+                .into_response())
+        }
+    };
+
+    let pull_request = match &submit.github_token {
+        Some(token) => {
+            upload_branch_objects(&submit.repo, &branch_name, token).await?;
+            let (repo, branch, push_token) = (submit.repo.clone(), branch_name.clone(), token.clone());
+            tokio::task::spawn_blocking(move || {
+                push_branch(&workspace().repository()?, &repo, &branch, &push_token)
+            })
+            .await??;
+            let title = format!(
+                "Automated update from direct submission ({} {})",
+                result.model_name, result.version
+            );
+            Some(
+                open_pull_request(
+                    token,
+                    &submit.repo,
+                    &branch_name,
+                    &submit.base_branch,
+                    &title,
+                    "Result submitted through the iocost-ci submission endpoint.",
+                    false,
+                )
+                .await?,
+            )
+        }
+        None => None,
+    };
+
+    Ok(Json(SubmitResponse {
+        model_name: result.model_name,
+        version: result.version,
+        branch: Some(branch_name),
+        pull_request,
+        held_until: None,
+    })
+    .into_response())
+}
+
+/// Builds the router for the API
+pub fn router(config: ServeConfig) -> Router {
+    let state = ServeState {
+        config,
+        submit_lock: Mutex::new(()),
+    };
+    Router::new()
+        .route("/models", get(list_models))
+        .route("/models/{name}/hwdb", get(get_model_hwdb))
+        .route("/models/{name}/summary", get(get_model_summary))
+        .route("/submit", post(submit))
+        .layer(DefaultBodyLimit::max(MAX_UPLOAD_SIZE))
+        .with_state(Arc::new(state))
+}
+
+/// Serves the API on `listen` until the process is stopped
+pub async fn serve(listen: &str, config: ServeConfig) -> Result<()> {
+    let listener = tokio::net::TcpListener::bind(listen).await?;
+    println!("Serving database {:?} on http://{}", config.database_dir, listen);
+    axum::serve(listener, router(config)).await?;
+    Ok(())
+}
@@ -0,0 +1,225 @@
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::common::{resctl_bench_path, BenchVersion, BENCH_VERSIONS};
+use crate::config::SetupConfig;
+use crate::git::checkout_remote_ref;
+
+/// Repository resctl-bench is built from when the config file names none
+static RESCTL_DEMO_REPO: &str = "https://github.com/facebookexperimental/resctl-demo";
+
+/// Branches or tags the versions are built from when the config file
+/// names none, as in the build-resctl-demo workflow
+static DEFAULT_REFS: &[(&str, &str)] = &[("2.1", "v2.1.3"), ("2.2", "main")];
+
+/// Where the missing resctl-bench binaries are built from
+#[derive(Debug, Clone)]
+pub struct SetupSettings {
+    pub repository: String,
+    /// Branch, tag or commit of each version (X.Y)
+    pub refs: BTreeMap<String, String>,
+}
+
+impl Default for SetupSettings {
+    fn default() -> Self {
+        SetupSettings {
+            repository: RESCTL_DEMO_REPO.to_string(),
+            refs: DEFAULT_REFS.iter().map(|(v, r)| (v.to_string(), r.to_string())).collect(),
+        }
+    }
+}
+
+impl SetupSettings {
+    /// Creates the settings of the [setup] section of the config file,
+    /// the refs it doesn't name being the default ones
+    pub fn from_config(config: &SetupConfig) -> Self {
+        let mut settings = SetupSettings::default();
+        if let Some(repository) = &config.repository {
+            settings.repository = repository.clone();
+        }
+        settings.refs.extend(config.refs.clone());
+        settings
+    }
+}
+
+/// How the resctl-bench binary of a version was provided
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BinarySource {
+    /// Already in the workspace, e.g. downloaded from the artifacts of
+    /// the build-resctl-demo workflow
+    Present,
+    Built,
+    Missing,
+}
+
+/// A resctl-bench version in the setup manifest
+#[derive(Debug, Serialize)]
+pub struct SetupEntry {
+    /// Major and minor version (X.Y) the pipeline refers to it by
+    pub version: String,
+    pub path: PathBuf,
+    pub source: BinarySource,
+    /// Branch, tag or commit it was built from
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub git_ref: Option<String>,
+    /// Full version reported by `resctl-bench --version`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub full_version: Option<String>,
+    /// Why the version can't be used
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl SetupEntry {
+    pub fn is_ready(&self) -> bool {
+        self.full_version.is_some() && self.error.is_none()
+    }
+}
+
+/// Manifest of a runner setup, which the later jobs of a workflow read
+/// to find the resctl-bench binaries
+#[derive(Debug, Serialize)]
+pub struct SetupManifest {
+    pub versions: Vec<SetupEntry>,
+}
+
+impl SetupManifest {
+    pub fn is_ready(&self) -> bool {
+        self.versions.iter().all(SetupEntry::is_ready)
+    }
+}
+
+/// Returns the versions to set up: `requested` if any, checked against
+/// the versions the pipeline knows about, or all of them
+pub fn setup_versions(requested: &[String]) -> Result<Vec<String>> {
+    if requested.is_empty() {
+        return Ok(BENCH_VERSIONS.iter().map(|v| v.to_string()).collect());
+    }
+    for version in requested {
+        if !BENCH_VERSIONS.contains(&version.as_str()) {
+            bail!("Unknown resctl-bench version {}, expected one of {}", version, BENCH_VERSIONS.join(", "));
+        }
+    }
+    Ok(requested.to_vec())
+}
+
+/// Checks out the ref of `version` and builds its resctl-bench binary
+/// in a `resctl-demo-src-v<version>` directory of the workspace, then
+/// copies it where the pipeline runs it from. Returns the ref.
+fn build_version(settings: &SetupSettings, version: &str) -> Result<String> {
+    let Some(git_ref) = settings.refs.get(version) else {
+        bail!("No ref to build resctl-bench {} from, add it to [setup.refs]", version);
+    };
+    let source_dir = PathBuf::from(format!("resctl-demo-src-v{}", version));
+    fs::create_dir_all(&source_dir)?;
+    eprintln!("Fetching {} of {} into {}", git_ref, settings.repository, source_dir.display());
+    checkout_remote_ref(&source_dir, &settings.repository, git_ref)?;
+    eprintln!("Building resctl-bench {}", version);
+    // The build output goes to stderr, stdout is the manifest
+    let status = Command::new("cargo")
+        .args(["build", "--release", "-p", "resctl-bench"])
+        .current_dir(&source_dir)
+        .stdout(Stdio::from(io::stderr()))
+        .status()
+        .context("Failed to run cargo")?;
+    if !status.success() {
+        bail!("Building resctl-bench {} failed: {}", version, status);
+    }
+    let path = resctl_bench_path(version);
+    fs::create_dir_all(path.parent().unwrap())?;
+    fs::copy(source_dir.join("target/release/resctl-bench"), &path)?;
+    Ok(git_ref.clone())
+}
+
+/// Checks that the binary of `version` runs and reports that version,
+/// returning its full version
+fn verify_version(version: &str) -> Result<String> {
+    let semver = BenchVersion::new(version)?.semver;
+    if format!("{}.{}", semver.major, semver.minor) != version {
+        bail!("{} reports version {}", resctl_bench_path(version).display(), semver);
+    }
+    Ok(semver.to_string())
+}
+
+/// Prepares the resctl-bench binaries of `versions` in the workspace,
+/// building the missing ones with `build`, and verifies them
+pub fn setup(settings: &SetupSettings, versions: &[String], build: bool) -> SetupManifest {
+    let mut entries = vec![];
+    for version in versions {
+        let path = resctl_bench_path(version);
+        let mut entry = SetupEntry {
+            version: version.clone(),
+            path: path.clone(),
+            source: BinarySource::Present,
+            git_ref: None,
+            full_version: None,
+            error: None,
+        };
+        if !path.exists() && !build {
+            entry.source = BinarySource::Missing;
+            entry.error = Some(format!("{} not found, run with --build to build it", path.display()));
+            entries.push(entry);
+            continue;
+        }
+        if !path.exists() {
+            match build_version(settings, version) {
+                Ok(git_ref) => {
+                    entry.source = BinarySource::Built;
+                    entry.git_ref = Some(git_ref);
+                }
+                Err(e) => {
+                    entry.source = BinarySource::Missing;
+                    entry.error = Some(format!("{:#}", e));
+                    entries.push(entry);
+                    continue;
+                }
+            }
+        }
+        match verify_version(version) {
+            Ok(full_version) => entry.full_version = Some(full_version),
+            Err(e) => entry.error = Some(format!("{:#}", e)),
+        }
+        entries.push(entry);
+    }
+    SetupManifest { versions: entries }
+}
+
+/// Runs the `setup` command: prints the versions to set up as a JSON
+/// array with `matrix`, for the matrix strategy of a workflow, or sets
+/// them up and prints the manifest, also writing it to `manifest_path`.
+/// Fails if some version isn't ready.
+pub fn run_setup(
+    settings: &SetupSettings,
+    requested: &[String],
+    build: bool,
+    matrix: bool,
+    manifest_path: Option<&Path>,
+) -> Result<()> {
+    let versions = setup_versions(requested)?;
+    if matrix {
+        println!("{}", serde_json::to_string(&versions)?);
+        return Ok(());
+    }
+    let manifest = setup(settings, &versions, build);
+    let json = serde_json::to_string_pretty(&manifest)?;
+    println!("{}", json);
+    if let Some(path) = manifest_path {
+        fs::write(path, &json).with_context(|| format!("Failed to write {}", path.display()))?;
+    }
+    let failed: Vec<&str> = manifest
+        .versions
+        .iter()
+        .filter(|e| !e.is_ready())
+        .map(|e| e.version.as_str())
+        .collect();
+    if !failed.is_empty() {
+        bail!("resctl-bench {} not ready", failed.join(", "));
+    }
+    Ok(())
+}
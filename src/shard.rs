@@ -0,0 +1,140 @@
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::hwdb::HwdbCandidate;
+use crate::manifest::Manifest;
+use crate::workspace::{HWDB_INPUTS_DIR, PDFS_DIR};
+
+/// Summary written by each shard of a merge, for the combine step
+pub static SHARD_SUMMARY_FILE: &str = "shard-summary.json";
+
+/// Directories of the shard outputs gathered by the combine step
+static SHARD_OUTPUT_DIRS: &[&str] = &[PDFS_DIR, HWDB_INPUTS_DIR];
+
+/// One of `count` deterministic partitions of the models of the
+/// database, numbered from 1, for merges split across matrix jobs.
+/// All the versions of a model are in the same shard, as the final hwdb
+/// entry is selected among them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Shard {
+    pub index: u32,
+    pub count: u32,
+}
+
+impl FromStr for Shard {
+    type Err = anyhow::Error;
+
+    /// Parses an `i/N` shard
+    fn from_str(s: &str) -> Result<Self> {
+        let Some((index, count)) = s.split_once('/') else {
+            bail!("Invalid shard {:?}, expected i/N", s);
+        };
+        let shard = Shard {
+            index: index.trim().parse()?,
+            count: count.trim().parse()?,
+        };
+        if shard.index == 0 || shard.index > shard.count {
+            bail!("Invalid shard {}, expected 1 <= i <= N", shard);
+        }
+        Ok(shard)
+    }
+}
+
+impl fmt::Display for Shard {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.index, self.count)
+    }
+}
+
+impl Shard {
+    /// Whether `model_name` belongs to the shard. The partition only
+    /// depends on the model name, so that it is stable across runs and
+    /// machines.
+    pub fn contains(&self, model_name: &str) -> bool {
+        let digest = md5::compute(model_name.as_bytes());
+        let hash = u64::from_le_bytes(digest.0[..8].try_into().unwrap());
+        hash % self.count as u64 == (self.index - 1) as u64
+    }
+}
+
+/// The outcome of a shard of the merge: the hwdb candidates of its
+/// models and the artifacts it generated
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ShardSummary {
+    pub shard: Shard,
+    pub candidates: Vec<HwdbCandidate>,
+    pub manifest: Manifest,
+}
+
+impl ShardSummary {
+    pub fn write_to(&self, path: &Path) -> Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    fn load(dir: &Path) -> Result<Self> {
+        let path = dir.join(SHARD_SUMMARY_FILE);
+        let contents = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read shard summary {:?}", path))?;
+        serde_json::from_str(&contents).with_context(|| format!("Failed to parse shard summary {:?}", path))
+    }
+
+    /// Gathers the outputs of the shards of a merge from their artifact
+    /// `dirs` into the workspace, as if they had been generated
+    /// by a single merge. Fails unless every shard is there exactly once
+    /// and all were merged from the same commit. Returns the hwdb
+    /// candidates and the manifest of all the shards.
+    pub fn combine(dirs: &[PathBuf]) -> Result<(Vec<HwdbCandidate>, Manifest)> {
+        let mut summaries = vec![];
+        for dir in dirs {
+            summaries.push((dir, Self::load(dir)?));
+        }
+        summaries.sort_by_key(|(_, s)| s.shard);
+        let Some(count) = summaries.first().map(|(_, s)| s.shard.count) else {
+            bail!("No shards to combine");
+        };
+        let indexes: Vec<u32> = summaries.iter().map(|(_, s)| s.shard.index).collect();
+        if summaries.iter().any(|(_, s)| s.shard.count != count) || indexes != (1..=count).collect::<Vec<_>>() {
+            bail!(
+                "Incomplete set of shards: got {}, expected 1 to {} of {}",
+                summaries.iter().map(|(_, s)| s.shard.to_string()).collect::<Vec<_>>().join(", "),
+                count,
+                count
+            );
+        }
+        let commit = summaries[0].1.manifest.commit.clone();
+        if let Some((_, s)) = summaries.iter().find(|(_, s)| s.manifest.commit != commit) {
+            bail!(
+                "Shard {} was merged from commit {:?}, shard 1/{} from {:?}",
+                s.shard,
+                s.manifest.commit,
+                count,
+                commit
+            );
+        }
+
+        let mut candidates = vec![];
+        let mut manifest = Manifest::new(commit);
+        for (dir, summary) in summaries {
+            for output_dir in SHARD_OUTPUT_DIRS {
+                let source = dir.join(output_dir);
+                if !source.is_dir() {
+                    continue;
+                }
+                fs::create_dir_all(output_dir)?;
+                for entry in fs::read_dir(&source)? {
+                    let entry = entry?;
+                    fs::copy(entry.path(), Path::new(output_dir).join(entry.file_name()))?;
+                }
+            }
+            candidates.extend(summary.candidates);
+            manifest.artifacts.extend(summary.manifest.artifacts);
+            manifest.excluded.extend(summary.manifest.excluded);
+        }
+        Ok((candidates, manifest))
+    }
+}
@@ -0,0 +1,128 @@
+use anyhow::Result;
+use indicatif::HumanBytes;
+use json::JsonValue;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::common::load_json;
+
+/// Key facts of a result file, read from its contents without running
+/// resctl-bench, to triage files before they are submitted
+#[derive(Debug, Serialize)]
+pub struct ResultFacts {
+    pub file: PathBuf,
+    /// Full version of resctl-bench, with its build information
+    pub bench_version: Option<String>,
+    pub model_name: Option<String>,
+    pub fwrev: Option<String>,
+    pub capacity_bytes: Option<u64>,
+    pub kernel_version: Option<String>,
+    /// System requirements of resctl-bench that were satisfied
+    pub sysreqs_satisfied: Vec<String>,
+    /// System requirements that were missed, with the reasons given
+    pub sysreqs_missed: BTreeMap<String, Vec<String>>,
+    pub runs: Vec<RunFacts>,
+}
+
+/// A benchmark run of a result file
+#[derive(Debug, Serialize)]
+pub struct RunFacts {
+    pub kind: String,
+    /// The iocost-tune series of the run, by name
+    pub series: BTreeMap<String, SeriesFacts>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SeriesFacts {
+    pub data_points: usize,
+    pub outliers: usize,
+}
+
+fn json_string(value: &JsonValue) -> Option<String> {
+    value.as_str().map(str::to_string)
+}
+
+impl ResultFacts {
+    pub fn load(path: &Path) -> Result<Self> {
+        let result = load_json(&path.to_string_lossy())?;
+        let sysinfo = &result[0]["sysinfo"];
+        let report = &sysinfo["sysreqs_report"];
+        // Depending on the version, the missed requirements are a map
+        // or wrapped in one
+        let missed = match &report["missed"]["map"] {
+            JsonValue::Null => &report["missed"],
+            map => map,
+        };
+        let runs = result
+            .members()
+            .filter(|r| !r["spec"].is_null())
+            .map(|r| RunFacts {
+                kind: json_string(&r["spec"]["kind"]).unwrap_or_default(),
+                series: r["result"]["data"]
+                    .entries()
+                    .map(|(name, data)| {
+                        let facts = SeriesFacts {
+                            data_points: data["data"].len(),
+                            outliers: data["outliers"].len(),
+                        };
+                        (name.to_string(), facts)
+                    })
+                    .collect(),
+            })
+            .collect();
+        Ok(ResultFacts {
+            file: path.to_path_buf(),
+            bench_version: json_string(&sysinfo["bench_version"]),
+            model_name: json_string(&report["scr_dev_model"]),
+            fwrev: json_string(&report["scr_dev_fwrev"]),
+            capacity_bytes: report["scr_dev_size"].as_u64(),
+            kernel_version: json_string(&report["kernel_version"]),
+            sysreqs_satisfied: report["satisfied"].members().filter_map(json_string).collect(),
+            sysreqs_missed: missed
+                .entries()
+                .map(|(sysreq, reasons)| (sysreq.to_string(), reasons.members().filter_map(json_string).collect()))
+                .collect(),
+            runs,
+        })
+    }
+
+    pub fn to_text(&self) -> String {
+        let unknown = |value: &Option<String>| value.clone().unwrap_or("unknown".to_string());
+        let mut text = format!("File:         {}\n", self.file.display());
+        text.push_str(&format!("resctl-bench: {}\n", unknown(&self.bench_version)));
+        text.push_str(&format!("Model:        {}\n", unknown(&self.model_name)));
+        text.push_str(&format!("Firmware:     {}\n", unknown(&self.fwrev)));
+        text.push_str(&format!(
+            "Capacity:     {}\n",
+            self.capacity_bytes.map_or("unknown".to_string(), |b| HumanBytes(b).to_string())
+        ));
+        text.push_str(&format!("Kernel:       {}\n", unknown(&self.kernel_version)));
+        if self.sysreqs_missed.is_empty() {
+            text.push_str(&format!("Sysreqs:      {} satisfied, none missed\n", self.sysreqs_satisfied.len()));
+        } else {
+            text.push_str(&format!(
+                "Sysreqs:      {} satisfied, {} missed\n",
+                self.sysreqs_satisfied.len(),
+                self.sysreqs_missed.len()
+            ));
+            for (sysreq, reasons) in &self.sysreqs_missed {
+                text.push_str(&format!("  - {}: {}\n", sysreq, reasons.join("; ")));
+            }
+        }
+        text.push_str(&format!("Runs:         {}\n", self.runs.len()));
+        for run in &self.runs {
+            text.push_str(&format!("  - {}\n", run.kind));
+            for (name, series) in &run.series {
+                text.push_str(&format!(
+                    "    {}: {} data points, {} outliers\n",
+                    name, series.data_points, series.outliers
+                ));
+            }
+        }
+        if !self.runs.iter().any(|r| r.kind == "iocost-tune") {
+            text.push_str("Warning: the result has no iocost-tune run, it can't be submitted\n");
+        }
+        text
+    }
+}
@@ -0,0 +1,141 @@
+use anyhow::{bail, Context, Result};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::OnceLock;
+
+use crate::config::{set_once, SignaturesConfig};
+
+/// Extensions of the detached OpenPGP signatures submitted next to the
+/// result files: binary and ASCII-armored
+pub static SIGNATURE_EXTENSIONS: &[&str] = &[".sig", ".asc"];
+
+/// Largest signature file downloaded, far above the size of an actual
+/// detached signature
+pub const MAX_SIGNATURE_BYTES: u64 = 64 * 1024;
+
+/// Default gpg binary the signatures are verified with
+const DEFAULT_GPG: &str = "gpg";
+
+/// OpenPGP public key of a vendor
+#[derive(Debug, Clone)]
+pub struct VendorKey {
+    /// Name of the vendor, as recorded in the metadata of the results
+    pub vendor: String,
+    /// Public key file, binary or ASCII-armored
+    pub public_key: PathBuf,
+}
+
+/// Public keys of the vendors the detached signatures submitted with the
+/// results are verified against, so that the vendors can make their
+/// results tamper-evident
+#[derive(Debug, Clone)]
+pub struct SignatureSettings {
+    /// gpg binary, run with a temporary home directory
+    pub gpg: String,
+    pub keys: Vec<VendorKey>,
+}
+
+impl SignatureSettings {
+    /// Creates the settings of the [signatures] section of the config
+    /// file, failing if a key file is missing
+    pub fn from_config(config: &SignaturesConfig) -> Result<Self> {
+        for key in &config.keys {
+            if !key.public_key.is_file() {
+                bail!("Public key {} of {} not found", key.public_key.display(), key.vendor);
+            }
+        }
+        Ok(SignatureSettings {
+            gpg: config.gpg.clone().unwrap_or(DEFAULT_GPG.to_string()),
+            keys: config
+                .keys
+                .iter()
+                .map(|k| VendorKey { vendor: k.vendor.clone(), public_key: k.public_key.clone() })
+                .collect(),
+        })
+    }
+
+    /// Returns the vendor whose key made the detached `signature` of
+    /// `file`, or an error if none did
+    pub fn verify(&self, file: &Path, signature: &Path) -> Result<String> {
+        for key in &self.keys {
+            if self.verify_with(key, file, signature)? {
+                return Ok(key.vendor.clone());
+            }
+        }
+        bail!("The signature of the result doesn't match the key of any registered vendor")
+    }
+
+    /// Whether `key` made the `signature` of `file`, checked in a keyring
+    /// of its own
+    fn verify_with(&self, key: &VendorKey, file: &Path, signature: &Path) -> Result<bool> {
+        let home = tempfile::tempdir()?;
+        let gpg = || {
+            let mut command = Command::new(&self.gpg);
+            command.arg("--homedir").arg(home.path()).args(["--batch", "--no-tty", "--status-fd", "1"]);
+            command
+        };
+        let import = gpg()
+            .arg("--import")
+            .arg(&key.public_key)
+            .output()
+            .with_context(|| format!("Failed to run {}", self.gpg))?;
+        if !import.status.success() {
+            bail!(
+                "Failed to import the key of {} from {}: {}",
+                key.vendor,
+                key.public_key.display(),
+                String::from_utf8_lossy(&import.stderr).trim()
+            );
+        }
+        let verify = gpg()
+            .arg("--verify")
+            .arg(signature)
+            .arg(file)
+            .output()
+            .with_context(|| format!("Failed to run {}", self.gpg))?;
+        // The signatures are good whether or not the keys are trusted, as
+        // they are the only ones of the keyring
+        let status = String::from_utf8_lossy(&verify.stdout);
+        Ok(verify.status.success() && status.lines().any(|l| l.starts_with("[GNUPG:] VALIDSIG ")))
+    }
+}
+
+/// Returns the file name of `link`, the last segment of its path
+fn file_name(link: &str) -> Option<String> {
+    let url = reqwest::Url::parse(link).ok()?;
+    url.path_segments()?.next_back().filter(|s| !s.is_empty()).map(str::to_string)
+}
+
+/// Whether `link` points to a detached signature
+pub fn is_signature_url(link: &str) -> bool {
+    file_name(link).is_some_and(|name| SIGNATURE_EXTENSIONS.iter().any(|e| name.to_lowercase().ends_with(e)))
+}
+
+/// Returns the signature of the result `url` among the `signatures`
+/// links: the first one whose file name is that of the result with a
+/// signature extension. The hosts and directories don't need to match,
+/// as the Github attachments each get a URL of their own.
+pub fn signature_link<'a>(url: &str, signatures: &'a [String]) -> Option<&'a str> {
+    let name = file_name(url)?;
+    signatures
+        .iter()
+        .find(|s| {
+            file_name(s).is_some_and(|signature| {
+                SIGNATURE_EXTENSIONS.iter().any(|e| signature.eq_ignore_ascii_case(&format!("{}{}", name, e)))
+            })
+        })
+        .map(String::as_str)
+}
+
+static SIGNATURE_SETTINGS: OnceLock<SignatureSettings> = OnceLock::new();
+
+/// Enables the verification of the signatures of the results.
+pub fn set_signature_settings(settings: SignatureSettings) -> Result<()> {
+    set_once(&SIGNATURE_SETTINGS, settings, "signature settings")
+}
+
+/// Returns the keys the signatures of the results are verified against,
+/// None if the signatures are ignored
+pub fn signature_settings() -> Option<&'static SignatureSettings> {
+    SIGNATURE_SETTINGS.get()
+}
@@ -0,0 +1,115 @@
+use anyhow::Result;
+use std::fmt;
+use std::sync::OnceLock;
+
+use crate::config::{set_once, SignOffConfig};
+
+/// Domain of the private email addresses of Github users
+const NOREPLY_DOMAIN: &str = "users.noreply.github.com";
+
+/// `Signed-off-by` trailer certifying that the submitter can contribute
+/// the results to the database, recorded in the commits importing them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignOff {
+    pub name: String,
+    pub email: String,
+}
+
+impl fmt::Display for SignOff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Signed-off-by: {} <{}>", self.name, self.email)
+    }
+}
+
+impl SignOff {
+    /// Returns the sign-off of the Github user `login`, with their
+    /// private email address
+    pub fn for_user(login: &str) -> Self {
+        SignOff {
+            name: login.to_string(),
+            email: format!("{}@{}", login, NOREPLY_DOMAIN),
+        }
+    }
+
+    /// Parses the `Signed-off-by: Name <email>` lines of `body`
+    pub fn parse_all(body: &str) -> Vec<Self> {
+        body.lines()
+            .filter_map(|line| {
+                let rest = line.trim().strip_prefix("Signed-off-by:")?;
+                let (name, email) = rest.trim().strip_suffix('>')?.split_once('<')?;
+                let (name, email) = (name.trim(), email.trim());
+                (!name.is_empty() && email.contains('@')).then(|| SignOff {
+                    name: name.to_string(),
+                    email: email.to_string(),
+                })
+            })
+            .collect()
+    }
+
+    /// Whether the sign-off is the one of the Github user `login`: its
+    /// name or the user part of its email is the login, or its email is
+    /// the private address of the user
+    pub fn is_from(&self, login: &str) -> bool {
+        let login = login.to_lowercase();
+        let email = self.email.to_lowercase();
+        let user = email.split('@').next().unwrap_or_default();
+        self.name.to_lowercase() == login
+            || user == login
+            || (email.ends_with(&format!("@{}", NOREPLY_DOMAIN))
+                && user.rsplit('+').next() == Some(login.as_str()))
+    }
+}
+
+/// Why a submission has no valid sign-off
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignOffError {
+    /// The submission doesn't have the sign-off of the issue author
+    Missing,
+    /// The results were submitted by another user than the issue author
+    NotAuthor { author: String },
+}
+
+/// Settings of the sign-off of the submissions
+#[derive(Debug, Default, Clone)]
+pub struct SignOffSettings {
+    /// Whether submissions need an explicit sign-off of the issue
+    /// author. Otherwise the sign-off of the submitter is recorded.
+    pub required: bool,
+}
+
+impl SignOffSettings {
+    /// Creates the settings of the [signoff] section of the config file
+    pub fn from_config(config: &SignOffConfig) -> Self {
+        SignOffSettings { required: config.required }
+    }
+
+    /// Returns the sign-off to record for the results `submitter`
+    /// submitted to the issue of `author`, from the sign-offs found in
+    /// `bodies`
+    pub fn verify(&self, bodies: &[&str], author: &str, submitter: &str) -> Result<SignOff, SignOffError> {
+        let explicit = bodies
+            .iter()
+            .flat_map(|body| SignOff::parse_all(body))
+            .find(|signoff| signoff.is_from(author));
+        if !self.required {
+            return Ok(explicit.filter(|_| submitter == author).unwrap_or_else(|| SignOff::for_user(submitter)));
+        }
+        if submitter != author {
+            return Err(SignOffError::NotAuthor { author: author.to_string() });
+        }
+        explicit.ok_or(SignOffError::Missing)
+    }
+}
+
+static SIGNOFF_SETTINGS: OnceLock<SignOffSettings> = OnceLock::new();
+
+/// Sets the settings of the sign-offs.
+pub fn set_signoff_settings(settings: SignOffSettings) -> Result<()> {
+    set_once(&SIGNOFF_SETTINGS, settings, "sign-off settings")
+}
+
+/// Returns the settings of the sign-offs, the default ones if none were
+/// set
+pub fn signoff_settings() -> &'static SignOffSettings {
+    SIGNOFF_SETTINGS.get_or_init(SignOffSettings::default)
+}
@@ -0,0 +1,571 @@
+use anyhow::{bail, Context, Result};
+use glob::glob;
+use hmac::{Hmac, Mac};
+use regex::Regex;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+use crate::config::{set_once, StorageConfig};
+use crate::http::{block_on, download_client};
+use crate::progress::log;
+
+/// Where the result files of the database and their metadata are kept.
+/// Files are identified by their key, their path relative to the
+/// database directory: `<version>/<model>/<file>`.
+pub trait Storage: Send + Sync {
+    /// Location of the files, for the logs
+    fn describe(&self) -> String;
+    /// Returns the keys of the files of every model directory
+    fn list(&self) -> Result<Vec<String>>;
+    /// Returns the contents of the file of `key`
+    fn read(&self, key: &str) -> Result<Vec<u8>>;
+    /// Creates or replaces the file of `key`
+    fn write(&self, key: &str, contents: &[u8]) -> Result<()>;
+    /// Removes the file of `key`
+    fn remove(&self, key: &str) -> Result<()>;
+    /// Saves what the storage keeps besides the files, such as the index
+    /// of an object storage, after writes and removals. Other runs may
+    /// not list the changes before.
+    fn flush(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Returns the key of `path` if it is the file of a model directory of
+/// the database in `root`
+pub fn key_for(root: &Path, path: &Path) -> Option<String> {
+    let relative = path.strip_prefix(root).ok()?;
+    let parts: Vec<&str> = relative.iter().map(|p| p.to_str()).collect::<Option<_>>()?;
+    (parts.len() == 3).then(|| parts.join("/"))
+}
+
+/// A directory of the local filesystem. The database directory of the
+/// git working tree, where the files are committed along with the rest
+/// of the repository, is the default storage.
+#[derive(Debug, Clone)]
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+impl LocalStorage {
+    pub fn new(root: &Path) -> Self {
+        LocalStorage { root: root.to_path_buf() }
+    }
+}
+
+impl Storage for LocalStorage {
+    fn describe(&self) -> String {
+        format!("directory {}", self.root.display())
+    }
+
+    fn list(&self) -> Result<Vec<String>> {
+        let mut keys = vec![];
+        for path in glob(&self.root.join("*").join("*").join("*").to_string_lossy())?.flatten() {
+            if path.is_file() {
+                keys.extend(key_for(&self.root, &path));
+            }
+        }
+        Ok(keys)
+    }
+
+    fn read(&self, key: &str) -> Result<Vec<u8>> {
+        let path = self.root.join(key);
+        fs::read(&path).with_context(|| format!("Can't read {:?}", path))
+    }
+
+    fn write(&self, key: &str, contents: &[u8]) -> Result<()> {
+        let path = self.root.join(key);
+        fs::create_dir_all(path.parent().unwrap())?;
+        fs::write(&path, contents).with_context(|| format!("Can't write {:?}", path))
+    }
+
+    fn remove(&self, key: &str) -> Result<()> {
+        let path = self.root.join(key);
+        fs::remove_file(&path).with_context(|| format!("Can't remove {:?}", path))
+    }
+}
+
+/// Name of the object of the index of an [S3Storage], next to the
+/// version directories where no file key can be
+const INDEX_OBJECT: &str = "index.sqlite";
+
+/// Number of times the index is saved again when other runs changed it
+/// in the meantime
+const INDEX_SAVE_ATTEMPTS: usize = 5;
+
+/// A change of the files of an object storage, to record in its index
+#[derive(Debug, Clone, PartialEq)]
+pub enum IndexChange {
+    Added(String),
+    Removed(String),
+}
+
+/// SQLite database of the keys of the files of an object storage, so
+/// that listing them takes a single download rather than a scan of the
+/// whole bucket
+#[derive(Debug)]
+pub struct ObjectIndex {
+    /// Local copy of the database, which SQLite works on
+    file: tempfile::NamedTempFile,
+    connection: rusqlite::Connection,
+}
+
+impl ObjectIndex {
+    /// Opens the index saved as `contents`, or a new empty one
+    pub fn load(contents: Option<&[u8]>) -> Result<Self> {
+        let file = tempfile::NamedTempFile::new()?;
+        if let Some(contents) = contents {
+            fs::write(file.path(), contents)?;
+        }
+        let connection = rusqlite::Connection::open(file.path())?;
+        connection
+            .execute_batch("CREATE TABLE IF NOT EXISTS files (key TEXT PRIMARY KEY NOT NULL)")
+            .context("Invalid index")?;
+        Ok(ObjectIndex { file, connection })
+    }
+
+    /// Returns the keys of the indexed files, in order
+    pub fn keys(&self) -> Result<Vec<String>> {
+        let mut statement = self.connection.prepare("SELECT key FROM files ORDER BY key")?;
+        let keys = statement.query_map([], |row| row.get(0))?.collect::<Result<_, _>>()?;
+        Ok(keys)
+    }
+
+    /// Records the `changes` of the files, in order
+    pub fn apply(&mut self, changes: &[IndexChange]) -> Result<()> {
+        let transaction = self.connection.transaction()?;
+        for change in changes {
+            match change {
+                IndexChange::Added(key) => {
+                    transaction.execute("INSERT OR IGNORE INTO files (key) VALUES (?1)", [key])?
+                }
+                IndexChange::Removed(key) => transaction.execute("DELETE FROM files WHERE key = ?1", [key])?,
+            };
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    /// Returns the database file of the index, to save
+    pub fn contents(&self) -> Result<Vec<u8>> {
+        Ok(fs::read(self.file.path())?)
+    }
+}
+
+/// The index of an [S3Storage] as seen by this run
+#[derive(Debug, Default)]
+struct IndexState {
+    /// Index downloaded from the bucket on the first listing, with the
+    /// changes of the run since
+    index: Option<ObjectIndex>,
+    /// Changes of the run not saved to the bucket yet
+    pending: Vec<IndexChange>,
+}
+
+/// Results and metadata kept as the objects of an S3 (or S3 compatible)
+/// bucket, for deployments too large for a git repository. The index of
+/// the database is an SQLite database kept in the bucket as
+/// `<prefix>index.sqlite`: listings download it instead of scanning the
+/// bucket, and writes and removals update it when flushed. A bucket
+/// without an index is scanned once to create it. The requests are
+/// signed with the credentials of the AWS_ACCESS_KEY_ID,
+/// AWS_SECRET_ACCESS_KEY and AWS_SESSION_TOKEN environment variables.
+#[derive(Debug)]
+pub struct S3Storage {
+    /// Base URL of the service, buckets are addressed by path
+    endpoint: reqwest::Url,
+    bucket: String,
+    region: String,
+    /// Prefix of the keys in the bucket, empty or ending with a slash
+    prefix: String,
+    access_key: String,
+    secret_key: String,
+    session_token: Option<String>,
+    index: Mutex<IndexState>,
+}
+
+/// Characters left as is by the URI encoding of the signature
+fn is_unreserved(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, b'-' | b'_' | b'.' | b'~')
+}
+
+/// URI encodes `s` as the signature expects it, leaving slashes as is
+/// unless `encode_slash` is set
+fn uri_encode(s: &str, encode_slash: bool) -> String {
+    s.bytes()
+        .map(|c| match c {
+            c if is_unreserved(c) => (c as char).to_string(),
+            b'/' if !encode_slash => "/".to_string(),
+            c => format!("%{:02X}", c),
+        })
+        .collect()
+}
+
+fn hmac_sha256(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Decodes the entities of the text of an XML element
+fn xml_unescape(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Fails with the error of the service for unsuccessful responses
+async fn check_status(response: reqwest::Response) -> Result<reqwest::Response> {
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        bail!("s3 request failed with {}: {}", status, body.trim());
+    }
+    Ok(response)
+}
+
+impl S3Storage {
+    /// Creates the storage of the [storage] section of the config file
+    pub fn from_config(config: &StorageConfig) -> Result<Self> {
+        let Some(bucket) = config.bucket.clone() else {
+            bail!("The s3 storage needs a bucket");
+        };
+        let region = config.region.clone().unwrap_or("us-east-1".to_string());
+        let endpoint = config
+            .endpoint
+            .clone()
+            .unwrap_or(format!("https://s3.{}.amazonaws.com", region));
+        let endpoint = reqwest::Url::parse(&endpoint).with_context(|| format!("Invalid S3 endpoint {:?}", endpoint))?;
+        let mut prefix = config.prefix.clone().unwrap_or_default().trim_matches('/').to_string();
+        if !prefix.is_empty() {
+            prefix.push('/');
+        }
+        let env = |name| std::env::var(name).with_context(|| format!("{} is not set for the s3 storage", name));
+        Ok(S3Storage {
+            endpoint,
+            bucket,
+            region,
+            prefix,
+            access_key: env("AWS_ACCESS_KEY_ID")?,
+            secret_key: env("AWS_SECRET_ACCESS_KEY")?,
+            session_token: std::env::var("AWS_SESSION_TOKEN").ok(),
+            index: Mutex::default(),
+        })
+    }
+
+    /// Sends a successful request for the object of `key`, or for the
+    /// bucket without one
+    async fn send(
+        &self,
+        method: reqwest::Method,
+        key: Option<&str>,
+        query: &[(&str, &str)],
+        body: Vec<u8>,
+    ) -> Result<reqwest::Response> {
+        check_status(self.request(method, key, query, &[], body).await?).await
+    }
+
+    /// Sends a request signed with AWS Signature Version 4 for the
+    /// object of `key`, or for the bucket without one, with the
+    /// `conditions` headers of conditional requests
+    async fn request(
+        &self,
+        method: reqwest::Method,
+        key: Option<&str>,
+        query: &[(&str, &str)],
+        conditions: &[(&str, &str)],
+        body: Vec<u8>,
+    ) -> Result<reqwest::Response> {
+        let mut path = format!("{}/{}", self.endpoint.path().trim_end_matches('/'), self.bucket);
+        if let Some(key) = key {
+            path = format!("{}/{}{}", path, self.prefix, key);
+        }
+        let path = uri_encode(&path, false);
+        let mut query: Vec<(String, String)> =
+            query.iter().map(|(k, v)| (uri_encode(k, true), uri_encode(v, true))).collect();
+        query.sort();
+        let query = query.iter().map(|(k, v)| format!("{}={}", k, v)).collect::<Vec<_>>().join("&");
+        let host = match self.endpoint.port() {
+            Some(port) => format!("{}:{}", self.endpoint.host_str().unwrap_or_default(), port),
+            None => self.endpoint.host_str().unwrap_or_default().to_string(),
+        };
+
+        let now = chrono::Utc::now();
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let scope = format!("{}/{}/s3/aws4_request", now.format("%Y%m%d"), self.region);
+        let payload_hash = hex::encode(Sha256::digest(&body));
+        let mut headers = vec![
+            ("host", host),
+            ("x-amz-content-sha256", payload_hash.clone()),
+            ("x-amz-date", amz_date.clone()),
+        ];
+        if let Some(token) = &self.session_token {
+            headers.push(("x-amz-security-token", token.clone()));
+        }
+        let canonical_headers: String = headers.iter().map(|(k, v)| format!("{}:{}\n", k, v)).collect();
+        let signed_headers = headers.iter().map(|(k, _)| *k).collect::<Vec<_>>().join(";");
+        let canonical_request = format!(
+            "{}\n{}\n{}\n{}\n{}\n{}",
+            method, path, query, canonical_headers, signed_headers, payload_hash
+        );
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            hex::encode(Sha256::digest(canonical_request.as_bytes()))
+        );
+        let mut signing_key = format!("AWS4{}", self.secret_key).into_bytes();
+        for part in scope.split('/') {
+            signing_key = hmac_sha256(&signing_key, part);
+        }
+        let signature = hex::encode(hmac_sha256(&signing_key, &string_to_sign));
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            self.access_key, scope, signed_headers, signature
+        );
+
+        let mut url = self.endpoint.clone();
+        url.set_path(&path);
+        url.set_query((!query.is_empty()).then_some(query.as_str()));
+        let mut request = download_client()?
+            .request(method, url)
+            .header(reqwest::header::AUTHORIZATION, authorization)
+            .body(body);
+        // The client sets the Host header from the URL
+        for (name, value) in headers.into_iter().skip(1) {
+            request = request.header(name, value);
+        }
+        for (name, value) in conditions {
+            request = request.header(*name, *value);
+        }
+        Ok(request.send().await.map_err(|e| e.without_url())?)
+    }
+
+    async fn list_objects(&self) -> Result<Vec<String>> {
+        let key_re = Regex::new(r"<Key>([^<]*)</Key>").unwrap();
+        let token_re = Regex::new(r"<NextContinuationToken>([^<]*)</NextContinuationToken>").unwrap();
+        let mut keys = vec![];
+        let mut token: Option<String> = None;
+        loop {
+            let mut query = vec![("list-type", "2"), ("prefix", self.prefix.as_str())];
+            if let Some(token) = &token {
+                query.push(("continuation-token", token.as_str()));
+            }
+            let response = self.send(reqwest::Method::GET, None, &query, vec![]).await?;
+            let body = response.text().await?;
+            for key in key_re.captures_iter(&body) {
+                let key = xml_unescape(&key[1]);
+                if let Some(key) = key.strip_prefix(&self.prefix).filter(|k| k.split('/').count() == 3) {
+                    keys.push(key.to_string());
+                }
+            }
+            match token_re.captures(&body) {
+                Some(next) => token = Some(xml_unescape(&next[1])),
+                None => return Ok(keys),
+            }
+        }
+    }
+
+    /// Downloads the index of the bucket along with its ETag, None if
+    /// the bucket has no index
+    async fn fetch_index(&self) -> Result<Option<(Vec<u8>, String)>> {
+        let response = self.request(reqwest::Method::GET, Some(INDEX_OBJECT), &[], &[], vec![]).await?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let response = check_status(response).await?;
+        let etag = response
+            .headers()
+            .get(reqwest::header::ETAG)
+            .and_then(|etag| etag.to_str().ok())
+            .context("The index has no ETag")?
+            .to_string();
+        Ok(Some((response.bytes().await?.to_vec(), etag)))
+    }
+
+    /// Loads the index of the bucket, with the `pending` changes of the
+    /// run. A bucket without an index, such as one filled by other
+    /// tools, is scanned to create it: its keys are added to the
+    /// `pending` changes so that the next flush saves the index.
+    async fn load_index(&self, pending: &mut Vec<IndexChange>) -> Result<ObjectIndex> {
+        let contents = match self.fetch_index().await? {
+            Some((contents, _)) => Some(contents),
+            None => {
+                log(format!("{} has no index yet, scanning the bucket", self.describe()));
+                pending.extend(self.list_objects().await?.into_iter().map(IndexChange::Added));
+                None
+            }
+        };
+        let mut index = ObjectIndex::load(contents.as_deref())?;
+        index.apply(pending)?;
+        Ok(index)
+    }
+
+    /// Saves the index of the bucket with the `changes` applied. The
+    /// index is only replaced if no other run saved it in the meantime,
+    /// otherwise the changes are applied again to the new one, so that
+    /// concurrent runs don't lose each other's changes. Returns the
+    /// saved index.
+    async fn save_index(&self, changes: &[IndexChange]) -> Result<ObjectIndex> {
+        for _ in 0..INDEX_SAVE_ATTEMPTS {
+            let current = self.fetch_index().await?;
+            let mut index = ObjectIndex::load(current.as_ref().map(|(contents, _)| contents.as_slice()))?;
+            index.apply(changes)?;
+            let condition = match &current {
+                Some((_, etag)) => ("If-Match", etag.as_str()),
+                None => ("If-None-Match", "*"),
+            };
+            let response = self
+                .request(reqwest::Method::PUT, Some(INDEX_OBJECT), &[], &[condition], index.contents()?)
+                .await?;
+            match response.status() {
+                reqwest::StatusCode::PRECONDITION_FAILED | reqwest::StatusCode::CONFLICT => continue,
+                _ => {
+                    check_status(response).await?;
+                    return Ok(index);
+                }
+            }
+        }
+        bail!("The index kept changing during {} attempts to save it", INDEX_SAVE_ATTEMPTS)
+    }
+
+    /// Records a change of the files in the loaded index, and for the
+    /// next flush to save
+    fn record(&self, change: IndexChange) -> Result<()> {
+        let mut state = self.index.lock().unwrap();
+        if let Some(index) = &mut state.index {
+            index.apply(std::slice::from_ref(&change))?;
+        }
+        state.pending.push(change);
+        Ok(())
+    }
+}
+
+impl Storage for S3Storage {
+    fn describe(&self) -> String {
+        format!("s3 bucket {}/{}", self.bucket, self.prefix)
+    }
+
+    fn list(&self) -> Result<Vec<String>> {
+        let mut state = self.index.lock().unwrap();
+        let IndexState { index, pending } = &mut *state;
+        if index.is_none() {
+            let loaded = block_on(self.load_index(pending));
+            *index = Some(loaded.with_context(|| format!("Failed to list {}", self.describe()))?);
+        }
+        index.as_ref().unwrap().keys()
+    }
+
+    fn read(&self, key: &str) -> Result<Vec<u8>> {
+        block_on(async {
+            let response = self.send(reqwest::Method::GET, Some(key), &[], vec![]).await?;
+            Ok::<_, anyhow::Error>(response.bytes().await?.to_vec())
+        })
+        .with_context(|| format!("Failed to read {} from {}", key, self.describe()))
+    }
+
+    fn write(&self, key: &str, contents: &[u8]) -> Result<()> {
+        block_on(self.send(reqwest::Method::PUT, Some(key), &[], contents.to_vec()))
+            .with_context(|| format!("Failed to write {} to {}", key, self.describe()))?;
+        self.record(IndexChange::Added(key.to_string()))
+    }
+
+    fn remove(&self, key: &str) -> Result<()> {
+        block_on(self.send(reqwest::Method::DELETE, Some(key), &[], vec![]))
+            .with_context(|| format!("Failed to remove {} from {}", key, self.describe()))?;
+        self.record(IndexChange::Removed(key.to_string()))
+    }
+
+    fn flush(&self) -> Result<()> {
+        let mut state = self.index.lock().unwrap();
+        if state.pending.is_empty() {
+            return Ok(());
+        }
+        let index = block_on(self.save_index(&state.pending))
+            .with_context(|| format!("Failed to save the index of {}", self.describe()))?;
+        state.index = Some(index);
+        state.pending.clear();
+        Ok(())
+    }
+}
+
+/// Creates the object storage of the [storage] section of the config
+/// file, None for the git backend
+pub fn object_storage_from_config(config: &StorageConfig) -> Result<Option<Box<dyn Storage>>> {
+    match config.backend.as_deref().unwrap_or("git") {
+        "git" => Ok(None),
+        "s3" => Ok(Some(Box::new(S3Storage::from_config(config)?))),
+        backend => bail!("Unknown storage backend {:?} (expected git or s3)", backend),
+    }
+}
+
+/// Downloads the files of `storage` missing from the database directory
+/// `root` and removes the local files it doesn't have anymore, such as
+/// retracted results, so that the merges run on its contents. Returns the
+/// number of downloaded and removed files.
+pub fn pull(storage: &dyn Storage, root: &Path) -> Result<(usize, usize)> {
+    let local = LocalStorage::new(root);
+    let remote: BTreeSet<String> = storage.list()?.into_iter().collect();
+    let existing: BTreeSet<String> = local.list()?.into_iter().collect();
+    for key in remote.difference(&existing) {
+        local.write(key, &storage.read(key)?)?;
+    }
+    for key in existing.difference(&remote) {
+        local.remove(key)?;
+        // Model directories left empty would fail to merge
+        fs::remove_dir(root.join(key).parent().unwrap()).ok();
+    }
+    Ok((remote.difference(&existing).count(), existing.difference(&remote).count()))
+}
+
+/// Uploads the files of the database directory `root` missing from
+/// `storage`, to seed it from a git database. Returns the number of
+/// uploaded files.
+pub fn push(storage: &dyn Storage, root: &Path) -> Result<usize> {
+    let local = LocalStorage::new(root);
+    let remote: BTreeSet<String> = storage.list()?.into_iter().collect();
+    let mut uploaded = 0;
+    for key in local.list()? {
+        if !remote.contains(&key) {
+            storage.write(&key, &local.read(&key)?)?;
+            uploaded += 1;
+        }
+    }
+    storage.flush()?;
+    Ok(uploaded)
+}
+
+static OBJECT_STORAGE: OnceLock<Box<dyn Storage>> = OnceLock::new();
+
+/// Keeps the results and metadata in `storage` rather than in the git
+/// repository.
+pub fn set_object_storage(storage: Box<dyn Storage>) -> Result<()> {
+    set_once(&OBJECT_STORAGE, storage, "object storage")
+}
+
+/// Returns the storage the results and metadata are kept in, None when
+/// they are committed to the git repository
+pub fn object_storage() -> Option<&'static dyn Storage> {
+    OBJECT_STORAGE.get().map(|s| s.as_ref())
+}
+
+/// Brings the database directory `root` up to date with the object
+/// storage, if the database has one
+pub fn sync_from_object_storage(root: &Path) -> Result<()> {
+    if let Some(storage) = object_storage() {
+        let (downloaded, removed) = pull(storage, root)?;
+        log(format!(
+            "Synced {} with {}: {} files downloaded, {} removed",
+            root.display(),
+            storage.describe(),
+            downloaded,
+            removed
+        ));
+    }
+    Ok(())
+}
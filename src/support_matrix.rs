@@ -0,0 +1,113 @@
+use anyhow::Result;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+
+use crate::common::BenchMerge;
+use crate::hwdb::HwdbCandidate;
+use crate::index::DatabaseIndex;
+use crate::vendors::Vendors;
+
+/// Document listing the supported devices at the root of the database,
+/// refreshed by every merge run
+pub static SUPPORT_MATRIX_FILE: &str = "SUPPORTED_DEVICES.md";
+
+/// Row of the support matrix: a model and its results across versions
+#[derive(Debug)]
+pub struct SupportedDevice {
+    pub model_name: String,
+    pub vendor: Option<String>,
+    /// resctl-bench versions the model has results for
+    pub versions: Vec<String>,
+    pub results: usize,
+    /// Firmware revisions the results were collected with
+    pub fwrevs: BTreeSet<String>,
+    /// Version and data points of the merge selected for the hwdb, if
+    /// the model has a hwdb entry
+    pub hwdb: Option<(String, usize)>,
+}
+
+/// Support matrix of the database
+#[derive(Debug)]
+pub struct SupportMatrix {
+    pub devices: Vec<SupportedDevice>,
+}
+
+impl SupportMatrix {
+    /// Builds the matrix of the models of `index`, with their `vendors`
+    /// and the hwdb `candidates` of the run. Without an override, the
+    /// candidate with the most data points is the one in the hwdb.
+    pub fn build(index: &DatabaseIndex, vendors: &Vendors, candidates: &[HwdbCandidate]) -> Result<Self> {
+        let mut best: BTreeMap<&str, &HwdbCandidate> = BTreeMap::new();
+        for candidate in candidates {
+            let entry = best.entry(candidate.model_name.as_str()).or_insert(candidate);
+            if candidate.data_points > entry.data_points {
+                *entry = candidate;
+            }
+        }
+        let mut devices = vec![];
+        for (model_name, versions) in index.models() {
+            let entries = index.model(model_name);
+            let mut fwrevs = BTreeSet::new();
+            for entry in &entries {
+                if let Some(dir) = entry.results.first().and_then(|r| r.parent()) {
+                    fwrevs.extend(
+                        BenchMerge::results_by_fwrev(dir)?
+                            .into_keys()
+                            .map(|fwrev| fwrev.trim_matches('"').to_string()),
+                    );
+                }
+            }
+            devices.push(SupportedDevice {
+                model_name: model_name.to_string(),
+                vendor: vendors.vendor_for(model_name).map(|(v, _)| v.to_string()),
+                versions: versions.iter().map(|v| v.to_string()).collect(),
+                results: entries.iter().map(|e| e.results.len()).sum(),
+                fwrevs,
+                hwdb: best.get(model_name).map(|c| (c.version.clone(), c.data_points)),
+            });
+        }
+        Ok(SupportMatrix { devices })
+    }
+
+    pub fn render(&self) -> String {
+        let mut text = format!(
+            "<!-- Generated by merge-results, changes will be overwritten -->\n\
+             # Supported devices\n\n\
+             {} models have results in the database, {} of them have a hwdb entry.\n\n\
+             | Model | Vendor | Versions | Results | Data points | Firmware revisions | hwdb |\n\
+             |---|---|---|---:|---:|---|---|\n",
+            self.devices.len(),
+            self.devices.iter().filter(|d| d.hwdb.is_some()).count()
+        );
+        for device in &self.devices {
+            let (data_points, hwdb) = match &device.hwdb {
+                Some((version, data_points)) => (data_points.to_string(), format!("yes ({})", version)),
+                None => ("-".to_string(), "no".to_string()),
+            };
+            text.push_str(&format!(
+                "| {} | {} | {} | {} | {} | {} | {} |\n",
+                device.model_name,
+                device.vendor.as_deref().unwrap_or("-"),
+                device.versions.join(", "),
+                device.results,
+                data_points,
+                device.fwrevs.iter().cloned().collect::<Vec<_>>().join(", "),
+                hwdb
+            ));
+        }
+        text
+    }
+
+    /// Writes the matrix at the root of the database in `database_dir`
+    /// if it changed. Returns whether it was written.
+    pub fn write_in(&self, database_dir: &Path) -> Result<bool> {
+        let path = database_dir.join(SUPPORT_MATRIX_FILE);
+        let text = self.render();
+        if fs::read_to_string(&path).is_ok_and(|current| current == text) {
+            return Ok(false);
+        }
+        fs::write(&path, text)?;
+        Ok(true)
+    }
+}
@@ -0,0 +1,573 @@
+use anyhow::Result;
+use handlebars::{
+    Context, Handlebars, Helper, HelperResult, Output, RenderContext, RenderErrorReason,
+};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::LazyLock;
+
+use crate::catalog::{locale, tr};
+use crate::diagnosis::diagnose;
+
+/// Directory of the database repository where templates overriding the
+/// built-in bot messages are looked up, as `<locale>/<name>.hbs` or
+/// `<name>.hbs` for all locales
+pub static TEMPLATES_DIR: &str = "templates";
+
+/// Bot messages posted on Github
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Message {
+    /// Acknowledges a new submission
+    Welcome,
+    /// All the submitted results were imported
+    Success,
+    /// Some of the submitted results failed validation
+    PartialFailure,
+    /// The user is not allowed to perform the requested action
+    PermissionDenied,
+    /// A result was held back for review
+    Quarantine,
+    /// Processing progress of a submission with many files
+    Progress,
+    /// Some of the submitted results were already imported from other
+    /// submissions
+    Duplicate,
+    /// A maintainer excluded a result from the merges or included it
+    /// again
+    Exclusion,
+    /// A maintainer retracted a result from the database
+    Retraction,
+    /// A submission was over the limits of the number of files or of
+    /// their total size
+    SubmissionTooLarge,
+    /// A submission didn't agree to the data license
+    LicenseRequired,
+    /// A submission didn't have the sign-off of the issue author
+    SignOffRequired,
+    /// Results were held back until the end of their embargo, or can't
+    /// be
+    Embargo,
+    /// An issue was opened without any result link
+    NeedsLinks,
+    /// The pull request importing the results of an issue was merged
+    Merged,
+    /// Preview of the hwdb entries a pull request would produce
+    HwdbPreview,
+    /// A submission was queued until the next day, over the daily limit
+    /// of its submitter
+    Queued,
+    /// The result links of a submission were all submitted in another
+    /// issue already
+    DuplicateSubmission,
+    /// The results of a first-time contributor were imported to the
+    /// pending area, until a maintainer accepts them
+    PendingReview,
+    /// A maintainer accepted the pending results of a submission
+    Accepted,
+}
+
+impl Message {
+    const ALL: [Message; 20] = [
+        Message::Welcome,
+        Message::Success,
+        Message::PartialFailure,
+        Message::PermissionDenied,
+        Message::Quarantine,
+        Message::Progress,
+        Message::Duplicate,
+        Message::Exclusion,
+        Message::Retraction,
+        Message::SubmissionTooLarge,
+        Message::LicenseRequired,
+        Message::SignOffRequired,
+        Message::Embargo,
+        Message::NeedsLinks,
+        Message::Merged,
+        Message::HwdbPreview,
+        Message::Queued,
+        Message::DuplicateSubmission,
+        Message::PendingReview,
+        Message::Accepted,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Message::Welcome => "welcome",
+            Message::Success => "success",
+            Message::PartialFailure => "partial-failure",
+            Message::PermissionDenied => "permission-denied",
+            Message::Quarantine => "quarantine",
+            Message::Progress => "progress",
+            Message::Duplicate => "duplicate",
+            Message::Exclusion => "exclusion",
+            Message::Retraction => "retraction",
+            Message::SubmissionTooLarge => "submission-too-large",
+            Message::LicenseRequired => "license-required",
+            Message::SignOffRequired => "signoff-required",
+            Message::Embargo => "embargo",
+            Message::NeedsLinks => "needs-links",
+            Message::Merged => "merged",
+            Message::HwdbPreview => "hwdb-preview",
+            Message::Queued => "queued",
+            Message::DuplicateSubmission => "duplicate-submission",
+            Message::PendingReview => "pending-review",
+            Message::Accepted => "accepted",
+        }
+    }
+
+    fn default_template(&self) -> &'static str {
+        match self {
+            Message::Welcome => include_str!("../templates/welcome.hbs"),
+            Message::Success => include_str!("../templates/success.hbs"),
+            Message::PartialFailure => include_str!("../templates/partial-failure.hbs"),
+            Message::PermissionDenied => include_str!("../templates/permission-denied.hbs"),
+            Message::Quarantine => include_str!("../templates/quarantine.hbs"),
+            Message::Progress => include_str!("../templates/progress.hbs"),
+            Message::Duplicate => include_str!("../templates/duplicate.hbs"),
+            Message::Exclusion => include_str!("../templates/exclusion.hbs"),
+            Message::Retraction => include_str!("../templates/retraction.hbs"),
+            Message::SubmissionTooLarge => include_str!("../templates/submission-too-large.hbs"),
+            Message::LicenseRequired => include_str!("../templates/license-required.hbs"),
+            Message::SignOffRequired => include_str!("../templates/signoff-required.hbs"),
+            Message::Embargo => include_str!("../templates/embargo.hbs"),
+            Message::NeedsLinks => include_str!("../templates/needs-links.hbs"),
+            Message::Merged => include_str!("../templates/merged.hbs"),
+            Message::HwdbPreview => include_str!("../templates/hwdb-preview.hbs"),
+            Message::Queued => include_str!("../templates/queued.hbs"),
+            Message::DuplicateSubmission => include_str!("../templates/duplicate-submission.hbs"),
+            Message::PendingReview => include_str!("../templates/pending-review.hbs"),
+            Message::Accepted => include_str!("../templates/accepted.hbs"),
+        }
+    }
+}
+
+/// Summary of the new results of a model, as listed in the messages
+#[derive(Debug, Clone, Serialize)]
+pub struct ModelSummary {
+    pub model_name: String,
+    pub version: String,
+    pub new_files: u64,
+    pub high_level: String,
+    /// The high-level summary as a Markdown table, if it could be parsed
+    pub high_level_table: Option<String>,
+}
+
+/// A submitted file that failed, as listed in the messages
+#[derive(Debug, Serialize)]
+pub struct FileError {
+    pub input: String,
+    pub error: String,
+    /// Catalog key of the hint of a recognized failure
+    pub hint: Option<String>,
+    /// Last lines of the resctl-bench stderr
+    pub stderr: Option<String>,
+}
+
+impl FileError {
+    /// Creates the entry of the `input` file which failed validation
+    /// with `error`, with the hint and stderr excerpt of its diagnosis
+    pub fn new(input: String, error: &anyhow::Error) -> Self {
+        let diagnosis = diagnose(error);
+        FileError {
+            input,
+            error: diagnosis.message,
+            hint: diagnosis.kind.map(|k| k.hint().to_string()),
+            stderr: diagnosis.excerpt,
+        }
+    }
+}
+
+/// Summaries of the new results of the models of a resctl-bench
+/// version
+#[derive(Debug, Serialize)]
+pub struct VersionSummary {
+    pub version: String,
+    pub models: Vec<ModelSummary>,
+}
+
+/// Context of the success and partial failure messages
+#[derive(Debug, Serialize)]
+pub struct SubmissionReport {
+    /// Summaries of the models, for the templates which don't group
+    /// them by version
+    pub summaries: Vec<ModelSummary>,
+    /// Summaries of the models, by version
+    pub versions: Vec<VersionSummary>,
+    /// Whether the results span several versions, listed under a
+    /// heading each
+    pub multi_version: bool,
+    pub errors: Vec<FileError>,
+}
+
+impl SubmissionReport {
+    /// Creates the report of the model `summaries` and `errors` of a
+    /// submission, grouping the summaries by version
+    pub fn new(summaries: Vec<ModelSummary>, errors: Vec<FileError>) -> Self {
+        let mut by_version: BTreeMap<String, Vec<ModelSummary>> = BTreeMap::new();
+        for summary in &summaries {
+            by_version.entry(summary.version.clone()).or_default().push(summary.clone());
+        }
+        let versions: Vec<VersionSummary> = by_version
+            .into_iter()
+            .map(|(version, mut models)| {
+                models.sort_by(|a, b| a.model_name.cmp(&b.model_name));
+                VersionSummary { version, models }
+            })
+            .collect();
+        SubmissionReport {
+            summaries,
+            multi_version: versions.len() > 1,
+            versions,
+            errors,
+        }
+    }
+
+    /// Renders the success or partial failure message, depending on
+    /// whether there are errors
+    pub fn render(&self) -> Result<String> {
+        let message = match self.errors.is_empty() {
+            true => Message::Success,
+            false => Message::PartialFailure,
+        };
+        MESSAGES.render(message, self)
+    }
+}
+
+/// A submitted file that was already in the database, with the
+/// submission it was first imported from
+#[derive(Debug, Serialize)]
+pub struct DuplicateFile {
+    pub input: String,
+    /// Issue and URL of the original submission
+    pub issue: Option<u64>,
+    pub url: Option<String>,
+    /// Pull request that imported the original submission
+    pub pull_request: Option<String>,
+}
+
+/// Context of the duplicate message
+#[derive(Debug, Serialize)]
+pub struct DuplicateReport {
+    pub duplicates: Vec<DuplicateFile>,
+}
+
+impl DuplicateReport {
+    pub fn render(&self) -> Result<String> {
+        MESSAGES.render(Message::Duplicate, self)
+    }
+}
+
+/// Context of the message of a submission whose result links were all
+/// submitted in another issue
+#[derive(Debug, Serialize)]
+pub struct DuplicateSubmission {
+    pub submitter: String,
+    pub issue: u64,
+    /// URL of the pull request importing the results of `issue`, if any
+    pub pull_request: Option<String>,
+}
+
+impl DuplicateSubmission {
+    pub fn render(&self) -> Result<String> {
+        MESSAGES.render(Message::DuplicateSubmission, self)
+    }
+}
+
+/// Context of the permission denied message
+#[derive(Debug, Serialize)]
+pub struct PermissionDenied {
+    pub submitter: String,
+    /// What the user tried to do
+    pub action: String,
+}
+
+impl PermissionDenied {
+    pub fn render(&self) -> Result<String> {
+        MESSAGES.render(Message::PermissionDenied, self)
+    }
+}
+
+/// Context of the exclusion message
+#[derive(Debug, Serialize)]
+pub struct ExclusionReport {
+    pub file: String,
+    pub model_name: String,
+    pub version: String,
+    /// Whether the result was excluded or included again
+    pub excluded: bool,
+    pub reason: Option<String>,
+    /// Branch the change was committed to
+    pub branch: String,
+}
+
+impl ExclusionReport {
+    pub fn render(&self) -> Result<String> {
+        MESSAGES.render(Message::Exclusion, self)
+    }
+}
+
+/// Context of the retraction message
+#[derive(Debug, Serialize)]
+pub struct RetractionReport {
+    pub file: String,
+    pub model_name: String,
+    pub version: String,
+    pub reason: String,
+    /// Branch the removal was committed to
+    pub branch: String,
+}
+
+impl RetractionReport {
+    pub fn render(&self) -> Result<String> {
+        MESSAGES.render(Message::Retraction, self)
+    }
+}
+
+/// Context of the message refusing a submission over the limits
+#[derive(Debug, Serialize)]
+pub struct SubmissionTooLarge {
+    pub submitter: String,
+    /// Whether the submission has too many files, rather than files
+    /// too large
+    pub too_many_urls: bool,
+    pub urls: usize,
+    pub max_urls: usize,
+    pub size: String,
+    pub max_size: String,
+}
+
+impl SubmissionTooLarge {
+    pub fn render(&self) -> Result<String> {
+        MESSAGES.render(Message::SubmissionTooLarge, self)
+    }
+}
+
+/// Context of the message refusing a submission which didn't agree to
+/// the data license
+#[derive(Debug, Serialize)]
+pub struct LicenseRequired {
+    pub submitter: String,
+    pub license: String,
+    pub license_url: Option<String>,
+    pub phrase: String,
+}
+
+impl LicenseRequired {
+    pub fn render(&self) -> Result<String> {
+        MESSAGES.render(Message::LicenseRequired, self)
+    }
+}
+
+/// Context of the message refusing a submission without the sign-off of
+/// the issue author
+#[derive(Debug, Serialize)]
+pub struct SignOffRequired {
+    pub submitter: String,
+    /// Whether the results were submitted by another user than the
+    /// issue author
+    pub not_author: bool,
+    pub author: String,
+}
+
+impl SignOffRequired {
+    pub fn render(&self) -> Result<String> {
+        MESSAGES.render(Message::SignOffRequired, self)
+    }
+}
+
+/// Context of the message about embargoed results
+#[derive(Debug, Serialize)]
+pub struct EmbargoReport {
+    pub submitter: String,
+    /// Whether the results were held back, rather than refused because
+    /// embargoes are not supported
+    pub held: bool,
+    pub count: usize,
+    /// End of the embargo (YYYY-MM-DD)
+    pub date: String,
+}
+
+impl EmbargoReport {
+    pub fn render(&self) -> Result<String> {
+        MESSAGES.render(Message::Embargo, self)
+    }
+}
+
+/// Context of the message explaining how to submit results on an issue
+/// without any result link
+#[derive(Debug, Serialize)]
+pub struct NeedsLinks {
+    pub submitter: String,
+    /// Links of the issue which are not result files on a supported host
+    pub ignored: Vec<String>,
+    /// Where a submission issue can be opened with the issue form
+    pub form_url: String,
+}
+
+impl NeedsLinks {
+    pub fn render(&self) -> Result<String> {
+        MESSAGES.render(Message::NeedsLinks, self)
+    }
+}
+
+/// Outputs of a model whose results were merged, as listed in the
+/// message closing their issue
+#[derive(Debug, Serialize)]
+pub struct MergedModel {
+    pub model_name: String,
+    pub version: String,
+    pub pdf_url: Option<String>,
+    pub hwdb_url: Option<String>,
+}
+
+/// Context of the message closing an issue once the pull request
+/// importing its results was merged
+#[derive(Debug, Serialize)]
+pub struct MergedReport {
+    pub commit_url: String,
+    pub models: Vec<MergedModel>,
+}
+
+impl MergedReport {
+    pub fn render(&self) -> Result<String> {
+        MESSAGES.render(Message::Merged, self)
+    }
+}
+
+/// hwdb entry a model would get once a pull request is merged
+#[derive(Debug, Serialize)]
+pub struct HwdbPreviewModel {
+    pub model_name: String,
+    pub version: String,
+    pub data_points: usize,
+    pub hwdb: String,
+    /// Whether the model has an entry in the published hwdb file
+    pub published: bool,
+    /// Diff against the published entry, None if it is unchanged
+    pub diff: Option<String>,
+}
+
+/// Context of the message previewing the hwdb entries of the models
+/// changed by a pull request
+#[derive(Debug, Serialize)]
+pub struct HwdbPreview {
+    pub models: Vec<HwdbPreviewModel>,
+}
+
+impl HwdbPreview {
+    pub fn render(&self) -> Result<String> {
+        MESSAGES.render(Message::HwdbPreview, self)
+    }
+}
+
+/// Context of the message queuing a submission over the daily limit of
+/// its submitter
+#[derive(Debug, Serialize)]
+pub struct QueuedReport {
+    pub submitter: String,
+    /// Other issues the submitter got results imported from today
+    pub submitted: usize,
+    pub max: usize,
+}
+
+impl QueuedReport {
+    pub fn render(&self) -> Result<String> {
+        MESSAGES.render(Message::Queued, self)
+    }
+}
+
+/// Context of the message holding the results of a first-time
+/// contributor for review
+#[derive(Debug, Serialize)]
+pub struct PendingReview {
+    pub submitter: String,
+    pub count: usize,
+}
+
+impl PendingReview {
+    pub fn render(&self) -> Result<String> {
+        MESSAGES.render(Message::PendingReview, self)
+    }
+}
+
+/// Context of the message of a maintainer accepting pending results
+#[derive(Debug, Serialize)]
+pub struct AcceptedReport {
+    pub maintainer: String,
+    pub count: usize,
+    pub branch: String,
+}
+
+impl AcceptedReport {
+    pub fn render(&self) -> Result<String> {
+        MESSAGES.render(Message::Accepted, self)
+    }
+}
+
+/// `{{t "key" name=value...}}` helper, rendering the message `key` of
+/// the catalog with the hash parameters as arguments
+fn translate_helper(
+    h: &Helper,
+    _: &Handlebars,
+    _: &Context,
+    _: &mut RenderContext,
+    out: &mut dyn Output,
+) -> HelperResult {
+    let key = h
+        .param(0)
+        .and_then(|p| p.value().as_str())
+        .ok_or(RenderErrorReason::ParamNotFoundForIndex("t", 0))?;
+    let args: Vec<(&str, String)> = h
+        .hash()
+        .iter()
+        .map(|(name, value)| match value.value() {
+            serde_json::Value::String(s) => (*name, s.clone()),
+            v => (*name, v.to_string()),
+        })
+        .collect();
+    out.write(&tr(key, &args))?;
+    Ok(())
+}
+
+/// The bot message templates: the built-in ones, overridden by those
+/// found in the templates directory. Their texts come from the message
+/// catalog of the current locale.
+pub struct Templates {
+    handlebars: Handlebars<'static>,
+}
+
+impl Templates {
+    /// Loads the built-in templates and the overrides in `dir`, if set
+    pub fn load(dir: Option<&Path>) -> Result<Self> {
+        let mut handlebars = Handlebars::new();
+        // Messages are markdown, not html
+        handlebars.register_escape_fn(handlebars::no_escape);
+        handlebars.register_helper("t", Box::new(translate_helper));
+        for message in Message::ALL {
+            let filename = format!("{}.hbs", message.name());
+            let path = dir.and_then(|d| {
+                [d.join(locale()).join(&filename), d.join(&filename)]
+                    .into_iter()
+                    .find(|p| p.exists())
+            });
+            if let Some(path) = path {
+                handlebars.register_template_file(message.name(), &path)?;
+            } else {
+                handlebars.register_template_string(message.name(), message.default_template())?;
+            }
+        }
+        Ok(Templates { handlebars })
+    }
+
+    pub fn render(&self, message: Message, data: &impl Serialize) -> Result<String> {
+        Ok(self.handlebars.render(message.name(), data)?.trim_end().to_string())
+    }
+}
+
+pub static MESSAGES: LazyLock<Templates> = LazyLock::new(|| {
+    Templates::load(Some(Path::new(TEMPLATES_DIR))).unwrap_or_else(|e| {
+        eprintln!("Failed to load the templates in {}, using the built-in ones: {:#}", TEMPLATES_DIR, e);
+        Templates::load(None).expect("Invalid built-in templates")
+    })
+});
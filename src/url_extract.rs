@@ -0,0 +1,275 @@
+use anyhow::{bail, Result};
+use linkify::{LinkFinder, LinkKind};
+use pulldown_cmark::{Event, Options, Parser, Tag, TagEnd};
+use regex::Regex;
+use std::sync::{LazyLock, OnceLock};
+
+use crate::config::{set_once, UrlsConfig};
+
+/// Extensions of the result files when the config file names none
+static DEFAULT_EXTENSIONS: &[&str] = &[".json.gz"];
+
+/// Query parameters of signed URLs: Azure SAS tokens and S3 and GCS
+/// signatures
+pub static SIGNATURE_PARAMS: &[&str] = &["sig", "X-Amz-Signature", "X-Goog-Signature"];
+
+/// Query parameters added by trackers and link shorteners, removed from
+/// the URLs which aren't signed
+static TRACKING_PARAMS: &[&str] = &["fbclid", "gclid", "mc_cid", "mc_eid", "ref_src"];
+
+/// Characters ending a sentence or some markdown formatting, which are
+/// not part of the URLs they follow
+static TRAILING_NOISE: &[char] = &['.', ',', ';', ':', '!', '?', '\'', '"', '*', '_', '~', '`', '>', '|'];
+
+/// HTML comments, e.g. the instructions of the issue templates, whose
+/// tags aren't links
+static HTML_COMMENT: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?s)<!--.*?-->").unwrap());
+
+/// HTML links and images, as the Github uploader inserts attachments,
+/// with their target
+static HTML_LINK: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?is)<(?:a|img)\b[^>]*?\b(?:href|src)\s*=\s*["']([^"']*)["'][^>]*>"#).unwrap()
+});
+
+/// Host the attachments of the issues are served from once redirected,
+/// with signed URLs, and the prefix of their buckets
+const GITHUB_OBJECTS_HOST: &str = "objects.githubusercontent.com";
+const GITHUB_FILES_BUCKET: &str = "github-production-repository-file-";
+
+/// Attachment of an issue or comment uploaded to Github, by what its URL
+/// tells of its owner
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GithubAttachment {
+    /// github.com/user-attachments/files/ID/NAME, of the current uploader.
+    /// The files belong to the user who uploaded them, and the API tells
+    /// nothing of the issues they were uploaded to.
+    User,
+    /// github.com/OWNER/REPO/files/ID/NAME, of the previous uploader
+    Repository(String),
+    /// objects.githubusercontent.com/github-production-repository-file-*/
+    /// REPO_ID/..., which the links of the previous uploader redirect to
+    RepositoryId(u64),
+}
+
+impl GithubAttachment {
+    /// Returns the attachment `link` points to, if it is one
+    pub fn parse(link: &str) -> Option<Self> {
+        let url = reqwest::Url::parse(link).ok()?;
+        let segments: Vec<&str> = url.path_segments()?.collect();
+        match (url.host_str()?, segments.as_slice()) {
+            ("github.com", ["user-attachments", "files", _, name]) if !name.is_empty() => Some(Self::User),
+            ("github.com", [owner, repo, "files", _, name]) if !name.is_empty() => {
+                Some(Self::Repository(format!("{}/{}", owner, repo)))
+            }
+            (GITHUB_OBJECTS_HOST, [bucket, repo_id, ..]) if bucket.starts_with(GITHUB_FILES_BUCKET) => {
+                repo_id.parse().ok().map(Self::RepositoryId)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns why the attachment doesn't belong to `repository`
+    /// (owner/name) with `repository_id`, if it can be told
+    pub fn mismatch(&self, repository: &str, repository_id: Option<u64>) -> Option<String> {
+        match self {
+            Self::User => None,
+            Self::Repository(owner) if !owner.eq_ignore_ascii_case(repository) => {
+                Some(format!("attachment of {} rather than {}", owner, repository))
+            }
+            Self::RepositoryId(id) if repository_id.is_some_and(|r| r != *id) => {
+                Some(format!("attachment of the repository with id {} rather than {}", id, repository))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Appends the targets of the HTML links and images of `html` to `text`
+fn push_html_links(text: &mut String, html: &str) {
+    for link in HTML_LINK.captures_iter(&HTML_COMMENT.replace_all(html, " ")) {
+        text.push(' ');
+        text.push_str(&link[1]);
+    }
+    text.push(' ');
+}
+
+/// Returns the text of the markdown `body` the links are searched in:
+/// its text, without the code blocks, and the targets of its markdown
+/// and HTML links and images. Inline code spans are kept, as links are
+/// often quoted that way.
+fn linkable_text(body: &str) -> String {
+    let mut text = String::new();
+    // The HTML of a block comes line by line, and is searched as a whole
+    let mut html = String::new();
+    let mut in_code_block = false;
+    for event in Parser::new_ext(body, Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH) {
+        if !matches!(event, Event::Html(_) | Event::InlineHtml(_)) && !html.is_empty() {
+            push_html_links(&mut text, &html);
+            html.clear();
+        }
+        match event {
+            Event::Start(Tag::CodeBlock(_)) => in_code_block = true,
+            Event::End(TagEnd::CodeBlock) => in_code_block = false,
+            Event::Text(t) | Event::Code(t) if !in_code_block => text.push_str(&t),
+            Event::Html(h) | Event::InlineHtml(h) => html.push_str(&h),
+            Event::Start(Tag::Link { dest_url, .. } | Tag::Image { dest_url, .. }) => {
+                text.push(' ');
+                text.push_str(&dest_url);
+                text.push(' ');
+            }
+            // Text in separate elements isn't one link
+            Event::Start(_) | Event::End(_) | Event::SoftBreak | Event::HardBreak => text.push('\n'),
+            _ => {}
+        }
+    }
+    push_html_links(&mut text, &html);
+    text
+}
+
+/// Whether `url` has the query string of a signed URL, whose signature
+/// covers the whole query
+pub fn is_signed(url: &reqwest::Url) -> bool {
+    url.query_pairs().any(|(k, _)| SIGNATURE_PARAMS.contains(&k.as_ref()))
+}
+
+/// Whether the query parameter `key` is only there for tracking
+fn is_tracking_param(key: &str) -> bool {
+    key.starts_with("utm_") || TRACKING_PARAMS.contains(&key)
+}
+
+/// Removes the trailing punctuation and the unbalanced closing brackets
+/// of markdown from `link`
+fn trim_link(mut link: &str) -> &str {
+    loop {
+        let trimmed = link.trim_end_matches(TRAILING_NOISE);
+        let trimmed = match trimmed.chars().last() {
+            Some(close @ (')' | ']')) => {
+                let open = if close == ')' { '(' } else { '[' };
+                match trimmed.matches(open).count() < trimmed.matches(close).count() {
+                    true => &trimmed[..trimmed.len() - 1],
+                    false => trimmed,
+                }
+            }
+            _ => trimmed,
+        };
+        if trimmed == link {
+            return link;
+        }
+        link = trimmed;
+    }
+}
+
+/// Cleans up a `link` found in a message: removes the markdown and
+/// punctuation around it, decodes the HTML-escaped ampersands of its
+/// query string, drops its fragment and, unless it is signed, its
+/// tracking parameters. Returns None if it isn't an http(s) URL.
+pub fn clean_url(link: &str) -> Option<String> {
+    let link = link.replace("&amp;", "&");
+    let mut url = reqwest::Url::parse(trim_link(&link)).ok()?;
+    if !matches!(url.scheme(), "http" | "https") || url.host_str().is_none() {
+        return None;
+    }
+    url.set_fragment(None);
+    if !is_signed(&url) {
+        // The parameters are filtered as they are written, so that the
+        // encoding of the others is left alone
+        let query: Vec<String> = url
+            .query()
+            .unwrap_or_default()
+            .split('&')
+            .filter(|p| !p.is_empty() && !is_tracking_param(p.split('=').next().unwrap_or_default()))
+            .map(str::to_string)
+            .collect();
+        url.set_query(Some(&query.join("&")).filter(|q| !q.is_empty()).map(String::as_str));
+    }
+    // Dropping the fragment or parameters can leave punctuation at the
+    // end, which is cleaned up in turn
+    let url = url.to_string();
+    match trim_link(&url) {
+        trimmed if trimmed.len() < url.len() => clean_url(trimmed),
+        _ => Some(url),
+    }
+}
+
+/// Finds the links to result files in the bodies of the submissions
+#[derive(Debug, Clone)]
+pub struct UrlExtractor {
+    /// Extensions the paths of the result URLs end with, lowercase
+    extensions: Vec<String>,
+}
+
+impl Default for UrlExtractor {
+    fn default() -> Self {
+        UrlExtractor {
+            extensions: DEFAULT_EXTENSIONS.iter().map(|e| e.to_string()).collect(),
+        }
+    }
+}
+
+impl UrlExtractor {
+    /// Creates the extractor of the [urls] section of the config file,
+    /// with the default extensions if it names none
+    pub fn from_config(config: &UrlsConfig) -> Result<Self> {
+        let Some(extensions) = &config.extensions else {
+            return Ok(UrlExtractor::default());
+        };
+        if extensions.is_empty() {
+            bail!("No result file extension in [urls]");
+        }
+        for extension in extensions {
+            if !extension.starts_with('.') || extension.len() < 2 {
+                bail!("Invalid result file extension {:?}, expected e.g. \".json.gz\"", extension);
+            }
+        }
+        Ok(UrlExtractor {
+            extensions: extensions.iter().map(|e| e.to_lowercase()).collect(),
+        })
+    }
+
+    pub fn extensions(&self) -> &[String] {
+        &self.extensions
+    }
+
+    /// Returns the http(s) URLs of the markdown `body`, cleaned up with
+    /// `clean_url`, in order and without duplicates. The links of its
+    /// code blocks, e.g. pasted logs, are left out.
+    pub fn links(&self, body: &str) -> Vec<String> {
+        let mut finder = LinkFinder::new();
+        finder.kinds(&[LinkKind::Url]);
+        let text = linkable_text(body);
+        let mut links: Vec<String> = vec![];
+        for link in finder.links(&text) {
+            // The text and target of a markdown link can both be URLs,
+            // which are found as one when nothing separates them
+            for part in link.as_str().split("](") {
+                match clean_url(part) {
+                    Some(url) if !links.contains(&url) => links.push(url),
+                    _ => {}
+                }
+            }
+        }
+        links
+    }
+
+    /// Whether `link` points to a result file, its path ending with one
+    /// of the extensions whatever its query string
+    pub fn is_result_url(&self, link: &str) -> bool {
+        reqwest::Url::parse(link).is_ok_and(|url| {
+            let path = url.path().to_lowercase();
+            self.extensions.iter().any(|e| path.ends_with(e.as_str()))
+        })
+    }
+}
+
+static URL_EXTRACTOR: OnceLock<UrlExtractor> = OnceLock::new();
+
+/// Sets the extractor of the result links.
+pub fn set_url_extractor(extractor: UrlExtractor) -> Result<()> {
+    set_once(&URL_EXTRACTOR, extractor, "extractor of the result links")
+}
+
+/// Returns the extractor of the result links, the default one if none
+/// was set
+pub fn url_extractor() -> &'static UrlExtractor {
+    URL_EXTRACTOR.get_or_init(UrlExtractor::default)
+}
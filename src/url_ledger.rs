@@ -0,0 +1,162 @@
+use anyhow::{Context, Result};
+use git2::{Repository, Tree};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::checkpoint::{ImportCheckpoint, UrlState};
+use crate::index::ResultMetadata;
+
+/// Directory of the database with the ledger of the processed URLs of
+/// each issue
+pub static URL_LEDGER_DIR: &str = "processed-urls";
+
+/// What came out of processing a URL of a submission
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "outcome", rename_all = "kebab-case")]
+pub enum ProcessedUrl {
+    /// The result was added to the database as `files`
+    Imported {
+        version: String,
+        model_name: String,
+        files: Vec<PathBuf>,
+    },
+    /// The result was already in the database, imported from another
+    /// submission
+    Duplicate { issue: Option<u64>, url: Option<String> },
+}
+
+/// Ledger of the URLs of an issue already processed, committed with its
+/// results, so that the runs triggered by the edits of the issue only
+/// download and validate its new links. The URLs, which may hold
+/// credentials, are recorded by their sha256. The failed ones aren't
+/// recorded, as their files may have been fixed in place.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct UrlLedger {
+    pub issue: u64,
+    pub urls: BTreeMap<String, ProcessedUrl>,
+}
+
+impl UrlLedger {
+    /// Returns the path of the ledger of `issue` in `database_path`
+    pub fn path_for(database_path: &Path, issue: u64) -> PathBuf {
+        database_path.join(URL_LEDGER_DIR).join(format!("{}.json", issue))
+    }
+
+    /// Returns the key of `url` in the ledger
+    pub fn key(url: &str) -> String {
+        format!("{:x}", Sha256::digest(url.as_bytes()))
+    }
+
+    /// Loads the ledger of `issue` in `database_path` and, over it, the
+    /// one in `tree` if any, i.e. of the branch of the submission
+    pub fn load(git_repo: &Repository, tree: Option<&Tree>, database_path: &Path, issue: u64) -> Result<Self> {
+        let path = Self::path_for(database_path, issue);
+        let mut ledger = match fs::read_to_string(&path) {
+            Ok(contents) => {
+                serde_json::from_str(&contents).with_context(|| format!("Invalid {}", path.display()))?
+            }
+            Err(_) => UrlLedger { issue, ..Default::default() },
+        };
+        if let Some(entry) = tree.and_then(|t| t.get_path(&path).ok()) {
+            let branch: Self = serde_json::from_slice(git_repo.find_blob(entry.id())?.content())
+                .with_context(|| format!("Invalid {} in the branch of #{}", path.display(), issue))?;
+            ledger.urls.extend(branch.urls);
+        }
+        Ok(ledger)
+    }
+
+    pub fn get(&self, url: &str) -> Option<&ProcessedUrl> {
+        self.urls.get(&Self::key(url))
+    }
+
+    pub fn record(&mut self, url: &str, processed: ProcessedUrl) {
+        self.urls.insert(Self::key(url), processed);
+    }
+
+    /// Records the outcome of the URLs of an import run: the results
+    /// staged or already merged, and the duplicates
+    pub fn record_run(&mut self, checkpoint: &ImportCheckpoint) {
+        for (url, state) in &checkpoint.urls {
+            let processed = match state {
+                UrlState::Staged { version, model_name, files } => ProcessedUrl::Imported {
+                    version: version.clone(),
+                    model_name: model_name.clone(),
+                    files: files.clone(),
+                },
+                UrlState::Merged { file } => match ResultMetadata::load_for(file) {
+                    Some(metadata) => ProcessedUrl::Imported {
+                        version: metadata.version,
+                        model_name: metadata.model_name,
+                        files: vec![file.clone(), ResultMetadata::path_for(file)],
+                    },
+                    None => continue,
+                },
+                UrlState::Duplicate { issue, url } => ProcessedUrl::Duplicate { issue: *issue, url: url.clone() },
+                _ => continue,
+            };
+            self.record(url, processed);
+        }
+    }
+
+    /// Saves the ledger in `database_path` and returns its path
+    pub fn save(&self, database_path: &Path) -> Result<PathBuf> {
+        let path = Self::path_for(database_path, self.issue);
+        fs::create_dir_all(path.parent().unwrap())?;
+        fs::write(&path, serde_json::to_string_pretty(self)?)?;
+        Ok(path)
+    }
+
+    /// Marks the `urls` the ledger records as processed in the
+    /// `checkpoint` of the run, unless it already has them: the results
+    /// merged since into the `head` tree, those of the `branch` tree of
+    /// the submission, whose files are restored from it, and the
+    /// duplicates. Results whose files are in neither are processed
+    /// again. Returns the number of URLs marked.
+    pub fn resume(
+        &self,
+        git_repo: &Repository,
+        head: &Tree,
+        branch: Option<&Tree>,
+        checkpoint: &mut ImportCheckpoint,
+        urls: &[String],
+    ) -> Result<usize> {
+        let mut resumed = 0;
+        for url in urls {
+            if checkpoint.get(url).is_some() {
+                continue;
+            }
+            let state = match self.get(url) {
+                Some(ProcessedUrl::Imported { files, .. }) if files.iter().all(|f| head.get_path(f).is_ok()) => {
+                    UrlState::Merged { file: files[0].clone() }
+                }
+                Some(ProcessedUrl::Imported { version, model_name, files }) => {
+                    let Some(branch) = branch.filter(|b| files.iter().all(|f| b.get_path(f).is_ok())) else {
+                        continue;
+                    };
+                    for file in files {
+                        let blob = git_repo.find_blob(branch.get_path(file)?.id())?;
+                        if let Some(dir) = file.parent() {
+                            fs::create_dir_all(dir)?;
+                        }
+                        fs::write(file, blob.content())?;
+                    }
+                    UrlState::Staged {
+                        version: version.clone(),
+                        model_name: model_name.clone(),
+                        files: files.clone(),
+                    }
+                }
+                Some(ProcessedUrl::Duplicate { issue, url }) => {
+                    UrlState::Duplicate { issue: *issue, url: url.clone() }
+                }
+                None => continue,
+            };
+            checkpoint.set(url, state)?;
+            resumed += 1;
+        }
+        Ok(resumed)
+    }
+}
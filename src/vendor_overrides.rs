@@ -0,0 +1,308 @@
+use anyhow::{bail, Context, Result};
+use glob::glob;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Directory of the database repository holding the vendor supplied
+/// overrides, one `<model>.toml` file per model
+pub static VENDOR_OVERRIDES_DIR: &str = "vendor-overrides";
+
+/// How a vendor override combines with the generated entry
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OverrideMode {
+    /// The override properties replace the generated entry entirely
+    #[default]
+    Replace,
+    /// The override properties replace the generated ones of the same
+    /// name, the others are kept
+    Merge,
+}
+
+impl fmt::Display for OverrideMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OverrideMode::Replace => write!(f, "replace"),
+            OverrideMode::Merge => write!(f, "merge"),
+        }
+    }
+}
+
+/// Tuned values supplied by a vendor for a model, e.g.:
+///
+/// ```toml
+/// model = "SAMSUNG MZVLB512HBJQ-000L7"
+/// vendor = "Samsung"
+/// reason = "Values validated on the production firmware"
+/// mode = "merge"
+///
+/// [properties]
+/// IOCOST_QOS_NAIVE = "rpct=95.00 rlat=5000 wpct=95.00 wlat=5000 min=50.00 max=150.00"
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VendorOverride {
+    /// Model name, as reported by the drive
+    pub model: String,
+    /// Firmware revision the override is restricted to
+    pub fwrev: Option<String>,
+    pub vendor: String,
+    pub reason: Option<String>,
+    #[serde(default)]
+    pub mode: OverrideMode,
+    pub properties: BTreeMap<String, String>,
+    /// File the override was loaded from
+    #[serde(skip)]
+    pub path: PathBuf,
+}
+
+impl VendorOverride {
+    fn load(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read vendor override {:?}", path))?;
+        let mut vendor_override: VendorOverride = toml::from_str(&contents)
+            .with_context(|| format!("Failed to parse vendor override {:?}", path))?;
+        vendor_override.path = path.to_path_buf();
+        vendor_override
+            .validate()
+            .with_context(|| format!("Invalid vendor override {:?}", path))?;
+        Ok(vendor_override)
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.model.is_empty() || self.model.contains(['\n', ':']) {
+            bail!("Invalid model name {:?}", self.model);
+        }
+        if self.properties.is_empty() {
+            bail!("No properties");
+        }
+        for (name, value) in &self.properties {
+            let valid_name = name.starts_with("IOCOST_")
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_');
+            if !valid_name {
+                bail!("Invalid property name {:?}, expected IOCOST_*", name);
+            }
+            if value.contains('\n') {
+                bail!("Invalid multi-line value for {}", name);
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the override applies to `model_name`, as found in the
+    /// database (with the spaces replaced with underscores)
+    fn is_for(&self, model_name: &str) -> bool {
+        self.model.replace(' ', "_") == model_name
+    }
+
+    fn match_key(&self) -> String {
+        format!(
+            "block:*:name:{}:fwrev:{}:",
+            self.model,
+            self.fwrev.as_deref().unwrap_or("*")
+        )
+    }
+
+    fn annotation(&self) -> String {
+        let mut annotation = format!(
+            "# Vendor override ({}) by {}, from {}",
+            self.mode,
+            self.vendor,
+            self.path.display()
+        );
+        if let Some(reason) = &self.reason {
+            annotation.push_str(&format!(": {}", reason));
+        }
+        annotation
+    }
+
+    /// Sets the override properties in the generated `section`
+    fn merge_into(&self, section: &mut Section) {
+        section.comments.push(self.annotation());
+        for (name, value) in &self.properties {
+            match section.properties.iter_mut().find(|(n, _)| n == name) {
+                Some(property) => property.1 = value.clone(),
+                None => section.properties.push((name.clone(), value.clone())),
+            }
+        }
+    }
+
+    /// Returns the section of the override, on top of the generated
+    /// `base` properties in merge mode
+    fn section(&self, base: Option<&Section>) -> Section {
+        let mut section = Section {
+            comments: vec![],
+            keys: vec![self.match_key()],
+            properties: match (self.mode, base) {
+                (OverrideMode::Merge, Some(base)) => base.properties.clone(),
+                _ => vec![],
+            },
+        };
+        self.merge_into(&mut section);
+        section
+    }
+}
+
+/// A hwdb entry: comments, match keys and ` NAME=value` properties
+#[derive(Debug, Clone)]
+struct Section {
+    comments: Vec<String>,
+    keys: Vec<String>,
+    properties: Vec<(String, String)>,
+}
+
+impl Section {
+    fn parse(text: &str) -> Self {
+        let mut section = Section {
+            comments: vec![],
+            keys: vec![],
+            properties: vec![],
+        };
+        for line in text.lines().filter(|l| !l.trim().is_empty()) {
+            if line.starts_with('#') {
+                section.comments.push(line.to_string());
+            } else if line.starts_with(char::is_whitespace) {
+                let (name, value) = line.trim().split_once('=').unwrap_or((line.trim(), ""));
+                section.properties.push((name.to_string(), value.to_string()));
+            } else {
+                section.keys.push(line.to_string());
+            }
+        }
+        section
+    }
+
+    /// Whether the section matches specific firmware revisions only
+    fn is_fwrev_specific(&self) -> bool {
+        self.keys
+            .iter()
+            .all(|k| k.split_once(":fwrev:").is_some_and(|(_, fwrev)| fwrev != "*:"))
+    }
+}
+
+impl fmt::Display for Section {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for line in self.comments.iter().chain(&self.keys) {
+            writeln!(f, "{}", line)?;
+        }
+        for (name, value) in &self.properties {
+            writeln!(f, " {}={}", name, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// The vendor overrides of the database, merged into the final hwdb
+/// with the following precedence, from lowest to highest:
+///
+/// 1. The generated entry of the best merge of the model (or the one
+///    selected with `OVERRIDE_BEST_<model>`).
+/// 2. A model-wide override, which replaces the generated entry or,
+///    in merge mode, the generated properties it sets in all of its
+///    sections.
+/// 3. A firmware specific override, written after the entries of the
+///    model so that it wins for that firmware revision. In merge mode,
+///    it is applied on top of the model-wide properties.
+///
+/// Overrides for models without results in the database are added as
+/// is, after the generated entries.
+#[derive(Debug, Default)]
+pub struct VendorOverrides {
+    overrides: Vec<VendorOverride>,
+}
+
+impl VendorOverrides {
+    /// Loads the overrides in `dir`. A missing directory means there
+    /// are no overrides.
+    pub fn load(dir: &Path) -> Result<Self> {
+        let mut overrides = vec![];
+        for path in glob(&dir.join("*.toml").to_string_lossy())?.flatten() {
+            overrides.push(VendorOverride::load(&path)?);
+        }
+        overrides.sort_by(|a, b| (&a.model, &a.fwrev).cmp(&(&b.model, &b.fwrev)));
+        if let Some(pair) = overrides
+            .windows(2)
+            .find(|p| (&p[0].model, &p[0].fwrev) == (&p[1].model, &p[1].fwrev))
+        {
+            bail!(
+                "Conflicting vendor overrides for {}: {:?} and {:?}",
+                pair[0].model,
+                pair[0].path,
+                pair[1].path
+            );
+        }
+        Ok(VendorOverrides { overrides })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.overrides.is_empty()
+    }
+
+    /// Returns the comment lines listing the overrides, for the header of
+    /// the final hwdb file
+    pub fn header(&self) -> String {
+        if self.is_empty() {
+            return String::new();
+        }
+        let mut header = String::from("#\n# Vendor overrides:\n");
+        for o in &self.overrides {
+            header.push_str(&format!(
+                "#   {}{}: {} by {} ({})\n",
+                o.model,
+                o.fwrev.as_ref().map(|f| format!(" (fwrev {})", f)).unwrap_or_default(),
+                o.mode,
+                o.vendor,
+                o.path.display()
+            ));
+        }
+        header
+    }
+
+    /// Applies the overrides of `model_name` to its generated hwdb
+    /// `entry`
+    pub fn apply(&self, model_name: &str, entry: &str) -> String {
+        let overrides: Vec<&VendorOverride> =
+            self.overrides.iter().filter(|o| o.is_for(model_name)).collect();
+        if overrides.is_empty() {
+            return entry.to_string();
+        }
+        let mut sections: Vec<Section> = entry
+            .split("\n\n")
+            .filter(|s| !s.trim().is_empty())
+            .map(Section::parse)
+            .collect();
+        // Model-wide overrides sort before the firmware specific ones
+        for o in overrides {
+            match (&o.fwrev, o.mode) {
+                (None, OverrideMode::Replace) => sections = vec![o.section(None)],
+                (None, OverrideMode::Merge) if !sections.is_empty() => {
+                    sections.iter_mut().for_each(|s| o.merge_into(s))
+                }
+                _ => {
+                    let base = sections.iter().find(|s| !s.is_fwrev_specific());
+                    let section = o.section(base);
+                    sections.push(section);
+                }
+            }
+        }
+        sections
+            .iter()
+            .map(|s| s.to_string())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Returns the entries of the overrides for models which are not in
+    /// `model_names`
+    pub fn unmatched_entries(&self, model_names: &[String]) -> Vec<String> {
+        self.overrides
+            .iter()
+            .filter(|o| !model_names.iter().any(|m| o.is_for(m)))
+            .map(|o| o.section(None).to_string())
+            .collect()
+    }
+}
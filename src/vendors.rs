@@ -0,0 +1,62 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// File of the database repository mapping the models to their vendors
+pub static VENDORS_FILE: &str = "vendors.toml";
+
+/// Vendor of the models whose name starts with one of its prefixes,
+/// e.g.:
+///
+/// ```toml
+/// [Samsung]
+/// prefixes = ["SAMSUNG", "MZVL"]
+/// contacts = ["@samsung-ssd"]
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Vendor {
+    /// Prefixes of the model names, as reported by the drives
+    pub prefixes: Vec<String>,
+    /// Handles mentioned on the regressions of the models
+    #[serde(default)]
+    pub contacts: Vec<String>,
+}
+
+/// Vendors of the models, by name
+#[derive(Debug, Default, Deserialize)]
+#[serde(transparent)]
+pub struct Vendors {
+    pub vendors: BTreeMap<String, Vendor>,
+}
+
+impl Vendors {
+    /// Loads the vendors file in `path`, if there is one
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Vendors::default());
+        }
+        let contents = fs::read_to_string(path).with_context(|| format!("Failed to read {:?}", path))?;
+        toml::from_str(&contents).with_context(|| format!("Failed to parse {:?}", path))
+    }
+
+    /// Returns the name and details of the vendor of `model_name`, the
+    /// one with the longest matching prefix. Spaces in the prefixes match
+    /// the underscores of the database directory names.
+    pub fn vendor_for(&self, model_name: &str) -> Option<(&str, &Vendor)> {
+        let model_name = model_name.replace(' ', "_");
+        self.vendors
+            .iter()
+            .flat_map(|(name, vendor)| vendor.prefixes.iter().map(move |p| (p.replace(' ', "_"), name, vendor)))
+            .filter(|(prefix, _, _)| model_name.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _, _)| prefix.len())
+            .map(|(_, name, vendor)| (name.as_str(), vendor))
+    }
+
+    /// Returns the contacts of the vendor of `model_name`
+    pub fn contacts(&self, model_name: &str) -> Vec<String> {
+        self.vendor_for(model_name).map(|(_, v)| v.contacts.clone()).unwrap_or_default()
+    }
+}
@@ -0,0 +1,183 @@
+use anyhow::Result;
+use std::fs;
+use std::path::Path;
+
+use crate::common::BenchMerge;
+use crate::database::Database;
+use crate::high_level::HighLevelSummary;
+use crate::legacy::legacy_versions;
+use crate::resources::append_job_summary;
+
+/// Relative difference of a QoS or model parameter between the solutions
+/// of two versions from which it is flagged as a large divergence
+pub const LARGE_DIVERGENCE: f64 = 0.25;
+
+/// Report of the comparisons written in the outputs of a merge
+pub const VERSION_REPORT_FILE: &str = "version-comparison.md";
+
+/// A parameter of a QoS target solved in both versions
+#[derive(Debug)]
+pub struct ParamDivergence<'a> {
+    pub target: &'a str,
+    pub param: &'a str,
+    pub legacy: f64,
+    pub current: f64,
+}
+
+impl ParamDivergence<'_> {
+    /// Relative difference of the newer solution from the legacy one
+    pub fn relative(&self) -> f64 {
+        (self.current - self.legacy) / self.legacy
+    }
+
+    pub fn is_large(&self) -> bool {
+        self.relative().abs() >= LARGE_DIVERGENCE
+    }
+}
+
+/// Solutions of a model found with the results of a legacy tree and
+/// with those of a newer resctl-bench version
+#[derive(Debug)]
+pub struct VersionComparison {
+    pub model_name: String,
+    pub legacy_version: String,
+    pub version: String,
+    pub legacy: HighLevelSummary,
+    pub current: HighLevelSummary,
+}
+
+impl VersionComparison {
+    /// Returns the first legacy version `model_name` has results for in
+    /// `database`, if any
+    pub fn legacy_version_of(database: &Database, model_name: &str) -> Option<String> {
+        legacy_versions()
+            .iter()
+            .find(|v| database.model_dir(v, model_name).is_ok_and(|dir| dir.is_dir()))
+            .map(str::to_string)
+    }
+
+    /// Compares the solutions of `merged`, the merged result of
+    /// `model_name` for a resctl-bench `version`, with those of its
+    /// results in the `legacy_version` tree of `database`. The merges
+    /// skip the legacy trees, so its results are merged here.
+    pub fn compute(
+        database: &Database,
+        model_name: &str,
+        legacy_version: &str,
+        version: &str,
+        merged: &Path,
+    ) -> Result<Self> {
+        let legacy_dir = database.model_dir(legacy_version, model_name)?;
+        let merge_dir = tempfile::tempdir()?;
+        let legacy_merged = merge_dir.path().join("legacy-merged.json.gz");
+        BenchMerge::do_merge(legacy_version, &legacy_dir, &legacy_merged)?;
+        Ok(VersionComparison {
+            model_name: model_name.to_string(),
+            legacy_version: legacy_version.to_string(),
+            version: version.to_string(),
+            legacy: BenchMerge::get_solutions(&legacy_merged)?,
+            current: BenchMerge::get_solutions(merged)?,
+        })
+    }
+
+    /// Returns the QoS and model parameters of the targets solved in
+    /// both versions, largest divergences first
+    pub fn divergences(&self) -> Vec<ParamDivergence<'_>> {
+        let mut divergences = vec![];
+        for legacy in &self.legacy.targets {
+            let Some(current) = self.current.target(&legacy.name) else {
+                continue;
+            };
+            for (param, value) in legacy.qos.iter().chain(&legacy.model) {
+                let current = current.qos.get(param).or(current.model.get(param));
+                if let Some(current) = current.filter(|_| *value != 0.0) {
+                    divergences.push(ParamDivergence {
+                        target: &legacy.name,
+                        param,
+                        legacy: *value,
+                        current: *current,
+                    });
+                }
+            }
+        }
+        divergences.sort_by(|a, b| b.relative().abs().total_cmp(&a.relative().abs()));
+        divergences
+    }
+
+    /// Whether some parameter diverges largely between the versions
+    pub fn diverges(&self) -> bool {
+        self.divergences().first().is_some_and(ParamDivergence::is_large)
+    }
+
+    /// Renders the parameters of both solutions as a markdown table,
+    /// flagging the large divergences
+    pub fn to_markdown(&self) -> String {
+        let mut md = format!("#### {} ({} and {})\n\n", self.model_name, self.legacy_version, self.version);
+        let divergences = self.divergences();
+        if divergences.is_empty() {
+            md.push_str("No QoS target was solved in both versions, nothing to compare.\n");
+            return md;
+        }
+        md.push_str(&format!(
+            "| Target | Parameter | {} | {} | Difference |\n|---|---|---:|---:|---:|\n",
+            self.legacy_version, self.version
+        ));
+        for d in &divergences {
+            md.push_str(&format!(
+                "| {} | {} | {} | {} | {:+.1}%{} |\n",
+                d.target,
+                d.param,
+                d.legacy,
+                d.current,
+                d.relative() * 100.0,
+                if d.is_large() { " :warning:" } else { "" }
+            ));
+        }
+        let large = divergences.iter().filter(|d| d.is_large()).count();
+        md.push_str(&match large {
+            0 => format!("\nThe solutions agree within {:.0}%.\n", LARGE_DIVERGENCE * 100.0),
+            _ => format!("\n{} parameters diverge by {:.0}% or more.\n", large, LARGE_DIVERGENCE * 100.0),
+        });
+        md
+    }
+}
+
+/// Comparisons of the solutions of the legacy and newer versions of the
+/// models merged in a run, to tell when the legacy tree can be retired
+#[derive(Debug, Default)]
+pub struct VersionReport {
+    pub comparisons: Vec<VersionComparison>,
+    /// Models which couldn't be compared, with the error
+    pub failures: Vec<(String, String)>,
+}
+
+impl VersionReport {
+    pub fn to_markdown(&self) -> String {
+        let diverging = self.comparisons.iter().filter(|c| c.diverges()).count();
+        let mut md = format!(
+            "### Solutions of the legacy and newer versions\n\n{} models compared, {} with large divergences.\n\n",
+            self.comparisons.len(),
+            diverging
+        );
+        for comparison in &self.comparisons {
+            md.push_str(&comparison.to_markdown());
+            md.push('\n');
+        }
+        if !self.failures.is_empty() {
+            md.push_str("Failed to compare:\n");
+            for (model_name, error) in &self.failures {
+                md.push_str(&format!("- {}: {}\n", model_name, error));
+            }
+        }
+        md
+    }
+
+    /// Writes the report in `outputs` and appends it to the Github job
+    /// summary when running in a workflow
+    pub fn write_in(&self, outputs: &Path) -> Result<()> {
+        let md = self.to_markdown();
+        fs::write(outputs.join(VERSION_REPORT_FILE), &md)?;
+        append_job_summary(&md)?;
+        Ok(())
+    }
+}
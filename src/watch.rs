@@ -0,0 +1,94 @@
+use anyhow::Result;
+use notify::{EventKind, RecursiveMode, Watcher};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::git::commit_to_branch;
+use crate::progress::log;
+use crate::workflow::{collect_result_files, import_local_files};
+use crate::workspace::workspace;
+
+/// Branch the batches imported in watch mode are committed to
+pub static WATCH_BRANCH: &str = "iocost-bot/watch";
+
+pub struct WatchConfig {
+    /// Directory to watch for new result files
+    pub dir: PathBuf,
+    pub database_dir: String,
+    /// Time without new files after which the pending ones are imported
+    pub batch_interval: Duration,
+}
+
+fn is_result_file(path: &Path) -> bool {
+    path.to_string_lossy().ends_with(".json.gz")
+}
+
+/// Imports the result files that appear in `config.dir` (recursively),
+/// committing them in batches. The files already in the directory are
+/// imported first; duplicates are skipped. Runs until interrupted.
+pub async fn watch(config: WatchConfig) -> Result<()> {
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+        if let Ok(event) = res {
+            if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                for path in event.paths.into_iter().filter(|p| is_result_file(p)) {
+                    tx.send(path).ok();
+                }
+            }
+        }
+    })?;
+    watcher.watch(&config.dir, RecursiveMode::Recursive)?;
+
+    let existing = collect_result_files(&[config.dir.to_string_lossy().to_string()])
+        .unwrap_or_default();
+    if !existing.is_empty() {
+        import_batch(&config, existing).await;
+    }
+
+    log(format!("Watching {} for new result files...", config.dir.display()));
+    while let Some(path) = rx.recv().await {
+        // Files are usually written in several steps and dropped in
+        // groups: wait until there are no new events for a while.
+        let mut batch = BTreeSet::from([path]);
+        while let Ok(Some(path)) = tokio::time::timeout(config.batch_interval, rx.recv()).await {
+            batch.insert(path);
+        }
+        let files = batch.into_iter().filter(|p| p.is_file()).collect();
+        import_batch(&config, files).await;
+    }
+    Ok(())
+}
+
+/// Imports a batch of `files` and commits them. Errors are reported but
+/// don't stop the watch.
+async fn import_batch(config: &WatchConfig, files: Vec<PathBuf>) {
+    if let Err(e) = try_import_batch(config, &files).await {
+        eprintln!("Failed to import {} files: {:#}", files.len(), e);
+    }
+}
+
+async fn try_import_batch(config: &WatchConfig, files: &[PathBuf]) -> Result<()> {
+    let (outcome, changed) = import_local_files(files, &config.database_dir).await?;
+    if changed.is_empty() {
+        return Ok(());
+    }
+    let git_repo = workspace().repository()?;
+    let mut index = git_repo.index()?;
+    for path in &changed {
+        index.add_path(path)?;
+    }
+    index.write()?;
+    let message = format!(
+        "Automated update from {}\n\n{} new results, {} failed validation",
+        config.dir.display(),
+        outcome.imported,
+        outcome.failed
+    );
+    let commit = commit_to_branch(&git_repo, &mut index, &message, WATCH_BRANCH)?;
+    log(format!(
+        "Committed {} new results to {} ({})",
+        outcome.imported, WATCH_BRANCH, commit
+    ));
+    Ok(())
+}
@@ -0,0 +1,189 @@
+use anyhow::Result;
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::{HeaderMap, StatusCode};
+use axum::routing::post;
+use axum::Router;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use crate::actions::{ContextPayload, EventPayload};
+use crate::bot_commands::BotCommand;
+use crate::checkpoint::ImportCheckpoint;
+use crate::git::reset_to_head;
+use crate::workflow::{process_bot_command, process_dispatch_event, process_issue_event, publish_branch};
+use crate::workspace::workspace;
+
+/// Settings for the webhook receiver
+pub struct WebhookConfig {
+    /// Secret configured for the webhook, used to verify the
+    /// `X-Hub-Signature-256` header of every delivery
+    pub secret: String,
+    /// Token used to comment on issues, push branches and open pull
+    /// requests
+    pub github_token: String,
+    pub database_dir: String,
+    /// Database repository (owner/name)
+    pub repo: String,
+    /// Branch pull requests are opened against
+    pub base_branch: String,
+}
+
+struct WebhookState {
+    config: WebhookConfig,
+    /// Serializes event processing, as all events operate on the same
+    /// git working tree
+    lock: Mutex<()>,
+}
+
+/// Checks the `sha256=<hex>` `signature` of `body` against `secret`
+pub fn verify_signature(secret: &str, body: &[u8], signature: &str) -> bool {
+    let signature = match signature.strip_prefix("sha256=").map(hex::decode) {
+        Some(Ok(s)) => s,
+        _ => return false,
+    };
+    let mut mac = match Hmac::<Sha256>::new_from_slice(secret.as_bytes()) {
+        Ok(m) => m,
+        Err(_) => return false,
+    };
+    mac.update(body);
+    mac.verify_slice(&signature).is_ok()
+}
+
+/// Brings the checkout back to a clean tree, as the previous event may
+/// have left the files it committed to its branch, or failed half-way.
+/// The checkpoint of `issue_id`, whose staged files are gone, is dropped.
+fn reset_checkout(config: &WebhookConfig, issue_id: Option<u64>) -> Result<()> {
+    reset_to_head(&workspace().repository()?, Path::new(&config.database_dir))?;
+    if let Some(issue_id) = issue_id {
+        match fs::remove_file(ImportCheckpoint::path_for(issue_id)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Runs the same processing as the Github workflow for a submission
+/// event or a maintainer command, then pushes the resulting branch and opens a pull request,
+/// which the workflow would otherwise do.
+async fn process_event(state: &WebhookState, context: ContextPayload) -> Result<()> {
+    let config = &state.config;
+    let issue_id = context.event.issue.as_ref().map(|i| i.number).unwrap();
+
+    let _guard = state.lock.lock().await;
+    reset_checkout(config, Some(issue_id))?;
+    let (branch_name, title, description, draft) = match BotCommand::from_context(&context) {
+        Some(command) => match process_bot_command(&context, &config.database_dir, command?).await? {
+            Some(outcome) => (outcome.branch_name, outcome.title, outcome.description, false),
+            None => return Ok(()),
+        },
+        None => {
+            let outcome = process_issue_event(&context, &config.database_dir).await?;
+            match outcome.branch_name {
+                Some(b) => (
+                    b,
+                    outcome.title.unwrap_or(format!("Automated update from issue {}", issue_id)),
+                    outcome.description.unwrap_or(format!("Closes #{}", issue_id)),
+                    outcome.pending,
+                ),
+                None => return Ok(()),
+            }
+        }
+    };
+    let (url, opened) = publish_branch(
+        &config.github_token,
+        &config.repo,
+        &config.base_branch,
+        &branch_name,
+        &title,
+        &description,
+        draft,
+    )
+    .await?;
+    println!("{} {} for issue {}", if opened { "Opened" } else { "Updated" }, url, issue_id);
+    Ok(())
+}
+
+/// POST /
+async fn receive(
+    State(state): State<Arc<WebhookState>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> StatusCode {
+    let signature = headers
+        .get("X-Hub-Signature-256")
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or_default();
+    if !verify_signature(&state.config.secret, &body, signature) {
+        return StatusCode::UNAUTHORIZED;
+    }
+    let event_name = match headers.get("X-GitHub-Event").and_then(|h| h.to_str().ok()) {
+        Some(e) => e.to_string(),
+        None => return StatusCode::BAD_REQUEST,
+    };
+    let event: EventPayload = match serde_json::from_slice(&body) {
+        Ok(e) => e,
+        Err(e) => {
+            eprintln!("Failed to parse {} webhook payload: {}", event_name, e);
+            return StatusCode::BAD_REQUEST;
+        }
+    };
+    let context =
+        ContextPayload::from_webhook(&event_name, event, Some(state.config.github_token.clone()));
+    if context.event_name == "repository_dispatch" {
+        tokio::task::spawn_blocking(move || {
+            let runtime = tokio::runtime::Handle::current();
+            let _guard = runtime.block_on(state.lock.lock());
+            let processed = reset_checkout(&state.config, None)
+                .and_then(|_| process_dispatch_event(&context, &state.config.database_dir));
+            if let Err(e) = processed {
+                eprintln!("Failed to process {} event: {:#}", event_name, e);
+            }
+        });
+        return StatusCode::ACCEPTED;
+    }
+    if !context.is_submission_event() {
+        println!("Ignoring {} event", event_name);
+        return StatusCode::NO_CONTENT;
+    }
+    // Only the maintainer commands are processed on the locked and closed
+    // issues, their links aren't
+    if let Some(issue) = context.event.issue.as_ref().filter(|i| i.locked || i.state != "open") {
+        if BotCommand::from_context(&context).is_none() {
+            println!("Ignoring {} event of #{}, locked or {}", event_name, issue.number, issue.state);
+            return StatusCode::NO_CONTENT;
+        }
+    }
+
+    // Github expects a response within a few seconds, so processing
+    // happens in the background. It holds libgit2 handles across await
+    // points, which can't be sent between threads, so it gets a blocking
+    // thread of its own.
+    tokio::task::spawn_blocking(move || {
+        let runtime = tokio::runtime::Handle::current();
+        if let Err(e) = runtime.block_on(process_event(&state, context)) {
+            eprintln!("Failed to process {} event: {:#}", event_name, e);
+        }
+    });
+    StatusCode::ACCEPTED
+}
+
+/// Receives Github webhooks on `listen` until the process is stopped
+pub async fn serve_webhooks(listen: &str, config: WebhookConfig) -> Result<()> {
+    let state = WebhookState {
+        config,
+        lock: Mutex::new(()),
+    };
+    let router = Router::new()
+        .route("/", post(receive))
+        .with_state(Arc::new(state));
+    let listener = tokio::net::TcpListener::bind(listen).await?;
+    println!("Receiving Github webhooks on http://{}", listen);
+    axum::serve(listener, router).await?;
+    Ok(())
+}
@@ -0,0 +1,1993 @@
+use anyhow::{bail, Context, Result};
+use git2::BranchType;
+use glob::glob;
+use indicatif::{HumanBytes, ParallelProgressIterator};
+use rayon::prelude::*;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use crate::actions::{ContextPayload, DispatchPayload};
+use crate::bot_commands::{is_maintainer, BotCommand};
+use crate::checkpoint::{ImportCheckpoint, UrlState};
+use crate::commits::commit_settings;
+use crate::common::BenchMerge;
+use crate::database::{validate_name, validate_version, Database};
+use crate::embargo::embargo_staging;
+use crate::error_report::{ErrorEntry, Stage, ERROR_REPORT};
+use crate::exit::ExitStatus;
+use crate::feedback::{estimate_secs_per_file, ProgressComment, PROGRESS_COMMENT_MIN_URLS};
+use crate::archive::HwdbArchive;
+use crate::git::{
+    added_files, changed_models, commit_to_branch, delete_remote_branch, fast_forward_branch, fast_forward_head,
+    fetch_branch, pending_submissions, push_branch, rebase_branch, stale_bot_branches,
+};
+use crate::fw_compare::FirmwareComparison;
+use crate::github::{find_pull_request, mark_ready_for_review, open_pull_request, post_review, update_pull_request};
+use crate::high_level::HighLevelSummary;
+use crate::history::{record_event, HistoryEvent};
+use crate::http::{advertised_md5, download_request, http_settings};
+use crate::hwdb::{diff_sections, hwdb_section_for, write_hwdb_file, HwdbCandidate, HWDB_FILE};
+use crate::url_extract::{is_signed, url_extractor, GithubAttachment};
+use crate::url_ledger::UrlLedger;
+use crate::vendor_overrides::{VendorOverrides, VENDOR_OVERRIDES_DIR};
+use crate::vendors::{Vendors, VENDORS_FILE};
+use crate::index::{CoverageStats, DatabaseIndex, ResultMetadata};
+use crate::legacy::legacy_versions;
+use crate::lfs::upload_branch_objects;
+use crate::manifest::{Artifact, ArtifactKind, Manifest, MANIFEST_FILE};
+use crate::labels::{issue_settings, model_label, submission_title, NEEDS_LINKS_LABEL, QUEUED_LABEL};
+use crate::limits::submission_limits;
+use crate::metrics::METRICS;
+use crate::policy::{check_statement, Refusal, Statement, Verdict};
+use crate::progress::{bar, download_bar, log};
+use crate::templates::{
+    AcceptedReport, DuplicateFile, DuplicateReport, DuplicateSubmission, EmbargoReport, ExclusionReport, FileError,
+    HwdbPreview, HwdbPreviewModel, LicenseRequired, MergedModel, MergedReport, ModelSummary, NeedsLinks,
+    PendingReview, PermissionDenied, QueuedReport, RetractionReport, SignOffRequired, SubmissionReport,
+    SubmissionTooLarge,
+};
+use crate::resources::{PipelineStage, RESOURCES};
+use crate::retract::retract;
+use crate::review::{accept_pending, pending_dir, review_settings, PENDING_DIR};
+use crate::signatures::{is_signature_url, signature_link, signature_settings, MAX_SIGNATURE_BYTES};
+use crate::signoff::{SignOff, SignOffError};
+use crate::storage::{key_for, object_storage};
+use crate::result::{check_result_contents, save_result_file, BenchResult, FailedSubmission, HighLevel};
+use crate::workspace::workspace;
+
+/// Bot branches not updated in this many days are considered stale
+const STALE_BRANCH_DAYS: i64 = 30;
+/// Coverage statistics of the database refreshed by the scheduled runs
+pub static COVERAGE_STATS_FILE: &str = "coverage-stats.json";
+
+/// URL prefixes results can be submitted from. Github serves the
+/// attachments of the issues from github.com/user-attachments and, once
+/// redirected, objects.githubusercontent.com.
+static ALLOWED_PREFIXES: &[&str] = &[
+    "https://github.com/",
+    "https://objects.githubusercontent.com/",
+    "https://iocost-submit-us-east-1.s3.us-east-1.amazonaws.com/",
+    "https://iocost-submit.s3.eu-north-1.amazonaws.com/",
+];
+
+/// Cloud storage hosts results can be submitted from, for any bucket:
+/// Google Cloud Storage path-style URLs
+static ALLOWED_STORAGE_HOSTS: &[&str] = &["storage.googleapis.com"];
+
+/// Cloud storage domains results can be submitted from, for any account
+/// or bucket: Azure Blob Storage and Google Cloud Storage virtual-hosted
+/// URLs
+static ALLOWED_STORAGE_DOMAINS: &[&str] = &[".blob.core.windows.net", ".storage.googleapis.com"];
+
+/// Suffix of the result files
+pub static RESULT_SUFFIX: &str = ".json.gz";
+
+/// Returns the URL prefixes of the built-in hosts the result files can
+/// be downloaded from, for the submitters. Private hosts of the config
+/// are not included.
+pub fn allowed_url_prefixes() -> Vec<String> {
+    ALLOWED_PREFIXES
+        .iter()
+        .map(|p| p.to_string())
+        .chain(ALLOWED_STORAGE_HOSTS.iter().map(|h| format!("https://{}/", h)))
+        .chain(ALLOWED_STORAGE_DOMAINS.iter().map(|d| format!("https://*{}/", d)))
+        .collect()
+}
+
+/// Returns `true` if the URL specified in `link` is allowed according
+/// to its domain name or is under one of the private hosts of the
+/// config. Returns `false` otherwise.
+fn is_url_allowlisted(link: &str) -> bool {
+    if http_settings().host_for(link).is_some() {
+        return true;
+    }
+    for prefix in ALLOWED_PREFIXES {
+        if link.starts_with(prefix) {
+            return true;
+        }
+    }
+    let Ok(url) = reqwest::Url::parse(link) else {
+        return false;
+    };
+    let host = url.host_str().unwrap_or_default();
+    url.scheme() == "https"
+        && (ALLOWED_STORAGE_HOSTS.contains(&host) || ALLOWED_STORAGE_DOMAINS.iter().any(|d| host.ends_with(d)))
+}
+
+/// Returns `url` without its query string if it is a signed one, so that
+/// its credentials don't end up in logs, comments and the database
+pub fn public_url(url: &str) -> String {
+    match reqwest::Url::parse(url) {
+        Ok(mut parsed) if is_signed(&parsed) => {
+            parsed.set_query(None);
+            parsed.to_string()
+        }
+        _ => url.to_string(),
+    }
+}
+
+/// Extracts the URLs found in a Github issue context: the result files,
+/// the ignored links and the detached signatures of the results.
+/// Only open and unlocked issues are processed
+fn get_urls(context: &ContextPayload) -> Result<(Vec<String>, Vec<String>, Vec<String>)> {
+    let issue = match &context.event.issue {
+        Some(i) => i,
+        None => bail!("{} event without an issue", context.event_name),
+    };
+
+    // The workflow and the webhook receiver should already filter this
+    // out, but double-check.
+    if issue.locked || issue.state != "open" {
+        bail!("Issue #{} is locked or {}, its links aren't processed", issue.number, issue.state);
+    }
+
+    let comment_body = context.event.comment.as_ref().and_then(|c| c.body.as_deref());
+    // created is always for comments, opened is always for issues.
+    let body = match context.event.action.as_deref() {
+        Some("created") => comment_body,
+        Some("opened") => issue.body.as_deref(),
+        Some("unlabeled") if is_resumed(context) => issue.body.as_deref(),
+        Some("edited") => {
+            if context.event_name == "issue_comment" {
+                comment_body
+            } else {
+                issue.body.as_deref()
+            }
+        }
+        action => bail!(
+            "Called for event we do not handle: {} / {}",
+            context.event_name,
+            action.unwrap_or("(no action)")
+        ),
+    }
+    .unwrap_or_default();
+
+    let mut urls = vec![];
+    let mut ignored = vec![];
+    let mut signatures = vec![];
+    let extractor = url_extractor();
+    for link in extractor.links(body) {
+        if !is_url_allowlisted(&link) {
+            println!("URL ignored due to not having a allowlisted prefix: {}", public_url(&link));
+            ignored.push(public_url(&link));
+        } else if is_signature_url(&link) {
+            println!("Signature found: {}", public_url(&link));
+            signatures.push(link);
+        } else if !extractor.is_result_url(&link) {
+            println!(
+                "URL ignored due to not ending with {}: {}",
+                extractor.extensions().join(" or "),
+                public_url(&link)
+            );
+            ignored.push(public_url(&link));
+        } else if let Some(mismatch) = attachment_mismatch(context, &link) {
+            println!("URL ignored due to being an {}: {}", mismatch, public_url(&link));
+            ignored.push(public_url(&link));
+        } else {
+            println!("URL found: {}", public_url(&link));
+            urls.push(link);
+        }
+    }
+    Ok((urls, ignored, signatures))
+}
+
+/// Returns why `link` is a Github attachment of another repository than
+/// the one the workflow runs in, if it is one and the repository is known
+fn attachment_mismatch(context: &ContextPayload, link: &str) -> Option<String> {
+    let repository = context.repository.as_deref()?;
+    let repository_id = context.event.repository.as_ref().and_then(|r| r.id);
+    GithubAttachment::parse(link)?.mismatch(repository, repository_id)
+}
+
+/// Returns the other issue all the `urls` of `issue_id` were submitted
+/// in, if any, from the metadata of the results in the database index
+/// and in the bot branches of the submissions being imported
+fn duplicate_submission(database_path: &str, issue_id: u64, urls: &[String]) -> Result<Option<u64>> {
+    if urls.is_empty() {
+        return Ok(None);
+    }
+    let index = DatabaseIndex::load(Path::new(database_path))?;
+    let pending = pending_submissions(&workspace().repository()?, Path::new(database_path))?;
+    let mut submitted: HashMap<&str, BTreeSet<u64>> = HashMap::new();
+    for metadata in index.entries.iter().flat_map(|e| &e.metadata).chain(&pending) {
+        if let (Some(url), Some(issue)) = (metadata.url.as_deref(), metadata.issue.filter(|i| *i != issue_id)) {
+            submitted.entry(url).or_default().insert(issue);
+        }
+    }
+    let mut issues = submitted.get(public_url(&urls[0]).as_str()).cloned().unwrap_or_default();
+    for url in &urls[1..] {
+        let found = submitted.get(public_url(url).as_str());
+        issues.retain(|i| found.is_some_and(|f| f.contains(i)));
+    }
+    Ok(issues.first().copied())
+}
+
+/// Returns whether the event is the removal of the queued label of an
+/// issue, which resumes its submission on behalf of the issue author
+fn is_resumed(context: &ContextPayload) -> bool {
+    context.event.action.as_deref() == Some("unlabeled")
+        && context.event.label.as_ref().is_some_and(|l| l.name == QUEUED_LABEL)
+}
+
+/// Attempts at downloading a file whose contents don't match the
+/// checksum advertised by its host
+const DOWNLOAD_ATTEMPTS: usize = 3;
+
+/// Contents of a downloaded file
+struct Download {
+    contents: Vec<u8>,
+    /// Content-Type header of the response
+    content_type: Option<String>,
+    /// Checksum advertised by the host the contents were verified
+    /// against, as `<header>:<md5>`
+    verified_checksum: Option<String>,
+}
+
+/// Downloads `url`, failing if it is larger than `max_bytes`. When the
+/// host advertises a checksum, the download is retried until the
+/// contents match it, so that truncated files are caught before
+/// validation.
+async fn fetch_url(url: &str, max_bytes: u64) -> Result<Download> {
+    let _stage = RESOURCES.start(PipelineStage::Download);
+    let shown = public_url(url);
+    for attempt in 1..=DOWNLOAD_ATTEMPTS {
+        METRICS.downloads.inc();
+        // Leave the URL out of the errors, it may hold the credentials
+        // of a private host.
+        let mut response = download_request(reqwest::Method::GET, url)?
+            .send()
+            .await
+            .map_err(|e| e.without_url())?;
+        let advertised = advertised_md5(&response);
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+        let pb = download_bar(response.content_length(), &shown);
+        let mut contents = vec![];
+        while let Some(chunk) = response.chunk().await.map_err(|e| e.without_url())? {
+            pb.inc(chunk.len() as u64);
+            contents.extend_from_slice(&chunk);
+            if contents.len() as u64 > max_bytes {
+                bail!(
+                    "{} is over the {} left to the submission",
+                    shown,
+                    HumanBytes(max_bytes)
+                );
+            }
+        }
+        pb.finish_and_clear();
+        let Some((header, expected)) = advertised else {
+            return Ok(Download { contents, content_type, verified_checksum: None });
+        };
+        let actual = format!("{:x}", md5::compute(&contents));
+        if actual == expected {
+            let verified_checksum = Some(format!("{}:{}", header, expected));
+            return Ok(Download { contents, content_type, verified_checksum });
+        }
+        eprintln!(
+            "Downloaded {} bytes of {} with md5 {}, its {} is {} (attempt {}/{})",
+            contents.len(),
+            shown,
+            actual,
+            header,
+            expected,
+            attempt,
+            DOWNLOAD_ATTEMPTS
+        );
+    }
+    bail!(
+        "{} doesn't match the checksum advertised by its host after {} attempts",
+        shown,
+        DOWNLOAD_ATTEMPTS
+    )
+}
+
+/// Downloads `url` to a result file, failing if it is larger than
+/// `max_bytes` or isn't a gzipped json file, such as the error or login
+/// page of its host. Returns the path of the file and the checksum it
+/// was verified against, as `<header>:<md5>`.
+async fn download_url(url: &str, max_bytes: u64) -> Result<(String, Option<String>)> {
+    let download = fetch_url(url, max_bytes).await?;
+    check_result_contents(&download.contents, download.content_type.as_deref())
+        .with_context(|| format!("{} isn't a result file", public_url(url)))?;
+    Ok((save_result_file(&download.contents)?, download.verified_checksum))
+}
+
+/// Downloads the detached signature of a result from `url`, and returns
+/// the path it is saved to
+async fn download_signature(url: &str) -> Result<String> {
+    let download = fetch_url(url, MAX_SIGNATURE_BYTES)
+        .await
+        .with_context(|| format!("Failed to download the signature {}", public_url(url)))?;
+    let path = format!("signature-{:x}.sig", md5::compute(&download.contents));
+    fs::write(&path, &download.contents)?;
+    Ok(path)
+}
+
+/// Result of a submission downloaded, waiting to be validated
+struct DownloadedResult {
+    url: String,
+    /// URL shown in logs, comments and the database
+    shown: String,
+    download_secs: f64,
+    result: BenchResult,
+}
+
+/// Outcome of processing a submission
+pub struct ImportOutcome {
+    /// Branch the imported results were committed to, if any
+    pub branch_name: Option<String>,
+    /// Title and description of the pull request of the branch, as in
+    /// its commit message
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub imported: usize,
+    pub failed: usize,
+    /// Why the results of the submission weren't processed, e.g.
+    /// `license-required` or `queued`, as recorded in its history
+    pub skipped: Option<&'static str>,
+    /// Whether the imported results are pending the review of a
+    /// maintainer, for the pull request to be opened as a draft
+    pub pending: bool,
+}
+
+impl ImportOutcome {
+    /// Outcome of a submission whose results weren't processed for
+    /// `reason`, counting `failed` results
+    fn skipped(reason: &'static str, failed: usize) -> Self {
+        ImportOutcome {
+            branch_name: None,
+            title: None,
+            description: None,
+            imported: 0,
+            failed,
+            skipped: Some(reason),
+            pending: false,
+        }
+    }
+
+    pub fn exit_status(&self) -> ExitStatus {
+        match (self.imported, self.failed) {
+            (_, 0) => ExitStatus::Success,
+            (0, _) => ExitStatus::ValidationError,
+            _ => ExitStatus::PartialFailure,
+        }
+    }
+}
+
+/// Appends `event` to the history of `issue`, only logging failures not
+/// to fail the run they happened in
+fn record_history(context: &ContextPayload, issue: u64, event: HistoryEvent) {
+    if let Err(e) = record_event(context, issue, &event) {
+        eprintln!("Failed to record the history of #{}: {:#}", issue, e);
+    }
+}
+
+/// Brings the local clone up to date before committing to the branch of
+/// `issue_id`: fast-forwards it to the default branch of the repository
+/// of the `context` and rebases the `iocost-bot/<issue>` branch onto it
+/// if there is one, so that the pull requests of long-open submissions
+/// don't conflict with the changes merged since. Only runs with a token
+/// in the context.
+fn sync_with_default_branch(context: &ContextPayload, git_repo: &git2::Repository, issue_id: u64) -> Result<()> {
+    let (Some(repo), Some(token)) = (&context.repository, &context.token) else {
+        return Ok(());
+    };
+    let default_branch = context
+        .event
+        .repository
+        .as_ref()
+        .and_then(|r| r.default_branch.as_deref())
+        .unwrap_or("main");
+    fetch_branch(git_repo, repo, default_branch, token)?;
+    let target = git_repo
+        .find_reference(&format!("refs/remotes/origin/{}", default_branch))?
+        .peel_to_commit()?;
+    if fast_forward_head(git_repo, &target)? {
+        log(format!("Fast-forwarded to {} ({})", default_branch, target.id()));
+    }
+    let branch_name = commit_settings().branch(issue_id);
+    fetch_branch(git_repo, repo, &branch_name, token)?;
+    match git_repo.find_reference(&format!("refs/remotes/origin/{}", branch_name)) {
+        Ok(remote) => {
+            git_repo.branch(&branch_name, &remote.peel_to_commit()?, true)?;
+        }
+        Err(_) => return Ok(()),
+    }
+    if rebase_branch(git_repo, &branch_name, target.id())? {
+        push_branch(git_repo, repo, &branch_name, token)?;
+        log(format!("Rebased {} onto {}", branch_name, default_branch));
+    }
+    Ok(())
+}
+
+/// Processes an issue or issue comment event described by a Github
+/// workflow `context`: downloads and validates the submitted results,
+/// adds them to the database in `database_path` and commits them to an
+/// `iocost-bot/<issue>` branch. The outcome is recorded in the history
+/// of the issue.
+pub async fn process_issue_event(
+    context: &ContextPayload,
+    database_path: &str,
+) -> Result<ImportOutcome> {
+    let result = import_submission(context, database_path).await;
+    if let Some(issue) = &context.event.issue {
+        let event = match &result {
+            Ok(outcome) => {
+                let status = match (outcome.skipped, outcome.imported, outcome.failed) {
+                    (Some(reason), _, _) => reason,
+                    (None, 0, 0) => "no-new-results",
+                    (None, _, 0) if outcome.pending => "pending-review",
+                    (None, _, 0) => "imported",
+                    (None, 0, _) => "failed",
+                    (None, _, _) => "partially-imported",
+                };
+                // The counts of skipped submissions only repeat the URLs
+                let processed = outcome.skipped.is_none();
+                HistoryEvent {
+                    imported: processed.then_some(outcome.imported),
+                    failed: processed.then_some(outcome.failed),
+                    branch: outcome.branch_name.clone(),
+                    ..HistoryEvent::new(context, status)
+                }
+            }
+            Err(e) => HistoryEvent { detail: Some(format!("{:#}", e)), ..HistoryEvent::new(context, "error") },
+        };
+        record_history(context, issue.number, event);
+    }
+    result
+}
+
+async fn import_submission(context: &ContextPayload, database_path: &str) -> Result<ImportOutcome> {
+    let (urls, ignored, signatures) = get_urls(context)?;
+    let issue = context.event.issue.as_ref().context("The event has no issue")?;
+    let issue_id = issue.number;
+    if urls.is_empty() && context.event_name == "issues" && context.event.action.as_deref() == Some("opened") {
+        triage_issue(context, issue_id, ignored).await?;
+        return Ok(ImportOutcome::skipped("no-links", 0));
+    }
+    let resumed = is_resumed(context);
+    let submitter = match resumed {
+        true => issue.user.as_ref().map(|u| u.login.clone()),
+        false => context.event.sender.as_ref().map(|s| s.login.clone()),
+    };
+    let comment_body = context.event.comment.as_ref().and_then(|c| c.body.as_deref());
+    let bodies: Vec<&str> = [issue.body.as_deref(), comment_body].into_iter().flatten().collect();
+    let login = submitter.clone().unwrap_or_default();
+    let author = issue.user.as_ref().map_or(login.clone(), |u| u.login.clone());
+    let statement = Statement { bodies: &bodies, author: &author, submitter: &login };
+    // Results are held back until the end of an embargo in the future
+    let (signoff, embargo) = match check_statement(&statement)? {
+        Verdict::Accepted { signoff, embargo } => (signoff, embargo),
+        // Without results, e.g. for a bot command, there is nothing to
+        // refuse
+        Verdict::Refused(_) if urls.is_empty() => (SignOff::for_user(&login), None),
+        Verdict::Refused(refusal) => {
+            let (reason, comment) = match refusal {
+                Refusal::LicenseRequired(consent) => {
+                    log(format!("Refusing submission without agreement to {}", consent.license));
+                    let comment = LicenseRequired {
+                        submitter: login,
+                        license: consent.license.clone(),
+                        license_url: consent.license_url.clone(),
+                        phrase: consent.phrase.clone(),
+                    };
+                    ("license-required", comment.render()?)
+                }
+                Refusal::SignOffRequired(e) => {
+                    log(format!("Refusing submission without the sign-off of {}", author));
+                    let comment = SignOffRequired {
+                        submitter: login,
+                        not_author: matches!(e, SignOffError::NotAuthor { .. }),
+                        author,
+                    };
+                    ("signoff-required", comment.render()?)
+                }
+                Refusal::Embargoed(date) => {
+                    log(format!("Refusing submission embargoed until {}", date));
+                    let comment = EmbargoReport {
+                        submitter: login,
+                        held: false,
+                        count: urls.len(),
+                        date: date.to_string(),
+                    };
+                    ("embargoed", comment.render()?)
+                }
+            };
+            post_comment(context, issue_id, comment).await?;
+            return Ok(ImportOutcome::skipped(reason, urls.len()));
+        }
+    };
+    let limits = submission_limits();
+    if let Some(exceeded) = limits.check(&urls).await {
+        log(format!("Refusing submission of {} files over the limits", exceeded.urls));
+        let comment = SubmissionTooLarge {
+            submitter: submitter.clone().unwrap_or_default(),
+            too_many_urls: exceeded.urls > limits.max_urls,
+            urls: exceeded.urls,
+            max_urls: limits.max_urls,
+            size: HumanBytes(exceeded.total_bytes).to_string(),
+            max_size: HumanBytes(limits.max_total_bytes).to_string(),
+        }
+        .render()?;
+        post_comment(context, issue_id, comment).await?;
+        return Ok(ImportOutcome::skipped("too-large", urls.len()));
+    }
+    // A second issue with the same links, e.g. opened while the first one
+    // is still being imported, would only create a conflicting branch.
+    if let Some(original) = duplicate_submission(database_path, issue_id, &urls)? {
+        log(format!("All the links of #{} were already submitted in #{}", issue_id, original));
+        let pull_request = match (&context.token, &context.repository_owner) {
+            (Some(token), Some(owner)) => {
+                let repo = format!("{}/iocost-benchmarks", owner);
+                find_pull_request(token, &repo, &commit_settings().branch(original))
+                    .await
+                    .map_err(|e| eprintln!("Failed to find the pull request of issue {}: {}", original, e))
+                    .ok()
+                    .flatten()
+            }
+            _ => None,
+        };
+        let comment = DuplicateSubmission { submitter: login, issue: original, pull_request }.render()?;
+        post_comment(context, issue_id, comment).await?;
+        return Ok(ImportOutcome::skipped("duplicate", 0));
+    }
+    // Users who are not maintainers get a number of submissions processed
+    // per day, the next ones wait for the next day to leave the runners to
+    // the others.
+    let association = match context.event_name.as_str() {
+        "issue_comment" => context.event.comment.as_ref().and_then(|c| c.author_association.as_deref()),
+        _ => issue.author_association.as_deref(),
+    };
+    if let Some(max) = limits.max_submissions_per_day.filter(|_| !is_maintainer(association)) {
+        let today = chrono::Utc::now().date_naive();
+        let mut submitted = DatabaseIndex::load(Path::new(database_path))?.issues_submitted_on(&login, today);
+        submitted.remove(&issue_id);
+        if submitted.len() >= max {
+            log(format!("Queuing the submission of {} after {} today", login, submitted.len()));
+            // Resumed submissions queued again were already commented on
+            let report = (!resumed).then(|| QueuedReport {
+                submitter: login.clone(),
+                submitted: submitted.len(),
+                max,
+            });
+            queue_submission(context, issue_id, report).await?;
+            return Ok(ImportOutcome::skipped("queued", 0));
+        }
+    }
+    // The results of first-time contributors wait in the pending area
+    // until a maintainer accepts them with /accept.
+    let for_review = !is_maintainer(association)
+        && review_settings().needs_review(&DatabaseIndex::load(Path::new(database_path))?, &login);
+    if for_review {
+        log(format!("Importing the results of first-time contributor {} for review", login));
+    }
+    // Sizes advertised by the servers can't be trusted, the downloads
+    // are capped to what is left of the limit too.
+    let mut bytes_left = limits.max_total_bytes;
+    let git_repo = workspace().repository()?;
+    if let Err(e) = sync_with_default_branch(context, &git_repo, issue_id) {
+        eprintln!("Failed to bring #{} up to date with the default branch: {:#}", issue_id, e);
+    }
+    let mut index = git_repo.index()?;
+    // HashMap to keep the complete set of results
+    let mut merged = HashMap::new();
+
+    // Download and validate all provided URLs, skipping the ones an
+    // interrupted previous run already processed.
+    let mut checkpoint = ImportCheckpoint::load(issue_id)?;
+    // Same for the links the previous runs of the issue processed, so
+    // that editing it doesn't import its results again.
+    let branch_tree = git_repo
+        .find_branch(&commit_settings().branch(issue_id), BranchType::Local)
+        .ok()
+        .and_then(|b| b.get().peel_to_tree().ok());
+    let mut ledger = UrlLedger::load(&git_repo, branch_tree.as_ref(), Path::new(database_path), issue_id)?;
+    let head_tree = git_repo.head()?.peel_to_tree()?;
+    let resumed = ledger.resume(&git_repo, &head_tree, branch_tree.as_ref(), &mut checkpoint, &urls)?;
+    if resumed > 0 {
+        log(format!("Skipping {} links of #{} processed by a previous run", resumed, issue_id));
+    }
+    let pending = urls
+        .iter()
+        .filter(|url| !matches!(
+            checkpoint.get(url),
+            Some(
+                UrlState::Staged { .. }
+                    | UrlState::Failed { .. }
+                    | UrlState::Duplicate { .. }
+                    | UrlState::Merged { .. }
+                    | UrlState::Embargoed { .. }
+            )
+        ))
+        .count();
+    // Let the submitter know how long processing will take when there
+    // are many files.
+    let mut progress = match (&context.token, &context.repository_owner) {
+        (Some(token), Some(owner)) if pending >= PROGRESS_COMMENT_MIN_URLS => {
+            let secs_per_file = estimate_secs_per_file(Path::new(database_path));
+            ProgressComment::post(token, owner, issue_id, pending, secs_per_file)
+                .await
+                .map_err(|e| eprintln!("Failed to post the progress comment: {}", e))
+                .ok()
+        }
+        _ => None,
+    };
+    let mut errors = vec![];
+    let mut duplicates = vec![];
+    // Results of the issue merged since it was last processed
+    let mut already_merged = vec![];
+    let mut held = 0;
+    // Results downloaded, validated in parallel once all are
+    let mut downloaded: Vec<DownloadedResult> = vec![];
+    // Files which can't be imported are reported with the others
+    let failure = |shown: &str, e: &anyhow::Error| FailedSubmission {
+        issue: Some(issue_id),
+        url: shown.to_string(),
+        submitter: submitter.clone(),
+        failed_at: chrono::Utc::now().to_rfc3339(),
+        error: e.to_string(),
+    };
+    let pb = bar(urls.len() as u64, "Downloading");
+    for url in urls {
+        // URL shown in logs, comments and the database
+        let shown = public_url(&url);
+        pb.set_message(shown.clone());
+        pb.inc(1);
+        match checkpoint.get(&url) {
+            Some(UrlState::Staged { version, model_name, files }) => {
+                stage_files(&mut index, database_path, files)?;
+                let high_level = merged
+                    .entry(format!("{}-{}", version, model_name))
+                    .or_insert_with(|| HighLevel::new(version, model_name));
+                high_level.increment();
+                if let Some(vendor) = files.first().and_then(|f| ResultMetadata::load_for(f)?.signed_by) {
+                    high_level.add_signed(&vendor);
+                }
+                continue;
+            }
+            Some(UrlState::Failed { error, ledger }) => {
+                index.add_path(ledger)?;
+                errors.push(FileError { input: shown.clone(), error: error.clone(), hint: None, stderr: None });
+                continue;
+            }
+            Some(UrlState::Embargoed { .. }) => {
+                held += 1;
+                continue;
+            }
+            Some(UrlState::Merged { file }) => {
+                already_merged.push((shown.clone(), file.clone()));
+                continue;
+            }
+            Some(UrlState::Duplicate { issue, url: original_url }) => {
+                duplicates.push(DuplicateFile {
+                    input: shown.clone(),
+                    issue: *issue,
+                    url: original_url.clone(),
+                    pull_request: None,
+                });
+                continue;
+            }
+            _ => {}
+        }
+        // Download resctl-bench result, to process and validate it
+        // with the others
+        let started = Instant::now();
+        let (path, verified_checksum) = match checkpoint.downloaded(&url) {
+            Some(downloaded) => downloaded,
+            None => match download_url(&url, bytes_left).await {
+                Ok((path, verified_checksum)) => {
+                    bytes_left = bytes_left.saturating_sub(fs::metadata(&path)?.len());
+                    checkpoint.set(
+                        &url,
+                        UrlState::Downloaded {
+                            path: path.clone(),
+                            verified_checksum: verified_checksum.clone(),
+                        },
+                    )?;
+                    (path, verified_checksum)
+                }
+                Err(e) => {
+                    ERROR_REPORT.record(ErrorEntry::new(Stage::Validation, &e).input(shown.clone()));
+                    record_failed_file(database_path, &mut index, &mut checkpoint, &url, failure(&shown, &e))?;
+                    errors.push(FileError::new(shown.clone(), &e));
+                    if let Some(progress) = progress.as_mut() {
+                        progress.file_done(true).await;
+                    }
+                    continue;
+                }
+            },
+        };
+        let mut result = match BenchResult::new(&path, database_path).await {
+            Ok(result) => result,
+            Err(e) => {
+                METRICS.validation_failures.inc();
+                ERROR_REPORT.record(ErrorEntry::new(Stage::Validation, &e).input(shown.clone()));
+                fs::remove_file(&path).ok();
+                record_failed_file(database_path, &mut index, &mut checkpoint, &url, failure(&shown, &e))?;
+                errors.push(FileError::new(shown.clone(), &e));
+                if let Some(progress) = progress.as_mut() {
+                    progress.file_done(true).await;
+                }
+                continue;
+            }
+        };
+        result.issue = Some(issue_id);
+        result.url = Some(shown.clone());
+        result.submitter = submitter.clone();
+        result.verified_checksum = verified_checksum;
+        match (signature_link(&url, &signatures), signature_settings()) {
+            (Some(signature), Some(_)) => match download_signature(signature).await {
+                Ok(path) => result.signature_file = Some(path),
+                Err(e) => {
+                    ERROR_REPORT.record(ErrorEntry::new(Stage::Validation, &e).input(shown.clone()));
+                    fs::remove_file(&path).ok();
+                    record_failed_file(database_path, &mut index, &mut checkpoint, &url, failure(&shown, &e))?;
+                    errors.push(FileError::new(shown.clone(), &e));
+                    if let Some(progress) = progress.as_mut() {
+                        progress.file_done(true).await;
+                    }
+                    continue;
+                }
+            },
+            (Some(signature), None) => {
+                println!("No vendor keys, ignoring the signature {}", public_url(signature))
+            }
+            _ => {}
+        }
+        // Let the submitter know when the result was already imported
+        // from another submission, rather than silently skipping it.
+        if let Some(original) = ResultMetadata::load_for(&result.db_file())
+            .filter(|m| m.issue != Some(issue_id))
+        {
+            fs::remove_file(&path)?;
+            checkpoint.set(
+                &url,
+                UrlState::Duplicate { issue: original.issue, url: original.url.clone() },
+            )?;
+            duplicates.push(DuplicateFile {
+                input: shown.clone(),
+                issue: original.issue,
+                url: original.url,
+                pull_request: None,
+            });
+            if let Some(progress) = progress.as_mut() {
+                progress.file_done(false).await;
+            }
+            continue;
+        }
+        // Results of the issue merged since it was last processed, e.g.
+        // from part of its branch, are not recreated on the new branch,
+        // and the same result linked twice is imported once.
+        if ResultMetadata::load_for(&result.db_file()).is_some()
+            || downloaded.iter().any(|d| d.result.db_file() == result.db_file())
+        {
+            fs::remove_file(&path)?;
+            checkpoint.set(&url, UrlState::Merged { file: result.db_file() })?;
+            already_merged.push((shown.clone(), result.db_file()));
+            if let Some(progress) = progress.as_mut() {
+                progress.file_done(false).await;
+            }
+            continue;
+        }
+        downloaded.push(DownloadedResult { url, shown, download_secs: started.elapsed().as_secs_f64(), result });
+    }
+    pb.finish_and_clear();
+
+    // Validation dominates the processing of the files, they are
+    // validated in parallel, as many at a time as there are jobs.
+    let pb = bar(downloaded.len() as u64, "Validating");
+    let validations: Vec<_> = downloaded
+        .par_iter_mut()
+        .progress_with(pb.clone())
+        .map(|d| {
+            let started = Instant::now();
+            let validation = d.result.check();
+            (validation, started.elapsed().as_secs_f64())
+        })
+        .collect();
+    pb.finish_and_clear();
+
+    // The results are added to the database in the order of the links
+    for (d, (validation, validation_secs)) in downloaded.into_iter().zip(validations) {
+        let DownloadedResult { url, shown, download_secs, mut result } = d;
+        if let Err(e) = validation {
+            METRICS.validation_failures.inc();
+            ERROR_REPORT.record(
+                ErrorEntry::new(Stage::Validation, &e)
+                    .input(shown.clone())
+                    .model(&result.model_name, &result.version),
+            );
+            record_failed_file(database_path, &mut index, &mut checkpoint, &url, failure(&shown, &e))?;
+            errors.push(FileError::new(shown.clone(), &e));
+            if let Some(progress) = progress.as_mut() {
+                progress.file_done(true).await;
+            }
+            continue;
+        }
+        result.processing_secs = Some(download_secs + validation_secs);
+        if let (Some(date), Some(staging)) = (embargo, embargo_staging()) {
+            result.embargo_until = Some(date.to_string());
+            result.hold_back(staging)?;
+            checkpoint.set(&url, UrlState::Embargoed { until: date.to_string() })?;
+            held += 1;
+            if let Some(progress) = progress.as_mut() {
+                progress.file_done(false).await;
+            }
+            continue;
+        }
+        if for_review {
+            result.dir = pending_dir(database_path, &result.dir).to_string_lossy().to_string();
+        }
+        let files = result.add_to_database(Some(&issue_id.to_string()))?;
+        stage_files(&mut index, database_path, &files)?;
+        checkpoint.set(
+            &url,
+            UrlState::Staged {
+                version: result.version.clone(),
+                model_name: result.model_name.clone(),
+                files,
+            },
+        )?;
+        let high_level = merged
+            .entry(format!("{}-{}", &result.version, &result.model_name))
+            .or_insert_with(|| HighLevel::new(&result.version, &result.model_name));
+        high_level.increment();
+        if let Some(vendor) = &result.signed_by {
+            high_level.add_signed(vendor);
+        }
+        if let Some(progress) = progress.as_mut() {
+            progress.file_done(false).await;
+        }
+    }
+
+    if !already_merged.is_empty() {
+        log(format!("Skipped {} results of #{} already merged", already_merged.len(), issue_id));
+    }
+    if !duplicates.is_empty() {
+        report_duplicates(context, issue_id, duplicates).await?;
+    }
+    if let (Some(date), true) = (embargo, held > 0) {
+        let comment = EmbargoReport {
+            submitter: login.clone(),
+            held: true,
+            count: held,
+            date: date.to_string(),
+        }
+        .render()?;
+        post_comment(context, issue_id, comment).await?;
+    }
+    let failed = errors.len();
+    if !errors.is_empty() {
+        let comment = SubmissionReport::new(vec![], errors).render()?;
+        let (token, owner) = match (&context.token, &context.repository_owner) {
+            (Some(t), Some(o)) => (t, o),
+            _ => bail!(
+                "No token or repository owner in the context to report errors: {}",
+                comment
+            ),
+        };
+        octocrab::OctocrabBuilder::new()
+            .personal_token(token.clone())
+            .build()?
+            .issues(owner, "iocost-benchmarks")
+            .create_comment(issue_id, comment)
+            .await?;
+    }
+    let mut outcome = ImportOutcome {
+        branch_name: None,
+        title: None,
+        description: None,
+        imported: merged.values().map(|v| v.new_files as usize).sum(),
+        failed,
+        skipped: None,
+        pending: false,
+    };
+    if merged.is_empty() {
+        println!("Found no new results files to merge...");
+        checkpoint.finish()?;
+        return Ok(outcome);
+    }
+    let models: BTreeSet<(String, String)> =
+        merged.values().map(|v| (v.model_name.clone(), v.version.clone())).collect();
+    if let Err(e) = label_issue(context, issue_id, &models).await {
+        eprintln!("Failed to label #{} with its models: {:#}", issue_id, e);
+    }
+
+    // Commit the new and changed files.
+    let database = Database::new(database_path);
+    database.ensure_layout()?;
+    let mut imported = vec![];
+    for v in merged.values() {
+        // Pending results are not part of the merges of the database yet
+        let summary = match for_review {
+            true => String::new(),
+            false => {
+                let summary = v.summarize(&database)?;
+                summary.high_level.clone()
+                    + &summary.format_series()
+                    + &firmware_note(&database, &v.version, &v.model_name)
+            }
+        };
+        imported.push(ImportedModel {
+            model_name: v.model_name.clone(),
+            version: v.version.clone(),
+            new_files: v.new_files,
+            signed_by: v.signed_by.clone(),
+            summary,
+        });
+    }
+    let description = import_description(issue_id, for_review, &already_merged, imported);
+    let commit_title = commit_settings().title(issue_id, &models);
+    let trailer = commit_settings().trailer(issue_id, &models, &signoff);
+    let commit_message = format!("{commit_title}\n\n{description}\n\n{trailer}");
+    let branch_name = commit_settings().branch(issue_id);
+    if let Some(storage) = object_storage() {
+        // The results went to the object storage, there is only
+        // something to commit if a submission failed.
+        if index.write_tree()? == git_repo.head()?.peel_to_tree()?.id() {
+            log(format!("Stored the new results in {}", storage.describe()));
+            checkpoint.finish()?;
+            return Ok(outcome);
+        }
+    }
+    ledger.record_run(&checkpoint);
+    index.add_path(&ledger.save(Path::new(database_path))?)?;
+    commit_to_branch(&git_repo, &mut index, &commit_message, &branch_name)?;
+    checkpoint.finish()?;
+    if for_review {
+        let comment = PendingReview { submitter: login, count: outcome.imported }.render()?;
+        post_comment(context, issue_id, comment).await?;
+    }
+
+    outcome.branch_name = Some(branch_name);
+    outcome.title = Some(commit_title);
+    outcome.description = Some(description);
+    outcome.pending = for_review;
+    Ok(outcome)
+}
+
+/// New results of a model in the pull request of an import
+#[derive(Debug, Clone)]
+pub struct ImportedModel {
+    pub model_name: String,
+    pub version: String,
+    pub new_files: u64,
+    /// New files signed by their vendor, by vendor
+    pub signed_by: BTreeMap<String, u64>,
+    /// High-level summary, data points and firmware note of its merge,
+    /// empty for the results pending review
+    pub summary: String,
+}
+
+/// Renders the description of the pull request importing the results of
+/// `issue_id`: the `already_merged` results, as (URL, database file), and
+/// the new results of the `models`, under a heading per version when they
+/// span several. With `for_review`, the results are in the pending area.
+pub fn import_description(
+    issue_id: u64,
+    for_review: bool,
+    already_merged: &[(String, PathBuf)],
+    mut models: Vec<ImportedModel>,
+) -> String {
+    models.sort_by(|a, b| (&a.version, &a.model_name).cmp(&(&b.version, &b.model_name)));
+    let versions: BTreeSet<&str> = models.iter().map(|m| m.version.as_str()).collect();
+    let mut description = format!("Closes #{}\n", issue_id);
+    if for_review {
+        description.push_str(&format!(
+            "\nThe results of this first-time contributor are in `{}/` until a maintainer comments `/accept` \
+             on #{}.\n",
+            PENDING_DIR, issue_id
+        ));
+    }
+    if !already_merged.is_empty() {
+        description.push_str(&format!(
+            "\n{} results of this issue were already merged, this update only adds the missing ones:\n",
+            already_merged.len()
+        ));
+        for (url, file) in already_merged {
+            description.push_str(&format!("- {} ({})\n", url, file.display()));
+        }
+    }
+    for version in &versions {
+        if versions.len() > 1 {
+            description.push_str(&format!("\n## resctl-bench {}\n", version));
+        }
+        for m in models.iter().filter(|m| m.version == *version) {
+            let signed: String = m
+                .signed_by
+                .iter()
+                .map(|(vendor, count)| format!(", {} signed by {}", count, vendor))
+                .collect();
+            description.push_str(&format!(
+                "\n[{} ({})] {} new files{}\n{}",
+                m.model_name, m.version, m.new_files, signed, m.summary
+            ));
+        }
+    }
+    description
+}
+
+/// Uploads the results and metadata among the database `files` of an
+/// import to the object storage of the database, if it has one. Returns
+/// the files left to commit.
+fn upload_files(database_path: &str, files: Vec<PathBuf>) -> Result<Vec<PathBuf>> {
+    let Some(storage) = object_storage() else {
+        return Ok(files);
+    };
+    let mut left = vec![];
+    for file in files {
+        match key_for(Path::new(database_path), &file) {
+            Some(key) => storage.write(&key, &fs::read(&file)?)?,
+            None => left.push(file),
+        }
+    }
+    storage.flush()?;
+    Ok(left)
+}
+
+/// Records that the file of `url` can't be imported in the ledger of
+/// failed submissions, staged in `index`, and in the checkpoint, so that
+/// the next runs of the issue report it rather than process it again
+fn record_failed_file(
+    database_path: &str,
+    index: &mut git2::Index,
+    checkpoint: &mut ImportCheckpoint,
+    url: &str,
+    failure: FailedSubmission,
+) -> Result<()> {
+    let ledger = failure.record(database_path)?;
+    index.add_path(&ledger)?;
+    checkpoint.set(url, UrlState::Failed { error: failure.error, ledger })
+}
+
+/// Stages the database `files` of an import in `index`, once the
+/// results and metadata are uploaded to the object storage if any
+fn stage_files(index: &mut git2::Index, database_path: &str, files: &[PathBuf]) -> Result<()> {
+    for file in upload_files(database_path, files.to_vec())? {
+        index.add_path(&file)?;
+    }
+    Ok(())
+}
+
+/// Returns a note comparing the solutions of the firmware revisions of
+/// `model_name`, if it has results for several of them. Failures are
+/// reported but otherwise ignored.
+fn firmware_note(database: &Database, version: &str, model_name: &str) -> String {
+    let fwrevs = database
+        .model_dir(version, model_name)
+        .and_then(|dir| BenchMerge::results_by_fwrev(&dir));
+    if !matches!(fwrevs, Ok(f) if f.len() > 1) {
+        return String::new();
+    }
+    match FirmwareComparison::compute(database, version, model_name) {
+        Ok(comparison) => format!("\n{}\n", comparison.summary()),
+        Err(e) => {
+            eprintln!("Failed to compare the firmware revisions of {}: {:#}", model_name, e);
+            String::new()
+        }
+    }
+}
+
+/// Comments on `issue_id` with the submitted files that were already
+/// imported from other submissions, linking to the pull requests that
+/// imported them
+async fn report_duplicates(
+    context: &ContextPayload,
+    issue_id: u64,
+    mut duplicates: Vec<DuplicateFile>,
+) -> Result<()> {
+    log(format!("Skipped {} files already in the database", duplicates.len()));
+    let (token, owner) = match (&context.token, &context.repository_owner) {
+        (Some(t), Some(o)) => (t, o),
+        _ => {
+            println!("{}", DuplicateReport { duplicates }.render()?);
+            println!("No token or repository owner in the context, can't report the duplicates");
+            return Ok(());
+        }
+    };
+    let repo = format!("{}/iocost-benchmarks", owner);
+    for duplicate in &mut duplicates {
+        if let Some(issue) = duplicate.issue {
+            duplicate.pull_request = find_pull_request(token, &repo, &commit_settings().branch(issue))
+                .await
+                .map_err(|e| eprintln!("Failed to find the pull request of issue {}: {}", issue, e))
+                .ok()
+                .flatten();
+        }
+    }
+    octocrab::OctocrabBuilder::new()
+        .personal_token(token.clone())
+        .build()?
+        .issues(owner, "iocost-benchmarks")
+        .create_comment(issue_id, DuplicateReport { duplicates }.render()?)
+        .await?;
+    Ok(())
+}
+
+/// Comments `body` on `issue_id`, or prints it if the context has no
+/// token to do so
+async fn post_comment(context: &ContextPayload, issue_id: u64, body: String) -> Result<()> {
+    let (token, owner) = match (&context.token, &context.repository_owner) {
+        (Some(t), Some(o)) => (t, o),
+        _ => {
+            println!("{}", body);
+            println!("No token or repository owner in the context, can't comment on #{}", issue_id);
+            return Ok(());
+        }
+    };
+    octocrab::OctocrabBuilder::new()
+        .personal_token(token.clone())
+        .build()?
+        .issues(owner, "iocost-benchmarks")
+        .create_comment(issue_id, body)
+        .await?;
+    Ok(())
+}
+
+/// Handles a new issue without any result link: explains how to submit
+/// results and labels it as needing links, rather than failing. The
+/// `ignored` links are listed, in case they were meant as results.
+async fn triage_issue(context: &ContextPayload, issue_id: u64, ignored: Vec<String>) -> Result<()> {
+    log(format!("No result links found in #{}", issue_id));
+    let owner = context.repository_owner.as_deref().unwrap_or("iocost-benchmark");
+    let comment = NeedsLinks {
+        submitter: context.event.sender.as_ref().map(|s| s.login.clone()).unwrap_or_default(),
+        ignored,
+        form_url: issue_settings().form_url(owner),
+    }
+    .render()?;
+    post_comment(context, issue_id, comment).await?;
+    if let (Some(token), Some(owner)) = (&context.token, &context.repository_owner) {
+        octocrab::OctocrabBuilder::new()
+            .personal_token(token.clone())
+            .build()?
+            .issues(owner, "iocost-benchmarks")
+            .add_labels(issue_id, &[NEEDS_LINKS_LABEL.to_string()])
+            .await?;
+    }
+    Ok(())
+}
+
+/// Queues the submission of `issue_id` until the next day by labelling
+/// it, for a scheduled run to resume it, and posts the `report` to let
+/// the submitter know.
+async fn queue_submission(context: &ContextPayload, issue_id: u64, report: Option<QueuedReport>) -> Result<()> {
+    if let Some(report) = report {
+        post_comment(context, issue_id, report.render()?).await?;
+    }
+    match (&context.token, &context.repository_owner) {
+        (Some(token), Some(owner)) => {
+            octocrab::OctocrabBuilder::new()
+                .personal_token(token.clone())
+                .build()?
+                .issues(owner, "iocost-benchmarks")
+                .add_labels(issue_id, &[QUEUED_LABEL.to_string()])
+                .await?;
+        }
+        _ => println!("No token or repository owner in the context, can't queue #{}", issue_id),
+    }
+    Ok(())
+}
+
+/// Resumes the queued submissions by removing their label, the
+/// `unlabeled` events of which run their import. The token of the
+/// context must not be the GITHUB_TOKEN of the workflow, whose events
+/// don't trigger workflows. Returns the number of resumed submissions.
+async fn release_queued_submissions(context: &ContextPayload) -> Result<usize> {
+    let (token, owner) = match (&context.token, &context.repository_owner) {
+        (Some(t), Some(o)) => (t, o),
+        _ => {
+            println!("No token or repository owner in the context, leaving the queued submissions");
+            return Ok(0);
+        }
+    };
+    let github = octocrab::OctocrabBuilder::new().personal_token(token.clone()).build()?;
+    let issues = github.issues(owner, "iocost-benchmarks");
+    let queued = issues
+        .list()
+        .labels(&[QUEUED_LABEL.to_string()])
+        .state(octocrab::params::State::Open)
+        .per_page(100)
+        .send()
+        .await?;
+    for issue in &queued.items {
+        issues.remove_label(issue.number, QUEUED_LABEL).await?;
+    }
+    Ok(queued.items.len())
+}
+
+/// Comments on the submission issue of a merged `iocost-bot/<issue>` pull
+/// request with links to the merge commit and to the outputs of the
+/// models, then closes the issue, rather than relying on the `Closes #N`
+/// of the pull request, which only applies to the default branch.
+async fn close_merged_submission(context: &ContextPayload, database_path: &str) -> Result<()> {
+    let pr = context.event.pull_request.as_ref().context("The event has no pull request")?;
+    let Some(issue_id) = commit_settings().issue_of(&pr.head.ref_name) else {
+        println!("Pull request #{} is not the import of a submission", pr.number);
+        return Ok(());
+    };
+    let (from, to) = context
+        .commit_range()
+        .with_context(|| format!("No commit range for the merge of pull request #{}", pr.number))?;
+    let git_repo = workspace().repository()?;
+    let settings = issue_settings();
+    let models = changed_models(&git_repo, &from, &to, Path::new(database_path))?
+        .into_iter()
+        .map(|(version, model_name)| {
+            let (pdf_url, hwdb_url) = settings.output_urls(&model_name, &version);
+            MergedModel { model_name, version, pdf_url, hwdb_url }
+        })
+        .collect();
+    let owner = context.repository_owner.as_deref().unwrap_or("iocost-benchmark");
+    let commit = pr.merge_commit_sha.as_deref().unwrap_or(&to);
+    let comment = MergedReport {
+        commit_url: format!("https://github.com/{}/iocost-benchmarks/commit/{}", owner, commit),
+        models,
+    }
+    .render()?;
+    post_comment(context, issue_id, comment).await?;
+    let event = HistoryEvent {
+        branch: Some(pr.head.ref_name.clone()),
+        detail: Some(format!("pull request #{} merged as {}", pr.number, commit)),
+        ..HistoryEvent::new(context, "merged")
+    };
+    record_history(context, issue_id, event);
+    let Some(token) = &context.token else {
+        println!("No token in the context, can't close #{}", issue_id);
+        return Ok(());
+    };
+    octocrab::OctocrabBuilder::new()
+        .personal_token(token.clone())
+        .build()?
+        .issues(owner, "iocost-benchmarks")
+        .update(issue_id)
+        .state(octocrab::models::IssueState::Closed)
+        .send()
+        .await?;
+    log(format!("Closed #{} after the merge of #{}", issue_id, pr.number));
+    Ok(())
+}
+
+/// Labels `issue_id` with the `models` (model, version) of the results
+/// imported from it, and appends them to its title if configured to, to
+/// make the submissions searchable by device, and removes its needs-links
+/// label. Embargoed results are not passed here, not to disclose their
+/// models before their release.
+async fn label_issue(
+    context: &ContextPayload,
+    issue_id: u64,
+    models: &BTreeSet<(String, String)>,
+) -> Result<()> {
+    let settings = issue_settings();
+    let mut labels: Vec<String> = match settings.model_labels {
+        true => models.iter().map(|(model, _)| model_label(model)).collect(),
+        false => vec![],
+    };
+    labels.dedup();
+    let issue = context.event.issue.as_ref();
+    let title = issue.and_then(|i| i.title.as_deref());
+    let title = title.filter(|_| settings.retitle).and_then(|t| submission_title(t, models));
+    let needed_links = issue.is_some_and(|i| i.labels.iter().any(|l| l.name == NEEDS_LINKS_LABEL));
+    if labels.is_empty() && title.is_none() && !needed_links {
+        return Ok(());
+    }
+    let (token, owner) = match (&context.token, &context.repository_owner) {
+        (Some(t), Some(o)) => (t, o),
+        _ => {
+            println!("No token or repository owner in the context, can't label #{}: {:?}", issue_id, labels);
+            return Ok(());
+        }
+    };
+    let github = octocrab::OctocrabBuilder::new().personal_token(token.clone()).build()?;
+    let issues = github.issues(owner, "iocost-benchmarks");
+    if !labels.is_empty() {
+        issues.add_labels(issue_id, &labels).await?;
+    }
+    if let Some(title) = title {
+        issues.update(issue_id).title(&title).send().await?;
+    }
+    if needed_links {
+        issues.remove_label(issue_id, NEEDS_LINKS_LABEL).await?;
+    }
+    Ok(())
+}
+
+/// Pushes `branch_name` to the Github repository `repo` (owner/name)
+/// with `token`, once its LFS objects are uploaded, and opens its pull
+/// request into `base`, as a `draft` if set. The pull request already
+/// open for a branch updated by /accept or a new run only gets its title
+/// and description updated. Returns the URL of the pull request and
+/// whether it was opened.
+pub async fn publish_branch(
+    token: &str,
+    repo: &str,
+    base: &str,
+    branch_name: &str,
+    title: &str,
+    description: &str,
+    draft: bool,
+) -> Result<(String, bool)> {
+    upload_branch_objects(repo, branch_name, token).await?;
+    push_branch(&workspace().repository()?, repo, branch_name, token)?;
+    if let Some(url) = update_pull_request(token, repo, branch_name, title, description).await? {
+        return Ok((url, false));
+    }
+    let url = open_pull_request(token, repo, branch_name, base, title, description, draft)
+        .await
+        .with_context(|| format!("Failed to open pull request for {}", branch_name))?;
+    Ok((url, true))
+}
+
+/// Outcome of a maintainer command: the branch its changes were
+/// committed to, with the title and description of their pull request
+pub struct CommandOutcome {
+    pub branch_name: String,
+    pub title: String,
+    pub description: String,
+}
+
+/// Runs a maintainer `command` posted as an issue comment on the
+/// database in `database_path`, committing the change to the branch of
+/// the command. Commands from users who are not maintainers of the
+/// repository are refused.
+pub async fn process_bot_command(
+    context: &ContextPayload,
+    database_path: &str,
+    command: BotCommand,
+) -> Result<Option<CommandOutcome>> {
+    let issue_id = match &context.event.issue {
+        Some(i) => i.number,
+        None => bail!("{} event without an issue", context.event_name),
+    };
+    let submitter = context.event.sender.as_ref().map(|s| s.login.clone()).unwrap_or_default();
+    let association = context.event.comment.as_ref().and_then(|c| c.author_association.as_deref());
+    let action = command.action();
+    if !is_maintainer(association) {
+        log(format!("Refusing {:?} from {} ({})", command, submitter, association.unwrap_or("unknown")));
+        let comment = PermissionDenied { submitter, action: action.to_string() }.render()?;
+        post_comment(context, issue_id, comment).await?;
+        let event = HistoryEvent {
+            detail: Some(action.to_string()),
+            ..HistoryEvent::new(context, "permission-denied")
+        };
+        record_history(context, issue_id, event);
+        return Ok(None);
+    }
+
+    let requested_by = format!("@{} in #{}", submitter, issue_id);
+    let (outcome, comment) = match &command {
+        BotCommand::Retract { md5, reason } => {
+            let retraction = retract(Path::new(database_path), md5, reason)?;
+            let branch_name = retraction.commit(Some(&requested_by))?;
+            let comment = RetractionReport {
+                file: format!("result-{}.json.gz", retraction.md5),
+                model_name: retraction.model_name.clone(),
+                version: retraction.version.clone(),
+                reason: retraction.reason.clone(),
+                branch: branch_name.clone(),
+            }
+            .render()?;
+            let outcome = CommandOutcome {
+                branch_name,
+                title: retraction.title(),
+                description: retraction.description(Some(&requested_by)),
+            };
+            (outcome, comment)
+        }
+        BotCommand::Accept => accept_submission(context, database_path, issue_id, &submitter).await?,
+        _ => set_result_metadata(&command, database_path, issue_id, &requested_by)?,
+    };
+    post_comment(context, issue_id, comment).await?;
+    let event = HistoryEvent {
+        branch: Some(outcome.branch_name.clone()),
+        detail: Some(outcome.title.clone()),
+        ..HistoryEvent::new(context, "command")
+    };
+    record_history(context, issue_id, event);
+    Ok(Some(outcome))
+}
+
+/// Runs the `/accept` command, which moves the results of the issue
+/// pending review into the database on the branch of the issue, and
+/// marks its draft pull request ready for review. Returns the outcome and
+/// the comment reporting it.
+async fn accept_submission(
+    context: &ContextPayload,
+    database_path: &str,
+    issue_id: u64,
+    maintainer: &str,
+) -> Result<(CommandOutcome, String)> {
+    let git_repo = workspace().repository()?;
+    let branch_name = BotCommand::Accept.branch(issue_id);
+    if let Err(e) = sync_with_default_branch(context, &git_repo, issue_id) {
+        eprintln!("Failed to bring #{} up to date with the default branch: {:#}", issue_id, e);
+    }
+    // The branch updated from the one pushed by the import, if it could
+    // be fetched
+    let tip = ["refs/heads", "refs/remotes/origin"]
+        .iter()
+        .find_map(|refs| {
+            git_repo
+                .find_reference(&format!("{}/{}", refs, branch_name))
+                .and_then(|r| r.peel_to_commit())
+                .ok()
+        })
+        .with_context(|| format!("No {} branch with results pending review", branch_name))?;
+    let message = format!(
+        "Accept the results of #{} pending review\n\nRequested by @{} in #{}",
+        issue_id, maintainer, issue_id
+    );
+    let moved = accept_pending(&git_repo, &branch_name, &tip, database_path, &message)?;
+    // Results counted per (model, version), from their <version>/<model>
+    // directory
+    let mut counts: BTreeMap<(String, String), usize> = BTreeMap::new();
+    for path in moved.iter().filter(|p| p.to_string_lossy().ends_with(RESULT_SUFFIX)) {
+        let dirs: Vec<String> = path
+            .strip_prefix(database_path)?
+            .iter()
+            .map(|c| c.to_string_lossy().to_string())
+            .collect();
+        if let [version, model_name, _] = &dirs[..] {
+            *counts.entry((model_name.clone(), version.clone())).or_default() += 1;
+        }
+    }
+    if let (Some(repo), Some(token)) = (&context.repository, &context.token) {
+        fast_forward_branch(&git_repo, repo, &branch_name, token)?;
+        mark_ready_for_review(token, repo, &branch_name).await?;
+    }
+    let comment = AcceptedReport {
+        maintainer: maintainer.to_string(),
+        count: counts.values().sum(),
+        branch: branch_name.clone(),
+    }
+    .render()?;
+    let models = counts.keys().cloned().collect();
+    let title = commit_settings().title(issue_id, &models);
+    let mut description = format!(
+        "Closes #{}\n\nThe results pending review were accepted by @{}:\n",
+        issue_id, maintainer
+    );
+    for ((model_name, version), count) in &counts {
+        description.push_str(&format!("\n[{} ({})] {} new files", model_name, version, count));
+    }
+    Ok((CommandOutcome { branch_name, title, description }, comment))
+}
+
+/// Runs the `/exclude` and `/include` commands, which only change the
+/// metadata of a result. Returns the outcome and the comment reporting
+/// it.
+fn set_result_metadata(
+    command: &BotCommand,
+    database_path: &str,
+    issue_id: u64,
+    requested_by: &str,
+) -> Result<(CommandOutcome, String)> {
+    let (result, metadata_path) = command.set_metadata(Path::new(database_path))?;
+    let metadata = ResultMetadata::load_for(&result)
+        .with_context(|| format!("Failed to load the metadata of {}", result.display()))?;
+    let file = result.file_name().context("No result file name")?.to_string_lossy().to_string();
+    let excluded = metadata.exclude;
+    let title = format!(
+        "{} {} {} the merges of {} ({})",
+        if excluded { "Exclude" } else { "Include" },
+        file,
+        if excluded { "from" } else { "in" },
+        metadata.model_name,
+        metadata.version
+    );
+    let mut description = format!("Requested by {}", requested_by);
+    if let Some(reason) = &metadata.exclude_reason {
+        description.push_str(&format!("\nReason: {}", reason));
+    }
+
+    let git_repo = workspace().repository()?;
+    let mut index = git_repo.index()?;
+    index.add_path(&metadata_path)?;
+    let branch_name = command.branch(issue_id);
+    commit_to_branch(&git_repo, &mut index, &format!("{title}\n\n{description}"), &branch_name)?;
+
+    let comment = ExclusionReport {
+        file,
+        model_name: metadata.model_name,
+        version: metadata.version,
+        excluded,
+        reason: metadata.exclude_reason,
+        branch: branch_name.clone(),
+    }
+    .render()?;
+    Ok((CommandOutcome { branch_name, title, description }, comment))
+}
+
+/// Expands the local `inputs` into the list of result files to import:
+/// directories are searched recursively for `*.json.gz` files and
+/// anything else is treated as a file path or glob pattern.
+pub fn collect_result_files(inputs: &[String]) -> Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    for input in inputs {
+        let path = Path::new(input);
+        let pattern = if path.is_dir() {
+            path.join("**").join("*.json.gz").to_string_lossy().to_string()
+        } else {
+            input.clone()
+        };
+        let matches: Vec<PathBuf> = glob(&pattern)?.flatten().filter(|p| p.is_file()).collect();
+        if matches.is_empty() {
+            bail!("No result files found in {}", input);
+        }
+        files.extend(matches);
+    }
+    files.sort();
+    files.dedup();
+    Ok(files)
+}
+
+/// Imports local result `files` into the database in `database_path`,
+/// skipping exact duplicates and recording the files that fail
+/// validation in the failed submissions ledger, like for results
+/// submitted through Github issues. Returns the outcome and the
+/// database files that were added or changed.
+pub async fn import_local_files(
+    files: &[PathBuf],
+    database_path: &str,
+) -> Result<(ImportOutcome, Vec<PathBuf>)> {
+    let mut merged = HashMap::new();
+    let mut changed = vec![];
+    let mut errors = vec![];
+    let mut duplicates = 0;
+
+    let pb = bar(files.len() as u64, "Validating");
+    for file in files {
+        pb.set_message(file.display().to_string());
+        pb.inc(1);
+        // Work on a copy named after its md5sum, so that the same
+        // result is stored only once whatever its original name.
+        let path = save_result_file(&fs::read(file)?)?;
+        let result = match BenchResult::new(&path, database_path).await {
+            Ok(result) if result.db_file().exists() => {
+                fs::remove_file(&path)?;
+                duplicates += 1;
+                continue;
+            }
+            Ok(mut result) => result.scrub().and_then(|_| result.validate()).map(|_| result),
+            Err(e) => Err(e),
+        };
+        let result = match result {
+            Ok(result) => result,
+            Err(e) => {
+                METRICS.validation_failures.inc();
+                ERROR_REPORT.record(
+                    ErrorEntry::new(Stage::Validation, &e).input(file.display().to_string()),
+                );
+                fs::remove_file(&path)?;
+                let failure = FailedSubmission {
+                    issue: None,
+                    url: file.display().to_string(),
+                    submitter: None,
+                    failed_at: chrono::Utc::now().to_rfc3339(),
+                    error: e.to_string(),
+                };
+                changed.push(failure.record(database_path)?);
+                errors.push(format!("File {} failed validation: {}", file.display(), e));
+                continue;
+            }
+        };
+        changed.extend(result.add_to_database(None)?);
+        merged
+            .entry(format!("{}-{}", &result.version, &result.model_name))
+            .or_insert_with(|| HighLevel::new(&result.version, &result.model_name))
+            .increment();
+    }
+    pb.finish_and_clear();
+
+    for v in merged.values() {
+        log(format!("[{} ({})] {} new files", v.model_name, v.version, v.new_files));
+    }
+    if duplicates > 0 {
+        log(format!("Skipped {} files already in the database", duplicates));
+    }
+    for error in &errors {
+        eprintln!("{}", error);
+    }
+    changed.sort();
+    changed.dedup();
+    let changed = upload_files(database_path, changed)?;
+    let outcome = ImportOutcome {
+        branch_name: None,
+        title: None,
+        description: None,
+        imported: merged.values().map(|v| v.new_files as usize).sum(),
+        failed: errors.len(),
+        skipped: None,
+        pending: false,
+    };
+    Ok((outcome, changed))
+}
+
+/// Regenerates the merged result, pdf and hwdb input of the models in
+/// `database_path` matching `version` and `model` (all of them if not
+/// set). Returns the merges that were done.
+pub fn remerge_models(
+    database_path: &str,
+    version: Option<&str>,
+    model: Option<&str>,
+) -> Result<Vec<BenchMerge>> {
+    if let Some(version) = version {
+        validate_version(version)?;
+    }
+    if let Some(model) = model {
+        validate_name("model", model)?;
+    }
+    let database = Database::new(database_path);
+    database.ensure_layout()?;
+    let pattern = Path::new(database_path).join(version.unwrap_or("*")).join(model.unwrap_or("*"));
+    let mut merged = vec![];
+    for model_dir in glob(&pattern.to_string_lossy())?.flatten().filter(|p| p.is_dir()) {
+        let model_name = model_dir.file_name().context("No model name")?.to_string_lossy().to_string();
+        let version = model_dir
+            .parent()
+            .and_then(|p| p.file_name())
+            .with_context(|| format!("No version for {}", model_dir.display()))?
+            .to_string_lossy()
+            .to_string();
+        if legacy_versions().contains(&version) {
+            println!("Ignoring {} {}, a legacy version", model_name, version);
+            continue;
+        }
+        println!("Re-merging {} ({})", model_name, version);
+        let merge = BenchMerge::merge(&database, version, model_name)?;
+        merge.save_pdf_in(&workspace().pdfs_dir())?;
+        merge.create_hwdb_in(&workspace().hwdb_inputs_dir())?;
+        merged.push(merge);
+    }
+    Ok(merged)
+}
+
+/// Processes a `repository_dispatch` event according to its client
+/// payload
+pub fn process_dispatch_event(context: &ContextPayload, database_path: &str) -> Result<()> {
+    match context.dispatch_payload()? {
+        DispatchPayload::Remerge { model, version } => {
+            let merged = remerge_models(database_path, version.as_deref(), model.as_deref())?.len();
+            if merged == 0 {
+                bail!(
+                    "No models found to re-merge for model {} version {}",
+                    model.as_deref().unwrap_or("*"),
+                    version.as_deref().unwrap_or("*")
+                );
+            }
+            println!("Re-merged {} models", merged);
+        }
+    }
+    Ok(())
+}
+
+/// Processes a `schedule` event: re-merges the whole database, cleans
+/// up stale bot branches, refreshes the coverage statistics and resumes
+/// the queued submissions. This gives the pipeline a periodic pass
+/// independent of submissions.
+pub async fn process_schedule_event(context: &ContextPayload, database_path: &str) -> Result<()> {
+    let merged = remerge_models(database_path, None, None)?.len();
+    println!("Re-merged {} models", merged);
+
+    let git_repo = workspace().repository()?;
+    for (branch_name, branch_type) in stale_bot_branches(&git_repo, STALE_BRANCH_DAYS)? {
+        println!("Removing stale branch {}", branch_name);
+        match branch_type {
+            BranchType::Local => git_repo.find_branch(&branch_name, branch_type)?.delete()?,
+            BranchType::Remote => match (&context.repository, &context.token) {
+                (Some(repo), Some(token)) => {
+                    delete_remote_branch(&git_repo, repo, &branch_name, token)?
+                }
+                _ => println!("No repository or token in the context, keeping remote {}", branch_name),
+            },
+        }
+    }
+
+    let stats = write_coverage_stats(Path::new(database_path))?;
+    println!(
+        "Coverage stats: {} models, {} results, {} merges with starved series, written to {}",
+        stats.models,
+        stats.results,
+        stats.starved().count(),
+        COVERAGE_STATS_FILE
+    );
+
+    let resumed = release_queued_submissions(context).await?;
+    println!("Resumed {} queued submissions", resumed);
+    Ok(())
+}
+
+/// Computes the coverage statistics of the database in `database_dir`
+/// and writes them to `COVERAGE_STATS_FILE`
+pub fn write_coverage_stats(database_dir: &Path) -> Result<CoverageStats> {
+    let vendors = Vendors::load(Path::new(VENDORS_FILE))?;
+    let mut stats = DatabaseIndex::load(database_dir)?.coverage_stats(&vendors);
+    stats.add_series_points(&Database::new(database_dir));
+    fs::write(COVERAGE_STATS_FILE, serde_json::to_string_pretty(&stats)?)?;
+    Ok(stats)
+}
+
+/// Processes a `pull_request` event on the database repository, for
+/// result files added by hand: validates the new `result-*.json.gz`
+/// files and comments on the pull request with the high-level summary
+/// of the affected models and a preview of their hwdb entries. Once a pull request of the bot is merged,
+/// closes the issue its results were submitted in.
+pub async fn process_pull_request_event(context: &ContextPayload, database_path: &str) -> Result<()> {
+    let pr = match &context.event.pull_request {
+        Some(pr) => pr,
+        None => bail!("pull_request event without pull request information"),
+    };
+    if context.event.action.as_deref() == Some("closed") {
+        if pr.merged {
+            close_merged_submission(context, database_path).await?;
+        }
+        return Ok(());
+    }
+    let git_repo = workspace().repository()?;
+    let new_files: Vec<PathBuf> = added_files(&git_repo, &pr.base.sha)?
+        .into_iter()
+        .filter(|p| p.starts_with(database_path))
+        .filter(|p| {
+            let name = p.file_name().unwrap_or_default().to_string_lossy();
+            name.starts_with("result-") && name.ends_with(".json.gz")
+        })
+        .collect();
+    if new_files.is_empty() {
+        println!("No new result files in pull request #{}", pr.number);
+        return Ok(());
+    }
+
+    let mut merged = HashMap::new();
+    let mut errors = vec![];
+    for path in &new_files {
+        let path = path.to_string_lossy().to_string();
+        println!("Validating {}", path);
+        let result = match BenchResult::new(&path, database_path).await {
+            Ok(result) => result,
+            Err(e) => {
+                errors.push(FileError::new(path, &e));
+                continue;
+            }
+        };
+        if let Err(e) = result.validate() {
+            errors.push(FileError::new(path, &e));
+            continue;
+        }
+        merged
+            .entry(format!("{}-{}", &result.version, &result.model_name))
+            .or_insert_with(|| HighLevel::new(&result.version, &result.model_name))
+            .increment();
+    }
+
+    let database = Database::new(database_path);
+    database.ensure_layout()?;
+    let summaries = merged
+        .values()
+        .map(|v| {
+            let high_level = v.format_high_level(&database)?;
+            Ok(ModelSummary {
+                model_name: v.model_name.clone(),
+                version: v.version.clone(),
+                new_files: v.new_files,
+                high_level_table: HighLevelSummary::parse(&high_level).ok().map(|s| s.to_markdown()),
+                high_level,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let comment = SubmissionReport::new(summaries, errors).render()?;
+    // The preview is informative, failing to generate it doesn't fail
+    // the validation of the results.
+    let preview = hwdb_preview(context, database_path).unwrap_or_else(|e| {
+        eprintln!("Failed to preview the hwdb entries of #{}: {:#}", pr.number, e);
+        None
+    });
+
+    let (token, owner) = match (&context.token, &context.repository_owner) {
+        (Some(t), Some(o)) => (t, o),
+        _ => {
+            println!("{}", comment);
+            if let Some(preview) = preview {
+                println!("{}", preview);
+            }
+            bail!("No token or repository owner in the context, can't review the pull request");
+        }
+    };
+    // Reviews, rather than comments, so that they show with the changes
+    let repo = format!("{}/iocost-benchmarks", owner);
+    post_review(token, &repo, pr.number, &comment).await?;
+    if let Some(preview) = preview {
+        post_review(token, &repo, pr.number, &preview).await?;
+    }
+    Ok(())
+}
+
+/// Renders the preview of the hwdb entries of the models changed by the
+/// pull request of the event, with their diff against the published
+/// hwdb file, so that reviewers know the effect of the new results on
+/// the tuning values. As merge-results does, the entry of a model is the
+/// merge of its version with the most data points. Returns None if no
+/// model with hwdb support changed.
+fn hwdb_preview(context: &ContextPayload, database_path: &str) -> Result<Option<String>> {
+    let pr = context.event.pull_request.as_ref().context("The event has no pull request")?;
+    let git_repo = workspace().repository()?;
+    let head = git_repo.head()?.peel_to_commit()?.id().to_string();
+    let changed: BTreeSet<String> = changed_models(&git_repo, &pr.base.sha, &head, Path::new(database_path))?
+        .into_iter()
+        .map(|(_, model_name)| model_name)
+        .collect();
+    let database = Database::new(database_path);
+    let vendor_overrides = VendorOverrides::load(Path::new(VENDOR_OVERRIDES_DIR))?;
+    let inputs = tempfile::tempdir()?;
+    let mut best: BTreeMap<String, (BenchMerge, String)> = BTreeMap::new();
+    for model_name in &changed {
+        validate_name("model", model_name)?;
+        for model_dir in glob(&Path::new(database_path).join("*").join(model_name).to_string_lossy())?.flatten() {
+            let version = model_dir
+                .parent()
+                .and_then(|p| p.file_name())
+                .with_context(|| format!("No version for {}", model_dir.display()))?
+                .to_string_lossy()
+                .to_string();
+            if legacy_versions().contains(&version) || !model_dir.is_dir() {
+                continue;
+            }
+            let merge = BenchMerge::merge(&database, version, model_name.clone())?;
+            if best.get(model_name).is_some_and(|(b, _)| b.data_points >= merge.data_points) {
+                continue;
+            }
+            let Some(input) = merge.create_hwdb_in(inputs.path())? else {
+                continue;
+            };
+            let hwdb = vendor_overrides.apply(model_name, &fs::read_to_string(input)?);
+            best.insert(model_name.clone(), (merge, hwdb.trim_matches('\n').to_string()));
+        }
+    }
+    if best.is_empty() {
+        return Ok(None);
+    }
+    let published = match HwdbArchive::new(workspace().hwdb_archive_dir()).latest_file(HWDB_FILE)? {
+        Some(path) => fs::read_to_string(path)?,
+        None => String::new(),
+    };
+    let models = best
+        .into_values()
+        .map(|(merge, hwdb)| {
+            let current = hwdb_section_for(&published, &merge.model_name);
+            HwdbPreviewModel {
+                published: current.is_some(),
+                diff: current.and_then(|current| diff_sections(&current, &hwdb)),
+                model_name: merge.model_name,
+                version: merge.version_str,
+                data_points: merge.data_points,
+                hwdb,
+            }
+        })
+        .collect();
+    Ok(Some(HwdbPreview { models }.render()?))
+}
+
+/// Processes a `push` event on the default branch: re-merges the models
+/// whose database files changed in the pushed commits and regenerates
+/// the final hwdb file, keeping the entries of the other models from
+/// the last archived one.
+pub fn process_push_event(context: &ContextPayload, database_path: &str) -> Result<()> {
+    let event = &context.event;
+    let default_branch = event
+        .repository
+        .as_ref()
+        .and_then(|r| r.default_branch.as_deref())
+        .unwrap_or("main");
+    if event.ref_name.as_deref() != Some(&format!("refs/heads/{}", default_branch)) {
+        println!("Ignoring push to {:?}, not the default branch", event.ref_name);
+        return Ok(());
+    }
+    let (before, after) = match (&event.before, &event.after) {
+        (Some(b), Some(a)) => (b, a),
+        _ => bail!("push event without before/after commits"),
+    };
+
+    let git_repo = workspace().repository()?;
+    let changed = changed_models(&git_repo, before, after, Path::new(database_path))?;
+    if changed.is_empty() {
+        println!("No database changes pushed, nothing to regenerate");
+        return Ok(());
+    }
+    regenerate_models(database_path, &changed, Some(after.clone()))?;
+    Ok(())
+}
+
+/// Re-merges every version of the models of the `changed` (version,
+/// model) pairs of the database in `database_path` and regenerates the
+/// final hwdb file as merge-results does, reusing the hwdb inputs of the
+/// other models from the last archived run. The new outputs are archived
+/// in turn. Fails if there is no archived run, rather than writing a
+/// hwdb file with only the changed models. Returns the number of models
+/// regenerated.
+pub fn regenerate_models(
+    database_path: &str,
+    changed: &BTreeSet<(String, String)>,
+    commit: Option<String>,
+) -> Result<usize> {
+    let archive = HwdbArchive::new(workspace().hwdb_archive_dir());
+    let Some(previous_path) = archive.latest_file(MANIFEST_FILE)? else {
+        bail!(
+            "No archived merge run in {} to take the unchanged models from, run merge-results instead",
+            archive.root().display()
+        );
+    };
+    let previous: Manifest = serde_json::from_str(&fs::read_to_string(&previous_path)?)
+        .with_context(|| format!("Failed to parse {}", previous_path.display()))?;
+    let previous_dir = previous_path.parent().unwrap_or(archive.root());
+
+    // The hwdb entry of a model is picked among the merges of all its
+    // versions, not only of those changed.
+    let models: BTreeSet<&str> = changed.iter().map(|(_, m)| m.as_str()).collect();
+    let inputs_dir = workspace().hwdb_inputs_dir();
+    let mut manifest = Manifest::new(commit.clone());
+    let mut alternatives: BTreeMap<String, Vec<HwdbCandidate>> = BTreeMap::new();
+    for model in &models {
+        for merge in remerge_models(database_path, None, Some(model))? {
+            alternatives.entry(merge.model_name.clone()).or_default().push(merge.hwdb_candidate());
+        }
+    }
+    fs::create_dir_all(&inputs_dir)?;
+    for candidate in previous.candidates {
+        if models.contains(candidate.model_name.as_str()) {
+            continue;
+        }
+        let source = previous_dir.join(&candidate.hwdb_input);
+        fs::copy(&source, inputs_dir.join(&candidate.hwdb_input))
+            .with_context(|| format!("Failed to reuse archived hwdb input {}", source.display()))?;
+        alternatives.entry(candidate.model_name.clone()).or_default().push(candidate);
+    }
+    for candidate in alternatives.values().flatten() {
+        let path = inputs_dir.join(&candidate.hwdb_input);
+        if path.exists() {
+            let (model_name, version) = (&candidate.model_name, &candidate.version);
+            manifest.push(Artifact::new(ArtifactKind::HwdbInput, &path, Some(model_name), Some(version))?);
+        }
+    }
+
+    let vendor_overrides = VendorOverrides::load(Path::new(VENDOR_OVERRIDES_DIR))?;
+    let hwdb_path = Path::new(HWDB_FILE);
+    manifest.hwdb_fallbacks =
+        write_hwdb_file(hwdb_path, &inputs_dir, &alternatives, commit.clone(), &vendor_overrides)?;
+    manifest.candidates = alternatives.into_values().flatten().collect();
+    manifest.push(Artifact::new(ArtifactKind::Hwdb, hwdb_path, None, None)?);
+    manifest.write_to(Path::new(MANIFEST_FILE))?;
+    let mut archived: Vec<PathBuf> = manifest.artifacts.iter().map(|a| a.path.clone()).collect();
+    archived.push(PathBuf::from(MANIFEST_FILE));
+    let entry = archive.store(commit.as_deref(), &archived)?;
+    println!(
+        "Regenerated {} for {} changed models, archived in {}",
+        HWDB_FILE,
+        models.len(),
+        archive.root().join(&entry.dir).display()
+    );
+    Ok(models.len())
+}
@@ -0,0 +1,104 @@
+use anyhow::{Context, Result};
+use std::env;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::archive::HWDB_ARCHIVE_DIR;
+use crate::config::set_once;
+use crate::database::MERGED_RESULTS_DIR;
+
+/// Directory of the database when neither the command line nor the
+/// config file name one
+pub static DATABASE_DIR: &str = "database";
+
+/// Directory of the pdfs generated from the merged results
+pub static PDFS_DIR: &str = "pdfs";
+
+/// Directory of the hwdb entries of the merged results, before they are
+/// combined into the final hwdb file
+pub static HWDB_INPUTS_DIR: &str = "hwdb-inputs";
+
+/// Directory the retired trees of the legacy versions are moved to, out
+/// of the database
+pub static LEGACY_ARCHIVE_DIR: &str = "archive";
+
+/// Checkout of the database repository the tools work in, holding the
+/// database and the files generated from it.
+///
+/// The derived paths are relative to the root, as the git index and the
+/// generated files reference them that way: once the workspace is
+/// entered, the root is the current directory and they resolve in it.
+#[derive(Debug, Clone)]
+pub struct Workspace {
+    root: PathBuf,
+}
+
+impl Default for Workspace {
+    fn default() -> Self {
+        Workspace::new(".")
+    }
+}
+
+impl Workspace {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Workspace { root: root.into() }
+    }
+
+    /// Root of the workspace: the root of the database repository
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Returns the default directory of the database
+    pub fn database_dir(&self) -> PathBuf {
+        PathBuf::from(DATABASE_DIR)
+    }
+
+    pub fn merged_results_dir(&self) -> PathBuf {
+        PathBuf::from(MERGED_RESULTS_DIR)
+    }
+
+    pub fn pdfs_dir(&self) -> PathBuf {
+        PathBuf::from(PDFS_DIR)
+    }
+
+    pub fn hwdb_inputs_dir(&self) -> PathBuf {
+        PathBuf::from(HWDB_INPUTS_DIR)
+    }
+
+    /// Returns the directory of the archived hwdb outputs. Unlike the
+    /// other outputs, it is committed to the database repository, as
+    /// every run reads the previous ones.
+    pub fn hwdb_archive_dir(&self) -> PathBuf {
+        PathBuf::from(HWDB_ARCHIVE_DIR)
+    }
+
+    /// Opens the database repository of the workspace
+    pub fn repository(&self) -> Result<git2::Repository, git2::Error> {
+        git2::Repository::open(&self.root)
+    }
+
+    /// Enters the workspace at `root`, making it the current directory.
+    /// Paths relative to the former current directory, e.g. the input
+    /// files named on the command line, have to be made absolute first.
+    pub fn enter(root: &Path) -> Result<Self> {
+        let root = root
+            .canonicalize()
+            .with_context(|| format!("Failed to find the workspace {}", root.display()))?;
+        env::set_current_dir(&root).with_context(|| format!("Failed to enter the workspace {}", root.display()))?;
+        Ok(Workspace { root })
+    }
+}
+
+static WORKSPACE: OnceLock<Workspace> = OnceLock::new();
+
+/// Sets the workspace of the tools.
+pub fn set_workspace(workspace: Workspace) -> Result<()> {
+    set_once(&WORKSPACE, workspace, "workspace")
+}
+
+/// Returns the workspace of the tools, the current directory if none was
+/// set
+pub fn workspace() -> &'static Workspace {
+    WORKSPACE.get_or_init(Workspace::default)
+}
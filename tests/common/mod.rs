@@ -0,0 +1,6 @@
+use std::path::{Path, PathBuf};
+
+/// Returns the path of the test fixture `name`
+pub fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name)
+}
@@ -0,0 +1,59 @@
+mod common;
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use iocost_benchmarks::common::BenchMerge;
+use iocost_benchmarks::error::Error;
+
+use common::fixture;
+
+/// Writes the gzipped `contents` as a result file in `dir`
+fn write_result(dir: &Path, contents: &str) -> PathBuf {
+    let path = dir.join("result.json.gz");
+    let mut encoder = libflate::gzip::Encoder::new(vec![]).unwrap();
+    encoder.write_all(contents.as_bytes()).unwrap();
+    std::fs::write(&path, encoder.finish().into_result().unwrap()).unwrap();
+    path
+}
+
+#[test]
+fn counts_the_mof_data_points_of_2_1_results() {
+    assert_eq!(BenchMerge::get_data_points(&fixture("iocost-tune-2.1.json.gz")).unwrap(), 6);
+}
+
+#[test]
+fn counts_the_mof_data_points_of_2_2_results() {
+    assert_eq!(BenchMerge::get_data_points(&fixture("iocost-tune-2.2.json.gz")).unwrap(), 9);
+}
+
+#[test]
+fn counts_results_without_outliers() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_result(
+        dir.path(),
+        r#"[{"spec": {"kind": "iocost-tune"},
+            "result": {"data": {"MOF": {"data": [{"vrate": 50, "val": 0.8}]}}}}]"#,
+    );
+    assert_eq!(BenchMerge::get_data_points(&path).unwrap(), 1);
+}
+
+#[test]
+fn fails_without_an_iocost_tune_run() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_result(dir.path(), r#"[{"spec": {"kind": "storage"}, "result": {}}]"#);
+    assert!(matches!(BenchMerge::get_data_points(&path), Err(Error::MissingIocostTune { .. })));
+}
+
+#[test]
+fn fails_without_the_mof_series() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_result(
+        dir.path(),
+        r#"[{"spec": {"kind": "iocost-tune"}, "result": {"data": {"aMOF": {"data": [], "outliers": []}}}}]"#,
+    );
+    assert!(matches!(
+        BenchMerge::get_data_points(&path),
+        Err(Error::MissingDataSeries { series, .. }) if series == "MOF"
+    ));
+}
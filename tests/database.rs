@@ -0,0 +1,56 @@
+use std::path::Path;
+
+use iocost_benchmarks::database::{validate_name, validate_version, Database};
+use iocost_benchmarks::error::Error;
+
+/// Asserts that `result` is rejected as an invalid `kind` name
+fn assert_invalid<T: std::fmt::Debug>(result: Result<T, Error>, kind: &str) {
+    match result {
+        Err(Error::InvalidName { kind: k, .. }) => assert_eq!(k, kind),
+        other => panic!("Expected an invalid {} name, got {:?}", kind, other),
+    }
+}
+
+#[test]
+fn builds_paths_from_valid_names() {
+    let database = Database::new("database").with_merged_dir("merged");
+    assert_eq!(database.model_dir("2.2", "WDC_SN550").unwrap(), Path::new("database/2.2/WDC_SN550"));
+    assert_eq!(
+        database.merged_path("2.2", "WDC", Some("1.0.2+b")).unwrap(),
+        Path::new("merged/2.2-WDC-1.0.2+b-merged-results.json.gz")
+    );
+}
+
+#[test]
+fn rejects_names_escaping_the_database() {
+    let database = Database::new("database");
+    let names = [
+        "..",
+        "../WDC",
+        "../../etc",
+        ".hidden",
+        "/etc/passwd",
+        "WDC/../..",
+        "WDC/SN550",
+        "WDC\\SN550",
+        "",
+    ];
+    for model_name in names {
+        assert_invalid(validate_name("model", model_name), "model");
+        assert_invalid(database.model_dir("2.2", model_name), "model");
+        assert_invalid(database.merged_path("2.2", model_name, None), "model");
+    }
+    assert_invalid(database.merged_path("2.2", "WDC", Some("../fw")), "detail");
+    assert_invalid(database.merged_path("2.2", "WDC", Some("a/b")), "detail");
+    // Longer names than directory names can be
+    assert_invalid(validate_name("model", &"a".repeat(256)), "model");
+}
+
+#[test]
+fn rejects_versions_escaping_the_database() {
+    let database = Database::new("database");
+    for version in ["..", "../2.2", "/2.2", "2.2/..", "2", "2.", ".2", "2.2.1", "v2.2"] {
+        assert_invalid(validate_version(version), "version");
+        assert_invalid(database.model_dir(version, "WDC"), "version");
+    }
+}
@@ -0,0 +1,44 @@
+use std::fs;
+use std::path::Path;
+
+use chrono::{Duration, Utc};
+use iocost_benchmarks::digest::{DataPointGain, Digest};
+
+/// Adds a copy of the `fixture` result to `model_dir` as `name`,
+/// imported `days_ago`
+fn add_result(model_dir: &Path, fixture: &str, name: &str, days_ago: i64) {
+    let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(fixture);
+    fs::copy(fixture, model_dir.join(format!("{}.json.gz", name))).unwrap();
+    let metadata = serde_json::json!({
+        "model_name": model_dir.file_name().unwrap().to_str(),
+        "version": "2.2",
+        "issue": days_ago,
+        "url": null,
+        "submitter": format!("user{}", days_ago),
+        "imported_at": (Utc::now() - Duration::days(days_ago)).to_rfc3339(),
+        "processing_secs": null,
+    });
+    fs::write(model_dir.join(format!("{}.json.metadata", name)), metadata.to_string()).unwrap();
+}
+
+#[test]
+fn reports_the_data_points_gained() {
+    let database = tempfile::tempdir().unwrap();
+    let wdc = database.path().join("2.2/WDC");
+    let samsung = database.path().join("2.2/Samsung");
+    fs::create_dir_all(&wdc).unwrap();
+    fs::create_dir_all(&samsung).unwrap();
+    add_result(&wdc, "iocost-tune-2.2.json.gz", "old", 30);
+    add_result(&wdc, "iocost-tune-2.2.json.gz", "new", 2);
+    add_result(&wdc, "iocost-tune-2.1.json.gz", "newer", 1);
+    add_result(&samsung, "iocost-tune-2.2.json.gz", "old", 60);
+
+    let digest = Digest::new(database.path(), 7).unwrap();
+    let wdc = ("WDC".to_string(), "2.2".to_string());
+    assert_eq!(digest.new_results[&wdc], 2);
+    assert_eq!(digest.data_points[&wdc], DataPointGain { before: 9, gained: 15 });
+    // Models without new results aren't reported
+    assert_eq!(digest.data_points.len(), 1);
+    assert!(digest.to_markdown().contains("| WDC | 2.2 | 2 | +15 (24 in total) |"));
+    assert!(digest.to_html().contains("<td>+15 (24 in total)</td>"));
+}
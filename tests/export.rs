@@ -0,0 +1,37 @@
+use std::fs;
+use std::path::Path;
+
+use iocost_benchmarks::export::{export, ExportFormat, Granularity};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+/// Database with the 2.2 fixture as the only result of a model
+fn database() -> tempfile::TempDir {
+    let database = tempfile::tempdir().unwrap();
+    let model_dir = database.path().join("2.2/WDC");
+    fs::create_dir_all(&model_dir).unwrap();
+    let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/iocost-tune-2.2.json.gz");
+    fs::copy(fixture, model_dir.join("result.json.gz")).unwrap();
+    database
+}
+
+#[test]
+fn writes_parquet_and_csv() {
+    let database = database();
+    let output = tempfile::tempdir().unwrap();
+
+    let parquet = output.path().join("points.parquet");
+    assert_eq!(ExportFormat::from_path(&parquet), ExportFormat::Parquet);
+    let rows = export(database.path(), &parquet, ExportFormat::Parquet, Granularity::DataPoint).unwrap();
+    assert!(rows > 0);
+    let reader = ParquetRecordBatchReaderBuilder::try_new(fs::File::open(&parquet).unwrap()).unwrap();
+    let columns: Vec<String> = reader.schema().fields().iter().map(|f| f.name().clone()).collect();
+    assert_eq!(columns, ["version", "model_name", "file", "fwrev", "issue", "series", "vrate", "val", "outlier"]);
+    let read: usize = reader.build().unwrap().map(|batch| batch.unwrap().num_rows()).sum();
+    assert_eq!(read, rows);
+
+    let csv = output.path().join("results.csv");
+    assert_eq!(export(database.path(), &csv, ExportFormat::from_path(&csv), Granularity::Result).unwrap(), 1);
+    let contents = fs::read_to_string(&csv).unwrap();
+    assert!(contents.starts_with("version,model_name,file,"));
+    assert!(contents.lines().nth(1).unwrap().starts_with("2.2,WDC,"));
+}
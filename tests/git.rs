@@ -0,0 +1,42 @@
+use std::fs;
+use std::path::Path;
+
+use git2::{Repository, Signature};
+use iocost_benchmarks::git::{commit_to_branch, reset_to_head};
+
+/// Stages `file`, with its name as contents, in the index of `repo`
+fn stage(repo: &Repository, file: &str) -> git2::Index {
+    fs::create_dir_all(Path::new(file).parent().unwrap()).unwrap();
+    fs::write(file, file).unwrap();
+    let mut index = repo.index().unwrap();
+    index.add_path(Path::new(file)).unwrap();
+    index
+}
+
+#[test]
+fn branches_of_a_long_lived_checkout_start_from_head() {
+    let dir = tempfile::tempdir().unwrap();
+    std::env::set_current_dir(dir.path()).unwrap();
+    let repo = Repository::init(".").unwrap();
+    let mut index = stage(&repo, "database/README");
+    let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+    let sig = Signature::now("test", "test@example.com").unwrap();
+    let head = repo.commit(Some("HEAD"), &sig, &sig, "Initial commit", &tree, &[]).unwrap();
+
+    let mut index = stage(&repo, "database/2.2/WDC/a.json.gz");
+    commit_to_branch(&repo, &mut index, "Issue 1", "iocost-bot/1").unwrap();
+    assert_eq!(repo.head().unwrap().peel_to_commit().unwrap().id(), head);
+
+    reset_to_head(&repo, Path::new("database")).unwrap();
+    assert!(!Path::new("database/2.2/WDC/a.json.gz").exists());
+    assert!(Path::new("database/README").exists());
+    assert!(repo.index().unwrap().get_path(Path::new("database/2.2/WDC/a.json.gz"), 0).is_none());
+
+    let mut index = stage(&repo, "database/2.2/WDC/b.json.gz");
+    let commit = commit_to_branch(&repo, &mut index, "Issue 2", "iocost-bot/2").unwrap();
+    let commit = repo.find_commit(commit).unwrap();
+    assert_eq!(commit.parent_ids().collect::<Vec<_>>(), [head]);
+    // The branch of the second issue doesn't have the results of the first
+    assert!(commit.tree().unwrap().get_path(Path::new("database/2.2/WDC/a.json.gz")).is_err());
+    assert!(commit.tree().unwrap().get_path(Path::new("database/2.2/WDC/b.json.gz")).is_ok());
+}
@@ -0,0 +1,195 @@
+//! Renders the hwdb file, the pull request descriptions and the markdown
+//! summaries from fixed inputs and compares them with the golden files
+//! in tests/golden. After an intended change of the outputs, regenerate
+//! the golden files with:
+//!
+//! ```text
+//! UPDATE_GOLDEN=1 cargo test --test golden
+//! ```
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use iocost_benchmarks::high_level::HighLevelSummary;
+use iocost_benchmarks::hwdb::{diff_sections, write_hwdb_file, HwdbCandidate, SignedResults, SupersededFirmware};
+use iocost_benchmarks::templates::{ModelSummary, SubmissionReport};
+use iocost_benchmarks::vendor_overrides::VendorOverrides;
+use iocost_benchmarks::version_compare::{VersionComparison, VersionReport};
+use iocost_benchmarks::workflow::{import_description, ImportedModel};
+
+const SAMSUNG_HIGH_LEVEL: &str = "\
+[naive]
+  info: isol=90.00% lat_imp=10.00% work_csv=70.00% missing=0.00%
+  model: rbps=1130000000 rseqiops=213000 rrandiops=193000 wbps=930000000 wseqiops=163000 wrandiops=133000
+  qos: rpct=95.00 rlat=1130 wpct=95.00 wlat=2130 min=63.00 max=63.00
+[isolated-bandwidth]
+  info: isol=91.02% lat_imp=13.57% work_csv=71.27% missing=0.20%
+  model: rbps=1130000000 rseqiops=213000 rrandiops=193000 wbps=930000000 wseqiops=163000 wrandiops=133000
+  qos: rpct=95.00 rlat=1234 wpct=95.00 wlat=2345 min=64.80 max=64.80
+";
+
+fn manifest_dir() -> &'static Path {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+}
+
+/// Compares `actual` with the golden file `name`, or overwrites the
+/// golden file with it when UPDATE_GOLDEN is set
+fn assert_golden(name: &str, actual: &str) {
+    let path = manifest_dir().join("tests/golden").join(name);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::write(&path, actual).unwrap();
+        return;
+    }
+    let golden = std::fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("Failed to read {}, run with UPDATE_GOLDEN=1: {}", path.display(), e));
+    assert!(
+        golden == actual,
+        "{} differs from the golden file, run with UPDATE_GOLDEN=1 if intended:\n{}",
+        name,
+        diff_sections(&golden, actual).unwrap_or_default()
+    );
+}
+
+fn candidate(model_name: &str, data_points: usize, hwdb_input: &str) -> HwdbCandidate {
+    HwdbCandidate {
+        model_name: model_name.to_string(),
+        version: "2.2".to_string(),
+        data_points,
+        hwdb_input: PathBuf::from(hwdb_input),
+        superseded: None,
+        signed: None,
+    }
+}
+
+#[test]
+fn hwdb_file() {
+    let mut samsung = candidate("SAMSUNG_X", 13, "iocost-tune-2.2-SAMSUNG_X.hwdb");
+    samsung.superseded = Some(SupersededFirmware {
+        model_name: "SAMSUNG_X".to_string(),
+        version: "2.2".to_string(),
+        fwrev: "FW2".to_string(),
+        superseded: vec!["FW1".to_string()],
+    });
+    samsung.signed = Some(SignedResults {
+        model_name: "SAMSUNG_X".to_string(),
+        version: "2.2".to_string(),
+        results: 3,
+        signed_by: BTreeMap::from([("Samsung".to_string(), 2)]),
+    });
+    let alternatives = BTreeMap::from([
+        ("SAMSUNG_X".to_string(), vec![samsung]),
+        (
+            "WDC_Y".to_string(),
+            vec![
+                candidate("WDC_Y", 6, "iocost-tune-2.2-WDC_Y.hwdb"),
+                // The best candidate has an empty input, the next one is
+                // used
+                candidate("WDC_Y", 9, "iocost-tune-2.2-WDC_Y-latest.hwdb"),
+            ],
+        ),
+    ]);
+    // Relative, as the paths of the overrides are listed in the header
+    let vendor_overrides = VendorOverrides::load(Path::new("tests/fixtures/golden/vendor-overrides")).unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("90-iocost-tune.hwdb");
+    let fallbacks = write_hwdb_file(
+        &path,
+        &manifest_dir().join("tests/fixtures/golden/hwdb-inputs"),
+        &alternatives,
+        Some("0123456789abcdef0123456789abcdef01234567".to_string()),
+        &vendor_overrides,
+    )
+    .unwrap();
+    assert_eq!(fallbacks.len(), 1);
+    let hwdb: String = std::fs::read_to_string(&path)
+        .unwrap()
+        .lines()
+        .filter(|l| !l.starts_with("# This file was auto-generated on"))
+        .map(|l| format!("{}\n", l))
+        .collect();
+    assert_golden("hwdb-file.hwdb", &hwdb);
+}
+
+fn imported(model_name: &str, version: &str, new_files: u64, summary: &str) -> ImportedModel {
+    ImportedModel {
+        model_name: model_name.to_string(),
+        version: version.to_string(),
+        new_files,
+        signed_by: BTreeMap::new(),
+        summary: summary.to_string(),
+    }
+}
+
+#[test]
+fn import_description_of_several_versions() {
+    let already_merged = [(
+        "https://example.com/result-1.json.gz".to_string(),
+        PathBuf::from("database/2.2/SAMSUNG_X/result-0f197d55808207ff423360a835e158d6.json.gz"),
+    )];
+    let mut samsung = imported("SAMSUNG_X", "2.2", 3, &format!("{}MOF: 13 data points\n", SAMSUNG_HIGH_LEVEL));
+    samsung.signed_by.insert("Samsung".to_string(), 2);
+    let models = vec![
+        imported("WDC_Y", "2.2", 1, "MOF: 6 data points\n"),
+        samsung,
+        imported("WDC_Y", "2.1", 2, "MOF: 5 data points\n"),
+    ];
+    assert_golden(
+        "import-description.md",
+        &import_description(42, false, &already_merged, models),
+    );
+}
+
+#[test]
+fn import_description_pending_review() {
+    let models = vec![imported("SAMSUNG_X", "2.2", 2, ""), imported("WDC_Y", "2.2", 1, "")];
+    assert_golden("import-description-pending.md", &import_description(43, true, &[], models));
+}
+
+#[test]
+fn submission_report() {
+    let summary = HighLevelSummary::parse(SAMSUNG_HIGH_LEVEL).unwrap();
+    let report = SubmissionReport::new(
+        vec![
+            ModelSummary {
+                model_name: "SAMSUNG_X".to_string(),
+                version: "2.2".to_string(),
+                new_files: 3,
+                high_level: SAMSUNG_HIGH_LEVEL.to_string(),
+                high_level_table: Some(summary.to_markdown()),
+            },
+            ModelSummary {
+                model_name: "WDC_Y".to_string(),
+                version: "2.1".to_string(),
+                new_files: 1,
+                high_level: String::new(),
+                high_level_table: None,
+            },
+        ],
+        vec![],
+    );
+    assert_golden("submission-report.md", &report.render().unwrap());
+}
+
+#[test]
+fn high_level_table() {
+    let summary = HighLevelSummary::parse(SAMSUNG_HIGH_LEVEL).unwrap();
+    assert_golden("high-level-table.md", &summary.to_markdown());
+}
+
+#[test]
+fn version_report() {
+    let current = HighLevelSummary::parse(SAMSUNG_HIGH_LEVEL).unwrap();
+    let legacy_high_level = SAMSUNG_HIGH_LEVEL.replace("rbps=1130000000", "rbps=800000000");
+    let legacy = HighLevelSummary::parse(&legacy_high_level).unwrap();
+    let report = VersionReport {
+        comparisons: vec![VersionComparison {
+            model_name: "SAMSUNG_X".to_string(),
+            legacy_version: "2.1".to_string(),
+            version: "2.2".to_string(),
+            legacy,
+            current,
+        }],
+        failures: vec![("WDC_Y".to_string(), "no solutions in the 2.1 results".to_string())],
+    };
+    assert_golden("version-report.md", &report.to_markdown());
+}
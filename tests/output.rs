@@ -0,0 +1,34 @@
+use iocost_benchmarks::output::to_yaml;
+use serde_json::{json, Value};
+
+#[test]
+fn yaml_reads_back_as_the_records() {
+    let records = json!([
+        {
+            "yes": "no",
+            "On": "off",
+            "null": "~",
+            "true": "false",
+            "123": "007",
+            "1.5": "1e3",
+            "0x1F": ".inf",
+            "": "",
+            "key: value": "- item",
+            "model": "WDC SN550",
+            "_count": 3,
+            "ratio": 0.5,
+            "enabled": true,
+            "empty": [],
+            "nested": {"#": "# comment", "list": [1, "2", null, {"a": "b"}]},
+        },
+        "multi\nline",
+    ]);
+    let yaml = to_yaml(&records);
+    assert!(yaml.starts_with("---\n"), "{}", yaml);
+    let read: Value = serde_yaml::from_str(&yaml).unwrap();
+    assert_eq!(read, records, "{}", yaml);
+    // Parsers following YAML 1.1 read unquoted yes and on as booleans
+    assert!(yaml.contains("\"yes\": \"no\"\n"), "{}", yaml);
+    assert!(yaml.contains("\"On\": \"off\"\n"), "{}", yaml);
+    assert!(yaml.contains("  model: \"WDC SN550\"\n"), "{}", yaml);
+}
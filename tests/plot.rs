@@ -0,0 +1,36 @@
+use std::fs;
+
+use iocost_benchmarks::plot::{render, PlotSeries};
+
+#[test]
+fn renders_charts_in_the_format_of_their_file() {
+    let series = [
+        PlotSeries {
+            label: "WDC_SN550 211070WD".to_string(),
+            points: vec![(10.0, 0.2), (50.0, 0.8), (90.0, 0.95)],
+            outliers: vec![(70.0, 0.1)],
+            solutions: vec![("naive".to_string(), 60.0), ("isolation".to_string(), 45.0)],
+        },
+        PlotSeries {
+            label: "WDC_SN550 211210WD".to_string(),
+            points: vec![(20.0, 0.3), (80.0, 0.9)],
+            outliers: vec![],
+            solutions: vec![],
+        },
+    ];
+    let dir = tempfile::tempdir().unwrap();
+
+    let svg = dir.path().join("chart.svg");
+    render(&svg, "MOF vs vrate", "MOF", &series).unwrap();
+    let svg = fs::read_to_string(svg).unwrap();
+    assert!(svg.starts_with("<svg"), "{}", svg);
+    for text in ["MOF vs vrate", "vrate (%)", "WDC_SN550 211070WD", "WDC_SN550 211210WD", "isolation"] {
+        assert!(svg.contains(text), "No {} in {}", text, svg);
+    }
+
+    let png = dir.path().join("chart.png");
+    render(&png, "MOF vs vrate", "MOF", &series).unwrap();
+    assert!(fs::read(png).unwrap().starts_with(b"\x89PNG\r\n\x1a\n"));
+
+    assert!(render(&dir.path().join("chart.pdf"), "MOF vs vrate", "MOF", &series).is_err());
+}
@@ -0,0 +1,45 @@
+use iocost_benchmarks::consent::{set_consent_settings, ConsentSettings};
+use iocost_benchmarks::policy::{check_statement, Refusal, Statement, Verdict};
+use iocost_benchmarks::signoff::{set_signoff_settings, SignOffError, SignOffSettings};
+
+/// Checks the statement `body` of the submission of `submitter` to the
+/// issue of `author`
+fn check(body: &str, author: &str, submitter: &str) -> Verdict {
+    check_statement(&Statement { bodies: &[body], author, submitter }).unwrap()
+}
+
+#[test]
+fn refuses_submissions_against_the_policies() {
+    set_consent_settings(ConsentSettings {
+        license: "CDLA-Permissive-2.0".to_string(),
+        license_url: None,
+        phrase: "I agree".to_string(),
+    })
+    .unwrap();
+    set_signoff_settings(SignOffSettings { required: true }).unwrap();
+    // Settings already set aren't silently kept
+    assert!(set_signoff_settings(SignOffSettings { required: false }).is_err());
+
+    let verdict = check("Signed-off-by: octocat <octocat@example.com>", "octocat", "octocat");
+    assert!(matches!(verdict, Verdict::Refused(Refusal::LicenseRequired(_))), "{:?}", verdict);
+    let verdict = check("I agree", "octocat", "octocat");
+    assert!(matches!(verdict, Verdict::Refused(Refusal::SignOffRequired(SignOffError::Missing))), "{:?}", verdict);
+    let body = "I agree\nSigned-off-by: octocat <octocat@example.com>";
+    let verdict = check(body, "octocat", "hubot");
+    assert!(
+        matches!(verdict, Verdict::Refused(Refusal::SignOffRequired(SignOffError::NotAuthor { .. }))),
+        "{:?}",
+        verdict
+    );
+    // Without an embargo staging area, embargoed results can't be held
+    let verdict = check(&format!("{}\nembargo-until: 2999-01-01", body), "octocat", "octocat");
+    assert!(matches!(verdict, Verdict::Refused(Refusal::Embargoed(_))), "{:?}", verdict);
+
+    match check(body, "octocat", "octocat") {
+        Verdict::Accepted { signoff, embargo } => {
+            assert_eq!(signoff.email, "octocat@example.com");
+            assert_eq!(embargo, None);
+        }
+        verdict => panic!("Expected the submission to be accepted, got {:?}", verdict),
+    }
+}
@@ -0,0 +1,16 @@
+use std::collections::BTreeSet;
+use std::path::Path;
+
+use iocost_benchmarks::hwdb::HWDB_FILE;
+use iocost_benchmarks::workflow::regenerate_models;
+
+#[test]
+fn refuses_to_regenerate_without_an_archived_run() {
+    let dir = tempfile::tempdir().unwrap();
+    std::env::set_current_dir(dir.path()).unwrap();
+    let changed = BTreeSet::from([("2.2".to_string(), "WDC".to_string())]);
+    let error = regenerate_models("database", &changed, None).unwrap_err();
+    assert!(error.to_string().contains("No archived merge run"), "{:#}", error);
+    // No hwdb file with only the changed models is written
+    assert!(!Path::new(HWDB_FILE).exists());
+}
@@ -0,0 +1,43 @@
+use std::io::Write;
+use std::path::Path;
+
+use iocost_benchmarks::result::check_result_contents;
+
+fn gzip(contents: &[u8]) -> Vec<u8> {
+    let mut encoder = libflate::gzip::Encoder::new(vec![]).unwrap();
+    encoder.write_all(contents).unwrap();
+    encoder.finish().into_result().unwrap()
+}
+
+fn error(contents: &[u8], content_type: Option<&str>) -> String {
+    check_result_contents(contents, content_type).unwrap_err().to_string()
+}
+
+#[test]
+fn accepts_gzipped_json() {
+    let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/self-test/wdc-y.json.gz");
+    let contents = std::fs::read(fixture).unwrap();
+    check_result_contents(&contents, Some("application/gzip")).unwrap();
+    check_result_contents(&contents, Some("binary/octet-stream")).unwrap();
+    check_result_contents(&gzip(b"\n [{}]"), None).unwrap();
+}
+
+#[test]
+fn refuses_web_pages() {
+    let page = b"<!DOCTYPE html>\n<html><body>Sign in to continue</body></html>";
+    assert!(error(page, Some("text/html; charset=utf-8")).contains("web page (text/html; charset=utf-8)"));
+    // Whatever the content type the host claims
+    assert!(error(page, Some("application/octet-stream")).contains("web page"));
+    assert!(error(&gzip(b"[]"), Some("TEXT/HTML")).contains("web page"));
+}
+
+#[test]
+fn refuses_other_contents() {
+    assert!(error(b"{\"results\": []}", Some("application/json")).contains("isn't gzipped"));
+    assert!(error(b"", None).contains("isn't gzipped"));
+    let truncated = gzip(&b"[{\"spec\": {}}]".repeat(100));
+    assert!(error(&truncated[..truncated.len() / 2], None).contains("corrupted or truncated"));
+    assert!(error(&gzip(b"Access Denied"), None).contains("doesn't hold json"));
+    // A file of zeros compresses over a thousand times
+    assert!(error(&gzip(&vec![0; 1 << 20]), None).contains("over 200 times its size"));
+}
@@ -0,0 +1,90 @@
+use std::fs;
+use std::path::Path;
+
+use git2::{BranchType, Repository, Signature};
+use iocost_benchmarks::bench_runner::{set_bench_runner, MockBench};
+use iocost_benchmarks::serve::{router, ServeConfig, SubmitConfig};
+use reqwest::StatusCode;
+
+/// Serves a database with submissions accepted with the "secret" token
+/// and returns the URL of the submit endpoint
+async fn submit_url() -> String {
+    let config = ServeConfig {
+        database_dir: "database".into(),
+        hwdb_file: "hwdb".into(),
+        submit: Some(SubmitConfig {
+            token: "secret".to_string(),
+            github_token: None,
+            repo: "iocost-benchmark/iocost-benchmarks".to_string(),
+            base_branch: "main".to_string(),
+        }),
+    };
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, router(config)).await.unwrap() });
+    format!("http://{}/submit", address)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn submissions_need_the_token() {
+    let url = format!("{}?submitter=octocat", submit_url().await);
+    let client = reqwest::Client::new();
+
+    let response = client.post(&url).body("not a result").send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = client.post(&url).bearer_auth("secreT").body("not a result").send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let response = client.post(&url).bearer_auth("secret2").body("not a result").send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let response = client.post(&url).header("Authorization", "secret").body("not a result").send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    // With the token the upload is validated, and rejected before
+    // anything is done with the database
+    let response = client.post(&url).bearer_auth("secret").body("not a result").send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn uploads_are_committed_to_the_database() {
+    let dir = tempfile::tempdir().unwrap();
+    std::env::set_current_dir(dir.path()).unwrap();
+    set_bench_runner(Box::new(MockBench)).unwrap();
+    let repo = Repository::init(".").unwrap();
+    let tree = repo.find_tree(repo.index().unwrap().write_tree().unwrap()).unwrap();
+    let sig = Signature::now("test", "test@example.com").unwrap();
+    repo.commit(Some("HEAD"), &sig, &sig, "Initial commit", &tree, &[]).unwrap();
+
+    let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/iocost-tune-2.2.json.gz");
+    let contents = fs::read(fixture).unwrap();
+    let url = submit_url().await;
+    let client = reqwest::Client::new();
+    let response = client.post(&url).bearer_auth("secret").body(contents.clone()).send().await.unwrap();
+    // Uploads need a submitter to record
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let url = format!("{}?submitter=octocat", url);
+    let response = client.post(&url).bearer_auth("secret").body(contents.clone()).send().await.unwrap();
+    let status = response.status();
+    let body = response.text().await.unwrap();
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let response: serde_json::Value = serde_json::from_str(&body).unwrap();
+    let md5 = format!("{:x}", md5::compute(&contents));
+    assert_eq!(response["branch"], format!("iocost-bot/upload-{}", md5));
+    assert_eq!(response["version"], "2.2");
+
+    let model_dir = Path::new("database/2.2").join(response["model_name"].as_str().unwrap());
+    let result = model_dir.join(format!("result-{}.json.gz", md5));
+    assert!(result.exists());
+    let metadata = fs::read_to_string(model_dir.join(format!("result-{}.json.metadata", md5))).unwrap();
+    let metadata: serde_json::Value = serde_json::from_str(&metadata).unwrap();
+    assert_eq!(metadata["submitter"], "octocat");
+    // Only staged files are left behind
+    assert!(!Path::new(&format!("result-{}.json.gz", md5)).exists());
+
+    let branch = repo.find_branch(&format!("iocost-bot/upload-{}", md5), BranchType::Local).unwrap();
+    let commit = branch.get().peel_to_commit().unwrap();
+    assert!(commit.tree().unwrap().get_path(&result).is_ok());
+    assert!(commit.message().unwrap().ends_with("Signed-off-by: octocat <octocat@users.noreply.github.com>"));
+}
@@ -0,0 +1,62 @@
+mod common;
+
+use iocost_benchmarks::signatures::{is_signature_url, signature_link, SignatureSettings, VendorKey};
+
+use common::fixture;
+
+/// Settings with the keys of the `vendors`, named after their key files,
+/// or None when gpg isn't installed
+fn keys_of(vendors: &[&str]) -> Option<SignatureSettings> {
+    if std::process::Command::new("gpg").arg("--version").output().is_err() {
+        eprintln!("gpg not found, skipping");
+        return None;
+    }
+    Some(SignatureSettings {
+        gpg: "gpg".to_string(),
+        keys: vendors
+            .iter()
+            .map(|v| VendorKey {
+                vendor: v.to_string(),
+                public_key: fixture(&format!("signatures/{}.asc", v)),
+            })
+            .collect(),
+    })
+}
+
+#[test]
+fn verifies_the_signatures_of_the_vendors() {
+    let Some(settings) = keys_of(&["other", "vendor"]) else {
+        return;
+    };
+    let result = fixture("self-test/wdc-y.json.gz");
+    let armored = fixture("signatures/wdc-y.json.gz.asc");
+    let binary = fixture("signatures/wdc-y.json.gz.sig");
+    assert_eq!(settings.verify(&result, &armored).unwrap(), "vendor");
+    assert_eq!(settings.verify(&result, &binary).unwrap(), "other");
+
+    // Signatures of other keys and of other contents are refused
+    let Some(settings) = keys_of(&["vendor"]) else {
+        return;
+    };
+    assert!(settings.verify(&result, &binary).is_err());
+    assert!(settings.verify(&fixture("self-test/wdc-y-2.1.json.gz"), &armored).is_err());
+}
+
+#[test]
+fn pairs_the_signatures_with_the_results() {
+    let signatures = vec![
+        "https://github.com/user-attachments/files/2/a.json.gz.ASC".to_string(),
+        "https://example.com/b.json.gz.sig?X-Amz-Signature=abc".to_string(),
+    ];
+    assert!(signatures.iter().all(|s| is_signature_url(s)));
+    assert!(!is_signature_url("https://example.com/a.json.gz"));
+    assert_eq!(
+        signature_link("https://github.com/user-attachments/files/1/a.json.gz", &signatures),
+        Some(signatures[0].as_str())
+    );
+    assert_eq!(
+        signature_link("https://example.com/results/b.json.gz", &signatures),
+        Some(signatures[1].as_str())
+    );
+    assert_eq!(signature_link("https://example.com/c.json.gz", &signatures), None);
+}
@@ -0,0 +1,22 @@
+use iocost_benchmarks::storage::{IndexChange, ObjectIndex};
+
+#[test]
+fn index_keeps_the_changes_of_the_files() {
+    let mut index = ObjectIndex::load(None).unwrap();
+    index
+        .apply(&[
+            IndexChange::Added("2.2/WDC/result-b.json.gz".to_string()),
+            IndexChange::Added("2.2/WDC/result-a.json.gz".to_string()),
+            IndexChange::Added("2.2/WDC/result-a.json.gz".to_string()),
+            IndexChange::Removed("2.2/WDC/result-b.json.gz".to_string()),
+            IndexChange::Removed("2.2/WDC/result-c.json.gz".to_string()),
+        ])
+        .unwrap();
+    assert_eq!(index.keys().unwrap(), ["2.2/WDC/result-a.json.gz"]);
+
+    // Saved indexes are loaded back with their keys
+    let mut index = ObjectIndex::load(Some(&index.contents().unwrap())).unwrap();
+    index.apply(&[IndexChange::Added("2.1/WDC/result-a.json.gz".to_string())]).unwrap();
+    assert_eq!(index.keys().unwrap(), ["2.1/WDC/result-a.json.gz", "2.2/WDC/result-a.json.gz"]);
+    assert!(ObjectIndex::load(Some(b"not an index")).is_err());
+}
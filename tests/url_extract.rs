@@ -0,0 +1,217 @@
+use iocost_benchmarks::config::UrlsConfig;
+use iocost_benchmarks::url_extract::{clean_url, GithubAttachment, UrlExtractor};
+
+const S3: &str = "https://iocost-submit-us-east-1.s3.us-east-1.amazonaws.com";
+
+fn result_links(extractor: &UrlExtractor, body: &str) -> Vec<String> {
+    extractor
+        .links(body)
+        .into_iter()
+        .filter(|l| extractor.is_result_url(l))
+        .collect()
+}
+
+#[test]
+fn extracts_links_wrapped_in_markdown() {
+    let body = format!(
+        "Here are my results:\n\
+         - [first run]({S3}/a.json.gz)\n\
+         - [{S3}/b.json.gz]({S3}/b.json.gz)\n\
+         - <{S3}/c.json.gz>\n\
+         - `{S3}/d.json.gz`\n\
+         - **{S3}/e.json.gz**\n\
+         - _{S3}/f.json.gz_\n\
+         | SSD | {S3}/g.json.gz |\n"
+    );
+    let links = result_links(&UrlExtractor::default(), &body);
+    let expected: Vec<String> = "abcdefg".chars().map(|c| format!("{S3}/{c}.json.gz")).collect();
+    assert_eq!(links, expected);
+}
+
+#[test]
+fn strips_trailing_punctuation() {
+    let body = format!(
+        "The results are at {S3}/a.json.gz. Also {S3}/b.json.gz, and {S3}/c.json.gz! \
+         (the older one is {S3}/d.json.gz). Last: \"{S3}/e.json.gz\";"
+    );
+    let links = result_links(&UrlExtractor::default(), &body);
+    let expected: Vec<String> = "abcde".chars().map(|c| format!("{S3}/{c}.json.gz")).collect();
+    assert_eq!(links, expected);
+}
+
+#[test]
+fn keeps_the_query_of_signed_urls() {
+    let signed = format!(
+        "{S3}/a.json.gz?X-Amz-Algorithm=AWS4-HMAC-SHA256&X-Amz-Credential=AKIA%2F20240101%2Fus-east-1\
+         &X-Amz-Expires=3600&utm_source=mail&X-Amz-Signature=abcdef0123456789"
+    );
+    let body = format!("Pre-signed link: [download]({})", signed.replace('&', "&amp;"));
+    assert_eq!(result_links(&UrlExtractor::default(), &body), vec![signed]);
+}
+
+#[test]
+fn strips_tracking_parameters_and_fragments() {
+    assert_eq!(
+        clean_url(&format!("{S3}/a.json.gz?utm_source=newsletter&utm_medium=email#results")).unwrap(),
+        format!("{S3}/a.json.gz")
+    );
+    assert_eq!(
+        clean_url("https://github.com/user/repo/raw/main/a.json.gz?raw=true&fbclid=IwAR0").unwrap(),
+        "https://github.com/user/repo/raw/main/a.json.gz?raw=true"
+    );
+}
+
+#[test]
+fn ignores_other_schemes_and_duplicates() {
+    let body = format!(
+        "ftp://example.com/a.json.gz file:///tmp/a.json.gz mailto:me@example.com\n\
+         {S3}/a.json.gz and again {S3}/a.json.gz#top"
+    );
+    assert_eq!(UrlExtractor::default().links(&body), vec![format!("{S3}/a.json.gz")]);
+}
+
+#[test]
+fn accepts_the_configured_extensions() {
+    let extractor = UrlExtractor::from_config(&UrlsConfig {
+        extensions: Some(vec![".json.gz".to_string(), ".JSON.GZIP".to_string()]),
+    })
+    .unwrap();
+    let body = format!("{S3}/a.json.gz {S3}/b.json.gzip {S3}/c.JSON.GZ {S3}/d.json {S3}/e.tar.gz");
+    assert_eq!(
+        result_links(&extractor, &body),
+        vec![format!("{S3}/a.json.gz"), format!("{S3}/b.json.gzip"), format!("{S3}/c.JSON.GZ")]
+    );
+    let invalid = UrlsConfig { extensions: Some(vec!["json.gz".to_string()]) };
+    assert!(UrlExtractor::from_config(&invalid).is_err());
+    assert!(UrlExtractor::from_config(&UrlsConfig { extensions: Some(vec![]) }).is_err());
+}
+
+#[test]
+fn survives_arbitrary_bodies() {
+    // Bodies mixing URL fragments with markdown and unicode, from a
+    // fixed-seed generator so that failures can be reproduced
+    let pieces = [
+        "https://", "http://", S3, "/", ".json.gz", "?", "&amp;", "&", "=", "#", "(", ")", "[", "]", "<", ">",
+        "`", "*", "_", ".", ",", " ", "\n", "%", "%2", "é", "日本", "\u{200b}", "::", "@", "utm_x=1",
+    ];
+    let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+    let extractor = UrlExtractor::default();
+    for _ in 0..2000 {
+        let mut body = String::new();
+        for _ in 0..40 {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            body.push_str(pieces[(state % pieces.len() as u64) as usize]);
+        }
+        for link in extractor.links(&body) {
+            let url = reqwest::Url::parse(&link).unwrap();
+            assert!(matches!(url.scheme(), "http" | "https"), "{} from {:?}", link, body);
+            // Cleaning is idempotent
+            assert_eq!(clean_url(&link).as_deref(), Some(link.as_str()), "from {:?}", body);
+        }
+    }
+}
+
+#[test]
+fn ignores_links_in_code_blocks() {
+    let body = format!(
+        "Results: {S3}/a.json.gz\n\
+         \n\
+         ```\n\
+         $ curl -O {S3}/log.json.gz\n\
+         ```\n\
+         ~~~~ text\n\
+         {S3}/tilde.json.gz\n\
+         ```\n\
+         still code {S3}/fence.json.gz\n\
+         ~~~~\n\
+         \n\
+         \x20   {S3}/indented.json.gz\n\
+         \n\
+         And {S3}/b.json.gz\n\
+         <!-- Paste the links, e.g. {S3}/example.json.gz -->\n"
+    );
+    assert_eq!(
+        result_links(&UrlExtractor::default(), &body),
+        vec![format!("{S3}/a.json.gz"), format!("{S3}/b.json.gz")]
+    );
+}
+
+#[test]
+fn finds_links_in_lists_and_tables() {
+    let body = format!(
+        "1. Samsung:\n\
+         \n\
+         \x20   {S3}/a.json.gz\n\
+         2. WDC: {S3}/b.json.gz\n\
+         \n\
+         | Drive | Result |\n\
+         |---|---|\n\
+         | WDC | [result]({S3}/c.json.gz) |\n\
+         | Samsung | {S3}/d.json.gz|\n"
+    );
+    let links = result_links(&UrlExtractor::default(), &body);
+    let expected: Vec<String> = "abcd".chars().map(|c| format!("{S3}/{c}.json.gz")).collect();
+    assert_eq!(links, expected);
+}
+
+#[test]
+fn finds_html_attachments() {
+    let body = "Uploaded with the new uploader:\n\
+        <a href=\"https://github.com/user-attachments/files/1/result.json.gz\">result.json.gz</a>\n\
+        <img width=\"600\" alt=\"graph\" src=\"https://github.com/user-attachments/assets/2/graph.png\" />\n\
+        <A HREF='https://github.com/user-attachments/files/3/other.json.gz?x=1&amp;y=2'>other</A>";
+    assert_eq!(
+        UrlExtractor::default().links(body),
+        vec![
+            "https://github.com/user-attachments/files/1/result.json.gz",
+            "https://github.com/user-attachments/assets/2/graph.png",
+            "https://github.com/user-attachments/files/3/other.json.gz?x=1&y=2",
+        ]
+    );
+}
+
+#[test]
+fn recognizes_github_attachments() {
+    const REPO: &str = "iocost-benchmark/iocost-benchmarks";
+    let user = "https://github.com/user-attachments/files/17480061/result.json.gz";
+    assert_eq!(GithubAttachment::parse(user), Some(GithubAttachment::User));
+    assert_eq!(GithubAttachment::parse(user).unwrap().mismatch(REPO, Some(1)), None);
+
+    let legacy = GithubAttachment::parse("https://github.com/Iocost-Benchmark/iocost-benchmarks/files/1/a.gz");
+    assert_eq!(legacy, Some(GithubAttachment::Repository("Iocost-Benchmark/iocost-benchmarks".to_string())));
+    assert_eq!(legacy.as_ref().unwrap().mismatch(REPO, None), None);
+    assert!(legacy.unwrap().mismatch("someone/fork", None).is_some());
+
+    let redirected = GithubAttachment::parse(
+        "https://objects.githubusercontent.com/github-production-repository-file-5c1aeb/4242/123\
+         ?X-Amz-Algorithm=AWS4-HMAC-SHA256&X-Amz-Signature=abc",
+    )
+    .unwrap();
+    assert_eq!(redirected, GithubAttachment::RepositoryId(4242));
+    assert_eq!(redirected.mismatch(REPO, Some(4242)), None);
+    assert_eq!(redirected.mismatch(REPO, None), None);
+    assert!(redirected.mismatch(REPO, Some(1)).is_some());
+
+    assert_eq!(GithubAttachment::parse("https://github.com/user/repo/raw/main/a.json.gz"), None);
+    assert_eq!(GithubAttachment::parse(&format!("{S3}/a.json.gz")), None);
+}
+
+#[test]
+fn follows_the_markdown_structure() {
+    let body = format!(
+        "See [the first run][1] and ![graph]({S3}/b.json.gz \"title\").\n\
+         \n\
+         > Quoted: {S3}/c.json.gz\n\
+         \n\
+         <!--\n\
+         <a href=\"{S3}/commented.json.gz\">example</a>\n\
+         -->\n\
+         \n\
+         [1]: {S3}/a.json.gz\n"
+    );
+    let links = result_links(&UrlExtractor::default(), &body);
+    let expected: Vec<String> = "abc".chars().map(|c| format!("{S3}/{c}.json.gz")).collect();
+    assert_eq!(links, expected);
+}
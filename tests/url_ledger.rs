@@ -0,0 +1,100 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use git2::{Repository, Signature};
+use iocost_benchmarks::checkpoint::{ImportCheckpoint, UrlState};
+use iocost_benchmarks::url_ledger::{ProcessedUrl, UrlLedger};
+
+const URL: &str = "https://example.com/results";
+
+/// Commits the `files`, with their names as contents, and returns the
+/// tree of the commit
+fn commit<'a>(repo: &'a Repository, files: &[&str]) -> git2::Tree<'a> {
+    let mut index = repo.index().unwrap();
+    for file in files {
+        fs::create_dir_all(Path::new(file).parent().unwrap()).unwrap();
+        fs::write(file, file).unwrap();
+        index.add_path(Path::new(file)).unwrap();
+    }
+    let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+    let sig = Signature::now("test", "test@example.com").unwrap();
+    let parents: Vec<_> = repo.head().ok().map(|h| h.peel_to_commit().unwrap()).into_iter().collect();
+    let parents: Vec<_> = parents.iter().collect();
+    repo.commit(Some("HEAD"), &sig, &sig, "test", &tree, &parents).unwrap();
+    tree
+}
+
+fn imported(files: &[&str]) -> ProcessedUrl {
+    ProcessedUrl::Imported {
+        version: "1.0".to_string(),
+        model_name: "WDC".to_string(),
+        files: files.iter().map(PathBuf::from).collect(),
+    }
+}
+
+#[test]
+fn skips_the_urls_already_processed() {
+    let dir = tempfile::tempdir().unwrap();
+    std::env::set_current_dir(dir.path()).unwrap();
+    let repo = Repository::init(".").unwrap();
+    let database = Path::new("database");
+
+    // a.json.gz was merged, b.json.gz is only in the branch of the
+    // submission and c.json.gz was lost
+    commit(&repo, &["database/a.json.gz", "database/a.json.metadata"]);
+    let head = repo.head().unwrap().peel_to_commit().unwrap();
+    let branch = commit(&repo, &["database/b.json.gz", "database/b.json.metadata"]);
+    repo.branch("issue-1", &repo.head().unwrap().peel_to_commit().unwrap(), true).unwrap();
+    repo.reset(head.as_object(), git2::ResetType::Hard, None).unwrap();
+    assert!(!Path::new("database/b.json.gz").exists());
+
+    let mut ledger = UrlLedger { issue: 1, ..Default::default() };
+    ledger.record(&format!("{URL}/a"), imported(&["database/a.json.gz", "database/a.json.metadata"]));
+    ledger.record(&format!("{URL}/b"), imported(&["database/b.json.gz", "database/b.json.metadata"]));
+    ledger.record(&format!("{URL}/c"), imported(&["database/c.json.gz"]));
+    ledger.record(&format!("{URL}/d"), ProcessedUrl::Duplicate { issue: Some(7), url: None });
+    ledger.save(database).unwrap();
+    // The URLs, which may hold credentials, aren't saved
+    let saved = fs::read_to_string(UrlLedger::path_for(database, 1)).unwrap();
+    assert!(!saved.contains(URL));
+    assert_eq!(UrlLedger::load(&repo, None, database, 1).unwrap().urls, ledger.urls);
+
+    let mut checkpoint = ImportCheckpoint::load(1).unwrap();
+    checkpoint.set(&format!("{URL}/a"), UrlState::Embargoed { until: "2030-01-01".to_string() }).unwrap();
+    let urls: Vec<String> = "abcde".chars().map(|c| format!("{URL}/{c}")).collect();
+    let head = head.tree().unwrap();
+    assert_eq!(ledger.resume(&repo, &head, Some(&branch), &mut checkpoint, &urls).unwrap(), 2);
+    // The state of the interrupted run wins
+    assert!(matches!(checkpoint.get(&urls[0]), Some(UrlState::Embargoed { .. })));
+    assert!(matches!(checkpoint.get(&urls[1]), Some(UrlState::Staged { .. })));
+    assert_eq!(fs::read_to_string("database/b.json.gz").unwrap(), "database/b.json.gz");
+    assert!(checkpoint.get(&urls[2]).is_none());
+    assert!(matches!(checkpoint.get(&urls[3]), Some(UrlState::Duplicate { issue: Some(7), .. })));
+    assert!(checkpoint.get(&urls[4]).is_none());
+
+    let mut checkpoint = ImportCheckpoint::load(1).unwrap();
+    assert_eq!(ledger.resume(&repo, &head, None, &mut checkpoint, &urls[..1]).unwrap(), 0);
+    checkpoint.finish().unwrap();
+    let mut checkpoint = ImportCheckpoint::load(1).unwrap();
+    assert_eq!(ledger.resume(&repo, &head, None, &mut checkpoint, &urls[..1]).unwrap(), 1);
+    assert!(matches!(checkpoint.get(&urls[0]), Some(UrlState::Merged { file }) if file.ends_with("a.json.gz")));
+    checkpoint.finish().unwrap();
+
+    // Only the results imported and the duplicates are recorded, the
+    // failures are retried by the next run
+    let mut checkpoint = ImportCheckpoint::load(1).unwrap();
+    checkpoint.set(&urls[2], UrlState::Failed { error: "invalid".to_string(), ledger: PathBuf::new() }).unwrap();
+    checkpoint.set(&urls[4], UrlState::Downloaded { path: "e".to_string(), verified_checksum: None }).unwrap();
+    let mut recorded = UrlLedger { issue: 1, ..Default::default() };
+    recorded.record_run(&checkpoint);
+    assert!(recorded.urls.is_empty(), "{:?}", recorded.urls);
+    checkpoint.set(&urls[3], UrlState::Duplicate { issue: None, url: None }).unwrap();
+    let files = vec![PathBuf::from("database/e.json.gz")];
+    let staged = UrlState::Staged { version: "1.0".to_string(), model_name: "WDC".to_string(), files };
+    checkpoint.set(&urls[4], staged).unwrap();
+    recorded.record_run(&checkpoint);
+    assert_eq!(recorded.get(&urls[3]), Some(&ProcessedUrl::Duplicate { issue: None, url: None }));
+    assert_eq!(recorded.get(&urls[4]), Some(&imported(&["database/e.json.gz"])));
+    assert_eq!(recorded.urls.len(), 2);
+    checkpoint.finish().unwrap();
+}
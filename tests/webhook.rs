@@ -0,0 +1,48 @@
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use iocost_benchmarks::webhook::verify_signature;
+
+const SECRET: &str = "It's a Secret to Everybody";
+const BODY: &[u8] = b"{\"action\": \"opened\", \"issue\": {\"number\": 1}}";
+
+/// Returns the `X-Hub-Signature-256` header Github sends with `body`
+fn signature_of(body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(SECRET.as_bytes()).unwrap();
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+#[test]
+fn accepts_valid_signatures() {
+    assert!(verify_signature(SECRET, BODY, &signature_of(BODY)));
+    // The example of the Github documentation
+    assert!(verify_signature(
+        SECRET,
+        b"Hello, World!",
+        "sha256=757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e17"
+    ));
+}
+
+#[test]
+fn refuses_tampered_bodies() {
+    let tampered = b"{\"action\": \"opened\", \"issue\": {\"number\": 2}}";
+    assert!(!verify_signature(SECRET, tampered, &signature_of(BODY)));
+    assert!(!verify_signature("another secret", BODY, &signature_of(BODY)));
+}
+
+#[test]
+fn refuses_missing_signatures() {
+    // The receiver passes an empty signature when the header is missing
+    assert!(!verify_signature(SECRET, BODY, ""));
+    assert!(!verify_signature(SECRET, BODY, "sha256="));
+}
+
+#[test]
+fn refuses_other_prefixes() {
+    let hex = signature_of(BODY).trim_start_matches("sha256=").to_string();
+    assert!(!verify_signature(SECRET, BODY, &hex));
+    assert!(!verify_signature(SECRET, BODY, &format!("sha1={}", hex)));
+    assert!(!verify_signature(SECRET, BODY, &format!("SHA256={}", hex)));
+    assert!(!verify_signature(SECRET, BODY, "sha256=not-hex"));
+}